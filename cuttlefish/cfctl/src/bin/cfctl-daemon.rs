@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::time::Duration;
 
@@ -9,6 +10,9 @@ use tracing_subscriber::{fmt, EnvFilter};
 #[derive(Debug, Parser)]
 #[command(name = "cfctl-daemon", about = "Cuttlefish control daemon", version)]
 struct Args {
+    /// TOML config file; its settings override these flags. Re-read on SIGHUP.
+    #[arg(long, env = "CFCTL_CONFIG")]
+    config: Option<PathBuf>,
     #[arg(long, env = "CFCTL_SOCKET", default_value = "/run/cfctl.sock")]
     socket: PathBuf,
     #[arg(long, env = "CFCTL_STATE_DIR", default_value = "/var/lib/cfctl")]
@@ -35,6 +39,9 @@ struct Args {
     start_timeout_secs: u64,
     #[arg(long, env = "CFCTL_ADB_TIMEOUT_SECS", default_value_t = 90)]
     adb_timeout_secs: u64,
+    /// How long a QEMU monitor savevm/loadvm/delvm may go without answering.
+    #[arg(long, env = "CFCTL_SNAPSHOT_TIMEOUT_SECS", default_value_t = 300)]
+    snapshot_timeout_secs: u64,
     #[arg(long, env = "CFCTL_JOURNAL_LINES", default_value_t = 200)]
     journal_lines: usize,
     #[arg(long, env = "CFCTL_ADB_HOST", default_value = "127.0.0.1")]
@@ -65,10 +72,19 @@ struct Args {
         default_value = "/var/lib/cuttlefish/images"
     )]
     cuttlefish_system_image_dir: PathBuf,
+    /// Host directory `push` reads from and `pull` writes to; other local
+    /// paths are refused.
+    #[arg(
+        long,
+        env = "CFCTL_TRANSFER_DIR",
+        default_value = "/var/lib/cfctl/transfer"
+    )]
+    transfer_dir: PathBuf,
     #[arg(long, env = "CFCTL_DISABLE_HOST_GPU", default_value_t = true)]
     disable_host_gpu: bool,
-    #[arg(long, env = "CFCTL_GUEST_USER", default_value = "justin")]
-    guest_user: String,
+    /// User guests run as (required here or as `[guest] user` in the config).
+    #[arg(long, env = "CFCTL_GUEST_USER")]
+    guest_user: Option<String>,
     #[arg(long, env = "CFCTL_GUEST_PRIMARY_GROUP", default_value = "cvdnetwork")]
     guest_primary_group: String,
     #[arg(
@@ -78,6 +94,57 @@ struct Args {
         value_delimiter = ','
     )]
     guest_capabilities: Vec<String>,
+    /// Warm instances to keep ready for `create-start --pool` (0 disables the pool).
+    #[arg(long, env = "CFCTL_POOL_SIZE", default_value_t = 0)]
+    pool_size: usize,
+    /// Create empty host instance/assembly directories for pooled instances
+    /// ahead of time. Only the directories; assembly still happens at start.
+    #[arg(long, env = "CFCTL_POOL_PRECREATE_DIRS", default_value_t = false)]
+    pool_precreate_dirs: bool,
+    /// Also run assemble_cvd for pooled instances, so claims that keep the
+    /// default images boot with --resume. Implies --pool-precreate-dirs.
+    #[arg(long, env = "CFCTL_POOL_PREASSEMBLE", default_value_t = false)]
+    pool_preassemble: bool,
+    /// Test-only: run guests with this cfctl-fake-guest binary instead of
+    /// launch_cvd. Hidden from --help; never set it on a real host.
+    #[arg(long, env = "CFCTL_FAKE_GUEST", hide = true)]
+    fake_guest: Option<PathBuf>,
+    /// Don't record adb logcat of running guests to their instance directory.
+    #[arg(long, env = "CFCTL_DISABLE_LOGCAT_CAPTURE", default_value_t = false)]
+    disable_logcat_capture: bool,
+    /// Rotate each instance's logcat capture at this size.
+    #[arg(long, env = "CFCTL_LOGCAT_ROTATE_MIB", default_value_t = 32)]
+    logcat_rotate_mib: u64,
+    /// Rotated logcat files to keep per instance.
+    #[arg(long, env = "CFCTL_LOGCAT_ROTATE_KEEP", default_value_t = 4)]
+    logcat_rotate_keep: usize,
+    /// Rotate each instance's run and console logs at this size.
+    #[arg(long, env = "CFCTL_LOG_ROTATE_MIB", default_value_t = 16)]
+    log_rotate_mib: u64,
+    /// Compressed run and console log segments to keep per instance.
+    #[arg(long, env = "CFCTL_LOG_ROTATE_KEEP", default_value_t = 5)]
+    log_rotate_keep: usize,
+    /// Disk quota for each instance's logs, rotated copies included (0 = unlimited).
+    #[arg(long, env = "CFCTL_LOG_QUOTA_MIB", default_value_t = 512)]
+    log_quota_mib: u64,
+    /// Seconds between log rotation checks.
+    #[arg(long, env = "CFCTL_LOG_CHECK_SECS", default_value_t = 15)]
+    log_check_secs: u64,
+    /// Serve Prometheus metrics at http://<addr>/metrics (e.g. 127.0.0.1:9464).
+    #[arg(long, env = "CFCTL_METRICS_ADDR")]
+    metrics_addr: Option<SocketAddr>,
+    /// Refuse to create more instances than this, warm ones included (0 = no limit).
+    #[arg(long, env = "CFCTL_MAX_INSTANCES", default_value_t = 0)]
+    max_instances: usize,
+    /// Refuse to start a guest while this many are running (0 = no limit).
+    #[arg(long, env = "CFCTL_MAX_RUNNING", default_value_t = 0)]
+    max_running: usize,
+    /// Prune unheld instances older than this in the background (0 = never).
+    #[arg(long, env = "CFCTL_PRUNE_MAX_AGE_SECS", default_value_t = 0)]
+    prune_max_age_secs: u64,
+    /// Seconds between background prune runs.
+    #[arg(long, env = "CFCTL_PRUNE_INTERVAL_SECS", default_value_t = 600)]
+    prune_interval_secs: u64,
 }
 
 #[tokio::main]
@@ -95,6 +162,7 @@ async fn main() -> Result<()> {
         default_init_boot_image: args.default_init_boot_image,
         start_timeout: Duration::from_secs(args.start_timeout_secs),
         adb_wait_timeout: Duration::from_secs(args.adb_timeout_secs),
+        snapshot_timeout: Duration::from_secs(args.snapshot_timeout_secs),
         journal_lines: args.journal_lines,
         adb_host: args.adb_host,
         base_adb_port: args.base_adb_port,
@@ -102,10 +170,29 @@ async fn main() -> Result<()> {
         cuttlefish_instances_dir: args.cuttlefish_instances_dir,
         cuttlefish_assembly_dir: args.cuttlefish_assembly_dir,
         cuttlefish_system_image_dir: args.cuttlefish_system_image_dir,
+        transfer_dir: args.transfer_dir,
         disable_host_gpu: args.disable_host_gpu,
-        guest_user: args.guest_user,
+        guest_user: args.guest_user.unwrap_or_default(),
         guest_primary_group: args.guest_primary_group,
         guest_capabilities: args.guest_capabilities,
+        pool_size: args.pool_size,
+        pool_precreate_dirs: args.pool_precreate_dirs,
+        pool_preassemble: args.pool_preassemble,
+        fake_guest: args.fake_guest,
+        logcat_capture: !args.disable_logcat_capture,
+        logcat_rotate_bytes: args.logcat_rotate_mib * 1024 * 1024,
+        logcat_rotate_keep: args.logcat_rotate_keep,
+        log_rotate_bytes: args.log_rotate_mib * 1024 * 1024,
+        log_rotate_keep: args.log_rotate_keep,
+        log_quota_bytes: args.log_quota_mib * 1024 * 1024,
+        log_check_interval: Duration::from_secs(args.log_check_secs.max(1)),
+        metrics_addr: args.metrics_addr,
+        max_instances: args.max_instances,
+        max_running: args.max_running,
+        prune_max_age: (args.prune_max_age_secs > 0)
+            .then(|| Duration::from_secs(args.prune_max_age_secs)),
+        prune_interval: Duration::from_secs(args.prune_interval_secs),
+        config_file: args.config,
     };
 
     let daemon = CfctlDaemon::new(config);
@@ -0,0 +1,387 @@
+//! Stand-in for `launch_cvd` and `adb` so the daemon can be exercised
+//! end to end without cuttlefish.
+//!
+//! `cfctl-fake-guest launch --script <file> --console <file> --adb-port <port>`
+//! plays a guest script (the instance's init_boot image), one command per line:
+//!
+//! ```text
+//! console <text>      append a line to the console log
+//! stdout <text>       print a line to the run log
+//! prop <name> <value> set a property returned by `getprop`
+//! logcat <text>       add a line to the guest's logcat buffer
+//! drop-adb            end every streaming `adb logcat` session
+//! adb                 start answering adb on 127.0.0.1:<adb-port>
+//! sleep <secs>        pause (fractional seconds allowed)
+//! exit <code>         exit with the given status
+//! crash               abort (SIGABRT)
+//! hang                ignore SIGTERM and block forever
+//! ```
+//!
+//! When the script ends the guest keeps running until it is signalled.
+//!
+//! `cfctl-fake-guest assemble --instance-dir <dir>` stands in for
+//! `assemble_cvd`: it leaves an `assembled` marker that a later
+//! `launch --resume true` reports picking up in the run log.
+//!
+//! `cfctl-fake-guest -- adb <args>` is the matching adb client, used by
+//! pointing the daemon's `--cuttlefish-fhs` at this binary. Connected serials
+//! are remembered under `$CFCTL_FAKE_ADB_DIR` so `adb devices` can list them.
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/// Left in the instance dir by `assemble`.
+const ASSEMBLED_MARKER: &str = "assembled";
+
+/// What the fake device answers adb with.
+#[derive(Default)]
+struct Device {
+    props: Mutex<HashMap<String, String>>,
+    /// Logcat buffer: time since the Unix epoch and message.
+    logcat: Mutex<Vec<(Duration, String)>>,
+    /// Bumped by `drop-adb`; streaming sessions end when it changes.
+    generation: AtomicU64,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("launch") => launch(&args[1..]),
+        Some("assemble") => assemble(&args[1..]),
+        Some("--") => match args.get(1).map(String::as_str) {
+            Some("adb") => adb(&args[2..]),
+            other => Err(anyhow!("fake fhs: unsupported program {:?}", other)),
+        },
+        _ => Err(anyhow!(
+            "usage: cfctl-fake-guest launch --script <file> --console <file> --adb-port <port> | assemble --instance-dir <dir> | -- adb <args>"
+        )),
+    };
+    match result {
+        Ok(code) => process::exit(code),
+        Err(err) => {
+            eprintln!("cfctl-fake-guest: {:#}", err);
+            process::exit(1);
+        }
+    }
+}
+
+fn launch(args: &[String]) -> Result<i32> {
+    let mut script = None;
+    let mut console = None;
+    let mut adb_port = None;
+    let mut instance_dir = None;
+    let mut resume = false;
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| anyhow!("{} requires a value", flag))?;
+        match flag.as_str() {
+            "--script" => script = Some(PathBuf::from(value)),
+            "--console" => console = Some(PathBuf::from(value)),
+            "--adb-port" => adb_port = Some(value.parse::<u16>().context("parsing --adb-port")?),
+            "--instance-dir" => instance_dir = Some(PathBuf::from(value)),
+            "--resume" => resume = value == "true",
+            other => bail!("unknown flag {}", other),
+        }
+    }
+    let script = script.ok_or_else(|| anyhow!("--script is required"))?;
+    let console = console.ok_or_else(|| anyhow!("--console is required"))?;
+    let adb_port = adb_port.ok_or_else(|| anyhow!("--adb-port is required"))?;
+
+    let text = fs::read_to_string(&script)
+        .with_context(|| format!("reading guest script {}", script.display()))?;
+    if let Some(parent) = console.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut console_log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&console)
+        .with_context(|| format!("opening console log {}", console.display()))?;
+    let device: Arc<Device> = Arc::default();
+
+    if resume {
+        let assembled = instance_dir.is_some_and(|dir| dir.join(ASSEMBLED_MARKER).exists());
+        println!(
+            "fake guest: resuming ({})",
+            if assembled {
+                "assembled ahead of time"
+            } else {
+                "nothing assembled"
+            }
+        );
+    }
+    println!("fake guest: running {}", script.display());
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        match command {
+            "console" => {
+                writeln!(console_log, "{}", rest)?;
+                console_log.flush()?;
+            }
+            "stdout" => println!("{}", rest),
+            "prop" => {
+                let (name, value) = rest.split_once(' ').unwrap_or((rest, ""));
+                device
+                    .props
+                    .lock()
+                    .unwrap()
+                    .insert(name.to_string(), value.trim().to_string());
+            }
+            "logcat" => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+                device.logcat.lock().unwrap().push((now, rest.to_string()));
+            }
+            "drop-adb" => {
+                device.generation.fetch_add(1, Ordering::SeqCst);
+            }
+            "adb" => serve_adb(adb_port, Arc::clone(&device))?,
+            "sleep" => {
+                let secs: f64 = rest
+                    .parse()
+                    .with_context(|| format!("invalid sleep {:?}", rest))?;
+                thread::sleep(Duration::from_secs_f64(secs));
+            }
+            "exit" => {
+                let code = rest
+                    .parse()
+                    .with_context(|| format!("invalid exit code {:?}", rest))?;
+                println!("fake guest: exiting with {}", code);
+                return Ok(code);
+            }
+            "crash" => {
+                println!("fake guest: crashing");
+                process::abort();
+            }
+            "hang" => {
+                println!("fake guest: hanging");
+                unsafe {
+                    libc::signal(libc::SIGTERM, libc::SIG_IGN);
+                }
+                break;
+            }
+            other => bail!("unknown guest script command {:?}", other),
+        }
+    }
+    loop {
+        thread::sleep(Duration::from_secs(3600));
+    }
+}
+
+fn assemble(args: &[String]) -> Result<i32> {
+    let instance_dir = match args {
+        [flag, dir] if flag == "--instance-dir" => PathBuf::from(dir),
+        _ => bail!("usage: cfctl-fake-guest assemble --instance-dir <dir>"),
+    };
+    fs::create_dir_all(&instance_dir)?;
+    fs::write(instance_dir.join(ASSEMBLED_MARKER), b"")
+        .with_context(|| format!("assembling {}", instance_dir.display()))?;
+    println!("fake guest: assembled {}", instance_dir.display());
+    Ok(0)
+}
+
+/// Answers one request per connection: a `shell` command line or `logcat`,
+/// replied to with the exit code on the first line followed by the output.
+fn serve_adb(port: u16, device: Arc<Device>) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("binding fake adb port {}", port))?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let device = Arc::clone(&device);
+            thread::spawn(move || {
+                let _ = answer_adb(stream, &device);
+            });
+        }
+    });
+    Ok(())
+}
+
+fn answer_adb(stream: TcpStream, device: &Device) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    if reader.read_line(&mut request)? == 0 {
+        return Ok(());
+    }
+    let words: Vec<&str> = request.split_whitespace().collect();
+    let (code, output) = match words.as_slice() {
+        ["shell", "getprop", name] => (
+            0,
+            device
+                .props
+                .lock()
+                .unwrap()
+                .get(*name)
+                .cloned()
+                .unwrap_or_default()
+                + "\n",
+        ),
+        ["shell", "echo", rest @ ..] => (0, rest.join(" ") + "\n"),
+        ["shell", "true"] => (0, String::new()),
+        ["shell", "false"] => (1, String::new()),
+        ["shell", cmd, ..] => (127, format!("/system/bin/sh: {}: not found\n", cmd)),
+        ["logcat", options @ ..] => return answer_logcat(stream, device, options),
+        _ => (1, format!("unsupported request {:?}\n", request.trim())),
+    };
+    let mut stream = stream;
+    write!(stream, "{}\n{}", code, output)?;
+    stream.shutdown(Shutdown::Write)?;
+    Ok(())
+}
+
+/// Streams the logcat buffer like `logcat -v <epoch|threadtime> [-d] [-T <time>]`.
+/// Without `-d` new lines keep coming until `drop-adb` or the client leaves.
+fn answer_logcat(mut stream: TcpStream, device: &Device, options: &[&str]) -> Result<()> {
+    let dump = options.contains(&"-d");
+    let epoch = options.windows(2).any(|pair| pair == ["-v", "epoch"]);
+    let since = options
+        .iter()
+        .position(|option| *option == "-T")
+        .map(|index| options[index + 1..].join(" "));
+    let generation = device.generation.load(Ordering::SeqCst);
+    writeln!(stream, "0")?;
+    let mut sent = 0;
+    loop {
+        let pending: Vec<String> = device.logcat.lock().unwrap()[sent..]
+            .iter()
+            .map(|(time, message)| format_logcat(*time, message, epoch))
+            .collect();
+        sent += pending.len();
+        for line in pending {
+            if since
+                .as_deref()
+                .is_some_and(|since| line[..since.len()] < *since)
+            {
+                continue;
+            }
+            writeln!(stream, "{}", line)?;
+        }
+        if dump || device.generation.load(Ordering::SeqCst) != generation {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    stream.shutdown(Shutdown::Write)?;
+    Ok(())
+}
+
+fn format_logcat(time: Duration, message: &str, epoch: bool) -> String {
+    let secs = time.as_secs();
+    let millis = time.subsec_millis();
+    if epoch {
+        return format!(
+            "{:>10}.{:03}  1000  1000 I fake    : {}",
+            secs, millis, message
+        );
+    }
+    let (month, day) = month_day((secs / 86_400) as i64);
+    format!(
+        "{:02}-{:02} {:02}:{:02}:{:02}.{:03}  1000  1000 I fake    : {}",
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        millis,
+        message
+    )
+}
+
+/// Month and day of a count of days since 1970-01-01.
+fn month_day(days: i64) -> (i64, i64) {
+    let day_of_era = (days + 719_468).rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    (month, day)
+}
+
+fn adb(args: &[String]) -> Result<i32> {
+    let state_dir = env::var_os("CFCTL_FAKE_ADB_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| env::temp_dir().join("cfctl-fake-adb"));
+    fs::create_dir_all(&state_dir)?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["connect", serial] => {
+            if TcpStream::connect(*serial).is_err() {
+                println!("failed to connect to {}", serial);
+                return Ok(1);
+            }
+            fs::write(state_dir.join(serial), b"")?;
+            println!("connected to {}", serial);
+            Ok(0)
+        }
+        ["devices"] => {
+            println!("List of devices attached");
+            for serial in connected_serials(&state_dir)? {
+                let state = if TcpStream::connect(&serial).is_ok() {
+                    "device"
+                } else {
+                    "offline"
+                };
+                println!("{}\t{}", serial, state);
+            }
+            Ok(0)
+        }
+        ["forward", "--list"] => Ok(0),
+        ["-s", _, "forward", ..] => Ok(0),
+        ["-s", serial, "shell", command @ ..] => {
+            device_request(serial, &format!("shell {}", command.join(" ")))
+        }
+        ["-s", serial, "logcat", options @ ..] => {
+            device_request(serial, &format!("logcat {}", options.join(" ")))
+        }
+        other => {
+            eprintln!("fake adb: unsupported command {:?}", other);
+            Ok(1)
+        }
+    }
+}
+
+/// Sends one request line to the fake device and relays its reply.
+fn device_request(serial: &str, request: &str) -> Result<i32> {
+    let mut stream =
+        TcpStream::connect(serial).with_context(|| format!("device '{}' not found", serial))?;
+    writeln!(stream, "{}", request)?;
+    let mut reply = BufReader::new(stream);
+    let mut code = String::new();
+    reply.read_line(&mut code)?;
+    io::copy(&mut reply, &mut io::stdout())?;
+    Ok(code.trim().parse().unwrap_or(1))
+}
+
+fn connected_serials(state_dir: &Path) -> Result<Vec<String>> {
+    let mut serials: Vec<String> = fs::read_dir(state_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    serials.sort();
+    Ok(serials)
+}
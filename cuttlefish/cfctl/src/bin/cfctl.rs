@@ -1,26 +1,40 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{
-    io::{BufRead, BufReader, Write},
-    net::Shutdown,
-    os::unix::net::UnixStream,
-    path::PathBuf,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
     process,
+    str::FromStr,
     sync::Arc,
     thread,
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{anyhow, Context, Result};
+use cfctl::client::Client;
 use cfctl::{
-    DeployRequest, DestroyOptions, InstanceId, LogsOptions, Request, Response, StartOptions,
+    BackendSpec, BatchEntry, BatchRequest, DeployRequest, DestroyOptions, HistoryQuery, InstanceId,
+    LogSource, LogsOptions, NetworkMode, PortForward, QemuOptions, ReadinessProbeSpec, Request,
+    Response, RunOutcome, Scenario, StartOptions,
 };
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+
+#[path = "cfctl/output.rs"]
+mod output;
+#[path = "cfctl/top.rs"]
+mod top;
+
+use output::OutputFormat;
 
 #[derive(Debug, Parser)]
 #[command(name = "cfctl", about = "CLI for the cfctl daemon", version)]
 struct Cli {
     #[arg(long, env = "CFCTL_SOCKET", default_value = "/run/cfctl.sock")]
     socket: PathBuf,
+    /// Output format: table, json, yaml or ids. Defaults to table on a
+    /// terminal and json otherwise.
+    #[arg(long, short = 'o', global = true, env = "CFCTL_OUTPUT", value_enum)]
+    output: Option<OutputFormat>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -29,6 +43,9 @@ struct Cli {
 enum Commands {
     #[command(subcommand)]
     Instance(InstanceCommands),
+    /// Save, restore and manage QEMU snapshots of an instance.
+    #[command(subcommand)]
+    Snapshot(SnapshotCommands),
     /// Copy boot/init images into the instance workspace and update env.
     Deploy {
         id: InstanceId,
@@ -43,16 +60,188 @@ enum Commands {
         #[arg(long)]
         timeout_secs: Option<u64>,
     },
-    /// Fetch recent journal entries for the instance.
+    /// Fetch an instance log: the run log by default, or the console,
+    /// kernel, launcher log or logcat.
     Logs {
         id: InstanceId,
+        /// Return only the last N selected lines.
         #[arg(long)]
         lines: Option<usize>,
         #[arg(long)]
         timeout_secs: Option<u64>,
         #[arg(long)]
         stdout: bool,
+        /// Log to read: run, console, kernel, launcher or logcat.
+        #[arg(long, default_value = "run")]
+        source: LogSource,
+        /// Byte range START:END of the log (END exclusive; either side may be empty).
+        #[arg(long, value_parser = parse_range::<u64>, conflicts_with = "line_range")]
+        bytes: Option<(Option<u64>, Option<u64>)>,
+        /// Line range START:END, counting from 1 (END inclusive).
+        #[arg(long, value_parser = parse_range::<usize>)]
+        line_range: Option<(Option<usize>, Option<usize>)>,
+        /// Only return lines matching this regex.
+        #[arg(long)]
+        grep: Option<String>,
+        /// Only return lines logged since a Unix time, or since a duration ago (90s, 10m, 2h, 1d).
+        #[arg(long, value_parser = parse_since)]
+        since: Option<u64>,
+    },
+    /// Run a TOML/JSON scenario end to end on a fresh instance.
+    Scenario {
+        /// Scenario file; `.json` is parsed as JSON, anything else as TOML.
+        path: PathBuf,
+        /// Write the JUnit XML report to this path.
+        #[arg(long)]
+        junit: Option<PathBuf>,
+    },
+    /// Boot one instance per init_boot image and compare the results.
+    Batch {
+        /// init_boot images to boot, one instance each.
+        #[arg(required = true)]
+        init_boot_images: Vec<PathBuf>,
+        /// boot image deployed to every instance.
+        #[arg(long)]
+        boot: Option<PathBuf>,
+        #[arg(long)]
+        purpose: Option<String>,
+        /// Maximum concurrent guests (clamped to host capacity).
+        #[arg(long)]
+        max_parallel: Option<usize>,
+        /// Keep the instances instead of destroying them afterwards.
+        #[arg(long)]
+        keep: bool,
+        #[arg(long)]
+        destroy_timeout_secs: Option<u64>,
+        /// Same as `--output json`.
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        start: StartArgs,
+    },
+    /// Run a command inside the guest via `adb shell`.
+    Shell {
+        id: InstanceId,
+        #[arg(long)]
+        timeout_secs: Option<u64>,
+        /// Print the command output directly and exit with its exit code.
+        #[arg(long)]
+        stdout: bool,
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Copy a host file into the guest via `adb push`.
+    Push {
+        id: InstanceId,
+        local: PathBuf,
+        remote: String,
+        #[arg(long)]
+        timeout_secs: Option<u64>,
+    },
+    /// Copy a guest file onto the host via `adb pull`.
+    Pull {
+        id: InstanceId,
+        remote: String,
+        local: PathBuf,
+        #[arg(long)]
+        timeout_secs: Option<u64>,
+    },
+    /// List the requests the daemon is currently working on.
+    Requests,
+    /// Cancel an in-flight request (see `requests`), rolling back its work.
+    Cancel { request_id: String },
+    /// Print daemon metrics in the Prometheus text format.
+    Metrics,
+    /// Query recorded start attempts, including those of destroyed instances.
+    #[command(subcommand)]
+    History(HistoryCommands),
+    /// Check the host for problems that make launches fail.
+    Doctor {
+        /// Also repair what is safe to fix: stale tap devices and a leftover
+        /// cuttlefish config.
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Live dashboard of all instances with their logs.
+    Top {
+        /// Seconds between refreshes.
+        #[arg(long, default_value_t = 2)]
+        interval_secs: u64,
+        /// Log lines shown under the instance table.
+        #[arg(long, default_value_t = 10)]
+        lines: usize,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum HistoryCommands {
+    /// List start attempts, newest first.
+    List {
+        #[command(flatten)]
+        filter: HistoryFilter,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
     },
+    /// Compare boot times across init images.
+    Compare {
+        #[command(flatten)]
+        filter: HistoryFilter,
+        /// sha256 prefix of the init image the others are compared to
+        /// (defaults to the oldest).
+        #[arg(long)]
+        baseline: Option<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct HistoryFilter {
+    #[arg(long)]
+    instance: Option<InstanceId>,
+    /// Only runs of the init image whose sha256 starts with this.
+    #[arg(long)]
+    init_sha256: Option<String>,
+    #[arg(long)]
+    purpose: Option<String>,
+    /// ok, failed or cancelled.
+    #[arg(long)]
+    outcome: Option<RunOutcome>,
+    /// Only runs started since a Unix time, or since a duration ago (90s, 10m, 2h, 1d).
+    #[arg(long, value_parser = parse_since)]
+    since: Option<u64>,
+}
+
+impl HistoryFilter {
+    fn query(self) -> HistoryQuery {
+        HistoryQuery {
+            instance_id: self.instance,
+            init_sha256: self.init_sha256,
+            purpose: self.purpose,
+            outcome: self.outcome,
+            since: self.since,
+            ..HistoryQuery::default()
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum SnapshotCommands {
+    /// Save the running guest's state (QEMU savevm).
+    Take {
+        id: InstanceId,
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Load a snapshot, starting the instance with --resume if it is stopped.
+    Restore {
+        id: InstanceId,
+        name: String,
+        #[command(flatten)]
+        start: Box<StartArgs>,
+    },
+    /// List the snapshots recorded for an instance.
+    List { id: InstanceId },
+    /// Delete a snapshot.
+    Delete { id: InstanceId, name: String },
 }
 
 #[derive(Debug, Subcommand)]
@@ -65,36 +254,42 @@ enum InstanceCommands {
     /// Start the systemd unit for the instance.
     Start {
         id: InstanceId,
-        #[arg(long)]
-        disable_webrtc: bool,
-        #[arg(long)]
-        timeout_secs: Option<u64>,
-        #[arg(long)]
-        verify_boot: bool,
-        #[arg(long)]
-        skip_adb_wait: bool,
-        #[arg(long)]
-        track: Option<String>,
+        #[command(flatten)]
+        start: StartArgs,
     },
     /// Create and immediately start a new instance.
     CreateStart {
         #[arg(long)]
         purpose: Option<String>,
+        /// Claim a warm instance from the daemon pool when one is available.
         #[arg(long)]
-        disable_webrtc: bool,
+        pool: bool,
+        /// boot image to deploy before starting.
         #[arg(long)]
-        timeout_secs: Option<u64>,
+        boot: Option<PathBuf>,
+        /// init_boot image to deploy before starting.
         #[arg(long)]
-        verify_boot: bool,
+        init: Option<PathBuf>,
+        #[command(flatten)]
+        start: StartArgs,
+    },
+    /// Create a new instance from another instance's images and start options.
+    Clone {
+        from: InstanceId,
         #[arg(long)]
-        skip_adb_wait: bool,
+        purpose: Option<String>,
+        /// Start the clone with the source's last start options.
         #[arg(long)]
-        track: Option<String>,
+        start: bool,
     },
     /// Stop the systemd unit for the instance.
     Stop { id: InstanceId },
+    /// Stop the instance and relaunch it with the options of its last start.
+    Restart { id: InstanceId },
     /// Hold the instance to prevent pruning.
     Hold { id: InstanceId },
+    /// Release a held instance so it can be pruned again.
+    Release { id: InstanceId },
     /// Destroy the instance and cleanup files.
     Destroy {
         id: InstanceId,
@@ -110,7 +305,11 @@ enum InstanceCommands {
         run_log_lines: usize,
     },
     /// List all known instances.
-    List,
+    List {
+        /// Include CPU, memory and process counts of running guests.
+        #[arg(long)]
+        resources: bool,
+    },
     /// Trigger expired instance pruning.
     Prune {
         #[arg(long, default_value_t = 24 * 60 * 60, help = "Maximum instance age before pruning in seconds")]
@@ -120,84 +319,200 @@ enum InstanceCommands {
     },
 }
 
-fn main() -> Result<()> {
+#[derive(Debug, Args)]
+struct StartArgs {
+    #[arg(long)]
+    disable_webrtc: bool,
+    #[arg(long)]
+    timeout_secs: Option<u64>,
+    #[arg(long)]
+    verify_boot: bool,
+    #[arg(long)]
+    skip_adb_wait: bool,
+    #[arg(long)]
+    track: Option<String>,
+    /// Readiness probe: adb, console:<regex>, tcp:[host:]<port> or alive:<secs>.
+    #[arg(long)]
+    readiness: Option<ReadinessProbeSpec>,
+    /// Keep the previous disk overlay (and its snapshots) instead of a fresh one.
+    #[arg(long)]
+    resume: bool,
+    /// Network mode: launcher (host-provided taps) or isolated (unbridged taps).
+    #[arg(long, default_value = "launcher")]
+    network: NetworkMode,
+    /// Forward a host TCP port to the guest once ready (HOST:GUEST, repeatable).
+    #[arg(long = "forward")]
+    port_forwards: Vec<PortForward>,
+    #[command(flatten)]
+    qemu: QemuArgs,
+}
+
+/// Selects the plain QEMU backend when `--qemu-kernel` is given.
+#[derive(Debug, Args)]
+struct QemuArgs {
+    /// Boot this kernel with plain QEMU instead of launch_cvd.
+    #[arg(long, requires = "qemu_initramfs")]
+    qemu_kernel: Option<PathBuf>,
+    /// Initramfs for --qemu-kernel.
+    #[arg(long, requires = "qemu_kernel")]
+    qemu_initramfs: Option<PathBuf>,
+    /// Extra kernel command line for --qemu-kernel.
+    #[arg(long, requires = "qemu_kernel")]
+    qemu_append: Option<String>,
+    #[arg(long, requires = "qemu_kernel")]
+    qemu_memory_mib: Option<u32>,
+    #[arg(long, requires = "qemu_kernel")]
+    qemu_cpus: Option<u32>,
+    /// QEMU binary on the daemon host (default qemu-system-x86_64).
+    #[arg(long, requires = "qemu_kernel")]
+    qemu_binary: Option<String>,
+    /// Extra QEMU argument (repeatable).
+    #[arg(
+        long = "qemu-arg",
+        allow_hyphen_values = true,
+        requires = "qemu_kernel"
+    )]
+    qemu_args: Vec<String>,
+}
+
+impl From<QemuArgs> for BackendSpec {
+    fn from(args: QemuArgs) -> Self {
+        let (Some(kernel), Some(initramfs)) = (args.qemu_kernel, args.qemu_initramfs) else {
+            return BackendSpec::Cuttlefish;
+        };
+        // The daemon resolves paths on its own host, so send absolute ones.
+        let absolute = |path: PathBuf| {
+            std::path::absolute(&path)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        BackendSpec::Qemu(QemuOptions {
+            kernel: absolute(kernel),
+            initramfs: absolute(initramfs),
+            append: args.qemu_append,
+            memory_mib: args.qemu_memory_mib,
+            cpus: args.qemu_cpus,
+            binary: args.qemu_binary,
+            extra_args: args.qemu_args,
+        })
+    }
+}
+
+impl From<StartArgs> for StartOptions {
+    fn from(args: StartArgs) -> Self {
+        StartOptions {
+            disable_webrtc: args.disable_webrtc,
+            timeout_secs: args.timeout_secs,
+            verify_boot: args.verify_boot,
+            skip_adb_wait: args.skip_adb_wait,
+            track: args.track,
+            readiness: args.readiness,
+            resume: args.resume,
+            network: args.network,
+            port_forwards: args.port_forwards,
+            backend: BackendSpec::from(args.qemu),
+        }
+    }
+}
+
+fn main() {
     let cli = Cli::parse();
+    let code = match run(cli) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {:#}", err);
+            output::error_exit_code(&err)
+        }
+    };
+    process::exit(code);
+}
+
+/// Sends the command's request and prints the response; returns the exit code.
+fn run(cli: Cli) -> Result<i32> {
+    let client = Client::new(&cli.socket);
+    let mut format = OutputFormat::resolve(cli.output);
     let response = match cli.command {
         Commands::Instance(cmd) => match cmd {
             InstanceCommands::Create { purpose } => {
-                send_request(&cli.socket, Request::CreateInstance { purpose })?
+                client.request(&Request::CreateInstance { purpose })?
             }
-            InstanceCommands::Start {
-                id,
-                disable_webrtc,
-                timeout_secs,
-                verify_boot,
-                skip_adb_wait,
-                track,
-            } => {
-                let options = StartOptions {
-                    disable_webrtc,
-                    timeout_secs,
-                    verify_boot,
-                    skip_adb_wait,
-                    track,
-                };
-                send_request(&cli.socket, Request::StartInstance { id, options })?
+            InstanceCommands::Start { id, start } => {
+                let options = StartOptions::from(start);
+                client.request(&Request::StartInstance { id, options })?
             }
             InstanceCommands::CreateStart {
                 purpose,
-                disable_webrtc,
-                timeout_secs,
-                verify_boot,
-                skip_adb_wait,
-                track,
+                pool,
+                boot,
+                init,
+                start,
             } => {
-                let options = StartOptions {
-                    disable_webrtc,
-                    timeout_secs,
-                    verify_boot,
-                    skip_adb_wait,
-                    track,
+                let options = StartOptions::from(start);
+                let request = Request::CreateStartInstance {
+                    purpose,
+                    options,
+                    pool,
+                    boot_image: boot.map(absolute_path_string).transpose()?,
+                    init_boot_image: init.map(absolute_path_string).transpose()?,
                 };
-                send_request(
-                    &cli.socket,
-                    Request::CreateStartInstance { purpose, options },
-                )?
-            }
-            InstanceCommands::Stop { id } => {
-                send_request(&cli.socket, Request::StopInstance { id })?
-            }
-            InstanceCommands::Hold { id } => {
-                send_request(&cli.socket, Request::HoldInstance { id })?
+                client.request(&request)?
             }
+            InstanceCommands::Clone {
+                from,
+                purpose,
+                start,
+            } => client.request(&Request::CloneInstance {
+                from,
+                purpose,
+                start,
+            })?,
+            InstanceCommands::Stop { id } => client.request(&Request::StopInstance { id })?,
+            InstanceCommands::Restart { id } => client.request(&Request::RestartInstance { id })?,
+            InstanceCommands::Hold { id } => client.request(&Request::HoldInstance { id })?,
+            InstanceCommands::Release { id } => client.request(&Request::ReleaseInstance { id })?,
             InstanceCommands::Destroy { id, timeout_secs } => {
                 let options = DestroyOptions { timeout_secs };
                 let progress = ProgressPrinter::spawn(
                     format!("Destroying instance {}", id),
                     Duration::from_secs(2),
                 );
-                let response = send_request(&cli.socket, Request::DestroyInstance { id, options })?;
+                let response = client.request(&Request::DestroyInstance { id, options })?;
                 drop(progress);
                 emit_cleanup_feedback(&response);
                 response
             }
-            InstanceCommands::Status { id } => send_request(&cli.socket, Request::Status { id })?,
-            InstanceCommands::Describe { id, run_log_lines } => send_request(
-                &cli.socket,
-                Request::Describe {
+            InstanceCommands::Status { id } => client.request(&Request::Status { id })?,
+            InstanceCommands::Describe { id, run_log_lines } => {
+                client.request(&Request::Describe {
                     id,
                     run_log_lines: Some(run_log_lines),
-                },
-            )?,
-            InstanceCommands::List => send_request(&cli.socket, Request::ListInstances)?,
+                })?
+            }
+            InstanceCommands::List { resources } => {
+                client.request(&Request::ListInstances { resources })?
+            }
             InstanceCommands::Prune { max_age_secs, all } => {
                 if all {
-                    send_request(&cli.socket, Request::PruneAll)?
+                    client.request(&Request::PruneAll)?
                 } else {
-                    send_request(&cli.socket, Request::PruneExpired { max_age_secs })?
+                    client.request(&Request::PruneExpired { max_age_secs })?
                 }
             }
         },
+        Commands::Snapshot(cmd) => match cmd {
+            SnapshotCommands::Take { id, name } => {
+                client.request(&Request::SnapshotInstance { id, name })?
+            }
+            SnapshotCommands::Restore { id, name, start } => {
+                let options = StartOptions::from(*start);
+                client.request(&Request::RestoreInstance { id, name, options })?
+            }
+            SnapshotCommands::List { id } => client.request(&Request::ListSnapshots { id })?,
+            SnapshotCommands::Delete { id, name } => {
+                client.request(&Request::DeleteSnapshot { id, name })?
+            }
+        },
         Commands::Deploy { id, boot, init } => {
             if boot.is_none() && init.is_none() {
                 return Err(anyhow!("deploy requires --boot and/or --init"));
@@ -207,108 +522,249 @@ fn main() -> Result<()> {
                 boot_image: boot.map(|p| p.to_string_lossy().to_string()),
                 init_boot_image: init.map(|p| p.to_string_lossy().to_string()),
             };
-            send_request(&cli.socket, Request::Deploy(req))?
+            client.request(&Request::Deploy(req))?
         }
         Commands::WaitAdb { id, timeout_secs } => {
-            send_request(&cli.socket, Request::WaitForAdb { id, timeout_secs })?
+            client.request(&Request::WaitForAdb { id, timeout_secs })?
         }
         Commands::Logs {
             id,
             lines,
             timeout_secs,
             stdout,
+            source,
+            bytes,
+            line_range,
+            grep,
+            since,
         } => {
+            let (from_byte, to_byte) = bytes.unwrap_or_default();
+            let (from_line, to_line) = line_range.unwrap_or_default();
             let options = LogsOptions {
                 timeout_secs,
                 stream_stdout: stdout,
+                source,
+                from_byte,
+                to_byte,
+                from_line,
+                to_line,
+                grep,
+                since,
             };
-            let response = send_request(&cli.socket, Request::Logs { id, lines, options })?;
-            match (stdout, response.ok) {
-                (true, true) => {
-                    if let Some(logs) = &response.logs {
-                        if let Some(journal) = &logs.journal {
-                            print!("{journal}");
-                        }
+            let response = client.request(&Request::Logs { id, lines, options })?;
+            if stdout && response.ok {
+                if let Some(logs) = &response.logs {
+                    if !logs.content.is_empty() {
+                        println!("{}", logs.content);
                     }
-                    return Ok(());
                 }
-                _ => {
-                    let output = serde_json::to_string_pretty(&response)?;
-                    println!("{}", output);
-                    if response.ok {
-                        return Ok(());
-                    } else {
-                        process::exit(1);
-                    }
+                return Ok(output::EXIT_OK);
+            }
+            response
+        }
+        Commands::Scenario { path, junit } => {
+            let scenario = load_scenario(&path)?;
+            let response = client.request(&Request::RunScenario { scenario })?;
+            if let (Some(junit), Some(result)) = (junit, &response.scenario) {
+                fs::write(&junit, &result.junit_xml)
+                    .with_context(|| format!("writing JUnit report {}", junit.display()))?;
+            }
+            response
+        }
+        Commands::Batch {
+            init_boot_images,
+            boot,
+            purpose,
+            max_parallel,
+            keep,
+            destroy_timeout_secs,
+            json,
+            start,
+        } => {
+            let boot = boot.map(absolute_path_string).transpose()?;
+            let entries = init_boot_images
+                .into_iter()
+                .map(|init| {
+                    Ok(BatchEntry {
+                        label: Some(init.display().to_string()),
+                        boot_image: boot.clone(),
+                        init_boot_image: Some(absolute_path_string(init)?),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let batch = BatchRequest {
+                entries,
+                purpose,
+                start: StartOptions::from(start),
+                max_parallel,
+                keep_instances: keep,
+                destroy_timeout_secs,
+            };
+            let progress = ProgressPrinter::spawn(
+                format!("Running batch of {}", batch.entries.len()),
+                Duration::from_secs(5),
+            );
+            let response = client.request(&Request::RunBatch { batch })?;
+            drop(progress);
+            if json {
+                format = OutputFormat::Json;
+            }
+            response
+        }
+        Commands::Shell {
+            id,
+            timeout_secs,
+            stdout,
+            command,
+        } => {
+            let response = client.request(&Request::Shell {
+                id,
+                command,
+                timeout_secs,
+            })?;
+            if stdout && response.ok {
+                if let Some(output) = &response.adb_command {
+                    print!("{}", output.stdout);
+                    eprint!("{}", output.stderr);
+                    return Ok(output.exit_code.unwrap_or(output::EXIT_FAILED));
                 }
             }
+            response
+        }
+        Commands::Push {
+            id,
+            local,
+            remote,
+            timeout_secs,
+        } => {
+            let local = local
+                .canonicalize()
+                .with_context(|| format!("resolving local path {}", local.display()))?;
+            client.request(&Request::Push {
+                id,
+                local_path: local.to_string_lossy().to_string(),
+                remote_path: remote,
+                timeout_secs,
+            })?
+        }
+        Commands::Pull {
+            id,
+            remote,
+            local,
+            timeout_secs,
+        } => {
+            let local = std::path::absolute(&local)
+                .with_context(|| format!("resolving local path {}", local.display()))?;
+            client.request(&Request::Pull {
+                id,
+                remote_path: remote,
+                local_path: local.to_string_lossy().to_string(),
+                timeout_secs,
+            })?
+        }
+        Commands::Requests => client.request(&Request::ListRequests)?,
+        Commands::Cancel { request_id } => client.request(&Request::Cancel { request_id })?,
+        Commands::Metrics => client.request(&Request::Metrics)?,
+        Commands::History(HistoryCommands::List { filter, limit }) => {
+            client.request(&Request::History(HistoryQuery {
+                limit: Some(limit),
+                ..filter.query()
+            }))?
+        }
+        Commands::History(HistoryCommands::Compare { filter, baseline }) => {
+            client.request(&Request::History(HistoryQuery {
+                compare: true,
+                baseline,
+                ..filter.query()
+            }))?
+        }
+        Commands::Doctor { fix } => client.request(&Request::Doctor { fix })?,
+        Commands::Top {
+            interval_secs,
+            lines,
+        } => {
+            top::run(
+                &cli.socket,
+                Duration::from_secs(interval_secs.max(1)),
+                lines,
+            )?;
+            return Ok(output::EXIT_OK);
         }
     };
 
-    let output = serde_json::to_string_pretty(&response)?;
-    println!("{}", output);
-
-    if response.ok {
-        Ok(())
-    } else {
-        process::exit(1);
-    }
+    output::print(&response, format)?;
+    Ok(output::exit_code(&response))
 }
 
-fn send_request(socket: &PathBuf, request: Request) -> Result<Response> {
-    let mut stream = UnixStream::connect(socket).map_err(|err| {
-        if err.kind() == std::io::ErrorKind::ConnectionRefused
-            || err.kind() == std::io::ErrorKind::NotFound
-        {
-            anyhow!(
-                "Cannot connect to cfctl daemon at {:?}. Is the daemon running?",
-                socket
-            )
-        } else {
-            anyhow!("connect to {:?}: {}", socket, err)
-        }
-    })?;
-
-    let payload = serde_json::to_vec(&request)?;
-    stream
-        .write_all(&payload)
-        .context("failed to send request to daemon")?;
-    stream
-        .write_all(b"\n")
-        .context("failed to send newline to daemon")?;
-    stream
-        .shutdown(Shutdown::Write)
-        .context("failed to shutdown write side of connection")?;
+fn absolute_path_string(path: PathBuf) -> Result<String> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("resolving {}", path.display()))?;
+    Ok(path.to_string_lossy().to_string())
+}
 
-    let mut reader = BufReader::new(stream);
-    let mut line = String::new();
-    reader.read_line(&mut line).map_err(|err| {
-        if err.kind() == std::io::ErrorKind::UnexpectedEof
-            || err.kind() == std::io::ErrorKind::ConnectionReset
-        {
-            anyhow!(
-                "Connection to daemon was interrupted while reading response. The daemon may have crashed or been restarted."
-            )
-        } else {
-            anyhow!("read response: {}", err)
-        }
-    })?;
+/// Parses `START:END` where either bound may be left out.
+fn parse_range<T: FromStr>(value: &str) -> Result<(Option<T>, Option<T>), String>
+where
+    T::Err: std::fmt::Display,
+{
+    let (start, end) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected START:END, got {:?}", value))?;
+    let bound = |bound: &str| {
+        (!bound.is_empty())
+            .then(|| bound.parse::<T>())
+            .transpose()
+            .map_err(|err| format!("invalid bound {:?}: {}", bound, err))
+    };
+    Ok((bound(start)?, bound(end)?))
+}
 
-    if line.trim().is_empty() {
-        return Err(anyhow!(
-            "Received empty response from daemon. This may indicate a protocol error."
-        ));
+/// Parses a Unix time, or a duration such as `90s`, `10m`, `2h` or `1d`
+/// counted back from now.
+fn parse_since(value: &str) -> Result<u64, String> {
+    if let Ok(epoch) = value.parse::<u64>() {
+        return Ok(epoch);
     }
-
-    let response: Response = serde_json::from_str(&line).map_err(|err| {
-        anyhow!(
-            "Failed to decode daemon response as JSON: {}. Response was: {}",
-            err,
-            line.trim()
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(digits);
+    let amount: u64 = amount.parse().map_err(|_| {
+        format!(
+            "expected a Unix time or a duration like 10m, got {:?}",
+            value
         )
     })?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        other => {
+            return Err(format!(
+                "unknown duration unit {:?} (use s, m, h or d)",
+                other
+            ))
+        }
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| err.to_string())?
+        .as_secs();
+    Ok(now.saturating_sub(amount.saturating_mul(unit_secs)))
+}
 
-    Ok(response)
+fn load_scenario(path: &Path) -> Result<Scenario> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("reading scenario {}", path.display()))?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&contents)
+            .with_context(|| format!("parsing JSON scenario {}", path.display()))
+    } else {
+        toml::from_str(&contents)
+            .with_context(|| format!("parsing TOML scenario {}", path.display()))
+    }
 }
 
 struct ProgressPrinter {
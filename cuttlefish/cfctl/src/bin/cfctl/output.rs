@@ -0,0 +1,814 @@
+//! Rendering of daemon responses for `--output`, and the exit code the CLI
+//! returns for each kind of failure.
+
+use std::{
+    io::{self, IsTerminal},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use cfctl::{
+    client::ClientError, BackendSpec, BatchResult, CheckStatus, DoctorCheck, HistoryResponse,
+    InFlightRequest, InstanceActionResponse, InstanceSummary, ReadinessProbeSpec, Response,
+    RunRecord, ScenarioResult, ScenarioStepStatus, SnapshotInfo, StartOptions, TimingStats,
+    VariantStats,
+};
+use clap::ValueEnum;
+
+/// Success.
+pub const EXIT_OK: i32 = 0;
+/// The request failed, a guest command exited non-zero, or a doctor check
+/// failed.
+pub const EXIT_FAILED: i32 = 1;
+/// The daemon rejected the arguments (clap also exits 2 on usage errors).
+pub const EXIT_INVALID: i32 = 2;
+/// The instance, snapshot or request does not exist.
+pub const EXIT_NOT_FOUND: i32 = 3;
+pub const EXIT_TIMEOUT: i32 = 4;
+pub const EXIT_CANCELLED: i32 = 5;
+/// The daemon could not be reached or sent back something unreadable.
+pub const EXIT_UNAVAILABLE: i32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns and readable summaries.
+    Table,
+    /// The daemon response as pretty-printed JSON.
+    Json,
+    /// The daemon response as YAML.
+    Yaml,
+    /// Only the ids in the response, one per line.
+    Ids,
+}
+
+impl OutputFormat {
+    /// Tables on a terminal and JSON when piped, unless chosen explicitly.
+    pub fn resolve(requested: Option<Self>) -> Self {
+        requested.unwrap_or(if io::stdout().is_terminal() {
+            Self::Table
+        } else {
+            Self::Json
+        })
+    }
+}
+
+/// Maps a daemon error code to an exit code by its suffix.
+pub fn exit_code_for(code: &str) -> i32 {
+    if code.ends_with("_not_found") {
+        EXIT_NOT_FOUND
+    } else if code.ends_with("_timeout") {
+        EXIT_TIMEOUT
+    } else if code.ends_with("_cancelled") {
+        EXIT_CANCELLED
+    } else if code.ends_with("_invalid") || code.contains("_invalid_") {
+        EXIT_INVALID
+    } else {
+        EXIT_FAILED
+    }
+}
+
+pub fn exit_code(response: &Response) -> i32 {
+    if !response.ok {
+        return response
+            .error
+            .as_ref()
+            .map_or(EXIT_FAILED, |error| exit_code_for(&error.code));
+    }
+    let command_failed = response
+        .adb_command
+        .as_ref()
+        .is_some_and(|output| output.exit_code != Some(0));
+    let check_failed = response
+        .doctor
+        .as_ref()
+        .is_some_and(|checks| checks.iter().any(|check| check.status == CheckStatus::Fail));
+    if command_failed || check_failed {
+        EXIT_FAILED
+    } else {
+        EXIT_OK
+    }
+}
+
+/// Exit code for an error raised before a response was available.
+pub fn error_exit_code(err: &anyhow::Error) -> i32 {
+    match err.downcast_ref::<ClientError>() {
+        Some(ClientError::Timeout(_)) => EXIT_TIMEOUT,
+        Some(
+            ClientError::Connect { .. }
+            | ClientError::Io(_)
+            | ClientError::EmptyResponse
+            | ClientError::Decode { .. },
+        ) => EXIT_UNAVAILABLE,
+        Some(err) => err.code().map_or(EXIT_FAILED, exit_code_for),
+        None => EXIT_FAILED,
+    }
+}
+
+pub fn print(response: &Response, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(response)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(response)?),
+        OutputFormat::Ids => {
+            for id in ids(response) {
+                println!("{}", id);
+            }
+        }
+        OutputFormat::Table => print_human(response),
+    }
+    Ok(())
+}
+
+/// Instance ids, or snapshot names, request ids, run ids and init image
+/// digests for responses that list those.
+fn ids(response: &Response) -> Vec<String> {
+    if let Some(instances) = &response.instances {
+        return instances
+            .iter()
+            .map(|summary| summary.id.to_string())
+            .collect();
+    }
+    if let Some(snapshots) = &response.snapshots {
+        return snapshots.iter().map(|info| info.name.clone()).collect();
+    }
+    if let Some(requests) = &response.requests {
+        return requests
+            .iter()
+            .map(|request| request.request_id.clone())
+            .collect();
+    }
+    if let Some(history) = &response.history {
+        return history
+            .runs
+            .iter()
+            .map(|run| run.run_id.to_string())
+            .chain(
+                history
+                    .variants
+                    .iter()
+                    .filter_map(|variant| variant.init_sha256.clone()),
+            )
+            .collect();
+    }
+    if let Some(batch) = &response.batch {
+        return batch
+            .entries
+            .iter()
+            .filter_map(|entry| entry.instance_id)
+            .map(|id| id.to_string())
+            .collect();
+    }
+    let id = response
+        .create
+        .as_ref()
+        .map(|create| create.summary.id)
+        .or_else(|| response.action.as_ref().map(|action| action.summary.id))
+        .or_else(|| {
+            response
+                .scenario
+                .as_ref()
+                .and_then(|result| result.instance_id)
+        });
+    id.map(|id| id.to_string()).into_iter().collect()
+}
+
+fn print_human(response: &Response) {
+    let mut printed = true;
+    if let Some(instances) = &response.instances {
+        print_instance_table(instances);
+    } else if let Some(create) = &response.create {
+        println!("Created instance {}", create.summary.id);
+        print_summary(&create.summary);
+    } else if let Some(action) = &response.action {
+        print_action(action);
+    } else if let Some(logs) = &response.logs {
+        if !logs.content.is_empty() {
+            println!("{}", logs.content);
+        }
+    } else if let Some(output) = &response.adb_command {
+        print!("{}", output.stdout);
+        eprint!("{}", output.stderr);
+        if output.exit_code != Some(0) {
+            eprintln!(
+                "Command exited with {}",
+                output
+                    .exit_code
+                    .map_or("no exit code".to_string(), |code| code.to_string())
+            );
+        }
+    } else if let Some(batch) = &response.batch {
+        print_batch_table(batch);
+    } else if let Some(scenario) = &response.scenario {
+        print_scenario(scenario);
+    } else if let Some(snapshots) = &response.snapshots {
+        print_snapshot_table(snapshots);
+    } else if let Some(requests) = &response.requests {
+        print_request_table(requests);
+    } else if let Some(metrics) = &response.metrics {
+        print!("{}", metrics);
+    } else if let Some(history) = &response.history {
+        print_history(history);
+    } else if let Some(checks) = &response.doctor {
+        print_doctor(checks);
+    } else {
+        printed = false;
+    }
+
+    if let Some(message) = &response.message {
+        if response.ok {
+            println!("{}", message);
+            printed = true;
+        }
+    }
+    if response.ok {
+        if !printed {
+            println!("ok");
+        }
+        return;
+    }
+    match &response.error {
+        Some(error) => eprintln!(
+            "Error: {}: {}",
+            error.code,
+            error.message.as_deref().unwrap_or("no details")
+        ),
+        None => eprintln!(
+            "Error: {}",
+            response.message.as_deref().unwrap_or("request failed")
+        ),
+    }
+}
+
+/// Columns padded to their widest cell.
+struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new(headers: &[&'static str]) -> Self {
+        Self {
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    fn print(&self) {
+        let mut widths: Vec<usize> = self.headers.iter().map(|header| header.len()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let print_row = |cells: Vec<&str>| {
+            let line: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+                .collect();
+            println!("{}", line.join("  ").trim_end());
+        };
+        print_row(self.headers.clone());
+        for row in &self.rows {
+            print_row(row.iter().map(String::as_str).collect());
+        }
+    }
+}
+
+fn print_instance_table(instances: &[InstanceSummary]) {
+    let mut table = Table::new(&["ID", "STATE", "PURPOSE", "AGE", "ADB SERIAL", "HELD"]);
+    let now = now_secs();
+    for summary in instances {
+        table.push(vec![
+            summary.id.to_string(),
+            summary.state.as_str().to_string(),
+            summary.purpose.clone().unwrap_or_else(|| "-".to_string()),
+            summary.created_at.map_or("-".to_string(), |created| {
+                format_age(now.saturating_sub(created))
+            }),
+            summary
+                .adb
+                .as_ref()
+                .map_or("-".to_string(), |adb| adb.serial.clone()),
+            if summary.held { "yes" } else { "no" }.to_string(),
+        ]);
+    }
+    table.print();
+    for summary in instances {
+        if let Some(error) = &summary.error {
+            eprintln!("instance {}: {}", summary.id, error);
+        }
+    }
+}
+
+/// `Label:` lines with the values lined up.
+fn print_fields(fields: &[(&str, String)]) {
+    let width = fields
+        .iter()
+        .map(|(label, _)| label.len() + 1)
+        .max()
+        .unwrap_or(0);
+    for (label, value) in fields {
+        println!(
+            "{:<width$}  {}",
+            format!("{}:", label),
+            value,
+            width = width
+        );
+    }
+}
+
+fn print_summary(summary: &InstanceSummary) {
+    print_fields(&summary_fields(summary));
+}
+
+fn summary_fields(summary: &InstanceSummary) -> Vec<(&'static str, String)> {
+    let mut fields = vec![
+        ("Instance", summary.id.to_string()),
+        ("State", summary.state.as_str().to_string()),
+    ];
+    if let Some(purpose) = &summary.purpose {
+        fields.push(("Purpose", purpose.clone()));
+    }
+    if let Some(created) = summary.created_at {
+        fields.push(("Age", format_age(now_secs().saturating_sub(created))));
+    }
+    if let Some(adb) = &summary.adb {
+        fields.push(("ADB", adb.serial.clone()));
+    }
+    if summary.held {
+        fields.push(("Held", "yes".to_string()));
+    }
+    if let Some(resources) = &summary.resources {
+        fields.push((
+            "Resources",
+            format!(
+                "{} processes, {} resident, {:.1}s CPU",
+                resources.processes,
+                format_bytes(resources.rss_bytes),
+                resources.cpu_time_ms as f64 / 1000.0
+            ),
+        ));
+    }
+    if let Some(error) = &summary.error {
+        fields.push(("Error", error.clone()));
+    }
+    fields
+}
+
+/// The readable `describe` view, also used for other instance actions.
+fn print_action(action: &InstanceActionResponse) {
+    let mut fields = summary_fields(&action.summary);
+    if let Some(verification) = &action.verification {
+        let mut checks = vec![
+            format!(
+                "adb {}",
+                if verification.adb_ready {
+                    "ready"
+                } else {
+                    "not ready"
+                }
+            ),
+            format!(
+                "boot marker {}",
+                if verification.boot_marker_observed {
+                    "seen"
+                } else {
+                    "not seen"
+                }
+            ),
+        ];
+        if let Some(ms) = verification.ready_after_ms {
+            checks.push(format!("ready after {}", format_ms(ms)));
+        }
+        if let Some(probe) = &verification.readiness_probe {
+            checks.push(format!("probe {}", probe));
+        }
+        if let Some(reason) = &verification.failure_reason {
+            checks.push(format!("failed: {}", reason));
+        }
+        fields.push(("Boot", checks.join(", ")));
+    }
+    if let Some(options) = &action.start_options {
+        fields.push(("Start options", describe_start_options(options)));
+    }
+    if let Some(launch) = &action.launch {
+        fields.push(("Launched", format_age_ago(launch.launched_at)));
+        fields.push(("Command", launch.argv.join(" ")));
+    }
+    if let Some(network) = &action.network {
+        let devices: Vec<String> = network
+            .devices
+            .iter()
+            .map(|device| {
+                format!(
+                    "{}{}",
+                    device.name,
+                    if device.present { "" } else { " (missing)" }
+                )
+            })
+            .collect();
+        let mut value = format!("{:?}", network.mode).to_lowercase();
+        if !devices.is_empty() {
+            value.push_str(&format!(", taps {}", devices.join(" ")));
+        }
+        if let Some(forwards) = network.port_forwards.as_ref().filter(|f| !f.is_empty()) {
+            let forwards: Vec<String> = forwards
+                .iter()
+                .map(|forward| format!("{}->{}", forward.host_port, forward.guest_port))
+                .collect();
+            value.push_str(&format!(", forwards {}", forwards.join(" ")));
+        }
+        fields.push(("Network", value));
+    }
+    if let Some(logcat) = &action.logcat {
+        let status = if logcat.connected {
+            "streaming"
+        } else if logcat.active {
+            "reconnecting"
+        } else {
+            "stopped"
+        };
+        let mut value = format!(
+            "{} ({}, {} written, {} reconnects)",
+            logcat.path,
+            status,
+            format_bytes(logcat.bytes_written),
+            logcat.reconnects
+        );
+        if let Some(error) = &logcat.last_error {
+            value.push_str(&format!(", last error: {}", error));
+        }
+        fields.push(("Logcat", value));
+    }
+    if let Some(usage) = &action.log_usage {
+        let quota = usage.quota_bytes.map_or("no quota".to_string(), |quota| {
+            format!("quota {}", format_bytes(quota))
+        });
+        fields.push((
+            "Log usage",
+            format!("{} ({})", format_bytes(usage.used_bytes), quota),
+        ));
+    }
+    if let Some(path) = &action.console_snapshot_path {
+        fields.push(("Console snapshot", path.clone()));
+    }
+    if let Some(cleanup) = &action.cleanup {
+        let mut value = if cleanup.guest_processes_killed {
+            "guest processes stopped".to_string()
+        } else {
+            format!("processes still running: {:?}", cleanup.remaining_pids)
+        };
+        if !cleanup.steps.is_empty() {
+            value.push_str(&format!(" ({})", cleanup.steps.join(" -> ")));
+        }
+        fields.push(("Cleanup", value));
+    }
+    print_fields(&fields);
+
+    if let Some(usage) = action
+        .log_usage
+        .as_ref()
+        .filter(|usage| !usage.files.is_empty())
+    {
+        println!();
+        let mut table = Table::new(&["LOG", "SIZE", "ROTATED", "PATH"]);
+        for file in &usage.files {
+            table.push(vec![
+                file.source.as_str().to_string(),
+                format_bytes(file.bytes),
+                format!("{} ({})", file.segments, format_bytes(file.segment_bytes)),
+                file.path.clone(),
+            ]);
+        }
+        table.print();
+    }
+    for (title, tail) in [
+        ("Run log", &action.run_log_tail),
+        ("Journal", &action.journal_tail),
+    ] {
+        if let Some(tail) = tail.as_deref().filter(|tail| !tail.trim().is_empty()) {
+            println!("\n{}:\n{}", title, tail.trim_end());
+        }
+    }
+}
+
+fn describe_start_options(options: &StartOptions) -> String {
+    let mut parts = vec![match &options.backend {
+        BackendSpec::Cuttlefish => "cuttlefish".to_string(),
+        BackendSpec::Qemu(qemu) => format!("qemu {}", qemu.kernel),
+    }];
+    parts.push(format!("network {:?}", options.network).to_lowercase());
+    if let Some(track) = &options.track {
+        parts.push(format!("track {}", track));
+    }
+    if let Some(readiness) = &options.readiness {
+        parts.push(format!("readiness {}", describe_probe(readiness)));
+    }
+    if let Some(secs) = options.timeout_secs {
+        parts.push(format!("timeout {}s", secs));
+    }
+    for (enabled, flag) in [
+        (options.verify_boot, "verify-boot"),
+        (options.skip_adb_wait, "skip-adb-wait"),
+        (options.resume, "resume"),
+        (options.disable_webrtc, "no webrtc"),
+    ] {
+        if enabled {
+            parts.push(flag.to_string());
+        }
+    }
+    for forward in &options.port_forwards {
+        parts.push(format!(
+            "forward {}:{}",
+            forward.host_port, forward.guest_port
+        ));
+    }
+    parts.join(", ")
+}
+
+/// The `--readiness` spelling of a probe.
+fn describe_probe(probe: &ReadinessProbeSpec) -> String {
+    match probe {
+        ReadinessProbeSpec::Adb => "adb".to_string(),
+        ReadinessProbeSpec::ConsoleRegex { pattern } => format!("console:{}", pattern),
+        ReadinessProbeSpec::TcpPort {
+            port,
+            host: Some(host),
+        } => format!("tcp:{}:{}", host, port),
+        ReadinessProbeSpec::TcpPort { port, host: None } => format!("tcp:{}", port),
+        ReadinessProbeSpec::ProcessAlive { secs } => format!("alive:{}", secs),
+    }
+}
+
+fn print_batch_table(batch: &BatchResult) {
+    let mut table = Table::new(&[
+        "ARTIFACT",
+        "ID",
+        "RESULT",
+        "READY",
+        "BOOT MARKER",
+        "TOTAL",
+        "ERROR",
+    ]);
+    let format_opt_ms = |ms: Option<u64>| ms.map_or("-".to_string(), format_ms);
+    for entry in &batch.entries {
+        let event = |name: &str| {
+            entry
+                .timeline
+                .iter()
+                .find(|event| event.event == name)
+                .map(|event| event.elapsed_ms)
+        };
+        let boot_marker = match &entry.verification {
+            Some(verification) if verification.boot_marker_observed => "yes",
+            Some(_) => "no",
+            None => "-",
+        };
+        table.push(vec![
+            entry.label.clone(),
+            entry
+                .instance_id
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            if entry.ok { "ok" } else { "FAIL" }.to_string(),
+            format_opt_ms(event("ready")),
+            boot_marker.to_string(),
+            format_opt_ms(entry.timeline.last().map(|event| event.elapsed_ms)),
+            entry
+                .error
+                .as_ref()
+                .map(|error| error.code.clone())
+                .unwrap_or_default(),
+        ]);
+    }
+    table.print();
+    let failed = batch.entries.iter().filter(|entry| !entry.ok).count();
+    println!(
+        "\n{} entries, {} failed, max_parallel={}, wall time {}",
+        batch.entries.len(),
+        failed,
+        batch.max_parallel,
+        format_ms(batch.duration_ms)
+    );
+}
+
+fn print_scenario(result: &ScenarioResult) {
+    let mut table = Table::new(&["STEP", "STATUS", "DURATION", "MESSAGE"]);
+    for step in &result.steps {
+        let status = match step.status {
+            ScenarioStepStatus::Passed => "passed",
+            ScenarioStepStatus::Failed => "FAILED",
+            ScenarioStepStatus::Skipped => "skipped",
+        };
+        table.push(vec![
+            step.name.clone(),
+            status.to_string(),
+            format_ms(step.duration_ms),
+            step.message.clone().unwrap_or_default(),
+        ]);
+    }
+    table.print();
+    println!(
+        "\nScenario {} {} in {}{}",
+        result.name,
+        if result.passed { "passed" } else { "FAILED" },
+        format_ms(result.duration_ms),
+        result
+            .instance_id
+            .map_or(String::new(), |id| format!(" on instance {}", id))
+    );
+}
+
+fn print_snapshot_table(snapshots: &[SnapshotInfo]) {
+    let mut table = Table::new(&["NAME", "CREATED", "BOOT", "INIT BOOT"]);
+    for snapshot in snapshots {
+        table.push(vec![
+            snapshot.name.clone(),
+            format_age_ago(snapshot.created_at),
+            snapshot.boot_image.clone(),
+            snapshot.init_boot_image.clone(),
+        ]);
+    }
+    table.print();
+}
+
+fn print_request_table(requests: &[InFlightRequest]) {
+    let mut table = Table::new(&["REQUEST", "INSTANCE", "RUNNING", "CANCELLED", "DESCRIPTION"]);
+    let now = now_secs();
+    for request in requests {
+        table.push(vec![
+            request.request_id.clone(),
+            request
+                .instance_id
+                .map_or("-".to_string(), |id| id.to_string()),
+            format_age(now.saturating_sub(request.started_at)),
+            if request.cancelled { "yes" } else { "no" }.to_string(),
+            request.description.clone(),
+        ]);
+    }
+    table.print();
+}
+
+fn print_history(history: &HistoryResponse) {
+    if !history.variants.is_empty() {
+        print_variant_table(&history.variants);
+    } else if !history.runs.is_empty() {
+        print_run_table(&history.runs);
+    } else {
+        println!("No recorded runs match.");
+    }
+}
+
+/// One row per check, then the remedies for those that did not pass.
+fn print_doctor(checks: &[DoctorCheck]) {
+    let mut table = Table::new(&["CHECK", "STATUS", "MESSAGE"]);
+    for check in checks {
+        let status = if check.fixed {
+            format!("{} (fixed)", check.status.as_str())
+        } else {
+            check.status.as_str().to_string()
+        };
+        table.push(vec![check.name.clone(), status, check.message.clone()]);
+    }
+    table.print();
+    let hints: Vec<&DoctorCheck> = checks
+        .iter()
+        .filter(|check| !check.fixed && check.hint.is_some())
+        .collect();
+    if !hints.is_empty() {
+        println!();
+        for check in hints {
+            println!("{}: {}", check.name, check.hint.as_deref().unwrap_or(""));
+        }
+    }
+}
+
+fn print_run_table(runs: &[RunRecord]) {
+    let mut table = Table::new(&[
+        "RUN", "INSTANCE", "STARTED", "OUTCOME", "INIT", "ADB", "MARKER", "TOTAL", "FAILURE",
+    ]);
+    for run in runs {
+        table.push(vec![
+            run.run_id.to_string(),
+            run.instance_id.to_string(),
+            format_age_ago(run.started_at),
+            run.outcome.as_str().to_string(),
+            run.init_image
+                .as_ref()
+                .map_or("-".to_string(), |image| short_sha256(&image.sha256)),
+            run.adb_ms.map_or("-".to_string(), format_ms),
+            run.marker_ms.map_or("-".to_string(), format_ms),
+            format_ms(run.duration_ms),
+            run.failure
+                .as_ref()
+                .map_or("-".to_string(), |failure| failure.code.clone()),
+        ]);
+    }
+    table.print();
+}
+
+/// One row per init image; the p50 columns show the change from the first
+/// (baseline) row.
+fn print_variant_table(variants: &[VariantStats]) {
+    let mut table = Table::new(&[
+        "INIT",
+        "RUNS",
+        "OK",
+        "ADB P50",
+        "ADB P90",
+        "MARKER P50",
+        "MARKER P90",
+        "LAST RUN",
+        "IMAGE",
+    ]);
+    let baseline = &variants[0];
+    let p90 = |stats: &Option<TimingStats>| {
+        stats
+            .as_ref()
+            .map_or("-".to_string(), |stats| format_ms(stats.p90))
+    };
+    for (index, variant) in variants.iter().enumerate() {
+        table.push(vec![
+            variant
+                .init_sha256
+                .as_deref()
+                .map_or("-".to_string(), short_sha256),
+            variant.runs.to_string(),
+            variant.ok.to_string(),
+            format_p50(&variant.adb_ms, &baseline.adb_ms, index == 0),
+            p90(&variant.adb_ms),
+            format_p50(&variant.marker_ms, &baseline.marker_ms, index == 0),
+            p90(&variant.marker_ms),
+            format_age_ago(variant.last_run_at),
+            variant
+                .init_image
+                .clone()
+                .unwrap_or_else(|| "-".to_string()),
+        ]);
+    }
+    table.print();
+}
+
+/// The median, with its change from the baseline's unless this is the
+/// baseline.
+fn format_p50(
+    stats: &Option<TimingStats>,
+    baseline: &Option<TimingStats>,
+    is_baseline: bool,
+) -> String {
+    match (stats, baseline) {
+        (None, _) => "-".to_string(),
+        (Some(stats), Some(baseline)) if !is_baseline && baseline.p50 > 0 => {
+            let change = (stats.p50 as f64 / baseline.p50 as f64 - 1.0) * 100.0;
+            format!("{} ({:+.0}%)", format_ms(stats.p50), change)
+        }
+        (Some(stats), _) => format_ms(stats.p50),
+    }
+}
+
+fn short_sha256(sha256: &str) -> String {
+    sha256.chars().take(12).collect()
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// Largest whole unit: `45s`, `12m`, `3h`, `2d`.
+pub fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+fn format_age_ago(timestamp: u64) -> String {
+    format!("{} ago", format_age(now_secs().saturating_sub(timestamp)))
+}
+
+fn format_ms(ms: u64) -> String {
+    format!("{:.1}s", ms as f64 / 1000.0)
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
@@ -0,0 +1,608 @@
+//! `cfctl top`: a live view of every instance. A background thread polls
+//! the daemon so slow requests never block the keyboard, and actions run on
+//! their own threads, reporting back through the same channel.
+
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use cfctl::{
+    client::{Client, ClientConfig},
+    DestroyOptions, InstanceId, InstanceState, InstanceSummary, LogSource, LogsOptions, Request,
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Cell, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+
+use crate::output::{format_age, format_bytes, now_secs};
+
+/// Log sources in the order `l` cycles through them.
+const LOG_SOURCES: [LogSource; 5] = [
+    LogSource::Console,
+    LogSource::Run,
+    LogSource::Logcat,
+    LogSource::Kernel,
+    LogSource::Launcher,
+];
+/// Lines fetched while the log pane fills the screen.
+const ZOOMED_LINES: usize = 500;
+/// A refresh that takes longer than this (e.g. logs of an instance that is
+/// being destroyed, which waits for its lock) is abandoned until next time.
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What the refresher fetches logs for; set by the UI thread.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Focus {
+    id: Option<InstanceId>,
+    source: LogSource,
+    lines: usize,
+}
+
+struct InstanceRow {
+    summary: InstanceSummary,
+    /// CPU use since the previous refresh, in percent of one core.
+    cpu_percent: Option<f64>,
+}
+
+enum Update {
+    Refreshed {
+        instances: Result<Vec<InstanceRow>, String>,
+        focus: Focus,
+        log: Option<Result<String, String>>,
+    },
+    ActionDone {
+        action: Action,
+        id: InstanceId,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Hold,
+    Release,
+    Stop,
+    Destroy,
+}
+
+impl Action {
+    fn request(self, id: InstanceId) -> Request {
+        match self {
+            Self::Hold => Request::HoldInstance { id },
+            Self::Release => Request::ReleaseInstance { id },
+            Self::Stop => Request::StopInstance { id },
+            Self::Destroy => Request::DestroyInstance {
+                id,
+                options: DestroyOptions::default(),
+            },
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Hold => "hold",
+            Self::Release => "release",
+            Self::Stop => "stop",
+            Self::Destroy => "destroy",
+        }
+    }
+}
+
+pub fn run(socket: &Path, interval: Duration, lines: usize) -> Result<()> {
+    if !io::stdout().is_terminal() {
+        bail!("cfctl top needs a terminal; use `cfctl instance list` in scripts");
+    }
+    let client = Client::with_config(ClientConfig {
+        socket: socket.to_path_buf(),
+        request_timeout: Some(REFRESH_TIMEOUT),
+        ..ClientConfig::default()
+    });
+    // Fail before taking over the screen if the daemon is not there.
+    client.list_instances()?;
+
+    let focus = Arc::new(Mutex::new(Focus {
+        id: None,
+        source: LOG_SOURCES[0],
+        lines,
+    }));
+    let (updates_tx, updates) = mpsc::channel();
+    let (wake, wake_rx) = mpsc::channel();
+    {
+        let focus = Arc::clone(&focus);
+        let updates = updates_tx.clone();
+        thread::spawn(move || refresh_loop(client, focus, interval, wake_rx, updates));
+    }
+
+    let mut app = App {
+        socket: socket.to_path_buf(),
+        focus,
+        wake,
+        updates_tx,
+        lines,
+        instances: Vec::new(),
+        list_error: None,
+        table: TableState::default(),
+        log: String::new(),
+        log_error: None,
+        zoomed: false,
+        pending: None,
+        in_flight: Vec::new(),
+        quitting: false,
+        status: None,
+        refreshed_at: None,
+    };
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, &updates);
+    ratatui::restore();
+    result
+}
+
+fn refresh_loop(
+    client: Client,
+    focus: Arc<Mutex<Focus>>,
+    interval: Duration,
+    wake: Receiver<()>,
+    updates: Sender<Update>,
+) {
+    let mut cpu_samples: HashMap<InstanceId, (u64, Instant)> = HashMap::new();
+    loop {
+        let focus = *focus.lock().unwrap();
+        let instances = client
+            .list_instances_with_resources()
+            .map(|summaries| {
+                let now = Instant::now();
+                cpu_samples.retain(|id, _| summaries.iter().any(|summary| summary.id == *id));
+                summaries
+                    .into_iter()
+                    .map(|summary| {
+                        let cpu_percent = summary.resources.and_then(|resources| {
+                            let previous =
+                                cpu_samples.insert(summary.id, (resources.cpu_time_ms, now))?;
+                            let wall_ms = now.duration_since(previous.1).as_millis() as f64;
+                            let cpu_ms = resources.cpu_time_ms.saturating_sub(previous.0) as f64;
+                            (wall_ms > 0.0).then(|| cpu_ms * 100.0 / wall_ms)
+                        });
+                        InstanceRow {
+                            summary,
+                            cpu_percent,
+                        }
+                    })
+                    .collect()
+            })
+            .map_err(|err| err.to_string());
+        let log = focus.id.map(|id| {
+            let options = LogsOptions {
+                source: focus.source,
+                ..LogsOptions::default()
+            };
+            client
+                .logs(id, Some(focus.lines), options)
+                .map(|logs| logs.content)
+                .map_err(|err| err.to_string())
+        });
+        let update = Update::Refreshed {
+            instances,
+            focus,
+            log,
+        };
+        if updates.send(update).is_err() {
+            return;
+        }
+        match wake.recv_timeout(interval) {
+            Ok(()) | Err(RecvTimeoutError::Timeout) => while wake.try_recv().is_ok() {},
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+struct App {
+    socket: PathBuf,
+    focus: Arc<Mutex<Focus>>,
+    wake: Sender<()>,
+    updates_tx: Sender<Update>,
+    lines: usize,
+    instances: Vec<InstanceRow>,
+    list_error: Option<String>,
+    table: TableState,
+    log: String,
+    log_error: Option<String>,
+    zoomed: bool,
+    /// A stop or destroy waiting for `y`.
+    pending: Option<(Action, InstanceId)>,
+    /// Actions whose request is still open; quitting closes their sockets,
+    /// which the daemon treats as a disconnect and cancels.
+    in_flight: Vec<(Action, InstanceId)>,
+    /// Quit was asked for while actions were in flight; top exits once
+    /// they finish.
+    quitting: bool,
+    status: Option<String>,
+    refreshed_at: Option<Instant>,
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal, updates: &Receiver<Update>) -> Result<()> {
+        loop {
+            for update in updates.try_iter() {
+                self.apply(update);
+            }
+            if self.quitting && self.in_flight.is_empty() {
+                return Ok(());
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn selected_id(&self) -> Option<InstanceId> {
+        self.table
+            .selected()
+            .and_then(|index| self.instances.get(index))
+            .map(|row| row.summary.id)
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Refreshed {
+                instances,
+                focus,
+                log,
+            } => {
+                self.refreshed_at = Some(Instant::now());
+                match instances {
+                    Ok(instances) => {
+                        let selected = self.selected_id();
+                        self.instances = instances;
+                        self.list_error = None;
+                        let index = selected
+                            .and_then(|id| {
+                                self.instances.iter().position(|row| row.summary.id == id)
+                            })
+                            .or_else(|| {
+                                (!self.instances.is_empty()).then(|| {
+                                    self.table
+                                        .selected()
+                                        .unwrap_or(0)
+                                        .min(self.instances.len() - 1)
+                                })
+                            });
+                        self.table.select(index);
+                        self.sync_focus();
+                    }
+                    Err(err) => self.list_error = Some(err),
+                }
+                // Logs fetched for an older selection are dropped.
+                if focus == *self.focus.lock().unwrap() {
+                    match log {
+                        Some(Ok(content)) => {
+                            self.log = content;
+                            self.log_error = None;
+                        }
+                        Some(Err(err)) => self.log_error = Some(err),
+                        None => self.log.clear(),
+                    }
+                }
+            }
+            Update::ActionDone {
+                action,
+                id,
+                message,
+            } => {
+                if let Some(index) = self.in_flight.iter().position(|a| *a == (action, id)) {
+                    self.in_flight.remove(index);
+                }
+                self.status = Some(message);
+                let _ = self.wake.send(());
+            }
+        }
+    }
+
+    /// Points the refresher at the selected instance and current log view.
+    fn sync_focus(&mut self) {
+        let id = self.selected_id();
+        let mut focus = self.focus.lock().unwrap();
+        let lines = if self.zoomed {
+            ZOOMED_LINES
+        } else {
+            self.lines
+        };
+        if focus.id != id || focus.lines != lines {
+            focus.id = id;
+            focus.lines = lines;
+            self.log.clear();
+            self.log_error = None;
+            let _ = self.wake.send(());
+        }
+    }
+
+    /// Returns false to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl_c =
+            key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+        if self.quitting {
+            if ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                return false;
+            }
+            self.quitting = false;
+            self.status = Some("quit cancelled".to_string());
+            return true;
+        }
+        if ctrl_c {
+            return self.request_quit();
+        }
+        if let Some((action, id)) = self.pending.take() {
+            if key.code == KeyCode::Char('y') {
+                self.start_action(action, id);
+            } else {
+                self.status = Some(format!("{} of instance {} cancelled", action.name(), id));
+            }
+            return true;
+        }
+        self.status = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return self.request_quit(),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Char('l') => {
+                let mut focus = self.focus.lock().unwrap();
+                let next = LOG_SOURCES
+                    .iter()
+                    .position(|source| *source == focus.source)
+                    .map_or(0, |index| (index + 1) % LOG_SOURCES.len());
+                focus.source = LOG_SOURCES[next];
+                self.log.clear();
+                self.log_error = None;
+                let _ = self.wake.send(());
+            }
+            KeyCode::Enter | KeyCode::Char('z') => {
+                self.zoomed = !self.zoomed;
+                self.sync_focus();
+            }
+            KeyCode::Char('r') => {
+                let _ = self.wake.send(());
+            }
+            KeyCode::Char('h') => {
+                if let Some(row) = self.table.selected().and_then(|i| self.instances.get(i)) {
+                    let action = if row.summary.held {
+                        Action::Release
+                    } else {
+                        Action::Hold
+                    };
+                    self.start_action(action, row.summary.id);
+                }
+            }
+            KeyCode::Char('s') => self.confirm(Action::Stop),
+            KeyCode::Char('d') => self.confirm(Action::Destroy),
+            _ => {}
+        }
+        true
+    }
+
+    /// Returns false to quit now. Quitting closes the sockets of in-flight
+    /// actions and the daemon cancels them, so with any in flight this only
+    /// starts waiting for them instead.
+    fn request_quit(&mut self) -> bool {
+        self.quitting = !self.in_flight.is_empty();
+        self.quitting
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if self.instances.is_empty() {
+            return;
+        }
+        let current = self.table.selected().unwrap_or(0) as isize;
+        let last = self.instances.len() as isize - 1;
+        self.table
+            .select(Some((current + delta).clamp(0, last) as usize));
+        self.sync_focus();
+    }
+
+    fn confirm(&mut self, action: Action) {
+        if let Some(id) = self.selected_id() {
+            self.pending = Some((action, id));
+        }
+    }
+
+    fn start_action(&mut self, action: Action, id: InstanceId) {
+        self.status = Some(format!("{} of instance {} in progress", action.name(), id));
+        // No request timeout: a destroy may legitimately take a while.
+        let client = Client::new(&self.socket);
+        let updates = self.updates_tx.clone();
+        self.in_flight.push((action, id));
+        thread::spawn(move || {
+            let message = match client.call(&action.request(id)) {
+                Ok(_) => format!("{} of instance {} finished", action.name(), id),
+                Err(err) => format!("{} of instance {} failed: {}", action.name(), id, err),
+            };
+            let _ = updates.send(Update::ActionDone {
+                action,
+                id,
+                message,
+            });
+        });
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let log_height = if self.zoomed {
+            Constraint::Min(5)
+        } else {
+            Constraint::Length(self.lines as u16 + 2)
+        };
+        let table_height = if self.zoomed {
+            Constraint::Length(0)
+        } else {
+            Constraint::Min(4)
+        };
+        let [header, table, log, footer] = Layout::vertical([
+            Constraint::Length(1),
+            table_height,
+            log_height,
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        self.draw_header(frame, header);
+        if !self.zoomed {
+            self.draw_table(frame, table);
+        }
+        self.draw_log(frame, log);
+        self.draw_footer(frame, footer);
+    }
+
+    fn draw_header(&self, frame: &mut Frame, area: Rect) {
+        let running = self
+            .instances
+            .iter()
+            .filter(|row| row.summary.state == InstanceState::Running)
+            .count();
+        let refreshed = self.refreshed_at.map_or("never".to_string(), |at| {
+            format!("{}s ago", at.elapsed().as_secs())
+        });
+        let mut text = format!(
+            "cfctl top - {} instances, {} running - refreshed {}",
+            self.instances.len(),
+            running,
+            refreshed
+        );
+        if let Some(err) = &self.list_error {
+            text.push_str(&format!(" - daemon: {}", err));
+        }
+        frame.render_widget(
+            Paragraph::new(text).style(Style::new().add_modifier(Modifier::BOLD)),
+            area,
+        );
+    }
+
+    fn draw_table(&mut self, frame: &mut Frame, area: Rect) {
+        let now = now_secs();
+        let rows = self.instances.iter().map(|row| {
+            let summary = &row.summary;
+            let state_color = match summary.state {
+                InstanceState::Running => Color::Green,
+                InstanceState::Starting => Color::Yellow,
+                InstanceState::Failed => Color::Red,
+                _ => Color::Reset,
+            };
+            let resources = summary.resources;
+            Row::new(vec![
+                Cell::from(summary.id.to_string()),
+                Cell::from(summary.state.as_str()).style(Style::new().fg(state_color)),
+                Cell::from(summary.purpose.clone().unwrap_or_else(|| "-".to_string())),
+                Cell::from(summary.created_at.map_or("-".to_string(), |created| {
+                    format_age(now.saturating_sub(created))
+                })),
+                Cell::from(if summary.held { "yes" } else { "" }),
+                Cell::from(
+                    row.cpu_percent
+                        .map_or("-".to_string(), |percent| format!("{:.0}%", percent)),
+                ),
+                Cell::from(resources.map_or("-".to_string(), |r| format_bytes(r.rss_bytes))),
+                Cell::from(resources.map_or("-".to_string(), |r| r.processes.to_string())),
+                Cell::from(
+                    summary
+                        .adb
+                        .as_ref()
+                        .map_or("-".to_string(), |adb| adb.serial.clone()),
+                ),
+            ])
+        });
+        let widths = [
+            Constraint::Length(4),
+            Constraint::Length(10),
+            Constraint::Fill(1),
+            Constraint::Length(5),
+            Constraint::Length(5),
+            Constraint::Length(6),
+            Constraint::Length(10),
+            Constraint::Length(6),
+            Constraint::Length(22),
+        ];
+        let header = Row::new(vec![
+            "ID",
+            "STATE",
+            "PURPOSE",
+            "AGE",
+            "HELD",
+            "CPU",
+            "MEM",
+            "PROCS",
+            "ADB SERIAL",
+        ])
+        .style(Style::new().add_modifier(Modifier::BOLD));
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::bordered().title(" instances "))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn draw_log(&self, frame: &mut Frame, area: Rect) {
+        let focus = *self.focus.lock().unwrap();
+        let title = match focus.id {
+            Some(id) => format!(" {} log of instance {} ", focus.source.as_str(), id),
+            None => " no instance selected ".to_string(),
+        };
+        let visible = area.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = match &self.log_error {
+            Some(err) => vec![Line::styled(err.clone(), Style::new().fg(Color::Red))],
+            None => {
+                let all: Vec<&str> = self.log.lines().collect();
+                all[all.len().saturating_sub(visible)..]
+                    .iter()
+                    .map(|line| Line::raw(*line))
+                    .collect()
+            }
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+
+    fn draw_footer(&self, frame: &mut Frame, area: Rect) {
+        let (text, style) = match (&self.pending, &self.status) {
+            _ if self.quitting => (
+                format!(
+                    "waiting for {} before quitting; q to quit now and cancel, any other key to stay",
+                    self.in_flight
+                        .iter()
+                        .map(|(action, id)| format!("{} of instance {}", action.name(), id))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            ),
+            (Some((action, id)), _) => (
+                format!("{} instance {}? y to confirm, any other key to cancel", action.name(), id),
+                Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            ),
+            (None, Some(status)) => (status.clone(), Style::new()),
+            (None, None) => (
+                "up/down select  l log source  enter zoom  h hold/release  s stop  d destroy  r refresh  q quit"
+                    .to_string(),
+                Style::new().add_modifier(Modifier::DIM),
+            ),
+        };
+        frame.render_widget(Paragraph::new(text).style(style), area);
+    }
+}
@@ -0,0 +1,641 @@
+//! Clients for the daemon's Unix socket: [`Client`] blocks, [`AsyncClient`]
+//! runs on tokio. Both send one newline-terminated JSON [`Request`] per
+//! connection and read back one [`Response`].
+//!
+//! Connecting retries with backoff until `connect_timeout`, so a client
+//! rides out a daemon restart. Once a request has been sent it is only
+//! retried if it is read-only. A request that runs past `request_timeout` is
+//! abandoned; the daemon notices the closed connection and cancels it.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::Shutdown,
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader},
+    net::UnixStream as AsyncUnixStream,
+    time,
+};
+
+use crate::protocol::{
+    AdbCommandResponse, DeployRequest, DestroyOptions, ErrorDetail, InstanceActionResponse,
+    InstanceId, InstanceSummary, LogsOptions, LogsResponse, Request, Response, StartOptions,
+};
+
+/// Where `cfctl-daemon` listens unless told otherwise.
+pub const DEFAULT_SOCKET: &str = "/run/cfctl.sock";
+
+const CONNECT_RETRY_MIN: Duration = Duration::from_millis(50);
+const CONNECT_RETRY_MAX: Duration = Duration::from_secs(1);
+/// Resends of a read-only request whose connection broke.
+const MAX_RESENDS: usize = 3;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("cannot connect to cfctl daemon at {}: {source}. Is the daemon running?", path.display())]
+    Connect {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("connection to the daemon was interrupted: {0}. The daemon may have crashed or been restarted")]
+    Io(#[from] io::Error),
+    #[error("no response from the daemon within {0:?}")]
+    Timeout(Duration),
+    #[error("received an empty response from the daemon")]
+    EmptyResponse,
+    #[error("failed to decode daemon response as JSON: {source}. Response was: {body}")]
+    Decode {
+        body: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The daemon answered with `ok: false`.
+    #[error("{code}: {}", message.as_deref().unwrap_or("no details"))]
+    Daemon {
+        code: String,
+        message: Option<String>,
+    },
+    #[error("daemon response has no {0}")]
+    MissingField(&'static str),
+}
+
+impl ClientError {
+    /// The daemon's error code, e.g. `wait_for_adb_timeout`.
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Self::Daemon { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    /// The client gave up waiting, or the daemon hit one of its timeouts.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Self::Timeout(_))
+            || self.code().is_some_and(|code| code.ends_with("_timeout"))
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.code().is_some_and(|code| code.ends_with("_cancelled"))
+    }
+}
+
+impl From<ErrorDetail> for ClientError {
+    fn from(detail: ErrorDetail) -> Self {
+        Self::Daemon {
+            code: detail.code,
+            message: detail.message,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub socket: PathBuf,
+    /// How long to keep retrying while the socket is missing or refusing.
+    pub connect_timeout: Duration,
+    /// Ceiling for one request, response included. `None` waits for as long
+    /// as the daemon takes (starts can legitimately run for minutes).
+    pub request_timeout: Option<Duration>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            socket: PathBuf::from(DEFAULT_SOCKET),
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: None,
+        }
+    }
+}
+
+/// Arguments of [`Client::create_start`] besides the start options.
+#[derive(Debug, Clone, Default)]
+pub struct CreateStart {
+    pub purpose: Option<String>,
+    /// Claim a warm instance from the daemon pool when one is available.
+    pub pool: bool,
+    pub boot_image: Option<String>,
+    pub init_boot_image: Option<String>,
+    pub options: StartOptions,
+}
+
+impl From<CreateStart> for Request {
+    fn from(args: CreateStart) -> Self {
+        Request::CreateStartInstance {
+            purpose: args.purpose,
+            options: args.options,
+            pool: args.pool,
+            boot_image: args.boot_image,
+            init_boot_image: args.init_boot_image,
+        }
+    }
+}
+
+/// Requests that change nothing, so they can be sent again after the
+/// connection broke mid-flight.
+fn retry_safe(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::Status { .. }
+            | Request::Describe { .. }
+            | Request::ListInstances { .. }
+            | Request::Logs { .. }
+            | Request::ListSnapshots { .. }
+            | Request::ListRequests
+            | Request::Metrics
+            | Request::History(_)
+            | Request::Doctor { fix: false }
+    )
+}
+
+/// Socket errors that mean the daemon is not (yet) listening.
+fn daemon_unavailable(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+    )
+}
+
+/// The connection broke before a response arrived.
+fn interrupted(err: &ClientError) -> bool {
+    match err {
+        ClientError::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof
+        ),
+        ClientError::EmptyResponse => true,
+        _ => false,
+    }
+}
+
+fn encode(request: &Request) -> Vec<u8> {
+    let mut payload = serde_json::to_vec(request).expect("requests always serialize");
+    payload.push(b'\n');
+    payload
+}
+
+fn decode(line: &str) -> Result<Response, ClientError> {
+    if line.trim().is_empty() {
+        return Err(ClientError::EmptyResponse);
+    }
+    serde_json::from_str(line).map_err(|source| ClientError::Decode {
+        body: line.trim().to_string(),
+        source,
+    })
+}
+
+/// Turns `ok: false` into [`ClientError::Daemon`].
+fn checked(response: Response) -> Result<Response, ClientError> {
+    if response.ok {
+        return Ok(response);
+    }
+    Err(response
+        .error
+        .map(ClientError::from)
+        .unwrap_or_else(|| ClientError::Daemon {
+            code: "unknown".to_string(),
+            message: response.message,
+        }))
+}
+
+fn action(response: Response) -> Result<InstanceActionResponse, ClientError> {
+    checked(response)?
+        .action
+        .ok_or(ClientError::MissingField("instance action"))
+}
+
+fn remaining(
+    deadline: Option<Instant>,
+    timeout: Option<Duration>,
+) -> Result<Option<Duration>, ClientError> {
+    match (deadline, timeout) {
+        (Some(deadline), Some(timeout)) => deadline
+            .checked_duration_since(Instant::now())
+            .filter(|left| !left.is_zero())
+            .map(Some)
+            .ok_or(ClientError::Timeout(timeout)),
+        _ => Ok(None),
+    }
+}
+
+/// Blocking client.
+#[derive(Debug, Clone)]
+pub struct Client {
+    config: ClientConfig,
+}
+
+impl Client {
+    pub fn new(socket: impl AsRef<Path>) -> Self {
+        Self::with_config(ClientConfig {
+            socket: socket.as_ref().to_path_buf(),
+            ..ClientConfig::default()
+        })
+    }
+
+    pub fn with_config(config: ClientConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Sends `request` and returns the daemon's response, `ok: false`
+    /// included.
+    pub fn request(&self, request: &Request) -> Result<Response, ClientError> {
+        let timeout = self.config.request_timeout;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut resends = 0;
+        loop {
+            match self.round_trip(request, deadline) {
+                Err(err) if interrupted(&err) && retry_safe(request) && resends < MAX_RESENDS => {
+                    resends += 1;
+                    remaining(deadline, timeout)?;
+                    thread::sleep(CONNECT_RETRY_MIN);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Like [`request`](Self::request), but daemon errors become
+    /// [`ClientError::Daemon`].
+    pub fn call(&self, request: &Request) -> Result<Response, ClientError> {
+        checked(self.request(request)?)
+    }
+
+    fn round_trip(
+        &self,
+        request: &Request,
+        deadline: Option<Instant>,
+    ) -> Result<Response, ClientError> {
+        let timeout = self.config.request_timeout;
+        let mut stream = self.connect()?;
+        stream.set_write_timeout(remaining(deadline, timeout)?)?;
+        stream
+            .write_all(&encode(request))
+            .map_err(|err| self.io_error(err))?;
+        stream.shutdown(Shutdown::Write)?;
+        stream.set_read_timeout(remaining(deadline, timeout)?)?;
+        let mut line = String::new();
+        BufReader::new(stream)
+            .read_line(&mut line)
+            .map_err(|err| self.io_error(err))?;
+        decode(&line)
+    }
+
+    fn connect(&self) -> Result<UnixStream, ClientError> {
+        let deadline = Instant::now() + self.config.connect_timeout;
+        let mut backoff = CONNECT_RETRY_MIN;
+        loop {
+            match UnixStream::connect(&self.config.socket) {
+                Ok(stream) => return Ok(stream),
+                Err(err) if daemon_unavailable(&err) && Instant::now() + backoff < deadline => {
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(CONNECT_RETRY_MAX);
+                }
+                Err(source) => {
+                    return Err(ClientError::Connect {
+                        path: self.config.socket.clone(),
+                        source,
+                    })
+                }
+            }
+        }
+    }
+
+    fn io_error(&self, err: io::Error) -> ClientError {
+        match (err.kind(), self.config.request_timeout) {
+            (io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut, Some(timeout)) => {
+                ClientError::Timeout(timeout)
+            }
+            _ => ClientError::Io(err),
+        }
+    }
+
+    pub fn create_instance(&self, purpose: Option<String>) -> Result<InstanceSummary, ClientError> {
+        checked(self.request(&Request::CreateInstance { purpose })?)?
+            .create
+            .map(|create| create.summary)
+            .ok_or(ClientError::MissingField("created instance"))
+    }
+
+    /// Creates (or claims from the pool), deploys and starts an instance.
+    pub fn create_start(&self, args: CreateStart) -> Result<InstanceActionResponse, ClientError> {
+        action(self.request(&args.into())?)
+    }
+
+    pub fn start(
+        &self,
+        id: InstanceId,
+        options: StartOptions,
+    ) -> Result<InstanceActionResponse, ClientError> {
+        action(self.request(&Request::StartInstance { id, options })?)
+    }
+
+    pub fn deploy(&self, deploy: DeployRequest) -> Result<Response, ClientError> {
+        self.call(&Request::Deploy(deploy))
+    }
+
+    pub fn status(&self, id: InstanceId) -> Result<InstanceSummary, ClientError> {
+        action(self.request(&Request::Status { id })?).map(|action| action.summary)
+    }
+
+    pub fn describe(&self, id: InstanceId) -> Result<InstanceActionResponse, ClientError> {
+        action(self.request(&Request::Describe {
+            id,
+            run_log_lines: None,
+        })?)
+    }
+
+    pub fn list_instances(&self) -> Result<Vec<InstanceSummary>, ClientError> {
+        checked(self.request(&Request::ListInstances { resources: false })?)?
+            .instances
+            .ok_or(ClientError::MissingField("instances"))
+    }
+
+    /// Like `list_instances`, with resource usage for running guests.
+    pub fn list_instances_with_resources(&self) -> Result<Vec<InstanceSummary>, ClientError> {
+        checked(self.request(&Request::ListInstances { resources: true })?)?
+            .instances
+            .ok_or(ClientError::MissingField("instances"))
+    }
+
+    pub fn logs(
+        &self,
+        id: InstanceId,
+        lines: Option<usize>,
+        options: LogsOptions,
+    ) -> Result<LogsResponse, ClientError> {
+        checked(self.request(&Request::Logs { id, lines, options })?)?
+            .logs
+            .ok_or(ClientError::MissingField("logs"))
+    }
+
+    /// Runs `command` in the guest; a non-zero exit is not an error.
+    pub fn shell(
+        &self,
+        id: InstanceId,
+        command: Vec<String>,
+        timeout_secs: Option<u64>,
+    ) -> Result<AdbCommandResponse, ClientError> {
+        checked(self.request(&Request::Shell {
+            id,
+            command,
+            timeout_secs,
+        })?)?
+        .adb_command
+        .ok_or(ClientError::MissingField("adb command output"))
+    }
+
+    pub fn destroy(
+        &self,
+        id: InstanceId,
+        options: DestroyOptions,
+    ) -> Result<InstanceActionResponse, ClientError> {
+        action(self.request(&Request::DestroyInstance { id, options })?)
+    }
+}
+
+/// Tokio client; the same requests as [`Client`], without blocking.
+#[derive(Debug, Clone)]
+pub struct AsyncClient {
+    config: ClientConfig,
+}
+
+impl AsyncClient {
+    pub fn new(socket: impl AsRef<Path>) -> Self {
+        Self::with_config(ClientConfig {
+            socket: socket.as_ref().to_path_buf(),
+            ..ClientConfig::default()
+        })
+    }
+
+    pub fn with_config(config: ClientConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Sends `request` and returns the daemon's response, `ok: false`
+    /// included. Dropping the future closes the connection, which cancels
+    /// the request on the daemon.
+    pub async fn request(&self, request: &Request) -> Result<Response, ClientError> {
+        let exchange = async {
+            let mut resends = 0;
+            loop {
+                match self.round_trip(request).await {
+                    Err(err)
+                        if interrupted(&err) && retry_safe(request) && resends < MAX_RESENDS =>
+                    {
+                        resends += 1;
+                        time::sleep(CONNECT_RETRY_MIN).await;
+                    }
+                    result => return result,
+                }
+            }
+        };
+        match self.config.request_timeout {
+            Some(timeout) => time::timeout(timeout, exchange)
+                .await
+                .map_err(|_| ClientError::Timeout(timeout))?,
+            None => exchange.await,
+        }
+    }
+
+    /// Like [`request`](Self::request), but daemon errors become
+    /// [`ClientError::Daemon`].
+    pub async fn call(&self, request: &Request) -> Result<Response, ClientError> {
+        checked(self.request(request).await?)
+    }
+
+    async fn round_trip(&self, request: &Request) -> Result<Response, ClientError> {
+        let mut stream = self.connect().await?;
+        stream.write_all(&encode(request)).await?;
+        stream.shutdown().await?;
+        let mut line = String::new();
+        AsyncBufReader::new(stream).read_line(&mut line).await?;
+        decode(&line)
+    }
+
+    async fn connect(&self) -> Result<AsyncUnixStream, ClientError> {
+        let deadline = Instant::now() + self.config.connect_timeout;
+        let mut backoff = CONNECT_RETRY_MIN;
+        loop {
+            match AsyncUnixStream::connect(&self.config.socket).await {
+                Ok(stream) => return Ok(stream),
+                Err(err) if daemon_unavailable(&err) && Instant::now() + backoff < deadline => {
+                    time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(CONNECT_RETRY_MAX);
+                }
+                Err(source) => {
+                    return Err(ClientError::Connect {
+                        path: self.config.socket.clone(),
+                        source,
+                    })
+                }
+            }
+        }
+    }
+
+    pub async fn create_instance(
+        &self,
+        purpose: Option<String>,
+    ) -> Result<InstanceSummary, ClientError> {
+        checked(self.request(&Request::CreateInstance { purpose }).await?)?
+            .create
+            .map(|create| create.summary)
+            .ok_or(ClientError::MissingField("created instance"))
+    }
+
+    /// Creates (or claims from the pool), deploys and starts an instance.
+    pub async fn create_start(
+        &self,
+        args: CreateStart,
+    ) -> Result<InstanceActionResponse, ClientError> {
+        action(self.request(&args.into()).await?)
+    }
+
+    pub async fn start(
+        &self,
+        id: InstanceId,
+        options: StartOptions,
+    ) -> Result<InstanceActionResponse, ClientError> {
+        action(
+            self.request(&Request::StartInstance { id, options })
+                .await?,
+        )
+    }
+
+    pub async fn deploy(&self, deploy: DeployRequest) -> Result<Response, ClientError> {
+        self.call(&Request::Deploy(deploy)).await
+    }
+
+    pub async fn status(&self, id: InstanceId) -> Result<InstanceSummary, ClientError> {
+        action(self.request(&Request::Status { id }).await?).map(|action| action.summary)
+    }
+
+    pub async fn describe(&self, id: InstanceId) -> Result<InstanceActionResponse, ClientError> {
+        action(
+            self.request(&Request::Describe {
+                id,
+                run_log_lines: None,
+            })
+            .await?,
+        )
+    }
+
+    pub async fn list_instances(&self) -> Result<Vec<InstanceSummary>, ClientError> {
+        checked(
+            self.request(&Request::ListInstances { resources: false })
+                .await?,
+        )?
+        .instances
+        .ok_or(ClientError::MissingField("instances"))
+    }
+
+    /// Like `list_instances`, with resource usage for running guests.
+    pub async fn list_instances_with_resources(&self) -> Result<Vec<InstanceSummary>, ClientError> {
+        checked(
+            self.request(&Request::ListInstances { resources: true })
+                .await?,
+        )?
+        .instances
+        .ok_or(ClientError::MissingField("instances"))
+    }
+
+    pub async fn logs(
+        &self,
+        id: InstanceId,
+        lines: Option<usize>,
+        options: LogsOptions,
+    ) -> Result<LogsResponse, ClientError> {
+        checked(self.request(&Request::Logs { id, lines, options }).await?)?
+            .logs
+            .ok_or(ClientError::MissingField("logs"))
+    }
+
+    /// Runs `command` in the guest; a non-zero exit is not an error.
+    pub async fn shell(
+        &self,
+        id: InstanceId,
+        command: Vec<String>,
+        timeout_secs: Option<u64>,
+    ) -> Result<AdbCommandResponse, ClientError> {
+        checked(
+            self.request(&Request::Shell {
+                id,
+                command,
+                timeout_secs,
+            })
+            .await?,
+        )?
+        .adb_command
+        .ok_or(ClientError::MissingField("adb command output"))
+    }
+
+    pub async fn destroy(
+        &self,
+        id: InstanceId,
+        options: DestroyOptions,
+    ) -> Result<InstanceActionResponse, ClientError> {
+        action(
+            self.request(&Request::DestroyInstance { id, options })
+                .await?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn waits_for_the_daemon_and_times_out_slow_requests() -> anyhow::Result<()> {
+        let temp = tempfile::tempdir()?;
+        let socket = temp.path().join("cfctl.sock");
+        let client = Client::with_config(ClientConfig {
+            socket: socket.clone(),
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Some(Duration::from_secs(1)),
+        });
+
+        // The daemon comes up after the client starts connecting, answers
+        // one request with an error and then stops answering.
+        let server = thread::spawn(move || -> io::Result<()> {
+            thread::sleep(Duration::from_millis(200));
+            let listener = UnixListener::bind(&socket)?;
+            let (stream, _) = listener.accept()?;
+            let mut request = String::new();
+            let mut reader = BufReader::new(stream);
+            reader.read_line(&mut request)?;
+            writeln!(
+                reader.get_mut(),
+                r#"{{"ok":false,"error":{{"code":"instance_not_found","message":"no such instance"}}}}"#
+            )?;
+            let (_silent, _) = listener.accept()?;
+            thread::sleep(Duration::from_secs(2));
+            Ok(())
+        });
+
+        let err = client.status(7).unwrap_err();
+        assert_eq!(err.code(), Some("instance_not_found"));
+        assert_eq!(err.to_string(), "instance_not_found: no such instance");
+        let err = client.list_instances().unwrap_err();
+        assert!(matches!(err, ClientError::Timeout(_)), "{:?}", err);
+        assert!(err.is_timeout());
+        server.join().unwrap()?;
+        Ok(())
+    }
+}
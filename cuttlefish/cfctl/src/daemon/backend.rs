@@ -0,0 +1,674 @@
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, Context, Result};
+use tracing::{debug, info, warn};
+
+use crate::protocol::{
+    BackendSpec, InstanceId, NetworkMode, QemuOptions, ReadinessProbeSpec, StartOptions,
+};
+
+use super::config::CfctlDaemonConfig;
+
+/// Default QEMU guest memory, matching `qemu-init/run.sh` in GUI mode.
+const QEMU_DEFAULT_MEMORY_MIB: u32 = 2048;
+const QEMU_DEFAULT_BINARY: &str = "qemu-system-x86_64";
+/// QMP socket created by cuttlefish's qemu_cli VM manager. The qemu backend
+/// uses the same name so snapshots work for both.
+pub(super) const QEMU_MONITOR_SOCKET: &str = "qemu_monitor.sock";
+
+/// Everything a backend needs to build the launch command for one start.
+pub(super) struct LaunchContext<'a> {
+    pub config: &'a CfctlDaemonConfig,
+    pub id: InstanceId,
+    pub instance_dir: &'a Path,
+    pub assembly_dir: &'a Path,
+    pub boot_image: &'a Path,
+    pub init_boot_image: &'a Path,
+    pub adb_port: u16,
+    pub options: &'a StartOptions,
+    pub resume: bool,
+}
+
+/// A way of launching a guest VM. The manager owns the lifecycle (metadata,
+/// run log, readiness, exit watching, cleanup); a backend only knows how to
+/// build the process and where its console ends up.
+pub(super) trait GuestBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Rejects start options the backend cannot honour.
+    fn validate(&self, _options: &StartOptions) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Host setup run after the instance directories exist, before launch.
+    fn prepare(&self, _ctx: &LaunchContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Builds the launch command. The manager sets stdio and spawns it.
+    fn command(&self, ctx: &LaunchContext) -> Result<Command>;
+
+    /// Builds a command that assembles the instance's disks without booting
+    /// it, so a later launch with `resume` skips that work. `None` when the
+    /// backend has no separate assembly step.
+    fn assemble_command(&self, _ctx: &LaunchContext) -> Option<Result<Command>> {
+        None
+    }
+
+    /// Where the guest's serial console is written.
+    fn console_log_path(&self, instance_dir: &Path, id: InstanceId) -> PathBuf;
+
+    /// Where the guest kernel log is written, if the backend keeps one.
+    fn kernel_log_path(&self, _instance_dir: &Path, _id: InstanceId) -> Option<PathBuf> {
+        None
+    }
+
+    /// The launcher's own log file, if it writes one besides its stdout.
+    fn launcher_log_path(&self, _instance_dir: &Path, _id: InstanceId) -> Option<PathBuf> {
+        None
+    }
+
+    /// The QMP socket of the guest's QEMU, if the backend runs QEMU.
+    fn monitor_socket_path(&self, _instance_dir: &Path, _id: InstanceId) -> Option<PathBuf> {
+        None
+    }
+
+    /// Readiness probe used when the start request does not pick one.
+    fn default_readiness(&self) -> ReadinessProbeSpec;
+
+    /// `pgrep -f` patterns matching every process the guest left behind.
+    fn process_patterns(
+        &self,
+        instance_dir: &Path,
+        _assembly_dir: &Path,
+        _id: InstanceId,
+    ) -> Vec<String> {
+        vec![format!("{}/", instance_dir.display())]
+    }
+
+    /// Whether the guest serves adb on the instance's ADB port.
+    fn has_adb(&self) -> bool {
+        true
+    }
+
+    /// Whether the launcher uses cuttlefish's shared host state: the
+    /// `cvd-*tap-NN` devices, `/var/lib/cuttlefish` config and permissions.
+    fn uses_cuttlefish_host(&self) -> bool {
+        false
+    }
+}
+
+pub(super) fn backend_for(config: &CfctlDaemonConfig, spec: &BackendSpec) -> Box<dyn GuestBackend> {
+    match spec {
+        BackendSpec::Cuttlefish => match &config.fake_guest {
+            Some(binary) => Box::new(FakeBackend {
+                binary: binary.clone(),
+            }),
+            None => Box::new(CuttlefishBackend),
+        },
+        BackendSpec::Qemu(options) => Box::new(QemuBackend {
+            options: options.clone(),
+        }),
+    }
+}
+
+/// Runs `launch_cvd` inside the cuttlefish FHS wrapper (or a cfenv track).
+pub(super) struct CuttlefishBackend;
+
+impl GuestBackend for CuttlefishBackend {
+    fn name(&self) -> &'static str {
+        "cuttlefish"
+    }
+
+    fn prepare(&self, ctx: &LaunchContext) -> Result<()> {
+        ensure_qemu_datadir(ctx.config)
+    }
+
+    fn command(&self, ctx: &LaunchContext) -> Result<Command> {
+        cvd_command(ctx, "launch_cvd")
+    }
+
+    fn assemble_command(&self, ctx: &LaunchContext) -> Option<Result<Command>> {
+        Some(cvd_command(ctx, "assemble_cvd"))
+    }
+
+    fn console_log_path(&self, instance_dir: &Path, id: InstanceId) -> PathBuf {
+        cvd_dir(instance_dir, id).join("console_log")
+    }
+
+    fn kernel_log_path(&self, instance_dir: &Path, id: InstanceId) -> Option<PathBuf> {
+        Some(cvd_dir(instance_dir, id).join("kernel.log"))
+    }
+
+    fn launcher_log_path(&self, instance_dir: &Path, id: InstanceId) -> Option<PathBuf> {
+        Some(cvd_dir(instance_dir, id).join("launcher.log"))
+    }
+
+    /// The qemu_cli VM manager keeps its monitor in the runtime dir's
+    /// `internal` directory.
+    fn monitor_socket_path(&self, instance_dir: &Path, id: InstanceId) -> Option<PathBuf> {
+        Some(
+            cvd_dir(instance_dir, id)
+                .join("internal")
+                .join(QEMU_MONITOR_SOCKET),
+        )
+    }
+
+    fn default_readiness(&self) -> ReadinessProbeSpec {
+        ReadinessProbeSpec::Adb
+    }
+
+    fn process_patterns(
+        &self,
+        instance_dir: &Path,
+        assembly_dir: &Path,
+        id: InstanceId,
+    ) -> Vec<String> {
+        vec![
+            format!("--instance_dir={}", instance_dir.display()),
+            format!("--assembly_dir={}", assembly_dir.display()),
+            format!("{}/", instance_dir.display()),
+            format!("{}/", assembly_dir.display()),
+            format!("cvd-{}", id),
+        ]
+    }
+
+    fn uses_cuttlefish_host(&self) -> bool {
+        true
+    }
+}
+
+/// Runs `program` (`launch_cvd` or `assemble_cvd`, which take the same
+/// flags) for the instance inside the FHS wrapper or a cfenv track.
+fn cvd_command(ctx: &LaunchContext, program: &str) -> Result<Command> {
+    let inst_name = ctx.id.to_string();
+    // Preserve CUTTLEFISH_* environment variables that we set below
+    let mut cmd = guest_user_command(
+        ctx.config,
+        &[
+            "CUTTLEFISH_INSTANCE",
+            "CUTTLEFISH_INSTANCE_NUM",
+            "CUTTLEFISH_ADB_TCP_PORT",
+            "CUTTLEFISH_DISABLE_HOST_GPU",
+            "GFXSTREAM_DISABLE_GRAPHICS_DETECTOR",
+            "GFXSTREAM_HEADLESS",
+        ],
+    )?;
+
+    // Use cfenv if track specified, otherwise direct FHS wrapper
+    if let Some(t) = ctx.options.track.as_deref() {
+        info!(target: "cfctl", "spawn_guest_process: using track '{}'", t);
+        cmd.arg("cfenv").arg("-t").arg(t).arg("--");
+    } else {
+        info!(target: "cfctl", "spawn_guest_process: using default FHS wrapper");
+        cmd.arg(&ctx.config.cuttlefish_fhs).arg("--");
+    };
+    let webrtc_enabled = !ctx.options.disable_webrtc;
+    cmd.arg(program)
+        .arg(format!(
+            "--system_image_dir={}",
+            ctx.config.cuttlefish_system_image_dir.display()
+        ))
+        .arg(format!("--instance_dir={}", ctx.instance_dir.display()))
+        .arg(format!("--assembly_dir={}", ctx.assembly_dir.display()))
+        .arg("--vm_manager=qemu_cli")
+        .arg("--enable_wifi=false")
+        .arg("--enable_host_bluetooth=false")
+        .arg("--enable_modem_simulator=false")
+        .arg(format!(
+            "--start_webrtc={}",
+            if webrtc_enabled { "true" } else { "false" }
+        ))
+        .arg(format!(
+            "--start_webrtc_sig_server={}",
+            if webrtc_enabled { "true" } else { "false" }
+        ))
+        .arg("--report_anonymous_usage_stats=n")
+        .arg("--daemon=false")
+        .arg("--console=true")
+        // Keep ttyS0 attached so the persisted console_log captures Android init chatter.
+        .arg("--extra_kernel_cmdline=console=ttyS0,115200")
+        .arg("--verbosity=DEBUG")
+        .arg(format!("--resume={}", ctx.resume));
+
+    if ctx.boot_image.exists() {
+        cmd.arg(format!("--boot_image={}", ctx.boot_image.display()));
+    } else {
+        debug!(
+            target: "cfctl",
+            "spawn_guest_process: boot image {} not found; skipping flag",
+            ctx.boot_image.display()
+        );
+    }
+    if ctx.init_boot_image.exists() {
+        cmd.arg(format!(
+            "--init_boot_image={}",
+            ctx.init_boot_image.display()
+        ));
+    } else {
+        debug!(
+            target: "cfctl",
+            "spawn_guest_process: init boot image {} not found; skipping flag",
+            ctx.init_boot_image.display()
+        );
+    }
+
+    if ctx.config.disable_host_gpu {
+        cmd.env("CUTTLEFISH_DISABLE_HOST_GPU", "1");
+    }
+
+    cmd.env("GFXSTREAM_DISABLE_GRAPHICS_DETECTOR", "1");
+    cmd.env("GFXSTREAM_HEADLESS", "1");
+
+    cmd.env("CUTTLEFISH_INSTANCE", &inst_name);
+    cmd.env("CUTTLEFISH_INSTANCE_NUM", inst_name.clone());
+    cmd.env("CUTTLEFISH_ADB_TCP_PORT", ctx.adb_port.to_string());
+
+    if let Some(parent) = ctx.instance_dir.parent() {
+        cmd.current_dir(parent);
+    }
+    Ok(cmd)
+}
+
+/// Runs the `cfctl-fake-guest` stub in place of `launch_cvd`, playing the
+/// instance's init_boot image as a guest script. It writes the console log
+/// where cuttlefish would and serves adb on the instance port, so the
+/// manager's lifecycle, probes and cleanup run unchanged.
+pub(super) struct FakeBackend {
+    binary: PathBuf,
+}
+
+impl GuestBackend for FakeBackend {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn command(&self, ctx: &LaunchContext) -> Result<Command> {
+        let mut cmd = Command::new(&self.binary);
+        cmd.arg("launch")
+            .arg("--instance-dir")
+            .arg(ctx.instance_dir)
+            .arg("--script")
+            .arg(ctx.init_boot_image)
+            .arg("--console")
+            .arg(self.console_log_path(ctx.instance_dir, ctx.id))
+            .arg("--adb-port")
+            .arg(ctx.adb_port.to_string())
+            .arg("--resume")
+            .arg(ctx.resume.to_string());
+        cmd.current_dir(ctx.instance_dir);
+        Ok(cmd)
+    }
+
+    fn assemble_command(&self, ctx: &LaunchContext) -> Option<Result<Command>> {
+        let mut cmd = Command::new(&self.binary);
+        cmd.arg("assemble")
+            .arg("--instance-dir")
+            .arg(ctx.instance_dir);
+        Some(Ok(cmd))
+    }
+
+    fn console_log_path(&self, instance_dir: &Path, id: InstanceId) -> PathBuf {
+        CuttlefishBackend.console_log_path(instance_dir, id)
+    }
+
+    fn kernel_log_path(&self, instance_dir: &Path, id: InstanceId) -> Option<PathBuf> {
+        CuttlefishBackend.kernel_log_path(instance_dir, id)
+    }
+
+    fn launcher_log_path(&self, instance_dir: &Path, id: InstanceId) -> Option<PathBuf> {
+        CuttlefishBackend.launcher_log_path(instance_dir, id)
+    }
+
+    fn monitor_socket_path(&self, instance_dir: &Path, id: InstanceId) -> Option<PathBuf> {
+        CuttlefishBackend.monitor_socket_path(instance_dir, id)
+    }
+
+    fn default_readiness(&self) -> ReadinessProbeSpec {
+        ReadinessProbeSpec::Adb
+    }
+}
+
+/// Boots a kernel and initramfs directly with QEMU: virtio-gpu plus virtio
+/// keyboard/mouse, no display, serial console to a file in the instance dir.
+pub(super) struct QemuBackend {
+    options: QemuOptions,
+}
+
+impl GuestBackend for QemuBackend {
+    fn name(&self) -> &'static str {
+        "qemu"
+    }
+
+    fn validate(&self, options: &StartOptions) -> Result<(), String> {
+        if options.track.is_some() {
+            return Err(
+                "tracks select a cuttlefish environment and do not apply to the qemu backend"
+                    .to_string(),
+            );
+        }
+        if options.resume {
+            return Err("the qemu backend has no disk overlay to resume".to_string());
+        }
+        if !options.port_forwards.is_empty() {
+            return Err(
+                "port forwards are set up over adb, which the qemu backend does not provide"
+                    .to_string(),
+            );
+        }
+        if matches!(options.readiness, Some(ReadinessProbeSpec::Adb)) {
+            return Err(
+                "the qemu backend does not provide adb; use a console, tcp or alive probe"
+                    .to_string(),
+            );
+        }
+        for (label, path) in [
+            ("kernel", &self.options.kernel),
+            ("initramfs", &self.options.initramfs),
+        ] {
+            if path.is_empty() {
+                return Err(format!("the qemu backend requires a {} path", label));
+            }
+        }
+        Ok(())
+    }
+
+    fn prepare(&self, _ctx: &LaunchContext) -> Result<()> {
+        for path in [&self.options.kernel, &self.options.initramfs] {
+            if !Path::new(path).is_file() {
+                return Err(anyhow!("qemu backend: {} does not exist", path));
+            }
+        }
+        Ok(())
+    }
+
+    fn command(&self, ctx: &LaunchContext) -> Result<Command> {
+        let mut cmd = guest_user_command(ctx.config, &[])?;
+        let mut append = "console=ttyS0 init=/init panic=1".to_string();
+        if let Some(extra) = self.options.append.as_deref() {
+            append.push(' ');
+            append.push_str(extra.trim());
+        }
+        let monitor = ctx.instance_dir.join(QEMU_MONITOR_SOCKET);
+        cmd.arg(
+            self.options
+                .binary
+                .as_deref()
+                .unwrap_or(QEMU_DEFAULT_BINARY),
+        )
+        .arg("-name")
+        .arg(format!("cfctl-{}", ctx.id))
+        .arg("-kernel")
+        .arg(&self.options.kernel)
+        .arg("-initrd")
+        .arg(&self.options.initramfs)
+        .arg("-append")
+        .arg(append)
+        .arg("-m")
+        .arg(format!(
+            "{}M",
+            self.options.memory_mib.unwrap_or(QEMU_DEFAULT_MEMORY_MIB)
+        ))
+        .arg("-smp")
+        .arg(self.options.cpus.unwrap_or(1).to_string())
+        .args(["-accel", "kvm", "-accel", "tcg"])
+        .args(["-device", "virtio-gpu-pci"])
+        .args(["-device", "virtio-keyboard-pci"])
+        .args(["-device", "virtio-mouse-pci"])
+        .args(["-vga", "none", "-display", "none"])
+        // Appending lets the log worker truncate the console log in place.
+        .arg("-chardev")
+        .arg(format!(
+            "file,id=serial0,append=on,path={}",
+            self.console_log_path(ctx.instance_dir, ctx.id).display()
+        ))
+        .args(["-serial", "chardev:serial0"])
+        .arg("-qmp")
+        .arg(format!("unix:{},server=on,wait=off", monitor.display()))
+        .arg("-no-reboot");
+        if ctx.options.network == NetworkMode::Isolated {
+            cmd.args(["-nic", "none"]);
+        }
+        cmd.args(&self.options.extra_args);
+        cmd.current_dir(ctx.instance_dir);
+        Ok(cmd)
+    }
+
+    fn console_log_path(&self, instance_dir: &Path, _id: InstanceId) -> PathBuf {
+        instance_dir.join("console_log")
+    }
+
+    fn monitor_socket_path(&self, instance_dir: &Path, _id: InstanceId) -> Option<PathBuf> {
+        Some(instance_dir.join(QEMU_MONITOR_SOCKET))
+    }
+
+    /// The kernel logs to ttyS0, so its messages are the console log.
+    fn kernel_log_path(&self, instance_dir: &Path, id: InstanceId) -> Option<PathBuf> {
+        Some(self.console_log_path(instance_dir, id))
+    }
+
+    fn has_adb(&self) -> bool {
+        false
+    }
+
+    fn default_readiness(&self) -> ReadinessProbeSpec {
+        // The launcher is QEMU itself, so surviving a few seconds means the
+        // kernel did not panic straight away (run with panic=1 and -no-reboot).
+        ReadinessProbeSpec::ProcessAlive { secs: 3 }
+    }
+}
+
+/// Per-instance directory launch_cvd creates under `--instance_dir`.
+fn cvd_dir(instance_dir: &Path, id: InstanceId) -> PathBuf {
+    instance_dir.join("instances").join(format!("cvd-{}", id))
+}
+
+/// `sudo -u <guest_user> -g <group> [--preserve-env=...] -- [setpriv ...]`,
+/// ready for the guest program to be appended.
+fn guest_user_command(config: &CfctlDaemonConfig, preserve_vars: &[&str]) -> Result<Command> {
+    // Use sudo to switch user with configured primary group before entering FHS
+    // This preserves the group through bubblewrap's namespace isolation
+    let target_user = &config.guest_user;
+    let primary_group = &config.guest_primary_group;
+
+    // Resolve UIDs/GIDs for logging
+    let uid = resolve_uid(target_user)?;
+    let gid = resolve_gid(primary_group)?;
+
+    info!(
+        target: "cfctl",
+        "spawn_guest_process: resolved credentials uid={}:{} gid={}:{} caps={:?}",
+        target_user, uid, primary_group, gid, config.guest_capabilities
+    );
+
+    let mut cmd = Command::new("sudo");
+    cmd.arg("-u").arg(target_user).arg("-g").arg(primary_group);
+    for var in preserve_vars {
+        cmd.arg(format!("--preserve-env={}", var));
+    }
+    cmd.arg("--");
+
+    // Add setpriv to set ambient capabilities if any are configured
+    let caps: Vec<String> = config
+        .guest_capabilities
+        .iter()
+        .map(|c| c.trim())
+        .filter(|c| !c.is_empty())
+        .map(|c| {
+            if c.starts_with('+') || c.starts_with('-') {
+                c.to_string()
+            } else {
+                format!("+{}", c)
+            }
+        })
+        .collect();
+    if !caps.is_empty() {
+        let caps_arg = caps.join(",");
+        cmd.arg("setpriv")
+            .arg("--ambient-caps")
+            .arg(&caps_arg)
+            .arg("--");
+    }
+    Ok(cmd)
+}
+
+/// Resolve a username to a UID using libc getpwnam
+pub(super) fn resolve_uid(username: &str) -> Result<u32> {
+    use std::ffi::CString;
+    let cname =
+        CString::new(username).with_context(|| format!("invalid username: {}", username))?;
+    unsafe {
+        let pwd = libc::getpwnam(cname.as_ptr());
+        if pwd.is_null() {
+            anyhow::bail!("user '{}' not found", username);
+        }
+        Ok((*pwd).pw_uid)
+    }
+}
+
+/// Resolve a group name to a GID using libc getgrnam
+pub(super) fn resolve_gid(groupname: &str) -> Result<u32> {
+    use std::ffi::CString;
+    let cname =
+        CString::new(groupname).with_context(|| format!("invalid group name: {}", groupname))?;
+    unsafe {
+        let grp = libc::getgrnam(cname.as_ptr());
+        if grp.is_null() {
+            anyhow::bail!("group '{}' not found", groupname);
+        }
+        Ok((*grp).gr_gid)
+    }
+}
+
+pub(super) fn ensure_qemu_datadir(config: &CfctlDaemonConfig) -> Result<()> {
+    let datadir = Path::new("/var/lib/cuttlefish/usr/share/qemu/x86_64-linux-gnu");
+    fs::create_dir_all(datadir)
+        .with_context(|| format!("creating qemu datadir {}", datadir.display()))?;
+    let target = datadir.join("kvmvapic.bin");
+    if target.exists() {
+        return Ok(());
+    }
+
+    debug!(
+        target: "cfctl",
+        "ensure_qemu_datadir: populating {} from cuttlefish-fhs",
+        target.display()
+    );
+    let output = Command::new(&config.cuttlefish_fhs)
+        .args(["--", "cat", "/usr/share/qemu/kvmvapic.bin"])
+        .output()
+        .with_context(|| {
+            format!(
+                "ensure_qemu_datadir: invoking {} to read kvmvapic.bin",
+                config.cuttlefish_fhs.display()
+            )
+        })?;
+    if !output.status.success() {
+        warn!(
+            target: "cfctl",
+            "ensure_qemu_datadir: failed to read kvmvapic.bin via cuttlefish-fhs (status: {})",
+            output.status
+        );
+        return Ok(());
+    }
+
+    fs::write(&target, &output.stdout)
+        .with_context(|| format!("ensure_qemu_datadir: writing {}", target.display()))?;
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o644)).with_context(|| {
+        format!(
+            "ensure_qemu_datadir: setting permissions on {}",
+            target.display()
+        )
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(cmd: &Command) -> Vec<String> {
+        cmd.get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn qemu_backend_boots_kernel_with_serial_to_instance_dir() -> Result<()> {
+        let config = CfctlDaemonConfig {
+            guest_user: "root".to_string(),
+            guest_primary_group: "root".to_string(),
+            guest_capabilities: Vec::new(),
+            ..CfctlDaemonConfig::default()
+        };
+        let options = StartOptions {
+            network: NetworkMode::Isolated,
+            backend: BackendSpec::Qemu(QemuOptions {
+                kernel: "/images/bzImage".to_string(),
+                initramfs: "/images/initramfs.cpio.gz".to_string(),
+                append: Some("quiet".to_string()),
+                ..QemuOptions::default()
+            }),
+            ..StartOptions::default()
+        };
+        let backend = backend_for(&config, &options.backend);
+        assert_eq!(backend.name(), "qemu");
+        backend.validate(&options).map_err(|err| anyhow!(err))?;
+
+        let instance_dir = Path::new("/var/lib/cuttlefish/instances/4");
+        let ctx = LaunchContext {
+            config: &config,
+            id: 4,
+            instance_dir,
+            assembly_dir: Path::new("/var/lib/cuttlefish/assembly/4"),
+            boot_image: Path::new("/unused/boot.img"),
+            init_boot_image: Path::new("/unused/init_boot.img"),
+            adb_port: 6523,
+            options: &options,
+            resume: false,
+        };
+        let cmd = backend.command(&ctx)?;
+        let args = args(&cmd);
+        let after = |flag: &str| {
+            let pos = args.iter().position(|arg| arg == flag).unwrap();
+            args[pos + 1].clone()
+        };
+        assert_eq!(after("-u"), "root");
+        assert_eq!(after("-kernel"), "/images/bzImage");
+        assert_eq!(after("-initrd"), "/images/initramfs.cpio.gz");
+        assert_eq!(after("-append"), "console=ttyS0 init=/init panic=1 quiet");
+        assert_eq!(
+            after("-chardev"),
+            "file,id=serial0,append=on,path=/var/lib/cuttlefish/instances/4/console_log"
+        );
+        assert_eq!(after("-serial"), "chardev:serial0");
+        assert_eq!(after("-nic"), "none");
+        assert!(args.contains(&"virtio-gpu-pci".to_string()));
+        assert!(args.contains(&"virtio-keyboard-pci".to_string()));
+        assert_eq!(
+            backend.console_log_path(instance_dir, 4),
+            instance_dir.join("console_log")
+        );
+        let monitor = backend.monitor_socket_path(instance_dir, 4).unwrap();
+        assert_eq!(
+            after("-qmp"),
+            format!("unix:{},server=on,wait=off", monitor.display())
+        );
+        assert_eq!(
+            CuttlefishBackend.monitor_socket_path(instance_dir, 4),
+            Some(instance_dir.join("instances/cvd-4/internal/qemu_monitor.sock"))
+        );
+
+        let with_track = StartOptions {
+            track: Some("production".to_string()),
+            ..options
+        };
+        assert!(backend.validate(&with_track).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+use std::{sync::Arc, time::Instant};
+
+use tokio::{sync::Semaphore, task};
+use tracing::{info, warn};
+
+use crate::protocol::{
+    BatchEntry, BatchEntryResult, BatchRequest, BatchResult, BatchTimelineEvent, DeployRequest,
+    DestroyOptions, ErrorDetail, InstanceId, Request, Response, StartOptions,
+};
+
+use super::cancel::CancelToken;
+use super::util::host_guest_capacity;
+use super::CfctlDaemon;
+
+/// Host resources assumed per guest when deriving the default concurrency.
+const GUEST_CPUS: usize = 4;
+const GUEST_MEMORY_MIB: u64 = 4096;
+
+struct BatchEntryRun {
+    label: String,
+    entry: BatchEntry,
+    purpose: Option<String>,
+    start: StartOptions,
+    keep_instance: bool,
+    destroy_timeout_secs: Option<u64>,
+}
+
+impl CfctlDaemon {
+    /// Boots every batch entry on its own instance, bounded by the host
+    /// capacity, and reports per-entry verification results and timelines.
+    pub(super) async fn run_batch(&self, batch: BatchRequest, cancel: &CancelToken) -> Response {
+        if batch.entries.is_empty() {
+            return Response::error_with_detail(ErrorDetail {
+                code: "batch_invalid".to_string(),
+                message: Some("batch requires at least one entry".to_string()),
+            });
+        }
+
+        let capacity = host_guest_capacity(GUEST_CPUS, GUEST_MEMORY_MIB);
+        let max_parallel = batch.max_parallel.unwrap_or(capacity).clamp(1, capacity);
+        info!(
+            target: "cfctl",
+            "run_batch: {} entries, max_parallel={} (host capacity {})",
+            batch.entries.len(),
+            max_parallel,
+            capacity
+        );
+
+        let started = Instant::now();
+        let semaphore = Arc::new(Semaphore::new(max_parallel));
+        let mut tasks = Vec::with_capacity(batch.entries.len());
+        let mut labels = Vec::with_capacity(batch.entries.len());
+        for (index, entry) in batch.entries.into_iter().enumerate() {
+            let label = entry.label.clone().unwrap_or_else(|| {
+                entry
+                    .init_boot_image
+                    .clone()
+                    .or_else(|| entry.boot_image.clone())
+                    .unwrap_or_else(|| format!("entry-{}", index))
+            });
+            labels.push(label.clone());
+            let run = BatchEntryRun {
+                label,
+                entry,
+                purpose: batch.purpose.clone(),
+                start: batch.start.clone(),
+                keep_instance: batch.keep_instances,
+                destroy_timeout_secs: batch.destroy_timeout_secs,
+            };
+            let daemon = self.clone();
+            let semaphore = Arc::clone(&semaphore);
+            let cancel = cancel.clone();
+            tasks.push(task::spawn(async move {
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("batch semaphore closed");
+                daemon.run_batch_entry(run, &cancel).await
+            }));
+        }
+
+        let mut entries = Vec::with_capacity(tasks.len());
+        for (handle, label) in tasks.into_iter().zip(labels) {
+            match handle.await {
+                Ok(result) => entries.push(result),
+                Err(err) => entries.push(BatchEntryResult {
+                    label,
+                    instance_id: None,
+                    ok: false,
+                    verification: None,
+                    error: Some(ErrorDetail {
+                        code: "batch_entry_panicked".to_string(),
+                        message: Some(err.to_string()),
+                    }),
+                    timeline: Vec::new(),
+                    cleanup: None,
+                }),
+            }
+        }
+
+        let failed = entries.iter().filter(|entry| !entry.ok).count();
+        let result = BatchResult {
+            max_parallel,
+            duration_ms: started.elapsed().as_millis() as u64,
+            entries,
+        };
+        if failed == 0 {
+            Response {
+                batch: Some(result),
+                ..Response::ok()
+            }
+        } else {
+            let total = result.entries.len();
+            Response {
+                batch: Some(result),
+                ..Response::error_with_detail(ErrorDetail {
+                    code: "batch_failed".to_string(),
+                    message: Some(format!("{} of {} entries failed", failed, total)),
+                })
+            }
+        }
+    }
+
+    async fn run_batch_entry(&self, run: BatchEntryRun, cancel: &CancelToken) -> BatchEntryResult {
+        let started = Instant::now();
+        let mut result = BatchEntryResult {
+            label: run.label,
+            instance_id: None,
+            ok: false,
+            verification: None,
+            error: None,
+            timeline: Vec::new(),
+            cleanup: None,
+        };
+        let mark = |result: &mut BatchEntryResult, event: &str| {
+            push_event(result, event, elapsed_ms(started));
+        };
+
+        let outcome = self
+            .boot_batch_entry(
+                &run.entry,
+                run.purpose,
+                run.start,
+                &mut result,
+                started,
+                cancel,
+            )
+            .await;
+        match outcome {
+            Ok(()) => result.ok = true,
+            Err(detail) => {
+                warn!(
+                    target: "cfctl",
+                    "run_batch: entry {} failed: {:?}",
+                    result.label,
+                    detail
+                );
+                mark(&mut result, "failed");
+                result.error = Some(detail);
+            }
+        }
+
+        if let (Some(id), false) = (result.instance_id, run.keep_instance) {
+            let options = DestroyOptions {
+                timeout_secs: run.destroy_timeout_secs,
+            };
+            // Teardown runs to completion even if the client went away.
+            match self
+                .dispatch_checked(
+                    Request::DestroyInstance { id, options },
+                    &CancelToken::new(),
+                )
+                .await
+            {
+                Ok(response) => {
+                    result.cleanup = response.action.and_then(|action| action.cleanup);
+                    mark(&mut result, "destroyed");
+                }
+                Err(detail) => {
+                    warn!(
+                        target: "cfctl",
+                        "run_batch: destroy of {} failed: {:?}",
+                        id,
+                        detail
+                    );
+                    mark(&mut result, "destroy_failed");
+                    if result.error.is_none() {
+                        result.ok = false;
+                        result.error = Some(detail);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    async fn boot_batch_entry(
+        &self,
+        entry: &BatchEntry,
+        purpose: Option<String>,
+        start: StartOptions,
+        result: &mut BatchEntryResult,
+        started: Instant,
+        cancel: &CancelToken,
+    ) -> Result<(), ErrorDetail> {
+        let purpose = purpose.or_else(|| Some(format!("batch:{}", result.label)));
+        let id: InstanceId = self
+            .dispatch_checked(Request::CreateInstance { purpose }, cancel)
+            .await?
+            .create
+            .map(|create| create.summary.id)
+            .ok_or_else(|| ErrorDetail {
+                code: "batch_create_failed".to_string(),
+                message: Some("create returned no instance".to_string()),
+            })?;
+        result.instance_id = Some(id);
+        push_event(result, "created", elapsed_ms(started));
+
+        if entry.boot_image.is_some() || entry.init_boot_image.is_some() {
+            let request = Request::Deploy(DeployRequest {
+                id,
+                boot_image: entry.boot_image.clone(),
+                init_boot_image: entry.init_boot_image.clone(),
+            });
+            self.dispatch_checked(request, cancel).await?;
+            push_event(result, "deployed", elapsed_ms(started));
+        }
+
+        let launched_ms = elapsed_ms(started);
+        let response = self
+            .dispatch_checked(Request::StartInstance { id, options: start }, cancel)
+            .await?;
+        if let Some(action) = response.action {
+            // The start reports its milestones relative to the launch.
+            let mut milestones: Vec<(&str, u64)> = [
+                ("adb_ready", action.adb_ms),
+                ("boot_marker", action.marker_ms),
+            ]
+            .into_iter()
+            .filter_map(|(event, ms)| Some((event, ms?)))
+            .collect();
+            milestones.sort_by_key(|(_, ms)| *ms);
+            for (event, ms) in milestones {
+                push_event(result, event, launched_ms + ms);
+            }
+            result.verification = action.verification;
+        }
+        push_event(result, "ready", elapsed_ms(started));
+        Ok(())
+    }
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
+fn push_event(result: &mut BatchEntryResult, event: &str, elapsed_ms: u64) {
+    result.timeline.push(BatchTimelineEvent {
+        event: event.to_string(),
+        elapsed_ms,
+    });
+}
@@ -0,0 +1,162 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use tokio::{sync::Notify, time};
+
+use crate::protocol::{InFlightRequest, InstanceId};
+
+use super::util::epoch_secs;
+
+/// Cooperative cancellation for one in-flight request. Clones share state;
+/// long waits in the instance manager select on [`CancelToken::cancelled`].
+#[derive(Debug, Clone, Default)]
+pub(super) struct CancelToken {
+    inner: Arc<CancelState>,
+}
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelToken {
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(super) fn cancel(&self) {
+        if !self.inner.cancelled.swap(true, Ordering::SeqCst) {
+            self.inner.notify.notify_waiters();
+        }
+    }
+
+    pub(super) fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once [`CancelToken::cancel`] has been called.
+    pub(super) async fn cancelled(&self) {
+        // A `Notified` receives `notify_waiters` from the moment it is
+        // created, so checking the flag afterwards cannot miss a cancel.
+        let notified = self.inner.notify.notified();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// The requests the daemon is working on, keyed by the id reported to
+/// `ListRequests` and accepted by `Cancel`.
+#[derive(Debug, Clone, Default)]
+pub(super) struct RequestTracker {
+    next_id: Arc<AtomicU64>,
+    requests: Arc<DashMap<String, Tracked>>,
+    finished: Arc<Notify>,
+}
+
+#[derive(Debug)]
+struct Tracked {
+    description: String,
+    instance_id: Option<InstanceId>,
+    started_at: u64,
+    cancel: CancelToken,
+}
+
+impl RequestTracker {
+    /// Records a new in-flight request. It stays listed until the returned
+    /// guard is dropped.
+    pub(super) fn register(
+        &self,
+        description: String,
+        instance_id: Option<InstanceId>,
+    ) -> TrackedRequest {
+        let request_id = format!("r{}", self.next_id.fetch_add(1, Ordering::SeqCst) + 1);
+        let cancel = CancelToken::new();
+        self.requests.insert(
+            request_id.clone(),
+            Tracked {
+                description,
+                instance_id,
+                started_at: epoch_secs().unwrap_or_default(),
+                cancel: cancel.clone(),
+            },
+        );
+        TrackedRequest {
+            request_id,
+            cancel,
+            tracker: self.clone(),
+        }
+    }
+
+    /// Lists in-flight requests, oldest first.
+    pub(super) fn list(&self) -> Vec<InFlightRequest> {
+        let mut requests: Vec<InFlightRequest> = self
+            .requests
+            .iter()
+            .map(|entry| InFlightRequest {
+                request_id: entry.key().clone(),
+                description: entry.description.clone(),
+                instance_id: entry.instance_id,
+                started_at: entry.started_at,
+                cancelled: entry.cancel.is_cancelled(),
+            })
+            .collect();
+        requests.sort_by_key(|request| request.request_id[1..].parse::<u64>().unwrap_or(0));
+        requests
+    }
+
+    /// Cancels `request_id` and waits up to `wait` for it to finish rolling
+    /// back. Returns the request's description, and whether it finished, or
+    /// `None` if no such request is in flight.
+    pub(super) async fn cancel(&self, request_id: &str, wait: Duration) -> Option<(String, bool)> {
+        let description = {
+            let tracked = self.requests.get(request_id)?;
+            tracked.cancel.cancel();
+            tracked.description.clone()
+        };
+        let finished = time::timeout(wait, async {
+            loop {
+                let notified = self.finished.notified();
+                if !self.requests.contains_key(request_id) {
+                    return;
+                }
+                notified.await;
+            }
+        })
+        .await
+        .is_ok();
+        Some((description, finished))
+    }
+}
+
+/// An in-flight request's registration and cancellation token.
+pub(super) struct TrackedRequest {
+    request_id: String,
+    cancel: CancelToken,
+    tracker: RequestTracker,
+}
+
+impl TrackedRequest {
+    pub(super) fn id(&self) -> &str {
+        &self.request_id
+    }
+
+    pub(super) fn token(&self) -> &CancelToken {
+        &self.cancel
+    }
+}
+
+impl Drop for TrackedRequest {
+    fn drop(&mut self) {
+        self.tracker.requests.remove(&self.request_id);
+        self.tracker.finished.notify_waiters();
+    }
+}
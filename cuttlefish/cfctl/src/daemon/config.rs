@@ -1,6 +1,14 @@
-use std::path::PathBuf;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use super::slots::MAX_INSTANCE_NUM;
+
 #[derive(Debug, Clone)]
 pub struct CfctlDaemonConfig {
     pub socket_path: PathBuf,
@@ -10,6 +18,8 @@ pub struct CfctlDaemonConfig {
     pub default_init_boot_image: PathBuf,
     pub start_timeout: Duration,
     pub adb_wait_timeout: Duration,
+    /// Bounds each QEMU monitor read/write of a snapshot, restore or delete.
+    pub snapshot_timeout: Duration,
     pub journal_lines: usize,
     pub adb_host: String,
     pub base_adb_port: u16,
@@ -17,10 +27,53 @@ pub struct CfctlDaemonConfig {
     pub cuttlefish_instances_dir: PathBuf,
     pub cuttlefish_assembly_dir: PathBuf,
     pub cuttlefish_system_image_dir: PathBuf,
+    /// The only host directory `Push` reads from and `Pull` writes to.
+    pub transfer_dir: PathBuf,
     pub disable_host_gpu: bool,
     pub guest_user: String,
     pub guest_primary_group: String,
     pub guest_capabilities: Vec<String>,
+    /// Number of warm instances kept ready for `CreateStartInstance { pool: true }`.
+    pub pool_size: usize,
+    /// Also create the (empty) host instance/assembly directories and qemu
+    /// datadir for pooled instances. Images are still assembled at start.
+    pub pool_precreate_dirs: bool,
+    /// Also run `assemble_cvd` for pooled instances, so a claim that keeps
+    /// the default images boots with `--resume` instead of assembling.
+    /// Implies `pool_precreate_dirs`.
+    pub pool_preassemble: bool,
+    /// Launch cuttlefish-backend guests with this `cfctl-fake-guest` binary
+    /// instead of `launch_cvd` (hermetic tests).
+    pub fake_guest: Option<PathBuf>,
+    /// Record `adb logcat` of every running adb-capable guest to disk.
+    pub logcat_capture: bool,
+    /// Rotate the logcat capture once it reaches this size.
+    pub logcat_rotate_bytes: u64,
+    /// Rotated logcat files kept per instance.
+    pub logcat_rotate_keep: usize,
+    /// Rotate run and console logs once they reach this size.
+    pub log_rotate_bytes: u64,
+    /// Rotated (compressed) run and console logs kept per instance.
+    pub log_rotate_keep: usize,
+    /// Per-instance cap on run, console and logcat logs including rotated
+    /// copies; the oldest copies are deleted first. 0 disables the cap.
+    pub log_quota_bytes: u64,
+    /// How often running instances' logs are checked for rotation.
+    pub log_check_interval: Duration,
+    /// Also serve Prometheus metrics over HTTP at `http://<addr>/metrics`.
+    pub metrics_addr: Option<SocketAddr>,
+    /// Refuse to create instances once this many exist, warm ones included.
+    /// 0 disables the limit.
+    pub max_instances: usize,
+    /// Refuse to start a guest while this many are running. 0 disables the
+    /// limit.
+    pub max_running: usize,
+    /// Prune unheld instances older than this in the background.
+    pub prune_max_age: Option<Duration>,
+    /// How often background pruning runs.
+    pub prune_interval: Duration,
+    /// TOML file layered over these settings at startup and on SIGHUP.
+    pub config_file: Option<PathBuf>,
 }
 
 impl Default for CfctlDaemonConfig {
@@ -33,6 +86,7 @@ impl Default for CfctlDaemonConfig {
             default_init_boot_image: PathBuf::from("/var/lib/cuttlefish/images/init_boot.img"),
             start_timeout: Duration::from_secs(120),
             adb_wait_timeout: Duration::from_secs(90),
+            snapshot_timeout: Duration::from_secs(300),
             journal_lines: 200,
             adb_host: "127.0.0.1".to_string(),
             base_adb_port: 6520,
@@ -40,10 +94,439 @@ impl Default for CfctlDaemonConfig {
             cuttlefish_instances_dir: PathBuf::from("/var/lib/cuttlefish/instances"),
             cuttlefish_assembly_dir: PathBuf::from("/var/lib/cuttlefish/assembly"),
             cuttlefish_system_image_dir: PathBuf::from("/var/lib/cuttlefish/images"),
+            transfer_dir: PathBuf::from("/var/lib/cfctl/transfer"),
             disable_host_gpu: true,
-            guest_user: "justin".to_string(),
+            // No default: the guest user is host specific.
+            guest_user: String::new(),
             guest_primary_group: "cvdnetwork".to_string(),
             guest_capabilities: vec!["net_admin".to_string()],
+            pool_size: 0,
+            pool_precreate_dirs: false,
+            pool_preassemble: false,
+            fake_guest: None,
+            logcat_capture: true,
+            logcat_rotate_bytes: 32 * 1024 * 1024,
+            logcat_rotate_keep: 4,
+            log_rotate_bytes: 16 * 1024 * 1024,
+            log_rotate_keep: 5,
+            log_quota_bytes: 512 * 1024 * 1024,
+            log_check_interval: Duration::from_secs(15),
+            metrics_addr: None,
+            max_instances: 0,
+            max_running: 0,
+            prune_max_age: None,
+            prune_interval: Duration::from_secs(600),
+            config_file: None,
+        }
+    }
+}
+
+impl CfctlDaemonConfig {
+    /// This config with `config_file` (if any) layered over it, validated.
+    pub fn load(&self) -> Result<Self> {
+        let mut config = self.clone();
+        let mut problems = Vec::new();
+        if let Some(path) = &self.config_file {
+            problems = ConfigFile::read(path)?.apply(&mut config);
+        }
+        config.check(&mut problems);
+        invalid(problems)?;
+        Ok(config)
+    }
+
+    /// Reports every invalid setting at once.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        self.check(&mut problems);
+        invalid(problems)
+    }
+
+    fn check(&self, problems: &mut Vec<String>) {
+        if self.guest_user.trim().is_empty() {
+            problems.push("guest_user is not set (--guest-user or [guest] user)".to_string());
+        }
+        if self.guest_primary_group.trim().is_empty() {
+            problems.push("guest_primary_group is empty".to_string());
+        }
+        for capability in &self.guest_capabilities {
+            let name = capability.trim().trim_start_matches(['+', '-']);
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+                problems.push(format!("invalid guest capability {:?}", capability));
+            }
+        }
+        for (name, path) in [
+            ("socket", &self.socket_path),
+            ("state_dir", &self.state_dir),
+            ("etc_instances_dir", &self.etc_instances_dir),
+            ("cuttlefish_instances_dir", &self.cuttlefish_instances_dir),
+            ("cuttlefish_assembly_dir", &self.cuttlefish_assembly_dir),
+            ("transfer_dir", &self.transfer_dir),
+        ] {
+            if !path.is_absolute() {
+                problems.push(format!("{} must be an absolute path", name));
+            }
+        }
+        for (name, value) in [
+            ("start_timeout", self.start_timeout),
+            ("adb_wait_timeout", self.adb_wait_timeout),
+            ("snapshot_timeout", self.snapshot_timeout),
+            ("log_check_interval", self.log_check_interval),
+            ("prune_interval", self.prune_interval),
+        ] {
+            if value.is_zero() {
+                problems.push(format!("{} must be greater than zero", name));
+            }
+        }
+        if self.prune_max_age.is_some_and(|age| age.is_zero()) {
+            problems.push("prune max_age must be greater than zero".to_string());
+        }
+        let last_port = u64::from(self.base_adb_port) + MAX_INSTANCE_NUM - 1;
+        if self.base_adb_port == 0 || last_port > u64::from(u16::MAX) {
+            problems.push(format!(
+                "base_adb_port {} leaves no room for {} instances",
+                self.base_adb_port, MAX_INSTANCE_NUM
+            ));
+        }
+        if self.max_instances as u64 > MAX_INSTANCE_NUM {
+            problems.push(format!(
+                "max_instances {} exceeds the {} instance slots",
+                self.max_instances, MAX_INSTANCE_NUM
+            ));
+        }
+        if self.max_instances > 0 && self.pool_size > self.max_instances {
+            problems.push(format!(
+                "pool_size {} exceeds max_instances {}",
+                self.pool_size, self.max_instances
+            ));
+        }
+    }
+
+    /// `next` with the settings that cannot change while the daemon runs
+    /// (listeners, directories holding instance state, slot numbering) kept
+    /// from `self`, plus the names of those that `next` tried to change.
+    pub(super) fn reloaded(&self, mut next: Self) -> (Self, Vec<&'static str>) {
+        let mut kept = Vec::new();
+        macro_rules! keep {
+            ($($field:ident),*) => {$(
+                if next.$field != self.$field {
+                    kept.push(stringify!($field));
+                    next.$field = self.$field.clone();
+                }
+            )*};
+        }
+        keep!(
+            socket_path,
+            state_dir,
+            etc_instances_dir,
+            cuttlefish_instances_dir,
+            cuttlefish_assembly_dir,
+            adb_host,
+            base_adb_port,
+            fake_guest,
+            metrics_addr,
+            config_file
+        );
+        (next, kept)
+    }
+}
+
+/// The current config, replaced as a whole when the daemon reloads.
+/// Requests take a snapshot when they start and keep it until they finish.
+#[derive(Clone)]
+pub(super) struct SharedConfig(Arc<RwLock<Arc<CfctlDaemonConfig>>>);
+
+impl SharedConfig {
+    pub fn new(config: CfctlDaemonConfig) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(config))))
+    }
+
+    pub fn current(&self) -> Arc<CfctlDaemonConfig> {
+        Arc::clone(&self.0.read().unwrap())
+    }
+
+    pub fn replace(&self, config: CfctlDaemonConfig) {
+        *self.0.write().unwrap() = Arc::new(config);
+    }
+}
+
+/// `--config` file contents. Every setting is optional and overrides the
+/// matching flag; unknown keys are errors so typos do not pass silently.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    paths: PathsSection,
+    timeouts: TimeoutsSection,
+    guest: GuestSection,
+    adb: AdbSection,
+    capacity: CapacitySection,
+    prune: PruneSection,
+    logs: LogsSection,
+    metrics: MetricsSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PathsSection {
+    socket: Option<PathBuf>,
+    state_dir: Option<PathBuf>,
+    etc_instances_dir: Option<PathBuf>,
+    default_boot_image: Option<PathBuf>,
+    default_init_boot_image: Option<PathBuf>,
+    cuttlefish_fhs: Option<PathBuf>,
+    cuttlefish_instances_dir: Option<PathBuf>,
+    cuttlefish_assembly_dir: Option<PathBuf>,
+    cuttlefish_system_image_dir: Option<PathBuf>,
+    transfer_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TimeoutsSection {
+    start_secs: Option<u64>,
+    adb_secs: Option<u64>,
+    snapshot_secs: Option<u64>,
+    log_check_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GuestSection {
+    user: Option<String>,
+    primary_group: Option<String>,
+    capabilities: Option<Vec<String>>,
+    disable_host_gpu: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AdbSection {
+    host: Option<String>,
+    base_port: Option<u16>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CapacitySection {
+    max_instances: Option<usize>,
+    max_running: Option<usize>,
+    pool_size: Option<usize>,
+    pool_precreate_dirs: Option<bool>,
+    pool_preassemble: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PruneSection {
+    /// 0 turns background pruning off.
+    max_age_secs: Option<u64>,
+    interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LogsSection {
+    journal_lines: Option<usize>,
+    logcat_capture: Option<bool>,
+    logcat_rotate_mib: Option<u64>,
+    logcat_rotate_keep: Option<usize>,
+    rotate_mib: Option<u64>,
+    rotate_keep: Option<usize>,
+    quota_mib: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MetricsSection {
+    addr: Option<SocketAddr>,
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("reading config file {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("parsing config file {}", path.display()))
+    }
+
+    /// Layers the file's settings over `config`, returning the values that
+    /// could not be applied.
+    fn apply(self, config: &mut CfctlDaemonConfig) -> Vec<String> {
+        let Self {
+            paths,
+            timeouts,
+            guest,
+            adb,
+            capacity,
+            prune,
+            logs,
+            metrics,
+        } = self;
+        let secs = |secs: Option<u64>| secs.map(Duration::from_secs);
+        let mut problems = Vec::new();
+        let mut mib = |name: &str, mib: Option<u64>| {
+            let mib = mib?;
+            let bytes = mib.checked_mul(1024 * 1024);
+            if bytes.is_none() {
+                problems.push(format!("logs {} {} is too large", name, mib));
+            }
+            bytes
+        };
+
+        set(&mut config.socket_path, paths.socket);
+        set(&mut config.state_dir, paths.state_dir);
+        set(&mut config.etc_instances_dir, paths.etc_instances_dir);
+        set(&mut config.default_boot_image, paths.default_boot_image);
+        set(
+            &mut config.default_init_boot_image,
+            paths.default_init_boot_image,
+        );
+        set(&mut config.cuttlefish_fhs, paths.cuttlefish_fhs);
+        set(
+            &mut config.cuttlefish_instances_dir,
+            paths.cuttlefish_instances_dir,
+        );
+        set(
+            &mut config.cuttlefish_assembly_dir,
+            paths.cuttlefish_assembly_dir,
+        );
+        set(
+            &mut config.cuttlefish_system_image_dir,
+            paths.cuttlefish_system_image_dir,
+        );
+        set(&mut config.transfer_dir, paths.transfer_dir);
+
+        set(&mut config.start_timeout, secs(timeouts.start_secs));
+        set(&mut config.adb_wait_timeout, secs(timeouts.adb_secs));
+        set(&mut config.snapshot_timeout, secs(timeouts.snapshot_secs));
+        set(
+            &mut config.log_check_interval,
+            secs(timeouts.log_check_secs),
+        );
+
+        set(&mut config.guest_user, guest.user);
+        set(&mut config.guest_primary_group, guest.primary_group);
+        set(&mut config.guest_capabilities, guest.capabilities);
+        set(&mut config.disable_host_gpu, guest.disable_host_gpu);
+
+        set(&mut config.adb_host, adb.host);
+        set(&mut config.base_adb_port, adb.base_port);
+
+        set(&mut config.max_instances, capacity.max_instances);
+        set(&mut config.max_running, capacity.max_running);
+        set(&mut config.pool_size, capacity.pool_size);
+        set(
+            &mut config.pool_precreate_dirs,
+            capacity.pool_precreate_dirs,
+        );
+        set(&mut config.pool_preassemble, capacity.pool_preassemble);
+
+        if let Some(max_age) = prune.max_age_secs {
+            config.prune_max_age = (max_age > 0).then(|| Duration::from_secs(max_age));
+        }
+        set(&mut config.prune_interval, secs(prune.interval_secs));
+
+        set(&mut config.journal_lines, logs.journal_lines);
+        set(&mut config.logcat_capture, logs.logcat_capture);
+        set(
+            &mut config.logcat_rotate_bytes,
+            mib("logcat_rotate_mib", logs.logcat_rotate_mib),
+        );
+        set(&mut config.logcat_rotate_keep, logs.logcat_rotate_keep);
+        set(
+            &mut config.log_rotate_bytes,
+            mib("rotate_mib", logs.rotate_mib),
+        );
+        set(&mut config.log_rotate_keep, logs.rotate_keep);
+        set(
+            &mut config.log_quota_bytes,
+            mib("quota_mib", logs.quota_mib),
+        );
+
+        if metrics.addr.is_some() {
+            config.metrics_addr = metrics.addr;
+        }
+        problems
+    }
+}
+
+fn invalid(problems: Vec<String>) -> Result<()> {
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("invalid daemon config: {}", problems.join("; ")))
+    }
+}
+
+fn set<T>(field: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *field = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_overrides_flags_and_reload_keeps_structural_settings() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let path = temp.path().join("cfctl.toml");
+        fs::write(
+            &path,
+            r#"
+[guest]
+user = "cuttlefish"
+capabilities = ["net_admin", "-sys_admin"]
+
+[adb]
+base_port = 7000
+
+[capacity]
+max_instances = 8
+
+[prune]
+max_age_secs = 3600
+"#,
+        )?;
+        let flags = CfctlDaemonConfig {
+            guest_user: "root".to_string(),
+            pool_size: 2,
+            config_file: Some(path.clone()),
+            ..CfctlDaemonConfig::default()
+        };
+        let config = flags.load()?;
+        assert_eq!(config.guest_user, "cuttlefish");
+        assert_eq!(config.base_adb_port, 7000);
+        assert_eq!((config.max_instances, config.pool_size), (8, 2));
+        assert_eq!(config.prune_max_age, Some(Duration::from_secs(3600)));
+
+        fs::write(
+            &path,
+            "[adb]\nbase_port = 7100\n[guest]\nuser = \"builder\"\n",
+        )?;
+        let (reloaded, kept) = config.reloaded(flags.load()?);
+        assert_eq!(kept, ["base_adb_port"]);
+        assert_eq!(reloaded.base_adb_port, 7000);
+        assert_eq!(reloaded.guest_user, "builder");
+        assert_eq!(reloaded.max_instances, 0);
+
+        fs::write(&path, "[capacity]\nmax_instance = 3\n")?;
+        let err = format!("{:#}", flags.load().unwrap_err());
+        assert!(err.contains("unknown field `max_instance`"), "{}", err);
+
+        fs::write(
+            &path,
+            "[guest]\nuser = \"\"\ncapabilities = [\"net admin\"]\n[timeouts]\nstart_secs = 0\n[capacity]\nmax_instances = 1\n[logs]\nquota_mib = 9223372036854775807\n",
+        )?;
+        let err = format!("{:#}", flags.load().unwrap_err());
+        for problem in [
+            "guest_user is not set",
+            "invalid guest capability \"net admin\"",
+            "start_timeout must be greater than zero",
+            "pool_size 2 exceeds max_instances 1",
+            "logs quota_mib 9223372036854775807 is too large",
+        ] {
+            assert!(err.contains(problem), "{}", err);
         }
+        Ok(())
     }
 }
@@ -0,0 +1,502 @@
+use std::{
+    collections::BTreeSet,
+    ffi::CString,
+    fs::{self, OpenOptions},
+    io,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+use tracing::{info, warn};
+
+use crate::protocol::{CheckStatus, DoctorCheck, InstanceId};
+
+use super::backend::{resolve_gid, resolve_uid};
+use super::config::CfctlDaemonConfig;
+use super::util::run_command_capture;
+
+/// Written by launch_cvd for the running group; a leftover one makes the
+/// next launch pick up the previous run's config.
+pub const CUTTLEFISH_CONFIG_LINK: &str = "/var/lib/cuttlefish/.cuttlefish_config.json";
+
+const KVM_DEVICE: &str = "/dev/kvm";
+const NET_CLASS_DIR: &str = "/sys/class/net";
+
+/// Free space below which launches are likely to fail part way through
+/// assembling the instance's disks.
+const DISK_FAIL_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+/// Free space below which a couple more instances may exhaust the disk.
+const DISK_WARN_BYTES: u64 = 20 * 1024 * 1024 * 1024;
+
+/// Lists the instances whose tap devices and cuttlefish config belong to a
+/// live launch and must be left alone.
+pub type InUse<'a> = &'a dyn Fn() -> Result<BTreeSet<InstanceId>>;
+
+/// Host preflight checks. `in_use` is asked again right before each fix, so
+/// a launch that began after the checks keeps its devices and config.
+pub struct Doctor<'a> {
+    config: &'a CfctlDaemonConfig,
+    in_use: InUse<'a>,
+    fix: bool,
+}
+
+impl<'a> Doctor<'a> {
+    pub fn new(config: &'a CfctlDaemonConfig, in_use: InUse<'a>, fix: bool) -> Self {
+        Self {
+            config,
+            in_use,
+            fix,
+        }
+    }
+
+    pub fn run(&self) -> Vec<DoctorCheck> {
+        vec![
+            self.kvm(),
+            self.guest_user(),
+            self.guest_group(),
+            self.cuttlefish_fhs(),
+            self.network_devices(),
+            self.cuttlefish_config(),
+            self.disk_space(),
+        ]
+    }
+
+    fn kvm(&self) -> DoctorCheck {
+        const NAME: &str = "kvm";
+        let metadata = match fs::metadata(KVM_DEVICE) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                // Fake guests never touch KVM, so a test host without it is fine.
+                let status = if self.config.fake_guest.is_some() {
+                    CheckStatus::Warn
+                } else {
+                    CheckStatus::Fail
+                };
+                return problem(
+                    NAME,
+                    status,
+                    format!("{}: {}", KVM_DEVICE, err),
+                    "enable virtualization (VT-x/AMD-V) in firmware and load kvm_intel or kvm_amd",
+                );
+            }
+        };
+        if let Err(err) = OpenOptions::new().read(true).write(true).open(KVM_DEVICE) {
+            return problem(
+                NAME,
+                CheckStatus::Fail,
+                format!("daemon cannot open {}: {}", KVM_DEVICE, err),
+                "check the permissions of /dev/kvm",
+            );
+        }
+        let user = &self.config.guest_user;
+        let Ok(uid) = resolve_uid(user) else {
+            // Reported by the guest_user check.
+            return pass(NAME, format!("{} is available", KVM_DEVICE));
+        };
+        let groups = user_groups(user).unwrap_or_default();
+        let mode = metadata.permissions().mode();
+        let accessible = uid == 0
+            || (metadata.uid() == uid && mode & 0o600 == 0o600)
+            || (groups.contains(&metadata.gid()) && mode & 0o060 == 0o060)
+            || mode & 0o006 == 0o006;
+        if accessible {
+            pass(NAME, format!("{} is available to {}", KVM_DEVICE, user))
+        } else {
+            problem(
+                NAME,
+                CheckStatus::Fail,
+                format!(
+                    "guest user {} cannot open {} (owner gid {}, mode {:o})",
+                    user,
+                    KVM_DEVICE,
+                    metadata.gid(),
+                    mode & 0o777
+                ),
+                &format!("add {} to the group owning /dev/kvm (usually kvm)", user),
+            )
+        }
+    }
+
+    fn guest_user(&self) -> DoctorCheck {
+        const NAME: &str = "guest_user";
+        let user = &self.config.guest_user;
+        match resolve_uid(user) {
+            Ok(uid) => pass(NAME, format!("{} (uid {})", user, uid)),
+            Err(err) => problem(
+                NAME,
+                CheckStatus::Fail,
+                format!("{:#}", err),
+                "create the user or point the daemon at an existing one with --guest-user",
+            ),
+        }
+    }
+
+    fn guest_group(&self) -> DoctorCheck {
+        const NAME: &str = "guest_group";
+        let user = &self.config.guest_user;
+        let group = &self.config.guest_primary_group;
+        let gid = match resolve_gid(group) {
+            Ok(gid) => gid,
+            Err(err) => {
+                return problem(
+                    NAME,
+                    CheckStatus::Fail,
+                    format!("{:#}", err),
+                    &format!(
+                        "create group {} (the cuttlefish module adds cvdnetwork) or set --guest-primary-group",
+                        group
+                    ),
+                );
+            }
+        };
+        match user_groups(user) {
+            Ok(groups) if groups.contains(&gid) => {
+                pass(NAME, format!("{} (gid {}) includes {}", group, gid, user))
+            }
+            Ok(_) => problem(
+                NAME,
+                CheckStatus::Warn,
+                format!("{} is not a member of {} (gid {})", user, group, gid),
+                &format!(
+                    "add {} to {} so it can use the tap devices and /var/lib/cuttlefish",
+                    user, group
+                ),
+            ),
+            // The user itself is reported by the guest_user check.
+            Err(_) => pass(NAME, format!("{} (gid {})", group, gid)),
+        }
+    }
+
+    fn cuttlefish_fhs(&self) -> DoctorCheck {
+        const NAME: &str = "cuttlefish_fhs";
+        let mut binaries = vec![&self.config.cuttlefish_fhs];
+        binaries.extend(&self.config.fake_guest);
+        for binary in binaries {
+            if let Err(err) = check_executable(binary) {
+                return problem(
+                    NAME,
+                    CheckStatus::Fail,
+                    format!("{:#}", err),
+                    "enable services.cuttlefish so cuttlefish-fhs is installed, or set --cuttlefish-fhs",
+                );
+            }
+        }
+        pass(
+            NAME,
+            format!("{} is executable", self.config.cuttlefish_fhs.display()),
+        )
+    }
+
+    fn network_devices(&self) -> DoctorCheck {
+        const NAME: &str = "network_devices";
+        let in_use = match (self.in_use)() {
+            Ok(in_use) => in_use,
+            Err(err) => return unknown_use(NAME, err),
+        };
+        let stale: Vec<String> = match fs::read_dir(NET_CLASS_DIR) {
+            Ok(entries) => entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| device_instance(name).is_some_and(|id| !in_use.contains(&id)))
+                .collect(),
+            Err(err) => {
+                return problem(
+                    NAME,
+                    CheckStatus::Warn,
+                    format!("listing {}: {}", NET_CLASS_DIR, err),
+                    "run the daemon with access to sysfs",
+                );
+            }
+        };
+        if stale.is_empty() {
+            return pass(NAME, "no leftover cuttlefish network devices".to_string());
+        }
+        let mut check = problem(
+            NAME,
+            CheckStatus::Warn,
+            format!(
+                "devices of instances that are not running: {}",
+                stale.join(", ")
+            ),
+            "remove them with `ip link del <device>` or `cfctl doctor --fix`",
+        );
+        if self.fix {
+            let failures: Vec<String> = stale
+                .iter()
+                .filter_map(|device| {
+                    let id = device_instance(device)?;
+                    match (self.in_use)() {
+                        Ok(in_use) if in_use.contains(&id) => {
+                            info!(
+                                target: "cfctl",
+                                "doctor: leaving {}: instance {} started meanwhile",
+                                device,
+                                id
+                            );
+                            return None;
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            warn!(target: "cfctl", "doctor: keeping {}: {:#}", device, err);
+                            return Some(device.clone());
+                        }
+                    }
+                    let result = run_command_capture("ip", &["link", "del", device]);
+                    match result {
+                        Ok(_) => {
+                            info!(target: "cfctl", "doctor: removed stale device {}", device);
+                            None
+                        }
+                        Err(err) => {
+                            warn!(target: "cfctl", "doctor: removing {}: {:#}", device, err);
+                            Some(device.clone())
+                        }
+                    }
+                })
+                .collect();
+            check.fixed = failures.is_empty();
+            if !check.fixed {
+                check.message = format!(
+                    "{}; could not remove {}",
+                    check.message,
+                    failures.join(", ")
+                );
+            }
+        }
+        check
+    }
+
+    fn cuttlefish_config(&self) -> DoctorCheck {
+        const NAME: &str = "cuttlefish_config";
+        let in_use = match (self.in_use)() {
+            Ok(in_use) => in_use,
+            Err(err) => return unknown_use(NAME, err),
+        };
+        match fs::symlink_metadata(CUTTLEFISH_CONFIG_LINK) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return pass(NAME, format!("no {}", CUTTLEFISH_CONFIG_LINK));
+            }
+            Err(err) => {
+                return problem(
+                    NAME,
+                    CheckStatus::Warn,
+                    format!("{}: {}", CUTTLEFISH_CONFIG_LINK, err),
+                    "check the permissions of /var/lib/cuttlefish",
+                );
+            }
+            Ok(_) if !in_use.is_empty() => return config_in_use(&in_use),
+            Ok(_) => {}
+        }
+        let mut check = problem(
+            NAME,
+            CheckStatus::Warn,
+            format!(
+                "{} is left over from a previous launch",
+                CUTTLEFISH_CONFIG_LINK
+            ),
+            "remove it or run `cfctl doctor --fix`",
+        );
+        if self.fix {
+            match (self.in_use)() {
+                Ok(in_use) if !in_use.is_empty() => return config_in_use(&in_use),
+                Ok(_) => {}
+                Err(err) => return unknown_use(NAME, err),
+            }
+            match fs::remove_file(CUTTLEFISH_CONFIG_LINK) {
+                Ok(()) => {
+                    info!(target: "cfctl", "doctor: removed {}", CUTTLEFISH_CONFIG_LINK);
+                    check.fixed = true;
+                }
+                Err(err) => {
+                    check.message = format!("{}; could not remove it: {}", check.message, err);
+                }
+            }
+        }
+        check
+    }
+
+    fn disk_space(&self) -> DoctorCheck {
+        const NAME: &str = "disk_space";
+        let dirs = [
+            &self.config.state_dir,
+            &self.config.cuttlefish_instances_dir,
+            &self.config.cuttlefish_assembly_dir,
+        ];
+        let mut seen_devices = BTreeSet::new();
+        let mut status = CheckStatus::Pass;
+        let mut reports = Vec::new();
+        for dir in dirs {
+            let device = fs::metadata(dir).map(|metadata| metadata.dev()).ok();
+            if device.is_some_and(|device| !seen_devices.insert(device)) {
+                continue;
+            }
+            match available_bytes(dir) {
+                Ok(available) => {
+                    status = status.max(disk_status(available));
+                    reports.push(format!("{} {} free", dir.display(), format_gib(available)));
+                }
+                Err(err) => {
+                    status = status.max(CheckStatus::Warn);
+                    reports.push(format!("{:#}", err));
+                }
+            }
+        }
+        let message = reports.join(", ");
+        if status == CheckStatus::Pass {
+            pass(NAME, message)
+        } else {
+            problem(
+                NAME,
+                status,
+                message,
+                &format!(
+                    "keep at least {} free: destroy or prune unused instances and clear old images",
+                    format_gib(DISK_WARN_BYTES)
+                ),
+            )
+        }
+    }
+}
+
+fn pass(name: &str, message: String) -> DoctorCheck {
+    DoctorCheck {
+        name: name.to_string(),
+        status: CheckStatus::Pass,
+        message,
+        hint: None,
+        fixed: false,
+    }
+}
+
+fn problem(name: &str, status: CheckStatus, message: String, hint: &str) -> DoctorCheck {
+    DoctorCheck {
+        name: name.to_string(),
+        status,
+        message,
+        hint: Some(hint.to_string()),
+        fixed: false,
+    }
+}
+
+fn config_in_use(in_use: &BTreeSet<InstanceId>) -> DoctorCheck {
+    let ids: Vec<String> = in_use.iter().map(ToString::to_string).collect();
+    DoctorCheck {
+        name: "cuttlefish_config".to_string(),
+        status: CheckStatus::InUse,
+        message: format!(
+            "{} belongs to running or starting instance(s) {}",
+            CUTTLEFISH_CONFIG_LINK,
+            ids.join(", ")
+        ),
+        hint: None,
+        fixed: false,
+    }
+}
+
+/// The check could not tell live launches apart, so it leaves everything.
+fn unknown_use(name: &str, err: anyhow::Error) -> DoctorCheck {
+    problem(
+        name,
+        CheckStatus::Warn,
+        format!("cannot tell which instances are running: {:#}", err),
+        "check the daemon log and the state dir",
+    )
+}
+
+/// The instance a cuttlefish network device (`cvd-tap-03`, `cvd-eth-03`)
+/// was created for.
+fn device_instance(name: &str) -> Option<InstanceId> {
+    let rest = name.strip_prefix("cvd-")?;
+    let (_, number) = rest.rsplit_once('-')?;
+    number.parse().ok()
+}
+
+fn disk_status(available: u64) -> CheckStatus {
+    if available < DISK_FAIL_BYTES {
+        CheckStatus::Fail
+    } else if available < DISK_WARN_BYTES {
+        CheckStatus::Warn
+    } else {
+        CheckStatus::Pass
+    }
+}
+
+fn format_gib(bytes: u64) -> String {
+    format!("{:.1} GiB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+}
+
+fn check_executable(path: &Path) -> Result<()> {
+    let metadata =
+        fs::metadata(path).with_context(|| format!("{} is not installed", path.display()))?;
+    if !metadata.is_file() || metadata.permissions().mode() & 0o111 == 0 {
+        return Err(anyhow!("{} is not an executable file", path.display()));
+    }
+    Ok(())
+}
+
+fn available_bytes(dir: &Path) -> Result<u64> {
+    let cpath = CString::new(dir.as_os_str().as_encoded_bytes())
+        .with_context(|| format!("invalid path {}", dir.display()))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(cpath.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("statvfs {}", dir.display()));
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Group ids `user` belongs to, its primary group included.
+fn user_groups(user: &str) -> Result<Vec<u32>> {
+    let cname = CString::new(user).with_context(|| format!("invalid username: {}", user))?;
+    unsafe {
+        let pwd = libc::getpwnam(cname.as_ptr());
+        if pwd.is_null() {
+            anyhow::bail!("user '{}' not found", user);
+        }
+        let primary = (*pwd).pw_gid;
+        let mut count: libc::c_int = 32;
+        loop {
+            let mut groups = vec![0 as libc::gid_t; count as usize];
+            let capacity = count;
+            if libc::getgrouplist(cname.as_ptr(), primary, groups.as_mut_ptr(), &mut count) >= 0 {
+                groups.truncate(count as usize);
+                return Ok(groups);
+            }
+            if count <= capacity {
+                count = capacity * 2;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_devices_and_free_space() {
+        assert_eq!(device_instance("cvd-tap-03"), Some(3));
+        assert_eq!(device_instance("cvd-mtap-12"), Some(12));
+        assert_eq!(device_instance("cvd-eth-01"), Some(1));
+        assert_eq!(device_instance("cvd-ebr"), None);
+        assert_eq!(device_instance("eth0"), None);
+
+        assert_eq!(disk_status(1024), CheckStatus::Fail);
+        assert_eq!(disk_status(DISK_FAIL_BYTES), CheckStatus::Warn);
+        assert_eq!(disk_status(DISK_WARN_BYTES), CheckStatus::Pass);
+        assert!(CheckStatus::Fail > CheckStatus::Warn);
+        assert!(CheckStatus::Warn > CheckStatus::InUse);
+        let held = config_in_use(&BTreeSet::from([3, 5]));
+        assert_eq!(held.status, CheckStatus::InUse);
+        assert!(
+            held.message.ends_with("instance(s) 3, 5"),
+            "{}",
+            held.message
+        );
+
+        let groups = user_groups("root").unwrap();
+        assert!(groups.contains(&0));
+        assert!(user_groups("no-such-cfctl-user").is_err());
+    }
+}
@@ -1,15 +1,10 @@
-use std::{
-    io,
-    os::unix::process::ExitStatusExt,
-    process::Child,
-    sync::{Arc, Mutex},
-    thread,
-    time::{Duration, Instant},
-};
+use std::{io, os::unix::process::ExitStatusExt, sync::Arc, time::Duration};
 
 use anyhow::{anyhow, Result};
 use dashmap::DashMap;
 use libc::{c_int, pid_t};
+use tokio::{process::Child, sync::watch};
+use tracing::warn;
 
 use crate::protocol::InstanceId;
 
@@ -34,92 +29,60 @@ impl ExitStatusInfo {
     }
 }
 
-#[derive(Debug)]
-struct ChildState {
-    child: Option<Child>,
-    exit: Option<ExitStatusInfo>,
-}
-
-impl ChildState {
-    fn new(child: Child) -> Self {
-        Self {
-            child: Some(child),
-            exit: None,
-        }
-    }
-}
-
+/// A launched guest. A reaper task owns the child and publishes its exit
+/// status, so any number of waiters can observe the exit without polling.
 #[derive(Debug)]
 pub struct GuestHandle {
     pid: pid_t,
-    state: Mutex<ChildState>,
+    exit: watch::Receiver<Option<ExitStatusInfo>>,
 }
 
 impl GuestHandle {
-    pub fn new(child: Child) -> Self {
-        let pid = child.id() as pid_t;
-        Self {
-            pid,
-            state: Mutex::new(ChildState::new(child)),
-        }
+    /// Takes ownership of `child` and starts reaping it. Must be called
+    /// from within the daemon's tokio runtime.
+    pub fn new(mut child: Child) -> Self {
+        let pid = child.id().unwrap_or_default() as pid_t;
+        let (tx, exit) = watch::channel(None);
+        tokio::spawn(async move {
+            let exit = match child.wait().await {
+                Ok(status) => ExitStatusInfo {
+                    code: status.code(),
+                    signal: status.signal(),
+                },
+                Err(err) => {
+                    warn!(target: "cfctl", "guest reaper: waiting for pid {} failed: {}", pid, err);
+                    ExitStatusInfo {
+                        code: None,
+                        signal: None,
+                    }
+                }
+            };
+            let _ = tx.send(Some(exit));
+        });
+        Self { pid, exit }
     }
 
     pub fn pid(&self) -> pid_t {
         self.pid
     }
 
-    pub fn try_wait(&self) -> Result<Option<ExitStatusInfo>> {
-        let mut state = self.state.lock().expect("poisoned guest handle mutex");
-        if let Some(exit) = state.exit {
-            return Ok(Some(exit));
-        }
-        if let Some(child) = state.child.as_mut() {
-            match child.try_wait()? {
-                Some(status) => {
-                    let exit = ExitStatusInfo {
-                        code: status.code(),
-                        signal: status.signal(),
-                    };
-                    state.child = None;
-                    state.exit = Some(exit);
-                    Ok(Some(exit))
-                }
-                None => Ok(None),
-            }
-        } else {
-            Ok(state.exit)
-        }
+    pub fn try_wait(&self) -> Option<ExitStatusInfo> {
+        *self.exit.borrow()
     }
 
-    pub fn wait(&self) -> Result<ExitStatusInfo> {
-        let mut state = self.state.lock().expect("poisoned guest handle mutex");
-        if let Some(exit) = state.exit {
-            return Ok(exit);
-        }
-
-        if let Some(mut child) = state.child.take() {
-            let status = child.wait()?;
-            let exit = ExitStatusInfo {
-                code: status.code(),
-                signal: status.signal(),
-            };
-            state.exit = Some(exit);
-            Ok(exit)
-        } else {
-            Err(anyhow!("guest process already awaited"))
-        }
+    pub async fn wait(&self) -> Result<ExitStatusInfo> {
+        let mut exit = self.exit.clone();
+        let status = exit
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| anyhow!("guest reaper for pid {} went away", self.pid))?;
+        Ok(status.expect("wait_for guarantees an exit status"))
     }
 
-    pub fn wait_timeout(&self, timeout: Duration) -> Result<Option<ExitStatusInfo>> {
-        let deadline = Instant::now() + timeout;
-        loop {
-            if let Some(exit) = self.try_wait()? {
-                return Ok(Some(exit));
-            }
-            if Instant::now() >= deadline {
-                return Ok(None);
-            }
-            thread::sleep(Duration::from_millis(200));
+    pub async fn wait_timeout(&self, timeout: Duration) -> Result<Option<ExitStatusInfo>> {
+        match tokio::time::timeout(timeout, self.wait()).await {
+            Ok(exit) => exit.map(Some),
+            Err(_) => Ok(None),
         }
     }
 
@@ -162,6 +125,11 @@ impl GuestRegistry {
         self.handles.contains_key(&id)
     }
 
+    /// Instances with a guest process registered.
+    pub fn ids(&self) -> Vec<InstanceId> {
+        self.handles.iter().map(|entry| *entry.key()).collect()
+    }
+
     pub fn remove_if_handle(
         &self,
         id: InstanceId,
@@ -0,0 +1,404 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Read,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use rusqlite::{params, params_from_iter, types::Value, Connection, OptionalExtension, Row};
+use sha2::{Digest, Sha256};
+
+use crate::protocol::{
+    ErrorDetail, HistoryQuery, HistoryResponse, ImageDigest, RunOutcome, RunRecord, TimingStats,
+    VariantStats,
+};
+
+pub const HISTORY_DB: &str = "history.sqlite3";
+
+/// Runs listed when a query does not set a limit.
+const DEFAULT_LIMIT: usize = 50;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    run_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    instance_id INTEGER NOT NULL,
+    purpose TEXT,
+    started_at INTEGER NOT NULL,
+    backend TEXT NOT NULL,
+    boot_image TEXT,
+    boot_sha256 TEXT,
+    init_image TEXT,
+    init_sha256 TEXT,
+    options TEXT NOT NULL,
+    adb_ms INTEGER,
+    marker_ms INTEGER,
+    duration_ms INTEGER NOT NULL,
+    outcome TEXT NOT NULL,
+    failure_code TEXT,
+    failure_message TEXT
+);
+CREATE INDEX IF NOT EXISTS runs_by_init_sha256 ON runs (init_sha256, started_at);
+CREATE TABLE IF NOT EXISTS image_digests (
+    path TEXT PRIMARY KEY,
+    size INTEGER NOT NULL,
+    mtime_ns INTEGER NOT NULL,
+    sha256 TEXT NOT NULL
+);
+";
+
+const RUN_COLUMNS: &str = "run_id, instance_id, purpose, started_at, backend, boot_image, \
+     boot_sha256, init_image, init_sha256, options, adb_ms, marker_ms, duration_ms, outcome, \
+     failure_code, failure_message";
+
+/// Every start attempt the daemon made, kept in an SQLite database under
+/// `state_dir` so the timings outlive the instances.
+pub struct RunHistory {
+    path: PathBuf,
+}
+
+impl RunHistory {
+    pub fn new(state_dir: &Path) -> Self {
+        Self {
+            path: state_dir.join(HISTORY_DB),
+        }
+    }
+
+    fn open(&self) -> Result<Connection> {
+        let conn = Connection::open(&self.path)
+            .with_context(|| format!("opening run history {}", self.path.display()))?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute_batch(SCHEMA)
+            .context("creating run history tables")?;
+        Ok(conn)
+    }
+
+    /// Hashes the image at `path`, reusing the digest recorded for it while
+    /// its size and mtime are unchanged.
+    pub fn digest(&self, path: &Path) -> Result<ImageDigest> {
+        let conn = self.open()?;
+        let metadata = fs::metadata(path).with_context(|| format!("reading {}", path.display()))?;
+        let key = path.display().to_string();
+        let size = metadata.len() as i64;
+        let mtime_ns = metadata.mtime() * 1_000_000_000 + metadata.mtime_nsec();
+        let cached: Option<String> = conn
+            .query_row(
+                "SELECT sha256 FROM image_digests WHERE path = ?1 AND size = ?2 AND mtime_ns = ?3",
+                params![key, size, mtime_ns],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(sha256) = cached {
+            return Ok(ImageDigest { path: key, sha256 });
+        }
+
+        let sha256 = sha256_file(path)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO image_digests (path, size, mtime_ns, sha256) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![key, size, mtime_ns, sha256],
+        )?;
+        Ok(ImageDigest { path: key, sha256 })
+    }
+
+    /// Stores `run` under a fresh run id, which is returned.
+    pub fn record(&self, run: &RunRecord) -> Result<u64> {
+        let conn = self.open()?;
+        let failure = run.failure.as_ref();
+        conn.execute(
+            "INSERT INTO runs (instance_id, purpose, started_at, backend, boot_image, \
+             boot_sha256, init_image, init_sha256, options, adb_ms, marker_ms, duration_ms, \
+             outcome, failure_code, failure_message) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            params![
+                run.instance_id,
+                run.purpose,
+                run.started_at as i64,
+                run.backend,
+                run.boot_image.as_ref().map(|image| &image.path),
+                run.boot_image.as_ref().map(|image| &image.sha256),
+                run.init_image.as_ref().map(|image| &image.path),
+                run.init_image.as_ref().map(|image| &image.sha256),
+                serde_json::to_string(&run.options)?,
+                run.adb_ms.map(|ms| ms as i64),
+                run.marker_ms.map(|ms| ms as i64),
+                run.duration_ms as i64,
+                run.outcome.as_str(),
+                failure.map(|detail| &detail.code),
+                failure.and_then(|detail| detail.message.as_ref()),
+            ],
+        )
+        .context("recording run")?;
+        Ok(conn.last_insert_rowid() as u64)
+    }
+
+    /// Lists the matching runs, or groups them by init image when the
+    /// query asks for a comparison. `baseline` is left to the caller.
+    pub fn query(&self, query: &HistoryQuery) -> Result<HistoryResponse> {
+        let conn = self.open()?;
+        let mut clauses = Vec::new();
+        let mut values = Vec::new();
+        if let Some(id) = query.instance_id {
+            clauses.push("instance_id = ?");
+            values.push(Value::Integer(id as i64));
+        }
+        if let Some(prefix) = &query.init_sha256 {
+            clauses.push("substr(init_sha256, 1, ?) = ?");
+            values.push(Value::Integer(prefix.len() as i64));
+            values.push(Value::Text(prefix.to_ascii_lowercase()));
+        }
+        if let Some(purpose) = &query.purpose {
+            clauses.push("purpose = ?");
+            values.push(Value::Text(purpose.clone()));
+        }
+        if let Some(outcome) = query.outcome {
+            clauses.push("outcome = ?");
+            values.push(Value::Text(outcome.as_str().to_string()));
+        }
+        if let Some(since) = query.since {
+            clauses.push("started_at >= ?");
+            values.push(Value::Integer(since as i64));
+        }
+        let filter = if clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", clauses.join(" AND "))
+        };
+
+        let sql = if query.compare {
+            format!("SELECT {RUN_COLUMNS} FROM runs{filter} ORDER BY run_id")
+        } else {
+            let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+            format!("SELECT {RUN_COLUMNS} FROM runs{filter} ORDER BY run_id DESC LIMIT {limit}")
+        };
+        let mut statement = conn.prepare(&sql)?;
+        let runs = statement
+            .query_map(params_from_iter(values), run_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("reading run history")?;
+
+        if query.compare {
+            Ok(HistoryResponse {
+                runs: Vec::new(),
+                variants: group_by_init_image(runs),
+            })
+        } else {
+            Ok(HistoryResponse {
+                runs,
+                variants: Vec::new(),
+            })
+        }
+    }
+}
+
+fn run_from_row(row: &Row) -> rusqlite::Result<RunRecord> {
+    let image = |path: usize, sha256: usize| -> rusqlite::Result<Option<ImageDigest>> {
+        let path: Option<String> = row.get(path)?;
+        let sha256: Option<String> = row.get(sha256)?;
+        Ok(path
+            .zip(sha256)
+            .map(|(path, sha256)| ImageDigest { path, sha256 }))
+    };
+    let options: String = row.get(9)?;
+    let options = serde_json::from_str(&options).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(9, rusqlite::types::Type::Text, Box::new(err))
+    })?;
+    let outcome: String = row.get(13)?;
+    let outcome = outcome.parse().map_err(|err: String| {
+        rusqlite::Error::FromSqlConversionFailure(13, rusqlite::types::Type::Text, err.into())
+    })?;
+    let failure_code: Option<String> = row.get(14)?;
+    Ok(RunRecord {
+        run_id: row.get::<_, i64>(0)? as u64,
+        instance_id: row.get(1)?,
+        purpose: row.get(2)?,
+        started_at: row.get::<_, i64>(3)? as u64,
+        backend: row.get(4)?,
+        boot_image: image(5, 6)?,
+        init_image: image(7, 8)?,
+        options,
+        adb_ms: row.get::<_, Option<i64>>(10)?.map(|ms| ms as u64),
+        marker_ms: row.get::<_, Option<i64>>(11)?.map(|ms| ms as u64),
+        duration_ms: row.get::<_, i64>(12)? as u64,
+        outcome,
+        failure: failure_code.map(|code| ErrorDetail {
+            code,
+            message: row.get(15).ok().flatten(),
+        }),
+    })
+}
+
+/// Groups runs (oldest first) by init image, in order of each image's
+/// first run.
+fn group_by_init_image(runs: Vec<RunRecord>) -> Vec<VariantStats> {
+    let mut groups: Vec<Vec<RunRecord>> = Vec::new();
+    let mut index: HashMap<Option<String>, usize> = HashMap::new();
+    for run in runs {
+        let key = run.init_image.as_ref().map(|image| image.sha256.clone());
+        let slot = *index.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[slot].push(run);
+    }
+    groups
+        .into_iter()
+        .map(|runs| variant_stats(&runs))
+        .collect()
+}
+
+fn variant_stats(runs: &[RunRecord]) -> VariantStats {
+    let first = &runs[0];
+    let last = &runs[runs.len() - 1];
+    VariantStats {
+        init_sha256: first.init_image.as_ref().map(|image| image.sha256.clone()),
+        init_image: last.init_image.as_ref().map(|image| image.path.clone()),
+        first_run_at: first.started_at,
+        last_run_at: last.started_at,
+        runs: runs.len(),
+        ok: runs
+            .iter()
+            .filter(|run| run.outcome == RunOutcome::Ok)
+            .count(),
+        adb_ms: timing_stats(runs.iter().filter_map(|run| run.adb_ms).collect()),
+        marker_ms: timing_stats(runs.iter().filter_map(|run| run.marker_ms).collect()),
+    }
+}
+
+fn timing_stats(mut samples: Vec<u64>) -> Option<TimingStats> {
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_unstable();
+    let rank = |percentile: usize| {
+        let rank = (percentile * samples.len()).div_ceil(100).max(1);
+        samples[rank - 1]
+    };
+    Some(TimingStats {
+        samples: samples.len(),
+        min: samples[0],
+        p50: rank(50),
+        p90: rank(90),
+        max: samples[samples.len() - 1],
+    })
+}
+
+/// Moves the variant whose init sha256 starts with `prefix` to the front.
+/// Returns false when no variant matches.
+pub fn put_baseline_first(variants: &mut Vec<VariantStats>, prefix: &str) -> bool {
+    let prefix = prefix.to_ascii_lowercase();
+    let Some(position) = variants.iter().position(|variant| {
+        variant
+            .init_sha256
+            .as_deref()
+            .is_some_and(|sha256| sha256.starts_with(&prefix))
+    }) else {
+        return false;
+    };
+    let baseline = variants.remove(position);
+    variants.insert(0, baseline);
+    true
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file
+            .read(&mut buf)
+            .with_context(|| format!("reading {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::StartOptions;
+
+    fn run(history: &RunHistory, image: &Path, marker_ms: u64, outcome: RunOutcome) -> Result<()> {
+        history.record(&RunRecord {
+            run_id: 0,
+            instance_id: 1,
+            purpose: Some("test".to_string()),
+            started_at: 1_700_000_000,
+            backend: "cuttlefish".to_string(),
+            boot_image: None,
+            init_image: Some(history.digest(image)?),
+            options: StartOptions::default(),
+            adb_ms: Some(marker_ms / 2),
+            marker_ms: (outcome == RunOutcome::Ok).then_some(marker_ms),
+            duration_ms: marker_ms + 100,
+            outcome,
+            failure: (outcome != RunOutcome::Ok).then(|| ErrorDetail {
+                code: "marker_timeout".to_string(),
+                message: Some("no marker".to_string()),
+            }),
+        })?;
+        Ok(())
+    }
+
+    #[test]
+    fn runs_are_listed_and_compared_by_init_image() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let history = RunHistory::new(dir.path());
+        let old = dir.path().join("old.img");
+        let new = dir.path().join("new.img");
+        fs::write(&old, b"old init")?;
+        fs::write(&new, b"new init")?;
+        for marker_ms in [1000, 1200, 1100] {
+            run(&history, &old, marker_ms, RunOutcome::Ok)?;
+        }
+        run(&history, &new, 2000, RunOutcome::Ok)?;
+        run(&history, &new, 0, RunOutcome::Failed)?;
+
+        let listed = history.query(&HistoryQuery {
+            limit: Some(2),
+            ..HistoryQuery::default()
+        })?;
+        assert_eq!(
+            listed.runs.iter().map(|run| run.run_id).collect::<Vec<_>>(),
+            [5, 4]
+        );
+        let failure = listed.runs[0].failure.as_ref().unwrap();
+        assert_eq!(failure.code, "marker_timeout");
+        assert_eq!(failure.message.as_deref(), Some("no marker"));
+
+        let new_sha = history.digest(&new)?.sha256;
+        let mut compared = history.query(&HistoryQuery {
+            compare: true,
+            ..HistoryQuery::default()
+        })?;
+        assert_eq!(compared.variants.len(), 2);
+        let old_variant = &compared.variants[0];
+        assert_eq!((old_variant.runs, old_variant.ok), (3, 3));
+        let marker = old_variant.marker_ms.as_ref().unwrap();
+        assert_eq!((marker.min, marker.p50, marker.max), (1000, 1100, 1200));
+        assert_eq!(compared.variants[1].runs, 2);
+        assert_eq!(compared.variants[1].ok, 1);
+
+        assert!(put_baseline_first(&mut compared.variants, &new_sha[..8]));
+        assert_eq!(compared.variants[0].init_sha256.as_deref(), Some(&*new_sha));
+        assert!(!put_baseline_first(&mut compared.variants, "ffff0000"));
+
+        let filtered = history.query(&HistoryQuery {
+            init_sha256: Some(new_sha[..12].to_uppercase()),
+            outcome: Some(RunOutcome::Failed),
+            ..HistoryQuery::default()
+        })?;
+        assert_eq!(filtered.runs.len(), 1);
+        assert_eq!(filtered.runs[0].run_id, 5);
+        Ok(())
+    }
+}
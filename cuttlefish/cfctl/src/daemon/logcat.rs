@@ -0,0 +1,376 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use dashmap::DashMap;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command as TokioCommand,
+    sync::mpsc,
+    task::{self, JoinHandle},
+    time,
+};
+use tracing::{info, warn};
+
+use crate::protocol::{InstanceId, LogcatCaptureStatus};
+
+use super::cancel::CancelToken;
+use super::guest::GuestHandle;
+use super::logrotate;
+use super::util::epoch_secs;
+
+/// Capture file in the instance state dir; rotated copies get `.1`, `.2`, ...
+/// and are compressed by the log worker.
+pub const LOGCAT_FILE: &str = "logcat.log";
+
+const RECONNECT_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+/// Lines buffered between the adb reader and the file writer.
+const WRITE_QUEUE: usize = 1024;
+
+/// Where one instance's logcat comes from and where it is written.
+pub struct LogcatTarget {
+    /// FHS wrapper adb runs inside (`<fhs> -- adb ...`).
+    pub fhs: PathBuf,
+    pub serial: String,
+    pub path: PathBuf,
+    pub rotate_bytes: u64,
+    pub rotate_keep: usize,
+}
+
+/// The newest timestamp written, and the lines carrying it, so a resumed
+/// `logcat -T` session can skip what was already captured.
+#[derive(Default)]
+struct ResumePoint {
+    stamp: Option<String>,
+    lines: Vec<Vec<u8>>,
+}
+
+struct Capture {
+    status: Mutex<LogcatCaptureStatus>,
+    stop: CancelToken,
+}
+
+/// Continuous `adb logcat` captures, one per running guest. Each capture is
+/// supervised: when adb drops it reconnects with backoff and resumes from
+/// the last timestamp it wrote, until the guest exits.
+#[derive(Default)]
+pub struct LogcatCaptures {
+    captures: DashMap<InstanceId, Arc<Capture>>,
+}
+
+impl LogcatCaptures {
+    /// Captures `id` until `guest` exits, replacing any earlier capture.
+    pub fn start(&self, id: InstanceId, target: LogcatTarget, guest: Arc<GuestHandle>) {
+        let capture = Arc::new(Capture {
+            status: Mutex::new(LogcatCaptureStatus {
+                path: target.path.display().to_string(),
+                active: true,
+                connected: false,
+                started_at: epoch_secs().unwrap_or_default(),
+                bytes_written: 0,
+                reconnects: 0,
+                rotations: 0,
+                last_error: None,
+            }),
+            stop: CancelToken::new(),
+        });
+        if let Some(previous) = self.captures.insert(id, Arc::clone(&capture)) {
+            previous.stop.cancel();
+        }
+        task::spawn(supervise(id, capture, target, guest));
+    }
+
+    pub fn status(&self, id: InstanceId) -> Option<LogcatCaptureStatus> {
+        self.captures
+            .get(&id)
+            .map(|capture| capture.status.lock().unwrap().clone())
+    }
+
+    /// Stops the instance's capture and forgets its status.
+    pub fn remove(&self, id: InstanceId) {
+        if let Some((_, capture)) = self.captures.remove(&id) {
+            capture.stop.cancel();
+        }
+    }
+}
+
+async fn supervise(
+    id: InstanceId,
+    capture: Arc<Capture>,
+    target: LogcatTarget,
+    guest: Arc<GuestHandle>,
+) {
+    info!(
+        target: "cfctl",
+        "logcat_capture: capturing instance {} into {}",
+        id,
+        target.path.display()
+    );
+    let mut backoff = RECONNECT_MIN;
+    let mut resume = ResumePoint::default();
+    loop {
+        let written_before = capture.status.lock().unwrap().bytes_written;
+        let reason = tokio::select! {
+            _ = guest.wait() => break,
+            _ = capture.stop.cancelled() => break,
+            session = stream_logcat(&capture, &target, &mut resume) => match session {
+                Ok(()) => anyhow!("adb logcat ended"),
+                Err(err) => err,
+            },
+        };
+        let progressed = {
+            let mut status = capture.status.lock().unwrap();
+            status.connected = false;
+            status.last_error = Some(format!("{:#}", reason));
+            status.bytes_written > written_before
+        };
+        if progressed {
+            backoff = RECONNECT_MIN;
+        }
+        warn!(
+            target: "cfctl",
+            "logcat_capture: instance {} disconnected ({:#}); reconnecting in {:?}",
+            id,
+            reason,
+            backoff
+        );
+        tokio::select! {
+            _ = guest.wait() => break,
+            _ = capture.stop.cancelled() => break,
+            _ = time::sleep(backoff) => {}
+        }
+        backoff = (backoff * 2).min(RECONNECT_MAX);
+        capture.status.lock().unwrap().reconnects += 1;
+    }
+    let mut status = capture.status.lock().unwrap();
+    status.active = false;
+    status.connected = false;
+    info!(
+        target: "cfctl",
+        "logcat_capture: stopped capturing instance {} after {} bytes",
+        id,
+        status.bytes_written
+    );
+}
+
+/// Runs one `adb logcat` session, appending its output until adb goes away.
+/// Writes and rotations happen on a blocking writer fed through a channel,
+/// never on a runtime thread.
+async fn stream_logcat(
+    capture: &Arc<Capture>,
+    target: &LogcatTarget,
+    resume: &mut ResumePoint,
+) -> Result<()> {
+    let connect = TokioCommand::new(&target.fhs)
+        .args(["--", "adb", "connect", &target.serial])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .with_context(|| format!("invoking adb connect {}", target.serial))?;
+    if !connect.status.success() {
+        bail!("adb connect {} returned {}", target.serial, connect.status);
+    }
+
+    let mut cmd = TokioCommand::new(&target.fhs);
+    cmd.args([
+        "--",
+        "adb",
+        "-s",
+        &target.serial,
+        "logcat",
+        "-v",
+        "threadtime",
+    ]);
+    // `-T` replays the lines stamped at the resume point too; those already
+    // written are dropped below.
+    if let Some(since) = resume.stamp.as_deref() {
+        cmd.args(["-T", since]);
+    }
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    let mut child = cmd.spawn().context("spawning adb logcat")?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("adb logcat has no stdout"))?;
+    let mut reader = BufReader::new(stdout);
+    let path = target.path.clone();
+    let (limit, keep) = (target.rotate_bytes, target.rotate_keep);
+    let file = task::spawn_blocking(move || RotatingFile::open(&path, limit, keep))
+        .await
+        .context("logcat writer task")??;
+    let (lines, writer) = spawn_writer(Arc::clone(capture), file);
+    capture.status.lock().unwrap().connected = true;
+
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        if let Some(stamp) = threadtime_stamp(&line) {
+            if resume.stamp.as_ref() == Some(&stamp) {
+                if resume.lines.contains(&line) {
+                    continue;
+                }
+            } else {
+                resume.stamp = Some(stamp);
+                resume.lines.clear();
+            }
+            resume.lines.push(line.clone());
+        }
+        // A closed channel means the writer failed; its error is reported below.
+        if lines.send(line.clone()).await.is_err() {
+            break;
+        }
+    }
+    drop(lines);
+    writer.await.context("logcat writer task")??;
+    let exit = child.wait().await?;
+    Err(anyhow!("adb logcat exited with {}", exit))
+}
+
+/// Appends the lines it is sent to `file` on the blocking pool, counting
+/// bytes and rotations in the capture status, until the sender is dropped.
+fn spawn_writer(
+    capture: Arc<Capture>,
+    mut file: RotatingFile,
+) -> (mpsc::Sender<Vec<u8>>, JoinHandle<Result<()>>) {
+    let (tx, mut rx) = mpsc::channel::<Vec<u8>>(WRITE_QUEUE);
+    let writer = task::spawn_blocking(move || {
+        while let Some(line) = rx.blocking_recv() {
+            let rotated = file.append(&line)?;
+            let mut status = capture.status.lock().unwrap();
+            status.bytes_written += line.len() as u64;
+            if rotated {
+                status.rotations += 1;
+            }
+        }
+        Ok(())
+    });
+    (tx, writer)
+}
+
+/// The `MM-DD HH:MM:SS.mmm` prefix of a `logcat -v threadtime` line, in the
+/// form `logcat -T` accepts.
+fn threadtime_stamp(line: &[u8]) -> Option<String> {
+    const SHAPE: &[u8] = b"00-00 00:00:00.000";
+    let stamp = line.get(..SHAPE.len())?;
+    let matches = stamp.iter().zip(SHAPE).all(|(&byte, &shape)| {
+        if shape == b'0' {
+            byte.is_ascii_digit()
+        } else {
+            byte == shape
+        }
+    });
+    matches.then(|| String::from_utf8_lossy(stamp).into_owned())
+}
+
+/// Appends to `path`, rotating it (keeping `keep` older copies) once it
+/// would grow past `limit` bytes.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    limit: u64,
+    keep: usize,
+}
+
+impl RotatingFile {
+    /// The parent must exist: recreating it could resurrect the state dir of
+    /// an instance that was destroyed while adb reconnected.
+    fn open(path: &Path, limit: u64, keep: usize) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("opening {}", path.display()))?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            len,
+            limit,
+            keep,
+        })
+    }
+
+    /// Returns whether the file was rotated first.
+    fn append(&mut self, data: &[u8]) -> Result<bool> {
+        let rotate = self.len > 0 && self.len + data.len() as u64 > self.limit;
+        if rotate {
+            logrotate::rotate_by_rename(&self.path, self.keep)?;
+            *self = Self::open(&self.path, self.limit, self.keep)?;
+        }
+        self.file
+            .write_all(data)
+            .with_context(|| format!("writing {}", self.path.display()))?;
+        self.len += data.len() as u64;
+        Ok(rotate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn rotates_by_size_and_keeps_the_newest_segments() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let path = temp.path().join(LOGCAT_FILE);
+        let mut file = RotatingFile::open(&path, 20, 2)?;
+        let mut rotations = 0;
+        for index in 0..5 {
+            let line = format!("10-18 12:00:0{}.000 line\n", index);
+            assert_eq!(threadtime_stamp(line.as_bytes()).unwrap().len(), 18);
+            rotations += file.append(line.as_bytes())? as usize;
+        }
+        assert_eq!(rotations, 4);
+        assert!(fs::read_to_string(&path)?.contains(":04.000"));
+        assert!(fs::read_to_string(logrotate::segment_path(&path, 1))?.contains(":03.000"));
+        assert!(fs::read_to_string(logrotate::segment_path(&path, 2))?.contains(":02.000"));
+        assert!(!logrotate::segment_path(&path, 3).exists());
+        assert_eq!(threadtime_stamp(b"--------- beginning of main\n"), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn writer_appends_and_counts_off_the_runtime() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let path = temp.path().join(LOGCAT_FILE);
+        let capture = Arc::new(Capture {
+            status: Mutex::new(LogcatCaptureStatus {
+                path: path.display().to_string(),
+                active: true,
+                connected: true,
+                started_at: 0,
+                bytes_written: 0,
+                reconnects: 0,
+                rotations: 0,
+                last_error: None,
+            }),
+            stop: CancelToken::new(),
+        });
+        let (lines, writer) = spawn_writer(Arc::clone(&capture), RotatingFile::open(&path, 12, 1)?);
+        for line in ["first\n", "second\n", "third\n"] {
+            lines.send(line.as_bytes().to_vec()).await?;
+        }
+        drop(lines);
+        writer.await??;
+        let status = capture.status.lock().unwrap().clone();
+        assert_eq!(status.bytes_written, 19);
+        assert_eq!(status.rotations, 2);
+        assert_eq!(fs::read_to_string(&path)?, "third\n");
+        Ok(())
+    }
+}
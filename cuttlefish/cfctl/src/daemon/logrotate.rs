@@ -0,0 +1,286 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use tokio::{task, time};
+use tracing::{debug, info, warn};
+
+use super::CfctlDaemon;
+
+/// Rotated copy `index` of `path` before compression: `path.<index>`.
+pub fn segment_path(path: &Path, index: usize) -> PathBuf {
+    with_suffix(path, &format!(".{}", index))
+}
+
+fn compressed_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".gz")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name: OsString = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// The existing form of segment `index`, compressed or not.
+fn existing_segment(path: &Path, index: usize) -> Option<PathBuf> {
+    let plain = segment_path(path, index);
+    let compressed = compressed_path(&plain);
+    if compressed.exists() {
+        Some(compressed)
+    } else if plain.exists() {
+        Some(plain)
+    } else {
+        None
+    }
+}
+
+/// Rotated copies of `path`, newest first.
+pub fn segments(path: &Path) -> Vec<PathBuf> {
+    (1..)
+        .map_while(|index| existing_segment(path, index))
+        .collect()
+}
+
+/// Moves segments up one slot so `path.1` is free, dropping those past `keep`.
+fn shift_segments(path: &Path, keep: usize) -> Result<()> {
+    let existing = segments(path);
+    for (position, segment) in existing.iter().enumerate().rev() {
+        let index = position + 1;
+        if index >= keep {
+            fs::remove_file(segment).with_context(|| format!("removing {}", segment.display()))?;
+            continue;
+        }
+        let mut target = segment_path(path, index + 1);
+        if segment.extension().is_some_and(|ext| ext == "gz") {
+            target = compressed_path(&target);
+        }
+        fs::rename(segment, &target).with_context(|| format!("rotating {}", segment.display()))?;
+    }
+    Ok(())
+}
+
+/// Rotates a file only cfctl writes: renames it to `path.1`, to be
+/// compressed later by [`compress_segments`]. The caller reopens `path`.
+pub fn rotate_by_rename(path: &Path, keep: usize) -> Result<()> {
+    if keep == 0 {
+        return fs::remove_file(path).with_context(|| format!("removing {}", path.display()));
+    }
+    shift_segments(path, keep)?;
+    fs::rename(path, segment_path(path, 1)).with_context(|| format!("rotating {}", path.display()))
+}
+
+/// Rotates a file another process holds open for appending: compresses its
+/// content into `path.1.gz` and truncates it in place. Lines written between
+/// the copy and the truncate are lost.
+pub fn rotate_by_copy(path: &Path, keep: usize) -> Result<()> {
+    if keep > 0 {
+        shift_segments(path, keep)?;
+        compress_file(path, &compressed_path(&segment_path(path, 1)))?;
+    }
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_len(0))
+        .with_context(|| format!("truncating {}", path.display()))
+}
+
+/// Compresses any uncompressed segments of `path`.
+pub fn compress_segments(path: &Path) -> Result<()> {
+    for segment in segments(path) {
+        if segment.extension().is_some_and(|ext| ext == "gz") {
+            continue;
+        }
+        compress_file(&segment, &compressed_path(&segment))?;
+        fs::remove_file(&segment).with_context(|| format!("removing {}", segment.display()))?;
+    }
+    Ok(())
+}
+
+fn compress_file(source: &Path, target: &Path) -> Result<()> {
+    let mut input = File::open(source).with_context(|| format!("opening {}", source.display()))?;
+    let partial = with_suffix(target, ".tmp");
+    let output =
+        File::create(&partial).with_context(|| format!("creating {}", partial.display()))?;
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    io::copy(&mut input, &mut encoder)
+        .with_context(|| format!("compressing {}", source.display()))?;
+    encoder.finish()?.sync_all()?;
+    fs::rename(&partial, target).with_context(|| format!("renaming {}", partial.display()))
+}
+
+/// Reads a whole segment, decompressing `.gz` ones.
+fn read_segment(path: &Path) -> io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    let file = File::open(path)?;
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        GzDecoder::new(file).read_to_end(&mut content)?;
+    } else {
+        io::BufReader::new(file).read_to_end(&mut content)?;
+    }
+    Ok(content)
+}
+
+/// The last `max_bytes` of `path`, continued into its rotated segments when
+/// the current file is shorter. Starts at a line boundary; the flag says
+/// whether older content was left out.
+pub fn tail_bytes(path: &Path, max_bytes: usize) -> io::Result<(Vec<u8>, bool)> {
+    let mut file = File::open(path)?;
+    let len = file.seek(SeekFrom::End(0))?;
+    let start = len.saturating_sub(max_bytes as u64);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    let mut cut = start > 0;
+    if !cut {
+        for segment in segments(path) {
+            let mut older = match read_segment(&segment) {
+                Ok(content) => content,
+                Err(err) => {
+                    debug!(
+                        target: "cfctl",
+                        "tail_bytes: skipping unreadable segment {}: {}",
+                        segment.display(),
+                        err
+                    );
+                    break;
+                }
+            };
+            let room = max_bytes.saturating_sub(tail.len());
+            if older.len() > room {
+                older.drain(..older.len() - room);
+                cut = true;
+            }
+            older.append(&mut tail);
+            tail = older;
+            if cut {
+                break;
+            }
+        }
+    }
+    if cut {
+        // Drop the partial first line.
+        let head = tail
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(tail.len(), |pos| pos + 1);
+        tail.drain(..head);
+    }
+    Ok((tail, cut))
+}
+
+/// On-disk size of `path` and its segments: `(current, segments, segment_bytes)`.
+pub fn usage(path: &Path) -> (u64, usize, u64) {
+    let current = fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    let segments = segments(path);
+    let segment_bytes = segments
+        .iter()
+        .filter_map(|segment| fs::metadata(segment).ok())
+        .map(|meta| meta.len())
+        .sum();
+    (current, segments.len(), segment_bytes)
+}
+
+/// Deletes the oldest segments across `paths` until everything fits in
+/// `quota` bytes. Current files are never removed. Returns the bytes used.
+pub fn enforce_quota(paths: &[PathBuf], quota: u64) -> Result<u64> {
+    let mut used: u64 = paths
+        .iter()
+        .map(|path| {
+            let (current, _, segment_bytes) = usage(path);
+            current + segment_bytes
+        })
+        .sum();
+    if quota == 0 || used <= quota {
+        return Ok(used);
+    }
+    let mut candidates: Vec<(usize, PathBuf)> = paths
+        .iter()
+        .flat_map(|path| segments(path).into_iter().enumerate())
+        .collect();
+    // Highest index first: the oldest copy of each log goes before newer ones.
+    candidates.sort_by(|(a, _), (b, _)| b.cmp(a));
+    for (_, segment) in candidates {
+        if used <= quota {
+            break;
+        }
+        let size = fs::metadata(&segment).map(|meta| meta.len()).unwrap_or(0);
+        fs::remove_file(&segment).with_context(|| format!("removing {}", segment.display()))?;
+        info!(
+            target: "cfctl",
+            "enforce_quota: removed {} ({} bytes) to stay under {} bytes",
+            segment.display(),
+            size,
+            quota
+        );
+        used = used.saturating_sub(size);
+    }
+    Ok(used)
+}
+
+impl CfctlDaemon {
+    /// Rotates, compresses and trims the logs of running instances.
+    pub(super) async fn run_log_worker(self) {
+        let config = self.config.current();
+        info!(
+            target: "cfctl",
+            "log_worker: rotating logs at {} bytes, keeping {}, quota {} bytes per instance",
+            config.log_rotate_bytes,
+            config.log_rotate_keep,
+            config.log_quota_bytes
+        );
+        loop {
+            time::sleep(self.config.current().log_check_interval).await;
+            let manager = self.manager.refreshed();
+            if let Err(err) = task::spawn_blocking(move || manager.maintain_logs()).await {
+                warn!(target: "cfctl", "log_worker: maintenance task failed: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotated_segments_are_compressed_tailed_and_trimmed() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let path = temp.path().join("launcher.log");
+        for run in 1..=3 {
+            fs::write(&path, format!("run {} first\nrun {} last\n", run, run))?;
+            rotate_by_copy(&path, 2)?;
+            assert_eq!(fs::metadata(&path)?.len(), 0);
+        }
+        fs::write(&path, "current\n")?;
+        rotate_by_rename(&path, 2)?;
+        fs::write(&path, "newest\n")?;
+        let names: Vec<_> = segments(&path)
+            .iter()
+            .map(|segment| segment.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["launcher.log.1", "launcher.log.2.gz"]);
+        compress_segments(&path)?;
+        assert!(segments(&path)[0].ends_with("launcher.log.1.gz"));
+
+        let (tail, cut) = tail_bytes(&path, 1024)?;
+        assert!(!cut);
+        assert_eq!(
+            String::from_utf8(tail)?,
+            "run 3 first\nrun 3 last\ncurrent\nnewest\n"
+        );
+        let (tail, cut) = tail_bytes(&path, 20)?;
+        assert!(cut);
+        assert_eq!(String::from_utf8(tail)?, "current\nnewest\n");
+
+        let used = enforce_quota(std::slice::from_ref(&path), 10)?;
+        assert!(segments(&path).is_empty());
+        assert_eq!(used, 7);
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Cursor, Seek, SeekFrom},
+    path::Path,
+};
+
+use regex::Regex;
+
+use crate::protocol::LogsOptions;
+
+use super::logrotate;
+use super::util::epoch_secs;
+
+/// Cap on the content returned by one `Logs` request.
+pub const MAX_LOG_BYTES: usize = 1024 * 1024;
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// Lines picked out of a log by a [`LogQuery`].
+#[derive(Debug)]
+pub struct LogSelection {
+    pub content: String,
+    pub size_bytes: u64,
+    pub next_byte: u64,
+    pub truncated: bool,
+    /// At least one line carried a timestamp the query understood.
+    pub timestamped: bool,
+}
+
+/// Selects lines from a log: an optional byte or line range, then the
+/// `since` and `grep` filters, then the last `tail` lines.
+pub struct LogQuery {
+    from_byte: u64,
+    to_byte: Option<u64>,
+    from_line: usize,
+    to_line: Option<usize>,
+    grep: Option<Regex>,
+    since: Option<u64>,
+    tail: Option<usize>,
+    clock: LogClock,
+}
+
+impl LogQuery {
+    /// `lines` caps the result to the last N selected lines; without an
+    /// explicit byte or line range it defaults to `default_tail`.
+    /// `boot_epoch` dates kernel-style `[uptime]` lines.
+    pub fn new(
+        options: &LogsOptions,
+        lines: Option<usize>,
+        default_tail: usize,
+        boot_epoch: Option<u64>,
+    ) -> Result<Self, String> {
+        let byte_range = options.from_byte.is_some() || options.to_byte.is_some();
+        let line_range = options.from_line.is_some() || options.to_line.is_some();
+        if byte_range && line_range {
+            return Err("byte and line ranges cannot be combined".to_string());
+        }
+        let from_byte = options.from_byte.unwrap_or(0);
+        if options.to_byte.is_some_and(|to| to < from_byte) {
+            return Err(format!("byte range ends before it starts at {}", from_byte));
+        }
+        let from_line = options.from_line.unwrap_or(1);
+        if from_line == 0 {
+            return Err("lines are counted from 1".to_string());
+        }
+        if options.to_line.is_some_and(|to| to < from_line) {
+            return Err(format!("line range ends before it starts at {}", from_line));
+        }
+        let grep = options
+            .grep
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|err| format!("invalid grep regex: {}", err))?;
+        let ranged = byte_range || line_range;
+        Ok(Self {
+            from_byte,
+            to_byte: options.to_byte,
+            from_line,
+            to_line: options.to_line,
+            grep,
+            since: options.since,
+            tail: lines.or((!ranged).then_some(default_tail)),
+            clock: LogClock::new(boot_epoch),
+        })
+    }
+
+    /// Plain "last N lines" reads only need the end of the source.
+    fn tail_only(&self) -> bool {
+        self.from_byte == 0
+            && self.to_byte.is_none()
+            && self.from_line == 1
+            && self.to_line.is_none()
+            && self.grep.is_none()
+            && self.since.is_none()
+    }
+
+    /// Selects from a log file. Ranges address the current file; a plain
+    /// tail continues into its rotated copies.
+    pub fn select_file(&self, path: &Path) -> io::Result<LogSelection> {
+        let file = File::open(path)?;
+        if !self.tail_only() {
+            return self.select(BufReader::new(file));
+        }
+        let size_bytes = file.metadata()?.len();
+        let (history, cut) = logrotate::tail_bytes(path, MAX_LOG_BYTES)?;
+        let mut selection = self.select(Cursor::new(history))?;
+        selection.truncated |= cut
+            && self
+                .tail
+                .is_some_and(|tail| selection.content.lines().count() < tail);
+        selection.size_bytes = size_bytes;
+        selection.next_byte = size_bytes;
+        Ok(selection)
+    }
+
+    pub fn select<R: BufRead + Seek>(&self, mut reader: R) -> io::Result<LogSelection> {
+        let size_bytes = reader.seek(SeekFrom::End(0))?;
+        let end = self.to_byte.map_or(size_bytes, |to| to.min(size_bytes));
+        let mut offset = self.from_byte.min(end);
+        let mut skipped_head = false;
+        if self.tail_only() && end - offset > MAX_LOG_BYTES as u64 {
+            // Start mid-file and drop the partial first line, like `tail_file`.
+            offset = end - MAX_LOG_BYTES as u64;
+            reader.seek(SeekFrom::Start(offset))?;
+            offset += reader.skip_until(b'\n')? as u64;
+            skipped_head = true;
+        } else {
+            reader.seek(SeekFrom::Start(offset))?;
+        }
+
+        let mut selected: VecDeque<String> = VecDeque::new();
+        let mut selected_bytes = 0;
+        let mut truncated = false;
+        let mut timestamped = false;
+        let mut current_time = None;
+        let mut line_no = 0;
+        let mut buf = Vec::new();
+        while offset < end && self.to_line.is_none_or(|to| line_no < to) {
+            buf.clear();
+            let read = reader.read_until(b'\n', &mut buf)?;
+            if read == 0 {
+                break;
+            }
+            let read = read.min((end - offset) as usize);
+            buf.truncate(read);
+            line_no += 1;
+            if line_no < self.from_line {
+                offset += read as u64;
+                continue;
+            }
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim_end_matches(['\n', '\r']);
+            // Lines without a timestamp of their own belong to the last one seen.
+            if let Some(time) = self.clock.timestamp(line) {
+                current_time = Some(time);
+                timestamped = true;
+            }
+            let wanted = self
+                .since
+                .is_none_or(|since| current_time.is_some_and(|time| time >= since))
+                && self.grep.as_ref().is_none_or(|grep| grep.is_match(line));
+            if !wanted {
+                offset += read as u64;
+                continue;
+            }
+            if self.tail.is_none() && selected_bytes + line.len() + 1 > MAX_LOG_BYTES {
+                // Leave `next_byte` at this line so the caller can resume here.
+                truncated = true;
+                break;
+            }
+            offset += read as u64;
+            selected_bytes += line.len() + 1;
+            selected.push_back(line.to_string());
+            if let Some(tail) = self.tail {
+                while selected.len() > tail || selected_bytes > MAX_LOG_BYTES {
+                    // Dropping a line the tail would have kept means the cap hit.
+                    truncated |= selected.len() <= tail;
+                    let dropped = selected.pop_front().unwrap_or_default();
+                    selected_bytes -= dropped.len() + 1;
+                }
+            }
+        }
+        if skipped_head && self.tail.is_some_and(|tail| selected.len() < tail) {
+            truncated = true;
+        }
+
+        Ok(LogSelection {
+            content: Vec::from(selected).join("\n"),
+            size_bytes,
+            next_byte: offset,
+            truncated,
+            timestamped,
+        })
+    }
+}
+
+/// Recognises the timestamps found at the start of guest and host log lines:
+/// Unix seconds (`logcat -v epoch`), kernel `[uptime]` relative to boot, and
+/// `[YYYY-]MM-DD HH:MM:SS` dates, read as UTC.
+struct LogClock {
+    boot_epoch: Option<u64>,
+    now: u64,
+    epoch: Regex,
+    uptime: Regex,
+    datetime: Regex,
+}
+
+impl LogClock {
+    fn new(boot_epoch: Option<u64>) -> Self {
+        Self {
+            boot_epoch,
+            now: epoch_secs().unwrap_or_default(),
+            epoch: Regex::new(r"^\s*(\d{10})(?:\.\d+)?\s").expect("valid regex"),
+            uptime: Regex::new(r"^\[\s*(\d+)\.\d+\]").expect("valid regex"),
+            datetime: Regex::new(
+                r"(?:^|[\s\[(])(?:(\d{4})-)?(\d{2})-(\d{2})[T ](\d{2}):(\d{2}):(\d{2})",
+            )
+            .expect("valid regex"),
+        }
+    }
+
+    fn timestamp(&self, line: &str) -> Option<u64> {
+        let head = line.get(..64).unwrap_or(line);
+        if let Some(captures) = self.epoch.captures(head) {
+            return captures[1].parse().ok();
+        }
+        if let Some(captures) = self.uptime.captures(head) {
+            let uptime: u64 = captures[1].parse().ok()?;
+            return self.boot_epoch.map(|boot| boot + uptime);
+        }
+        let captures = self.datetime.captures(head)?;
+        let field = |index: usize| captures[index].parse::<i64>().ok();
+        let time = [field(2)?, field(3)?, field(4)?, field(5)?, field(6)?];
+        match captures.get(1) {
+            Some(year) => utc_epoch(year.as_str().parse().ok()?, time),
+            None => {
+                // Year-less dates (cuttlefish launcher logs) are assumed to be
+                // from the last twelve months.
+                let year = year_of(self.now);
+                utc_epoch(year, time)
+                    .filter(|&secs| secs <= self.now + DAY_SECS)
+                    .or_else(|| utc_epoch(year - 1, time))
+            }
+        }
+    }
+}
+
+fn utc_epoch(year: i64, [month, day, hour, minute, second]: [i64; 5]) -> Option<u64> {
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    let secs =
+        days_from_civil(year, month, day) * DAY_SECS as i64 + hour * 3600 + minute * 60 + second;
+    u64::try_from(secs).ok()
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn year_of(epoch: u64) -> i64 {
+    let mut year = 1970 + (epoch / 31_556_952) as i64;
+    while days_from_civil(year, 1, 1) * DAY_SECS as i64 > epoch as i64 {
+        year -= 1;
+    }
+    while days_from_civil(year + 1, 1, 1) * DAY_SECS as i64 <= epoch as i64 {
+        year += 1;
+    }
+    year
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn select(options: LogsOptions, lines: Option<usize>, text: &str) -> LogSelection {
+        LogQuery::new(&options, lines, 3, Some(1_700_000_000))
+            .expect("valid query")
+            .select(Cursor::new(text.as_bytes()))
+            .expect("in-memory read")
+    }
+
+    #[test]
+    fn ranges_select_lines_and_bytes() {
+        let text = "one\ntwo\nthree\nfour\nfive\n";
+        assert_eq!(
+            select(LogsOptions::default(), None, text).content,
+            "three\nfour\nfive"
+        );
+
+        let lines = select(
+            LogsOptions {
+                from_line: Some(2),
+                to_line: Some(4),
+                ..LogsOptions::default()
+            },
+            None,
+            text,
+        );
+        assert_eq!(lines.content, "two\nthree\nfour");
+        assert_eq!(lines.next_byte, 19);
+
+        let bytes = select(
+            LogsOptions {
+                from_byte: Some(8),
+                to_byte: Some(19),
+                ..LogsOptions::default()
+            },
+            None,
+            text,
+        );
+        assert_eq!(bytes.content, "three\nfour");
+        assert_eq!((bytes.size_bytes, bytes.next_byte), (24, 19));
+
+        let mixed = LogsOptions {
+            from_byte: Some(1),
+            from_line: Some(1),
+            ..LogsOptions::default()
+        };
+        assert!(LogQuery::new(&mixed, None, 3, None).is_err());
+    }
+
+    #[test]
+    fn since_and_grep_filter_timestamped_lines() {
+        let text = "\
+preamble without a time
+2023-11-14 22:13:10 launcher: starting
+  continuation line
+2023-11-14T22:13:30 launcher: error: disk missing
+[    5.000000] kernel: error: late oops
+1699999000.000  100  100 E tag: old error
+";
+        let since = select(
+            LogsOptions {
+                since: Some(1_699_999_980),
+                ..LogsOptions::default()
+            },
+            Some(10),
+            text,
+        );
+        assert!(since.timestamped);
+        assert_eq!(
+            since.content,
+            "2023-11-14 22:13:10 launcher: starting\n  continuation line\n\
+             2023-11-14T22:13:30 launcher: error: disk missing\n\
+             [    5.000000] kernel: error: late oops"
+        );
+
+        let grep = select(
+            LogsOptions {
+                grep: Some("error".to_string()),
+                since: Some(1_700_000_004),
+                ..LogsOptions::default()
+            },
+            None,
+            text,
+        );
+        assert_eq!(
+            grep.content,
+            "2023-11-14T22:13:30 launcher: error: disk missing\n\
+             [    5.000000] kernel: error: late oops"
+        );
+    }
+
+    #[test]
+    fn year_less_dates_fall_in_the_last_year() {
+        let clock = LogClock::new(None);
+        let year = year_of(clock.now);
+        let stamp = clock
+            .timestamp("launch_cvd(42) I 01-01 00:00:00 started")
+            .expect("parsed");
+        assert_eq!(year_of(stamp), year);
+        assert_eq!(utc_epoch(2023, [11, 14, 22, 13, 20]), Some(1_700_000_000));
+    }
+}
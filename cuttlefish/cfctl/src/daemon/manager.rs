@@ -1,60 +1,63 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashSet},
     fs::{self, File, OpenOptions},
-    io::{Read, Seek, SeekFrom, Write},
+    future::{self, Future},
+    io::{self, Read, Seek, SeekFrom, Write},
+    net::{SocketAddr, ToSocketAddrs},
     os::unix::fs::PermissionsExt,
-    path::{Path, PathBuf},
-    process::{self, Child, Command, Stdio},
-    sync::{mpsc, Arc},
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    process::{self, Command, Stdio},
+    sync::{Arc, Mutex},
     thread,
     time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Context, Result};
+use dashmap::DashMap;
 use fs2::FileExt;
-use serde::{Deserialize, Serialize};
+use regex::Regex;
+use tokio::{
+    net::TcpStream,
+    process::{Child, Command as TokioCommand},
+    task, time,
+};
 use tracing::{debug, info, warn};
 
 use crate::protocol::{
-    AdbInfo, BootVerificationResult, CleanupSummary, CreateInstanceResponse, DestroyOptions,
-    ErrorDetail, InstanceActionResponse, InstanceId, InstanceState, InstanceSummary, LogsOptions,
-    LogsResponse, Request, Response, StartOptions,
+    AdbCommandResponse, BackendSpec, BootVerificationResult, CleanupSummary,
+    CreateInstanceResponse, DeployRequest, DestroyOptions, DoctorCheck, ErrorDetail, HistoryQuery,
+    HistoryResponse, ImageDigest, InstanceActionResponse, InstanceId, InstanceState,
+    InstanceSummary, LaunchCommand, LogFileUsage, LogSource, LogUsage, LogsOptions, LogsResponse,
+    NetworkDeviceStatus, NetworkMode, NetworkStatus, PortForward, ReadinessProbeSpec, Request,
+    Response, RunOutcome, RunRecord, SnapshotInfo, StartOptions,
 };
 
-use super::config::CfctlDaemonConfig;
+use super::backend::{backend_for, ensure_qemu_datadir, GuestBackend, LaunchContext};
+use super::cancel::CancelToken;
+use super::config::{CfctlDaemonConfig, SharedConfig};
+use super::doctor::{Doctor, CUTTLEFISH_CONFIG_LINK};
 use super::guest::{ExitStatusInfo, GuestHandle, GuestRegistry};
-use super::util::{epoch_secs, run_command_allow_failure, run_command_capture, tail_file};
+use super::history::{self, RunHistory};
+use super::logcat::{LogcatCaptures, LogcatTarget, LOGCAT_FILE};
+use super::logrotate;
+use super::logs::{LogQuery, LogSelection};
+use super::metrics::Metrics;
+use super::procstat;
+use super::qmp::{self, QmpClient};
+use super::slots::{tap_names, tap_present, Slot, SlotAllocator, MAX_INSTANCE_NUM};
+use super::store::{InstanceMetadata, LoadError, MetadataStore, METADATA_FILE};
+use super::util::{
+    epoch_secs, run_command_allow_failure, run_command_capture, run_command_timeout,
+    run_command_timeout_blocking, shell_quote, tail_file,
+};
 
 const ID_ALLOC_FILE: &str = "next_id";
-const METADATA_FILE: &str = "metadata.json";
-
-/// Resolve a username to a UID using libc getpwnam
-fn resolve_uid(username: &str) -> Result<u32> {
-    use std::ffi::CString;
-    let cname = CString::new(username)
-        .with_context(|| format!("invalid username: {}", username))?;
-    unsafe {
-        let pwd = libc::getpwnam(cname.as_ptr());
-        if pwd.is_null() {
-            anyhow::bail!("user '{}' not found", username);
-        }
-        Ok((*pwd).pw_uid)
-    }
-}
-
-/// Resolve a group name to a GID using libc getgrnam
-fn resolve_gid(groupname: &str) -> Result<u32> {
-    use std::ffi::CString;
-    let cname = CString::new(groupname)
-        .with_context(|| format!("invalid group name: {}", groupname))?;
-    unsafe {
-        let grp = libc::getgrnam(cname.as_ptr());
-        if grp.is_null() {
-            anyhow::bail!("group '{}' not found", groupname);
-        }
-        Ok((*grp).gr_gid)
-    }
-}
+/// Purpose recorded on warm instances until a client claims them.
+const POOL_PURPOSE: &str = "pool";
+const SNAPSHOTS_FILE: &str = "snapshots.json";
+/// Limit for shell, push, pull and other adb commands without `timeout_secs`.
+const ADB_COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
 
 struct InstancePaths {
     root: PathBuf,
@@ -96,7 +99,10 @@ impl InstancePaths {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
     use std::path::Path;
+    use std::thread;
     use tempfile::TempDir;
 
     fn test_config(root: &Path) -> CfctlDaemonConfig {
@@ -108,6 +114,7 @@ mod tests {
             default_init_boot_image: root.join("images/init_boot.img"),
             start_timeout: Duration::from_secs(5),
             adb_wait_timeout: Duration::from_secs(2),
+            snapshot_timeout: Duration::from_secs(1),
             journal_lines: 20,
             adb_host: "127.0.0.1".to_string(),
             base_adb_port: 6500,
@@ -115,12 +122,13 @@ mod tests {
             cuttlefish_instances_dir: root.join("cf_instances"),
             cuttlefish_assembly_dir: root.join("cf_assembly"),
             cuttlefish_system_image_dir: root.join("images"),
+            transfer_dir: root.join("transfer"),
             disable_host_gpu: true,
             ..CfctlDaemonConfig::default()
         }
     }
 
-    fn init_metadata(manager: &mut InstanceManager, id: InstanceId) -> Result<InstanceMetadata> {
+    fn init_metadata(manager: &InstanceManager, id: InstanceId) -> Result<InstanceMetadata> {
         let mut metadata = InstanceMetadata {
             id,
             purpose: Some("test".to_string()),
@@ -131,11 +139,16 @@ mod tests {
             created_at: epoch_secs()?,
             updated_at: epoch_secs()?,
             held: false,
+            pooled: false,
+            host_prepared: false,
+            host_assembled: false,
+            start_options: None,
+            launch: None,
         };
         let paths = manager.paths(id);
         fs::create_dir_all(&paths.root)?;
         fs::create_dir_all(&paths.artifacts)?;
-        manager.write_metadata(&paths, &metadata)?;
+        manager.write_metadata(&metadata)?;
         manager.write_env_file(&paths, &metadata)?;
         {
             let mut log = manager.prepare_run_log(&paths)?;
@@ -156,6 +169,7 @@ mod tests {
         fs::create_dir_all(&config.cuttlefish_instances_dir)?;
         fs::create_dir_all(&config.cuttlefish_assembly_dir)?;
         fs::create_dir_all(&config.cuttlefish_system_image_dir)?;
+        fs::create_dir_all(&config.transfer_dir)?;
         if let Some(parent) = config.default_boot_image.parent() {
             fs::create_dir_all(parent)?;
         }
@@ -165,16 +179,19 @@ mod tests {
         fs::write(&config.default_boot_image, b"boot")?;
         fs::write(&config.default_init_boot_image, b"init")?;
         let registry = Arc::new(GuestRegistry::new());
-        Ok((temp, InstanceManager::new(config, registry)))
+        Ok((
+            temp,
+            InstanceManager::new(SharedConfig::new(config), registry),
+        ))
     }
 
-    #[test]
-    fn wait_for_adb_returns_error_when_guest_exits_quickly() -> Result<()> {
-        let (_temp, mut manager) = setup_manager()?;
+    #[tokio::test]
+    async fn wait_for_adb_returns_error_when_guest_exits_quickly() -> Result<()> {
+        let (_temp, manager) = setup_manager()?;
         let id = 1;
-        let _metadata = init_metadata(&mut manager, id)?;
+        let _metadata = init_metadata(&manager, id)?;
 
-        let child = Command::new("sh")
+        let child = TokioCommand::new("sh")
             .arg("-c")
             .arg("exit 42")
             .spawn()
@@ -184,6 +201,7 @@ mod tests {
 
         let err = manager
             .wait_for_adb(id, Some(2))
+            .await
             .expect_err("expected failure");
         assert_eq!(err.code, "wait_for_adb_guest_exit");
         let message = err.message.as_deref().unwrap_or_default();
@@ -202,17 +220,17 @@ mod tests {
 
         let metadata_after = manager.metadata(id)?;
         assert_eq!(metadata_after.state, InstanceState::Failed);
-        assert!(handle.try_wait()?.is_some(), "child should be reaped");
+        assert!(handle.try_wait().is_some(), "child should be reaped");
         Ok(())
     }
 
-    #[test]
-    fn wait_for_adb_times_out_when_adb_never_appears() -> Result<()> {
-        let (_temp, mut manager) = setup_manager()?;
+    #[tokio::test]
+    async fn wait_for_adb_times_out_when_adb_never_appears() -> Result<()> {
+        let (_temp, manager) = setup_manager()?;
         let id = 2;
-        let _metadata = init_metadata(&mut manager, id)?;
+        let _metadata = init_metadata(&manager, id)?;
 
-        let child = Command::new("sh")
+        let child = TokioCommand::new("sh")
             .arg("-c")
             .arg("sleep 30")
             .spawn()
@@ -222,6 +240,7 @@ mod tests {
 
         let err = manager
             .wait_for_adb(id, Some(0))
+            .await
             .expect_err("expected timeout");
         assert_eq!(err.code, "wait_for_adb_timeout");
         let message = err.message.as_deref().unwrap_or_default();
@@ -237,47 +256,387 @@ mod tests {
         let metadata_after = manager.metadata(id)?;
         assert_eq!(metadata_after.state, InstanceState::Failed);
         assert!(
-            handle.try_wait()?.is_some(),
+            handle.try_wait().is_some(),
             "timeout path should terminate the child"
         );
         Ok(())
     }
-}
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct InstanceMetadata {
-    id: InstanceId,
-    #[serde(default)]
-    purpose: Option<String>,
-    #[serde(default)]
-    adb_port: u16,
-    #[serde(default)]
-    state: InstanceState,
-    #[serde(default)]
-    boot_image: PathBuf,
-    #[serde(default)]
-    init_boot_image: PathBuf,
-    #[serde(default)]
-    created_at: u64,
-    #[serde(default)]
-    updated_at: u64,
-    #[serde(default)]
-    held: bool,
-}
 
-impl InstanceMetadata {
-    fn summary(&self, host: &str) -> InstanceSummary {
-        InstanceSummary {
-            id: self.id,
-            adb: Some(AdbInfo {
-                host: host.to_string(),
-                port: self.adb_port,
-                serial: format!("{host}:{}", self.adb_port),
-            }),
-            state: self.state.clone(),
+    #[tokio::test]
+    async fn console_regex_probe_reports_ready() -> Result<()> {
+        let (_temp, manager) = setup_manager()?;
+        let id = 3;
+        let metadata = init_metadata(&manager, id)?;
+        let console_log = manager.console_log_path(id);
+        fs::create_dir_all(console_log.parent().unwrap())?;
+        fs::write(&console_log, "booting\n[heartbeat] pid1 up\n")?;
+
+        let child = TokioCommand::new("sh")
+            .arg("-c")
+            .arg("sleep 30")
+            .spawn()
+            .context("spawning long-running child")?;
+        let handle = Arc::new(GuestHandle::new(child));
+        manager.guest_registry.insert(id, Arc::clone(&handle));
+
+        let spec: ReadinessProbeSpec = "console:heartbeat.*up".parse().unwrap();
+        let mut probe = manager.readiness_probe(id, &metadata, &spec)?;
+        let (response, _) = manager
+            .wait_for_readiness(id, probe.as_mut(), Duration::from_secs(2), "readiness")
+            .await
+            .expect("console marker should satisfy probe");
+        assert_eq!(response.summary.state, InstanceState::Running);
+        manager.terminate_guest(id, Duration::from_secs(1)).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn process_alive_probe_fails_when_guest_exits() -> Result<()> {
+        let (_temp, manager) = setup_manager()?;
+        let id = 4;
+        let metadata = init_metadata(&manager, id)?;
+
+        let child = TokioCommand::new("sh")
+            .arg("-c")
+            .arg("exit 1")
+            .spawn()
+            .context("spawning short-lived child")?;
+        manager
+            .guest_registry
+            .insert(id, Arc::new(GuestHandle::new(child)));
+
+        let spec = ReadinessProbeSpec::ProcessAlive { secs: 1 };
+        let mut probe = manager.readiness_probe(id, &metadata, &spec)?;
+        let err = manager
+            .wait_for_readiness(id, probe.as_mut(), Duration::from_secs(3), "readiness")
+            .await
+            .expect_err("guest exit should fail the probe");
+        assert_eq!(err.code, "readiness_guest_exit");
+        assert_eq!(manager.metadata(id)?.state, InstanceState::Failed);
+        Ok(())
+    }
+
+    fn install_fake_adb(manager: &mut InstanceManager, root: &Path) -> Result<()> {
+        let script = root.join("fake-fhs");
+        fs::write(
+            &script,
+            "#!/bin/sh\n\
+             shift\n\
+             case \"$2\" in\n\
+             connect) echo \"connected to $3\" ;;\n\
+             devices) printf 'List of devices attached\\n0.0.0.0:6500\\tdevice\\n' ;;\n\
+             forward) printf '0.0.0.0:6500 tcp:7000 tcp:7001\\nemulator-5554 tcp:8000 tcp:8000\\n' ;;\n\
+             -s) shift 3; echo \"ran $*\"; echo oops >&2; exit 3 ;;\n\
+             esac\n",
+        )?;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+        Arc::make_mut(&mut manager.config).cuttlefish_fhs = script;
+        Ok(())
+    }
+
+    #[test]
+    fn pool_instances_are_claimed_lowest_first() -> Result<()> {
+        let (_temp, manager) = setup_manager()?;
+        let first = manager.create_pool_instance()?;
+        let second = manager.create_pool_instance()?;
+        assert_eq!(manager.pool_instances()?, vec![first, second]);
+
+        let claimed = manager.claim_pool_instance(Some("dev".to_string()))?;
+        assert_eq!(claimed, Some(first));
+        let metadata = manager.metadata(first)?;
+        assert!(!metadata.pooled);
+        assert_eq!(metadata.purpose.as_deref(), Some("dev"));
+        assert_eq!(manager.pool_instances()?, vec![second]);
+
+        assert_eq!(manager.claim_pool_instance(None)?, Some(second));
+        assert_eq!(manager.claim_pool_instance(None)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn list_reports_instances_with_corrupt_metadata() -> Result<()> {
+        let (_temp, manager) = setup_manager()?;
+        let good = manager.create_pool_instance()?;
+        let paths = manager.paths(7);
+        fs::create_dir_all(&paths.root)?;
+        fs::write(&paths.metadata, b"not json")?;
+
+        let listed = manager.list_instances()?;
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].id, good);
+        assert!(listed[0].error.is_none());
+        assert_eq!(listed[1].id, 7);
+        assert_eq!(listed[1].state, InstanceState::Unknown);
+        assert!(listed[1].error.as_deref().unwrap().contains("corrupt"));
+        assert!(!paths.metadata.exists(), "corrupt file is quarantined");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn snapshot_requests_validate_instance_state() -> Result<()> {
+        let (_temp, manager) = setup_manager()?;
+        let id = 6;
+        init_metadata(&manager, id)?;
+
+        let err = manager
+            .snapshot_instance(id, Some("base".to_string()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, "snapshot_instance_not_running");
+
+        let err = manager
+            .restore_instance(id, "base", StartOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, "snapshot_not_found");
+        assert_eq!(
+            validate_snapshot_name("bad name").unwrap_err().code,
+            "snapshot_invalid_name"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unanswered_savevm_leaves_snapshot_state_unknown() -> Result<()> {
+        let (_temp, manager) = setup_manager()?;
+        let id = 6;
+        init_metadata(&manager, id)?;
+        let child = TokioCommand::new("sleep")
+            .arg("30")
+            .kill_on_drop(true)
+            .spawn()
+            .context("spawning stand-in guest")?;
+        manager
+            .guest_registry
+            .insert(id, Arc::new(GuestHandle::new(child)));
+
+        let err = manager
+            .snapshot_instance(id, Some("base".to_string()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, "snapshot_monitor_unavailable");
+        let runtime_socket = manager
+            .host_instance_dir(id)
+            .join("instances/cvd-6/internal/qemu_monitor.sock");
+        assert!(err
+            .message
+            .as_deref()
+            .unwrap()
+            .contains(&runtime_socket.display().to_string()));
+
+        // A monitor that completes the handshake but never answers savevm.
+        fs::create_dir_all(runtime_socket.parent().unwrap())?;
+        let listener = UnixListener::bind(&runtime_socket)?;
+        let monitor = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            writeln!(writer, r#"{{"QMP": {{}}}}"#).unwrap();
+            reader.read_line(&mut line).unwrap();
+            writeln!(writer, r#"{{"return": {{}}}}"#).unwrap();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 {}
+        });
+        let err = manager
+            .snapshot_instance(id, Some("base".to_string()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, "snapshot_state_unknown");
+        assert!(manager.read_snapshots(id)?.is_empty());
+        monitor.join().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn clone_copies_deployed_images_and_start_options() -> Result<()> {
+        let (temp, manager) = setup_manager()?;
+        let source = manager
+            .create_instance(Some("repro".to_string()))?
+            .summary
+            .id;
+        let init_boot = temp.path().join("custom_init_boot.img");
+        fs::write(&init_boot, b"custom")?;
+        manager.deploy(DeployRequest {
+            id: source,
+            boot_image: None,
+            init_boot_image: Some(init_boot.display().to_string()),
+        })?;
+        let mut metadata = manager.metadata(source)?;
+        metadata.start_options = Some(StartOptions {
+            track: Some("pid1".to_string()),
+            ..StartOptions::default()
+        });
+        manager.write_metadata(&metadata)?;
+
+        let clone = manager.clone_instance(source, None).unwrap().summary;
+        assert_ne!(clone.id, source);
+        let cloned = manager.metadata(clone.id)?;
+        assert_ne!(cloned.adb_port, metadata.adb_port);
+        assert_eq!(cloned.purpose.as_deref(), Some("repro"));
+        assert_eq!(
+            cloned.init_boot_image,
+            manager.paths(clone.id).artifacts.join("init_boot.img")
+        );
+        assert_eq!(fs::read(&cloned.init_boot_image)?, b"custom");
+        assert_eq!(cloned.boot_image, manager.config.default_boot_image);
+        assert_eq!(
+            cloned
+                .start_options
+                .and_then(|options| options.track)
+                .as_deref(),
+            Some("pid1")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn describe_reports_stored_launch_and_restart_requires_it() -> Result<()> {
+        let (_temp, manager) = setup_manager()?;
+        let id = 7;
+        let mut metadata = init_metadata(&manager, id)?;
+
+        let err = manager.restart_instance(id).await.unwrap_err();
+        assert_eq!(err.code, "restart_no_start_options");
+
+        metadata.start_options = Some(StartOptions {
+            disable_webrtc: true,
+            ..StartOptions::default()
+        });
+        metadata.launch = Some(LaunchCommand {
+            argv: vec!["launch_cvd".to_string(), "--resume=false".to_string()],
+            env: [("CUTTLEFISH_INSTANCE".to_string(), id.to_string())].into(),
+            cwd: None,
+            launched_at: epoch_secs()?,
+        });
+        manager.write_metadata(&metadata)?;
+
+        let described = manager.describe(id, Some(5)).await?;
+        assert!(described.start_options.unwrap().disable_webrtc);
+        let launch = described.launch.unwrap();
+        assert_eq!(launch.argv[1], "--resume=false");
+        assert_eq!(launch.env["CUTTLEFISH_INSTANCE"], "7");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn describe_lists_instance_port_forwards() -> Result<()> {
+        let (temp, mut manager) = setup_manager()?;
+        install_fake_adb(&mut manager, temp.path())?;
+        let id = 8;
+        init_metadata(&manager, id)?;
+
+        let network = manager.describe(id, Some(1)).await?.network.unwrap();
+        assert_eq!(network.mode, NetworkMode::Launcher);
+        assert_eq!(network.devices[0].name, "cvd-mtap-08");
+        assert_eq!(
+            network.port_forwards,
+            Some(vec![PortForward {
+                host_port: 7000,
+                guest_port: 7001,
+            }])
+        );
+        assert_eq!(
+            "5555".parse(),
+            Ok(PortForward {
+                host_port: 5555,
+                guest_port: 5555
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn adb_shell_returns_exit_code_and_output() -> Result<()> {
+        let (temp, mut manager) = setup_manager()?;
+        install_fake_adb(&mut manager, temp.path())?;
+        let id = 1;
+        let _metadata = init_metadata(&manager, id)?;
+
+        let output = manager
+            .adb_shell(id, vec!["getprop".to_string(), "ro.x".to_string()], Some(5))
+            .await
+            .expect("shell should run");
+        assert_eq!(output.serial, "0.0.0.0:6500");
+        assert_eq!(output.exit_code, Some(3));
+        assert_eq!(output.stdout.trim(), "ran shell getprop ro.x");
+        assert_eq!(output.stderr.trim(), "oops");
+
+        let command = ["sh", "-c", "echo a b", "it's", ""]
+            .map(String::from)
+            .to_vec();
+        let output = manager
+            .adb_shell(id, command, Some(5))
+            .await
+            .expect("shell should run");
+        assert_eq!(
+            output.stdout.trim(),
+            r#"ran shell sh -c 'echo a b' 'it'\''s' ''"#
+        );
+
+        let err = manager
+            .adb_shell(id, Vec::new(), None)
+            .await
+            .expect_err("empty command should be rejected");
+        assert_eq!(err.code, "adb_command_invalid");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn push_and_pull_stay_inside_the_transfer_dir() -> Result<()> {
+        let (temp, mut manager) = setup_manager()?;
+        install_fake_adb(&mut manager, temp.path())?;
+        let id = 1;
+        init_metadata(&manager, id)?;
+        let transfer = manager.config.transfer_dir.clone();
+        fs::write(transfer.join("payload"), b"x")?;
+        fs::write(temp.path().join("secret"), b"x")?;
+        std::os::unix::fs::symlink(temp.path().join("secret"), transfer.join("escape"))?;
+        std::os::unix::fs::symlink(temp.path().join("missing"), transfer.join("dangling"))?;
+
+        let inside = transfer.join("payload").display().to_string();
+        let output = manager
+            .adb_push(id, &inside, "/data/local/tmp/payload", Some(5))
+            .await
+            .expect("push from the transfer dir should run");
+        assert_eq!(
+            output.stdout.trim(),
+            format!("ran push {} /data/local/tmp/payload", inside)
+        );
+        let fresh = transfer.join("trace.txt").display().to_string();
+        manager
+            .adb_pull(id, "/data/local/tmp/trace.txt", &fresh, Some(5))
+            .await
+            .expect("pull to a new file in the transfer dir should run");
+
+        let outside = temp.path().join("secret").display().to_string();
+        let dotdot = format!("{}/../secret", transfer.display());
+        let escape = transfer.join("escape").display().to_string();
+        for path in [outside.as_str(), "payload", &dotdot, &escape] {
+            let err = manager
+                .adb_push(id, path, "/data/local/tmp/x", Some(5))
+                .await
+                .expect_err("push from outside the transfer dir");
+            assert_eq!(err.code, "adb_command_invalid", "{}", path);
+        }
+        let dangling = transfer.join("dangling").display().to_string();
+        for path in ["/etc/sudoers.d/x", &dotdot, &escape, &dangling] {
+            let err = manager
+                .adb_pull(id, "/data/local/tmp/x", path, Some(5))
+                .await
+                .expect_err("pull to outside the transfer dir");
+            assert_eq!(err.code, "adb_command_invalid", "{}", path);
         }
+        Ok(())
     }
 }
 
+/// How long after launch a starting guest reached each boot milestone.
+#[derive(Debug, Clone, Copy, Default)]
+struct BootTimings {
+    adb: Option<Duration>,
+    marker: Option<Duration>,
+}
+
 #[derive(Debug, Clone)]
 pub struct CleanupOutcome {
     pub guest_processes_killed: bool,
@@ -304,6 +663,21 @@ impl CleanupOutcome {
     }
 }
 
+fn validate_snapshot_name(name: &str) -> Result<(), ErrorDetail> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(error_detail(
+            "snapshot_invalid_name",
+            format!("invalid snapshot name {:?} (use [A-Za-z0-9._-])", name),
+        ))
+    }
+}
+
 fn error_detail(code: &str, message: impl Into<String>) -> ErrorDetail {
     ErrorDetail {
         code: code.to_string(),
@@ -311,6 +685,23 @@ fn error_detail(code: &str, message: impl Into<String>) -> ErrorDetail {
     }
 }
 
+fn cancelled_detail(code_prefix: &str) -> ErrorDetail {
+    error_detail(
+        &format!("{code_prefix}_cancelled"),
+        "request cancelled before it completed",
+    )
+}
+
+fn boot_marker_verified() -> BootVerificationResult {
+    BootVerificationResult {
+        adb_ready: true,
+        boot_marker_observed: true,
+        failure_reason: None,
+        readiness_probe: None,
+        ready_after_ms: None,
+    }
+}
+
 fn deadline_from_timeout(timeout_secs: Option<u64>) -> Option<Instant> {
     timeout_secs.map(|secs| Instant::now() + Duration::from_secs(secs))
 }
@@ -325,51 +716,264 @@ fn secs_remaining(deadline: Option<Instant>) -> Option<u64> {
     })
 }
 
+type ProbeFuture<'a> = Pin<Box<dyn Future<Output = Result<bool>> + Send + 'a>>;
+
+/// A readiness signal polled while a freshly launched guest boots.
+trait ReadinessProbe: Send {
+    fn describe(&self) -> String;
+
+    /// Resolves to `Ok(true)` once ready; errors are treated as transient.
+    fn poll<'a>(&'a mut self, manager: &'a InstanceManager) -> ProbeFuture<'a>;
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+}
+
+struct AdbProbe {
+    serial: String,
+    connect_serial: String,
+}
+
+impl ReadinessProbe for AdbProbe {
+    fn describe(&self) -> String {
+        "adb".to_string()
+    }
+
+    fn poll<'a>(&'a mut self, manager: &'a InstanceManager) -> ProbeFuture<'a> {
+        Box::pin(async move {
+            manager
+                .adb_connect(&self.connect_serial)
+                .await
+                .with_context(|| format!("connecting to {}", self.connect_serial))?;
+            let active = manager
+                .resolve_active_adb_serial(&self.serial, &self.connect_serial)
+                .await
+                .with_context(|| format!("listing adb device {}", self.serial))?;
+            Ok(active.is_some())
+        })
+    }
+}
+
+/// Incrementally scans a growing log file for a line matching `regex`.
+struct LogRegexProbe {
+    label: &'static str,
+    path: PathBuf,
+    regex: Regex,
+    offset: u64,
+    carry: String,
+}
+
+impl LogRegexProbe {
+    fn new(label: &'static str, path: PathBuf, regex: Regex) -> Self {
+        Self {
+            label,
+            path,
+            regex,
+            offset: 0,
+            carry: String::new(),
+        }
+    }
+
+    /// Reads whatever was appended since the last scan and reports whether
+    /// any line matched.
+    fn scan(&mut self) -> Result<bool> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+        let len = file.metadata()?.len();
+        if len < self.offset {
+            // The log was recreated; start over.
+            self.offset = 0;
+            self.carry.clear();
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut chunk = Vec::new();
+        file.read_to_end(&mut chunk)?;
+        self.offset += chunk.len() as u64;
+        self.carry.push_str(&String::from_utf8_lossy(&chunk));
+
+        let complete = match self.carry.rfind('\n') {
+            Some(pos) => pos + 1,
+            None => return Ok(self.regex.is_match(&self.carry)),
+        };
+        if self.carry[..complete]
+            .lines()
+            .any(|line| self.regex.is_match(line))
+        {
+            return Ok(true);
+        }
+        self.carry.drain(..complete);
+        Ok(self.regex.is_match(&self.carry))
+    }
+}
+
+impl ReadinessProbe for LogRegexProbe {
+    fn describe(&self) -> String {
+        format!("{} match /{}/", self.label, self.regex.as_str())
+    }
+
+    fn poll<'a>(&'a mut self, _manager: &'a InstanceManager) -> ProbeFuture<'a> {
+        Box::pin(future::ready(self.scan()))
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_millis(500)
+    }
+}
+
+struct TcpPortProbe {
+    addr: SocketAddr,
+}
+
+impl ReadinessProbe for TcpPortProbe {
+    fn describe(&self) -> String {
+        format!("tcp port {}", self.addr)
+    }
+
+    fn poll<'a>(&'a mut self, _manager: &'a InstanceManager) -> ProbeFuture<'a> {
+        Box::pin(async move {
+            time::timeout(Duration::from_secs(1), TcpStream::connect(self.addr))
+                .await
+                .map_err(|_| anyhow!("timed out"))
+                .and_then(|connected| connected.map_err(Into::into))
+                .with_context(|| format!("connecting to {}", self.addr))?;
+            Ok(true)
+        })
+    }
+}
+
+struct ProcessAliveProbe {
+    started: Instant,
+    duration: Duration,
+}
+
+impl ReadinessProbe for ProcessAliveProbe {
+    fn describe(&self) -> String {
+        format!("launcher alive for {}s", self.duration.as_secs())
+    }
+
+    fn poll<'a>(&'a mut self, _manager: &'a InstanceManager) -> ProbeFuture<'a> {
+        // The readiness loop checks for guest exit before polling.
+        Box::pin(future::ready(Ok(self.started.elapsed() >= self.duration)))
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_millis(200)
+    }
+}
+
+/// Owns instance state for the lifetime of the daemon. Clones share the
+/// metadata cache and guest registry; each request runs on a clone carrying
+/// that request's [`CancelToken`] and a snapshot of the config.
+#[derive(Clone)]
 pub struct InstanceManager {
-    config: CfctlDaemonConfig,
-    metadata_cache: HashMap<InstanceId, InstanceMetadata>,
+    config: Arc<CfctlDaemonConfig>,
+    shared_config: SharedConfig,
+    metadata_cache: Arc<DashMap<InstanceId, InstanceMetadata>>,
     guest_registry: Arc<GuestRegistry>,
+    logcat: Arc<LogcatCaptures>,
+    /// Held while sweeping guest processes: concurrent `pkill -f <dir>/`
+    /// runs (destroy racing the exit watcher) match each other's pgrep.
+    process_sweep: Arc<Mutex<()>>,
+    metrics: Arc<Metrics>,
+    history: Arc<RunHistory>,
+    cancel: CancelToken,
 }
 
 impl InstanceManager {
-    pub fn new(config: CfctlDaemonConfig, guest_registry: Arc<GuestRegistry>) -> Self {
+    pub(super) fn new(shared_config: SharedConfig, guest_registry: Arc<GuestRegistry>) -> Self {
+        let config = shared_config.current();
         Self {
+            history: Arc::new(RunHistory::new(&config.state_dir)),
             config,
-            metadata_cache: HashMap::new(),
+            shared_config,
+            metadata_cache: Arc::new(DashMap::new()),
             guest_registry,
+            logcat: Arc::default(),
+            process_sweep: Arc::default(),
+            metrics: Arc::default(),
+            cancel: CancelToken::new(),
+        }
+    }
+
+    pub(super) fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Returns a handle sharing this manager's state that sees the config
+    /// as it is now, for work starting after a reload.
+    pub(super) fn refreshed(&self) -> Self {
+        Self {
+            config: self.shared_config.current(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a refreshed handle whose waits are interrupted by `cancel`.
+    pub(super) fn with_cancel(&self, cancel: CancelToken) -> Self {
+        Self {
+            cancel,
+            ..self.refreshed()
+        }
+    }
+
+    /// Runs blocking host work (pgrep/pkill, `ip`, recursive removals) on the
+    /// blocking pool with a clone of this manager.
+    pub(super) async fn blocking<T, F>(&self, work: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&InstanceManager) -> T + Send + 'static,
+    {
+        let manager = self.clone();
+        task::spawn_blocking(move || work(&manager))
+            .await
+            .context("blocking manager task failed")
+    }
+
+    /// Writes `metadata` on the blocking pool: the store fsyncs the file and
+    /// its directory, which would otherwise stall a runtime thread.
+    async fn save_metadata(&self, metadata: &InstanceMetadata) -> Result<()> {
+        let metadata = metadata.clone();
+        self.blocking(move |manager| manager.write_metadata(&metadata))
+            .await
+            .and_then(|result| result)
+    }
+
+    /// Sleeps for `duration`, failing with `<code_prefix>_cancelled` if the
+    /// request is cancelled first.
+    async fn pause(&self, duration: Duration, code_prefix: &str) -> Result<(), ErrorDetail> {
+        tokio::select! {
+            _ = time::sleep(duration) => Ok(()),
+            _ = self.cancel.cancelled() => Err(cancelled_detail(code_prefix)),
         }
     }
 
-    pub fn handle(&mut self, request: Request) -> Result<Response> {
+    pub async fn handle(&self, request: Request) -> Result<Response> {
         info!(target: "cfctl", "handle: beginning request processing: {:?}", request);
         match request {
             Request::CreateInstance { purpose } => {
                 info!(target: "cfctl", "handle: CreateInstance with purpose: {:?}", purpose);
-                let response = self.create_instance(purpose)?;
+                let response = self
+                    .blocking(move |manager| manager.create_instance(purpose))
+                    .await
+                    .and_then(|result| result)?;
                 info!(target: "cfctl", "handle: CreateInstance completed successfully for instance {}", response.summary.id);
                 Ok(Response {
-                    ok: true,
-                    message: None,
                     create: Some(response),
-                    action: None,
-                    logs: None,
-                    instances: None,
-                    error: None,
+                    ..Response::ok()
                 })
             }
             Request::StartInstance { id, options } => {
                 info!(target: "cfctl", "handle: StartInstance for instance {}", id);
-                match self.start_instance(id, options) {
+                match self.start_instance(id, options).await {
                     Ok(response) => {
                         info!(target: "cfctl", "handle: StartInstance completed successfully for instance {}", response.summary.id);
                         Ok(Response {
-                            ok: true,
-                            message: None,
-                            create: None,
                             action: Some(response),
-                            logs: None,
-                            instances: None,
-                            error: None,
+                            ..Response::ok()
                         })
                     }
                     Err(detail) => {
@@ -378,19 +982,28 @@ impl InstanceManager {
                     }
                 }
             }
-            Request::CreateStartInstance { purpose, options } => {
-                info!(target: "cfctl", "handle: CreateStartInstance purpose={:?}", purpose);
-                match self.create_and_start_instance(purpose, options) {
-                    Ok(response) => {
+            Request::CreateStartInstance {
+                purpose,
+                options,
+                pool,
+                boot_image,
+                init_boot_image,
+            } => {
+                info!(target: "cfctl", "handle: CreateStartInstance purpose={:?} pool={}", purpose, pool);
+                let deploy = DeployRequest {
+                    id: 0,
+                    boot_image,
+                    init_boot_image,
+                };
+                match self.create_and_start_instance(purpose, options, pool, deploy).await {
+                    Ok((response, claimed)) => {
                         info!(target: "cfctl", "handle: CreateStartInstance completed successfully for instance {}", response.summary.id);
+                        let message = claimed
+                            .then(|| format!("claimed warm instance {}", response.summary.id));
                         Ok(Response {
-                            ok: true,
-                            message: None,
-                            create: None,
                             action: Some(response),
-                            logs: None,
-                            instances: None,
-                            error: None,
+                            message,
+                            ..Response::ok()
                         })
                     }
                     Err(detail) => {
@@ -399,47 +1012,91 @@ impl InstanceManager {
                     }
                 }
             }
+            Request::CloneInstance { from, purpose, start } => {
+                info!(target: "cfctl", "handle: CloneInstance from {} start={}", from, start);
+                let create = match self
+                    .blocking(move |manager| manager.clone_instance(from, purpose))
+                    .await?
+                {
+                    Ok(create) => create,
+                    Err(detail) => return Ok(Response::error_with_detail(detail)),
+                };
+                if !start {
+                    return Ok(Response {
+                        create: Some(create),
+                        ..Response::ok()
+                    });
+                }
+                let id = create.summary.id;
+                let options = self
+                    .metadata(id)?
+                    .start_options
+                    .unwrap_or_default();
+                match self.start_instance(id, options).await {
+                    Ok(response) => Ok(Response {
+                        create: Some(create),
+                        action: Some(response),
+                        ..Response::ok()
+                    }),
+                    Err(detail) => Ok(Response {
+                        create: Some(create),
+                        ..Response::error_with_detail(detail)
+                    }),
+                }
+            }
             Request::StopInstance { id } => {
                 info!(target: "cfctl", "handle: StopInstance for instance {}", id);
-                let response = self.stop_instance(id)?;
+                let response = self.stop_instance(id).await?;
                 info!(target: "cfctl", "handle: StopInstance completed successfully for instance {}", response.summary.id);
                 Ok(Response {
-                    ok: true,
-                    message: None,
-                    create: None,
                     action: Some(response),
-                    logs: None,
-                    instances: None,
-                    error: None,
+                    ..Response::ok()
                 })
             }
+            Request::RestartInstance { id } => {
+                info!(target: "cfctl", "handle: RestartInstance for instance {}", id);
+                match self.restart_instance(id).await {
+                    Ok(response) => Ok(Response {
+                        action: Some(response),
+                        ..Response::ok()
+                    }),
+                    Err(detail) => {
+                        warn!(target: "cfctl", "handle: RestartInstance failed for {}: {:?}", id, detail);
+                        Ok(Response::error_with_detail(detail))
+                    }
+                }
+            }
             Request::HoldInstance { id } => {
                 info!(target: "cfctl", "handle: HoldInstance for instance {}", id);
-                let response = self.hold_instance(id)?;
+                let response = self
+                    .blocking(move |manager| manager.hold_instance(id))
+                    .await
+                    .and_then(|result| result)?;
                 info!(target: "cfctl", "handle: HoldInstance completed successfully for instance {}", response.summary.id);
                 Ok(Response {
-                    ok: true,
-                    message: None,
-                    create: None,
                     action: Some(response),
-                    logs: None,
-                    instances: None,
-                    error: None,
+                    ..Response::ok()
+                })
+            }
+            Request::ReleaseInstance { id } => {
+                info!(target: "cfctl", "handle: ReleaseInstance for instance {}", id);
+                let response = self
+                    .blocking(move |manager| manager.release_instance(id))
+                    .await
+                    .and_then(|result| result)?;
+                Ok(Response {
+                    action: Some(response),
+                    ..Response::ok()
                 })
             }
             Request::DestroyInstance { id, options } => {
                 info!(target: "cfctl", "handle: DestroyInstance for instance {}", id);
-                match self.destroy_instance(id, options) {
+                match self.destroy_instance(id, options).await {
                     Ok(response) => {
                         info!(target: "cfctl", "handle: DestroyInstance completed successfully for instance {}", response.summary.id);
                         Ok(Response {
-                            ok: true,
-                            message: None,
-                            create: None,
                             action: Some(response),
-                            logs: None,
-                            instances: None,
-                            error: None,
+                            ..Response::ok()
                         })
                     }
                     Err(detail) => {
@@ -449,71 +1106,154 @@ impl InstanceManager {
                 }
             }
             Request::Deploy(req) => {
-                self.deploy(req)?;
+                self.blocking(move |manager| manager.deploy(req))
+                    .await
+                    .and_then(|result| result)?;
                 Ok(Response::ok().with_message("deploy updated"))
             }
-            Request::WaitForAdb { id, timeout_secs } => match self.wait_for_adb(id, timeout_secs) {
+            Request::WaitForAdb { id, timeout_secs } => match self.wait_for_adb(id, timeout_secs).await {
                 Ok(response) => Ok(Response {
-                    ok: true,
-                    message: None,
-                    create: None,
                     action: Some(response),
-                    logs: None,
-                    instances: None,
-                    error: None,
+                    ..Response::ok()
                 }),
                 Err(detail) => Ok(Response::error_with_detail(detail)),
             },
-            Request::Logs { id, lines, options } => match self.logs(id, lines, options) {
+            Request::Logs { id, lines, options } => match self.logs(id, lines, options).await {
                 Ok(logs) => Ok(Response {
-                    ok: true,
-                    message: None,
-                    create: None,
-                    action: None,
                     logs: Some(logs),
-                    instances: None,
-                    error: None,
+                    ..Response::ok()
+                }),
+                Err(detail) => Ok(Response::error_with_detail(detail)),
+            },
+            Request::Shell {
+                id,
+                command,
+                timeout_secs,
+            } => match self.adb_shell(id, command, timeout_secs).await {
+                Ok(output) => Ok(Response {
+                    adb_command: Some(output),
+                    ..Response::ok()
+                }),
+                Err(detail) => Ok(Response::error_with_detail(detail)),
+            },
+            Request::Push {
+                id,
+                local_path,
+                remote_path,
+                timeout_secs,
+            } => match self.adb_push(id, &local_path, &remote_path, timeout_secs).await {
+                Ok(output) => Ok(Response {
+                    adb_command: Some(output),
+                    ..Response::ok()
+                }),
+                Err(detail) => Ok(Response::error_with_detail(detail)),
+            },
+            Request::Pull {
+                id,
+                remote_path,
+                local_path,
+                timeout_secs,
+            } => match self.adb_pull(id, &remote_path, &local_path, timeout_secs).await {
+                Ok(output) => Ok(Response {
+                    adb_command: Some(output),
+                    ..Response::ok()
+                }),
+                Err(detail) => Ok(Response::error_with_detail(detail)),
+            },
+            Request::SnapshotInstance { id, name } => match self.snapshot_instance(id, name).await {
+                Ok(snapshot) => Ok(Response {
+                    snapshots: Some(vec![snapshot]),
+                    ..Response::ok()
                 }),
                 Err(detail) => Ok(Response::error_with_detail(detail)),
             },
+            Request::RestoreInstance { id, name, options } => {
+                match self.restore_instance(id, &name, options).await {
+                    Ok(response) => Ok(Response {
+                        action: Some(response),
+                        ..Response::ok()
+                    }),
+                    Err(detail) => {
+                        warn!(target: "cfctl", "handle: RestoreInstance failed for {}: {:?}", id, detail);
+                        Ok(Response::error_with_detail(detail))
+                    }
+                }
+            }
+            Request::ListSnapshots { id } => {
+                let snapshots = self
+                    .blocking(move |manager| {
+                        manager.metadata(id)?;
+                        manager.read_snapshots(id)
+                    })
+                    .await
+                    .and_then(|result| result)?;
+                Ok(Response {
+                    snapshots: Some(snapshots),
+                    ..Response::ok()
+                })
+            }
+            Request::DeleteSnapshot { id, name } => match self.delete_snapshot(id, &name).await {
+                Ok(()) => Ok(Response::ok().with_message(format!("snapshot {} deleted", name))),
+                Err(detail) => Ok(Response::error_with_detail(detail)),
+            },
             Request::Status { id } => {
-                let response = self.status(id)?;
+                let response = self
+                    .blocking(move |manager| manager.status(id))
+                    .await
+                    .and_then(|result| result)?;
                 Ok(Response {
-                    ok: true,
-                    message: None,
-                    create: None,
                     action: Some(response),
-                    logs: None,
-                    instances: None,
-                    error: None,
+                    ..Response::ok()
                 })
             }
             Request::Describe { id, run_log_lines } => {
-                let response = self.describe(id, run_log_lines)?;
+                let response = self.describe(id, run_log_lines).await?;
                 Ok(Response {
-                    ok: true,
-                    message: None,
-                    create: None,
                     action: Some(response),
-                    logs: None,
-                    instances: None,
-                    error: None,
+                    ..Response::ok()
                 })
             }
-            Request::ListInstances => {
-                let instances = self.list_instances()?;
+            Request::ListInstances { resources } => {
+                let instances = self
+                    .blocking(move |manager| {
+                        let mut instances = manager.list_instances()?;
+                        if resources {
+                            manager.attach_resources(&mut instances);
+                        }
+                        Ok(instances)
+                    })
+                    .await
+                    .and_then(|result| result)?;
                 Ok(Response {
-                    ok: true,
-                    message: None,
-                    create: None,
-                    action: None,
-                    logs: None,
                     instances: Some(instances),
-                    error: None,
+                    ..Response::ok()
+                })
+            }
+            Request::RunScenario { .. } | Request::RunBatch { .. } => Err(anyhow!(
+                "scenario and batch requests are orchestrated by the daemon, not the instance manager"
+            )),
+            Request::ListRequests | Request::Cancel { .. } => Err(anyhow!(
+                "request tracking is handled by the daemon, not the instance manager"
+            )),
+            Request::History(query) => match self.history(query).await {
+                Ok(history) => Ok(Response {
+                    history: Some(history),
+                    ..Response::ok()
+                }),
+                Err(detail) => Ok(Response::error_with_detail(detail)),
+            },
+            Request::Doctor { fix } => {
+                let checks = self.doctor(fix).await?;
+                Ok(Response {
+                    doctor: Some(checks),
+                    ..Response::ok()
                 })
             }
+            Request::Metrics => Err(anyhow!(
+                "metrics are rendered by the daemon, not the instance manager"
+            )),
             Request::PruneExpired { max_age_secs } => {
-                let (pruned, retained) = self.prune_expired_instances(max_age_secs)?;
+                let (pruned, retained) = self.prune_expired_instances(max_age_secs).await?;
                 let msg = if retained > 0 {
                     format!(
                         "pruned {} expired instances; {} still running",
@@ -525,7 +1265,7 @@ impl InstanceManager {
                 Ok(Response::ok().with_message(msg))
             }
             Request::PruneAll => {
-                let (pruned, retained) = self.prune_all_instances()?;
+                let (pruned, retained) = self.prune_all_instances().await?;
                 let msg = if retained > 0 {
                     format!("pruned {} instances; {} still running", pruned, retained)
                 } else {
@@ -536,7 +1276,7 @@ impl InstanceManager {
         }
     }
 
-    fn list_instances(&mut self) -> Result<Vec<InstanceSummary>> {
+    pub(super) fn list_instances(&self) -> Result<Vec<InstanceSummary>> {
         let instances_dir = self.config.state_dir.join("instances");
         let entries_iter = match fs::read_dir(&instances_dir) {
             Ok(iter) => iter,
@@ -547,6 +1287,7 @@ impl InstanceManager {
         let mut entries = Vec::new();
         let mut found_count = 0;
         let mut skipped_count = 0;
+        let mut unreadable_count = 0;
 
         for entry in entries_iter {
             let entry = entry?;
@@ -564,7 +1305,23 @@ impl InstanceManager {
             let metadata = match self.metadata(id) {
                 Ok(metadata) => metadata,
                 Err(err) => {
-                    debug!(target: "cfctl", "list_instances: failed to load metadata for {}: {}", id, err);
+                    warn!(
+                        target: "cfctl",
+                        "list_instances: metadata for {} is unreadable: {}",
+                        id,
+                        err
+                    );
+                    entries.push(InstanceSummary {
+                        id,
+                        adb: None,
+                        state: InstanceState::Unknown,
+                        purpose: None,
+                        created_at: None,
+                        held: false,
+                        resources: None,
+                        error: Some(err.to_string()),
+                    });
+                    unreadable_count += 1;
                     continue;
                 }
             };
@@ -590,14 +1347,15 @@ impl InstanceManager {
         entries.sort_by_key(|summary| summary.id);
         info!(
             target: "cfctl",
-            "list_instances: found {} instances, skipped {} entries",
+            "list_instances: found {} instances, skipped {} entries, {} unreadable",
             found_count,
-            skipped_count
+            skipped_count,
+            unreadable_count
         );
         Ok(entries)
     }
 
-    fn prune_expired_instances(&mut self, max_age_secs: u64) -> Result<(usize, usize)> {
+    async fn prune_expired_instances(&self, max_age_secs: u64) -> Result<(usize, usize)> {
         let instances_dir = self.config.state_dir.join("instances");
         let entries_iter = match fs::read_dir(&instances_dir) {
             Ok(iter) => iter,
@@ -627,7 +1385,13 @@ impl InstanceManager {
             let metadata = match self.metadata(id) {
                 Ok(metadata) => metadata,
                 Err(err) => {
-                    debug!(target: "cfctl", "prune_expired: failed to load metadata for {}: {}", id, err);
+                    warn!(
+                        target: "cfctl",
+                        "prune_expired: keeping instance {} with unreadable metadata: {}",
+                        id,
+                        err
+                    );
+                    retained += 1;
                     continue;
                 }
             };
@@ -638,10 +1402,14 @@ impl InstanceManager {
                 debug!(target: "cfctl", "prune_expired: skipping held instance {}", id);
                 continue;
             }
+            if metadata.pooled {
+                debug!(target: "cfctl", "prune_expired: skipping pooled instance {}", id);
+                continue;
+            }
             if metadata.updated_at > cutoff {
                 continue;
             }
-            match self.prune_instance(id) {
+            match self.prune_instance(id).await {
                 Ok(true) => pruned += 1,
                 Ok(false) => {
                     retained += 1;
@@ -661,7 +1429,7 @@ impl InstanceManager {
         Ok((pruned, retained))
     }
 
-    fn prune_all_instances(&mut self) -> Result<(usize, usize)> {
+    async fn prune_all_instances(&self) -> Result<(usize, usize)> {
         let instances_dir = self.config.state_dir.join("instances");
         let entries_iter = match fs::read_dir(&instances_dir) {
             Ok(iter) => iter,
@@ -686,22 +1454,23 @@ impl InstanceManager {
                 Ok(value) => value,
                 Err(_) => continue,
             };
-            let metadata = match self.metadata(id) {
-                Ok(metadata) => metadata,
-                Err(err) => {
-                    debug!(target: "cfctl", "prune_all: failed to load metadata for {}: {}", id, err);
+            match self.metadata(id) {
+                Ok(metadata) if metadata.state == InstanceState::Destroyed => continue,
+                Ok(metadata) if metadata.held => {
+                    debug!(target: "cfctl", "prune_all: skipping held instance {}", id);
+                    retained += 1;
                     continue;
                 }
-            };
-            if metadata.state == InstanceState::Destroyed {
-                continue;
-            }
-            if metadata.held {
-                debug!(target: "cfctl", "prune_all: skipping held instance {}", id);
-                retained += 1;
-                continue;
+                Ok(_) => {}
+                // Nothing records whether it was held, so it goes with the rest.
+                Err(err) => warn!(
+                    target: "cfctl",
+                    "prune_all: pruning instance {} with unreadable metadata: {}",
+                    id,
+                    err
+                ),
             }
-            match self.prune_instance(id) {
+            match self.prune_instance(id).await {
                 Ok(true) => pruned += 1,
                 Ok(false) => retained += 1,
                 Err(err) => {
@@ -719,12 +1488,13 @@ impl InstanceManager {
         Ok((pruned, retained))
     }
 
-    fn prune_instance(&mut self, id: InstanceId) -> Result<bool> {
+    async fn prune_instance(&self, id: InstanceId) -> Result<bool> {
         debug!(target: "cfctl", "prune_instance: begin for {}", id);
-        self.prepare_destroy(id)?;
-        let mut cleanup_manager =
-            InstanceManager::new(self.config.clone(), self.guest_registry.clone());
-        match cleanup_manager.finish_destroy(id) {
+        self.prepare_destroy(id).await?;
+        match self
+            .blocking(move |manager| manager.finish_destroy(id))
+            .await?
+        {
             Ok(outcome) => {
                 if outcome.guest_processes_killed {
                     debug!(
@@ -734,9 +1504,6 @@ impl InstanceManager {
                     );
                     Ok(true)
                 } else {
-                    if let Ok(metadata) = cleanup_manager.metadata(id) {
-                        self.metadata_cache.insert(id, metadata);
-                    }
                     warn!(
                         target: "cfctl",
                         "prune_instance: instance {} still running after prune (pids: {:?})",
@@ -753,58 +1520,62 @@ impl InstanceManager {
                     id,
                     err
                 );
-                let _ = cleanup_manager.mark_metadata_state(id, InstanceState::Failed);
+                let _ = self
+                    .blocking(move |manager| manager.mark_metadata_state(id, InstanceState::Failed))
+                    .await;
                 Err(err)
             }
         }
     }
 
-    pub(super) fn allocate_id(&self) -> Result<InstanceId> {
+    /// Allocates the lowest free slot and reserves it by creating its state
+    /// dir while holding the allocation file lock.
+    pub(super) fn allocate_slot(&self) -> Result<Slot> {
         let id_dir = self.config.state_dir.join("control");
         fs::create_dir_all(&id_dir)?;
         let path = id_dir.join(ID_ALLOC_FILE);
-        let mut file = OpenOptions::new()
+        let file = OpenOptions::new()
             .create(true)
             .read(true)
             .write(true)
             .truncate(false)
             .open(&path)?;
         file.lock_exclusive()?;
-        let mut buf = String::new();
-        file.read_to_string(&mut buf)?;
-        let mut next: InstanceId = buf.trim().parse().unwrap_or(1);
-        if next == 0 {
-            next = 1;
-        }
-
-        let mut candidate = next;
-        let mut selected: Option<InstanceId> = None;
-        for _ in 0..99 {
-            if candidate == 0 || candidate > 99 {
-                candidate = 1;
-            }
-            let paths = self.paths(candidate);
-            if !paths.metadata.exists() && !paths.root.exists() {
-                selected = Some(candidate);
-                candidate = candidate % 99 + 1;
-                break;
-            }
-            candidate = candidate % 99 + 1;
-        }
 
-        let id = selected.ok_or_else(|| anyhow!("no available instance slots (1-99)"))?;
-
-        file.set_len(0)?;
-        file.seek(SeekFrom::Start(0))?;
-        write!(file, "{}", candidate)?;
-        file.sync_all()?;
+        let known = |id: InstanceId| {
+            let paths = self.paths(id);
+            paths.metadata.exists() || paths.root.exists()
+        };
+        let max_instances = self.config.max_instances;
+        let slot = if max_instances > 0
+            && (1..=MAX_INSTANCE_NUM).filter(|id| known(*id)).count() >= max_instances
+        {
+            Err(anyhow!(
+                "instance limit reached: max_instances is {}",
+                max_instances
+            ))
+        } else {
+            SlotAllocator::new(&self.config).allocate(known)
+        };
+        let reserved = slot.and_then(|slot| {
+            let root = self.paths(slot.id).root;
+            fs::create_dir_all(&root)
+                .with_context(|| format!("reserving instance dir {}", root.display()))?;
+            Ok(slot)
+        });
         file.unlock()?;
-        Ok(id)
+        let slot = reserved?;
+        debug!(
+            target: "cfctl",
+            "allocate_slot: reserved instance {} with adb port {}",
+            slot.id,
+            slot.adb_port
+        );
+        Ok(slot)
     }
 
-    fn create_instance(&mut self, purpose: Option<String>) -> Result<CreateInstanceResponse> {
-        let id = self.allocate_id()?;
-        let adb_port = self.config.base_adb_port + id as u16 - 1;
+    fn create_instance(&self, purpose: Option<String>) -> Result<CreateInstanceResponse> {
+        let Slot { id, adb_port } = self.allocate_slot()?;
         let paths = self.paths(id);
 
         fs::create_dir_all(&paths.root)
@@ -823,31 +1594,372 @@ impl InstanceManager {
             created_at: now,
             updated_at: now,
             held: false,
+            pooled: false,
+            host_prepared: false,
+            host_assembled: false,
+            start_options: None,
+            launch: None,
         };
 
-        self.write_metadata(&paths, &metadata)?;
+        self.write_metadata(&metadata)?;
         self.write_env_file(&paths, &metadata)?;
 
         let summary = metadata.summary(&self.config.adb_host);
-        self.metadata_cache.insert(id, metadata);
         Ok(CreateInstanceResponse { summary })
     }
 
-    fn create_and_start_instance(
-        &mut self,
+    /// Creates (or claims from the warm pool when `pool` is set) an instance,
+    /// applies any images in `deploy`, and starts it. The returned flag reports
+    /// whether a pooled instance was claimed.
+    async fn create_and_start_instance(
+        &self,
         purpose: Option<String>,
         options: StartOptions,
-    ) -> Result<InstanceActionResponse, ErrorDetail> {
+        pool: bool,
+        mut deploy: DeployRequest,
+    ) -> Result<(InstanceActionResponse, bool), ErrorDetail> {
+        let claimed = if pool {
+            let purpose = purpose.clone();
+            self.blocking(move |manager| manager.claim_pool_instance(purpose))
+                .await
+                .and_then(|result| result)
+                .map_err(|err| error_detail("create_start_pool_claim_failed", format!("{err:#}")))?
+        } else {
+            None
+        };
+        let id = match claimed {
+            Some(id) => id,
+            None => {
+                if pool {
+                    info!(
+                        target: "cfctl",
+                        "create_and_start_instance: warm pool empty, creating a new instance"
+                    );
+                }
+                self.blocking(move |manager| manager.create_instance(purpose))
+                    .await
+                    .and_then(|result| result)
+                    .map_err(|err| error_detail("create_start_create_failed", err.to_string()))?
+                    .summary
+                    .id
+            }
+        };
+        if deploy.boot_image.is_some() || deploy.init_boot_image.is_some() {
+            deploy.id = id;
+            self.blocking(move |manager| manager.deploy(deploy))
+                .await
+                .and_then(|result| result)
+                .map_err(|err| error_detail("create_start_deploy_failed", format!("{err:#}")))?;
+        }
+        let response = self.start_instance(id, options).await?;
+        Ok((response, claimed.is_some()))
+    }
+
+    /// Creates a new instance (own id, ADB port and host directories) carrying
+    /// `from`'s deployed images and last start options.
+    fn clone_instance(
+        &self,
+        from: InstanceId,
+        purpose: Option<String>,
+    ) -> Result<CreateInstanceResponse, ErrorDetail> {
+        let source = self
+            .metadata(from)
+            .map_err(|err| error_detail("instance_not_found", err.to_string()))?;
+        let purpose = purpose
+            .or_else(|| source.purpose.clone())
+            .or_else(|| Some(format!("clone of {}", from)));
         let create = self
             .create_instance(purpose)
-            .map_err(|err| error_detail("create_start_create_failed", err.to_string()))?;
-        self.start_instance(create.summary.id, options)
+            .map_err(|err| error_detail("clone_create_failed", err.to_string()))?;
+        let id = create.summary.id;
+
+        let source_artifacts = self.paths(from).artifacts;
+        let paths = self.paths(id);
+        let mut metadata = self
+            .metadata(id)
+            .map_err(|err| error_detail("clone_metadata", err.to_string()))?;
+        let copy_image = |image: &Path| -> Result<PathBuf> {
+            // Images outside the source's artifacts dir are shared defaults.
+            let Ok(relative) = image.strip_prefix(&source_artifacts) else {
+                return Ok(image.to_path_buf());
+            };
+            let dest = paths.artifacts.join(relative);
+            fs::copy(image, &dest)
+                .with_context(|| format!("copy {} -> {}", image.display(), dest.display()))?;
+            Ok(dest)
+        };
+        metadata.boot_image = copy_image(&source.boot_image)
+            .map_err(|err| error_detail("clone_copy_failed", format!("{err:#}")))?;
+        metadata.init_boot_image = copy_image(&source.init_boot_image)
+            .map_err(|err| error_detail("clone_copy_failed", format!("{err:#}")))?;
+        metadata.start_options = source.start_options.map(|options| StartOptions {
+            resume: false,
+            ..options
+        });
+
+        self.write_metadata(&metadata)
+            .map_err(|err| error_detail("clone_write_metadata", err.to_string()))?;
+        self.write_env_file(&paths, &metadata)
+            .map_err(|err| error_detail("clone_write_env", err.to_string()))?;
+        info!(
+            target: "cfctl",
+            "clone_instance: cloned instance {} into {}",
+            from,
+            id
+        );
+        let summary = metadata.summary(&self.config.adb_host);
+        Ok(CreateInstanceResponse { summary })
+    }
+
+    /// Lists unclaimed warm instances, lowest id first.
+    pub(super) fn pool_instances(&self) -> Result<Vec<InstanceId>> {
+        let instances_dir = self.config.state_dir.join("instances");
+        let entries_iter = match fs::read_dir(&instances_dir) {
+            Ok(iter) => iter,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut ids = Vec::new();
+        for entry in entries_iter {
+            let entry = entry?;
+            let Ok(id) = entry.file_name().to_string_lossy().parse::<InstanceId>() else {
+                continue;
+            };
+            let metadata = match self.metadata(id) {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    debug!(target: "cfctl", "pool_instances: failed to load metadata for {}: {}", id, err);
+                    continue;
+                }
+            };
+            if metadata.pooled && metadata.state == InstanceState::Created {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// Creates a warm instance for the pool. With `pool_precreate_dirs` the
+    /// empty host instance/assembly directories and the qemu datadir are
+    /// created now instead of at start. With `pool_preassemble` the instance
+    /// is only offered to claims once `assemble_pool_instance` ran.
+    pub(super) fn create_pool_instance(&self) -> Result<InstanceId> {
+        let id = self
+            .create_instance(Some(POOL_PURPOSE.to_string()))?
+            .summary
+            .id;
+        let mut metadata = self.metadata(id)?;
+        metadata.pooled = !self.config.pool_preassemble;
+        if self.config.pool_precreate_dirs || self.config.pool_preassemble {
+            self.prepare_host_directories(id)?;
+            ensure_qemu_datadir(&self.config)?;
+            metadata.host_prepared = true;
+        }
+        self.write_metadata(&metadata)?;
+        Ok(id)
+    }
+
+    /// Assembles a warm instance's disks with the default images and then
+    /// offers it to claims. A failed assembly still offers the instance; it
+    /// just boots cold.
+    pub(super) fn assemble_pool_instance(&self, id: InstanceId) -> Result<()> {
+        let metadata = self.metadata(id)?;
+        if metadata.state != InstanceState::Created
+            || metadata.purpose.as_deref() != Some(POOL_PURPOSE)
+        {
+            return Err(anyhow!(
+                "instance {} was used before the pool could assemble it",
+                id
+            ));
+        }
+        let assembled = self.run_pool_assembly(id);
+        let mut metadata = self.metadata(id)?;
+        metadata.pooled = true;
+        metadata.host_assembled = assembled.is_ok();
+        metadata.updated_at = epoch_secs()?;
+        self.write_metadata(&metadata)?;
+        assembled
     }
 
-    fn start_instance(
-        &mut self,
+    fn run_pool_assembly(&self, id: InstanceId) -> Result<()> {
+        let metadata = self.metadata(id)?;
+        let options = StartOptions::default();
+        let backend = backend_for(&self.config, &options.backend);
+        let instance_dir = self.host_instance_dir(id);
+        let assembly_dir = self.host_assembly_dir(id);
+        let ctx = LaunchContext {
+            config: &self.config,
+            id,
+            instance_dir: &instance_dir,
+            assembly_dir: &assembly_dir,
+            boot_image: &metadata.boot_image,
+            init_boot_image: &metadata.init_boot_image,
+            adb_port: metadata.adb_port,
+            options: &options,
+            resume: false,
+        };
+        backend.prepare(&ctx)?;
+        let Some(mut cmd) = backend.assemble_command(&ctx).transpose()? else {
+            return Err(anyhow!(
+                "the {} backend has no assembly step",
+                backend.name()
+            ));
+        };
+        info!(
+            target: "cfctl",
+            "assemble_pool_instance: assembling warm instance {}",
+            id
+        );
+        let output = run_command_timeout_blocking(&mut cmd, self.config.start_timeout)?
+            .ok_or_else(|| {
+                anyhow!(
+                    "assembling instance {} exceeded {}s",
+                    id,
+                    self.config.start_timeout.as_secs()
+                )
+            })?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "assembling instance {} failed ({}): {}",
+                id,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    fn claim_pool_instance(&self, purpose: Option<String>) -> Result<Option<InstanceId>> {
+        let Some(id) = self
+            .pool_instances()?
+            .into_iter()
+            .find(|id| !self.guest_registry.contains(*id))
+        else {
+            return Ok(None);
+        };
+        let mut metadata = self.metadata(id)?;
+        metadata.pooled = false;
+        metadata.purpose = purpose;
+        metadata.updated_at = epoch_secs()?;
+        self.write_metadata(&metadata)?;
+        info!(
+            target: "cfctl",
+            "claim_pool_instance: claimed warm instance {}",
+            id
+        );
+        Ok(Some(id))
+    }
+
+    /// Starts the guest and records the attempt, successful or not, in the
+    /// run history.
+    async fn start_instance(
+        &self,
+        id: InstanceId,
+        options: StartOptions,
+    ) -> Result<InstanceActionResponse, ErrorDetail> {
+        let started_at = epoch_secs().unwrap_or(0);
+        let started = Instant::now();
+        let mut timings = BootTimings::default();
+        let result = self
+            .launch_instance(id, options.clone(), &mut timings)
+            .await
+            .map(|response| InstanceActionResponse {
+                adb_ms: timings.adb.map(|elapsed| elapsed.as_millis() as u64),
+                marker_ms: timings.marker.map(|elapsed| elapsed.as_millis() as u64),
+                ..response
+            });
+        if let Some(adb) = timings.adb {
+            self.metrics.record_boot_to_adb(adb);
+        }
+        if let Some(marker) = timings.marker {
+            self.metrics.record_boot_to_marker(marker);
+        }
+
+        let failure = result.as_ref().err().cloned();
+        let outcome = match &failure {
+            None => RunOutcome::Ok,
+            Some(detail) if detail.code.ends_with("_cancelled") => RunOutcome::Cancelled,
+            Some(_) if self.cancel.is_cancelled() => RunOutcome::Cancelled,
+            Some(_) => RunOutcome::Failed,
+        };
+        let run = RunRecord {
+            run_id: 0,
+            instance_id: id,
+            purpose: None,
+            started_at,
+            backend: backend_for(&self.config, &options.backend)
+                .name()
+                .to_string(),
+            boot_image: None,
+            init_image: None,
+            options,
+            adb_ms: timings.adb.map(|elapsed| elapsed.as_millis() as u64),
+            marker_ms: timings.marker.map(|elapsed| elapsed.as_millis() as u64),
+            duration_ms: started.elapsed().as_millis() as u64,
+            outcome,
+            failure,
+        };
+        match self
+            .blocking(move |manager| manager.record_run(run))
+            .await
+            .and_then(|result| result)
+        {
+            Ok(Some(run_id)) => debug!(
+                target: "cfctl",
+                "start_instance: recorded run {} for instance {}",
+                run_id,
+                id
+            ),
+            Ok(None) => {}
+            Err(err) => warn!(
+                target: "cfctl",
+                "start_instance: failed to record run for instance {}: {:#}",
+                id,
+                err
+            ),
+        }
+        result
+    }
+
+    /// Fills in the instance's purpose and image digests and stores `run`.
+    /// Starts of instances that do not exist are not recorded.
+    fn record_run(&self, mut run: RunRecord) -> Result<Option<u64>> {
+        let Ok(metadata) = self.metadata(run.instance_id) else {
+            return Ok(None);
+        };
+        run.purpose = metadata.purpose;
+        let (boot, init) = match &run.options.backend {
+            BackendSpec::Qemu(qemu) => {
+                (PathBuf::from(&qemu.kernel), PathBuf::from(&qemu.initramfs))
+            }
+            BackendSpec::Cuttlefish => (metadata.boot_image, metadata.init_boot_image),
+        };
+        let digest = |path: PathBuf| -> Option<ImageDigest> {
+            match self.history.digest(&path) {
+                Ok(digest) => Some(digest),
+                Err(err) => {
+                    debug!(
+                        target: "cfctl",
+                        "record_run: not hashing {}: {:#}",
+                        path.display(),
+                        err
+                    );
+                    None
+                }
+            }
+        };
+        run.boot_image = digest(boot);
+        run.init_image = digest(init);
+        self.history.record(&run).map(Some)
+    }
+
+    async fn launch_instance(
+        &self,
         id: InstanceId,
         options: StartOptions,
+        timings: &mut BootTimings,
     ) -> Result<InstanceActionResponse, ErrorDetail> {
         info!(
             target: "cfctl",
@@ -855,10 +1967,37 @@ impl InstanceManager {
             id
         );
 
+        let backend = backend_for(&self.config, &options.backend);
+        backend
+            .validate(&options)
+            .map_err(|err| error_detail("start_instance_invalid_options", err))?;
         if options.skip_adb_wait && options.verify_boot {
             return Err(error_detail(
                 "start_instance_invalid_options",
-                "cannot use both skip_adb_wait and verify_boot (boot verification requires ADB)".to_string(),
+                "cannot use both skip_adb_wait and verify_boot (boot verification requires ADB)"
+                    .to_string(),
+            ));
+        }
+        let readiness_spec = options
+            .readiness
+            .clone()
+            .unwrap_or_else(|| backend.default_readiness());
+        if options.skip_adb_wait && options.readiness.is_some() {
+            return Err(error_detail(
+                "start_instance_invalid_options",
+                "cannot use both skip_adb_wait and a readiness probe",
+            ));
+        }
+        if options.verify_boot && readiness_spec != ReadinessProbeSpec::Adb {
+            return Err(error_detail(
+                "start_instance_invalid_options",
+                "verify_boot requires the adb readiness probe",
+            ));
+        }
+        if options.skip_adb_wait && !options.port_forwards.is_empty() {
+            return Err(error_detail(
+                "start_instance_invalid_options",
+                "port forwards are set up over adb and cannot be combined with skip_adb_wait",
             ));
         }
 
@@ -873,6 +2012,16 @@ impl InstanceManager {
                 format!("instance {} already running", id),
             ));
         }
+        let running = self.guest_registry.ids().len();
+        if self.config.max_running > 0 && running >= self.config.max_running {
+            return Err(error_detail(
+                "start_instance_capacity_exceeded",
+                format!(
+                    "{} guests already running (max_running is {})",
+                    running, self.config.max_running
+                ),
+            ));
+        }
 
         let mut metadata = self
             .metadata(id)
@@ -885,6 +2034,10 @@ impl InstanceManager {
         );
 
         metadata.state = InstanceState::Starting;
+        metadata.pooled = false;
+        metadata.start_options = Some(options.clone());
+        let host_prepared = std::mem::take(&mut metadata.host_prepared);
+        let host_assembled = std::mem::take(&mut metadata.host_assembled);
         metadata.updated_at = epoch_secs()
             .map_err(|err| error_detail("start_instance_timestamp", err.to_string()))?;
         let paths = self.paths(id);
@@ -892,13 +2045,15 @@ impl InstanceManager {
             target: "cfctl",
             "start_instance: writing updated metadata with state Starting"
         );
-        self.write_metadata(&paths, &metadata)
+        self.save_metadata(&metadata)
+            .await
             .map_err(|err| error_detail("start_instance_write_metadata", err.to_string()))?;
         self.write_env_file(&paths, &metadata)
             .map_err(|err| error_detail("start_instance_write_env", err.to_string()))?;
-        self.metadata_cache.insert(id, metadata.clone());
 
-        self.preflight_cleanup(id)
+        self.blocking(move |manager| manager.preflight_cleanup(id))
+            .await
+            .and_then(|result| result)
             .map_err(|err| error_detail("start_instance_preflight", err.to_string()))?;
 
         info!(
@@ -906,17 +2061,77 @@ impl InstanceManager {
             "start_instance: preparing host directories for instance {}",
             id
         );
-        self.prepare_host_directories(id)
-            .map_err(|err| error_detail("start_instance_prepare_dirs", err.to_string()))?;
-        self.ensure_qemu_datadir()
-            .map_err(|err| error_detail("start_instance_ensure_qemu", err.to_string()))?;
+        // Disks the pool assembled with the default images and launcher are
+        // picked up like a resume.
+        let preassembled =
+            host_assembled && options.backend == BackendSpec::Cuttlefish && options.track.is_none();
+        let resume = (options.resume || preassembled) && self.host_instance_dir(id).exists();
+        if preassembled {
+            debug!(
+                target: "cfctl",
+                "start_instance: resuming disks assembled by the pool for {}",
+                id
+            );
+        }
+        if options.resume && !resume {
+            warn!(
+                target: "cfctl",
+                "start_instance: no host state to resume for instance {}, cold booting",
+                id
+            );
+        }
+        if host_prepared {
+            debug!(
+                target: "cfctl",
+                "start_instance: reusing host directories prepared by the pool for {}",
+                id
+            );
+        } else if resume {
+            debug!(
+                target: "cfctl",
+                "start_instance: keeping existing host directories for resume of {}",
+                id
+            );
+        } else {
+            self.prepare_host_directories(id)
+                .map_err(|err| error_detail("start_instance_prepare_dirs", err.to_string()))?;
+            // A fresh overlay drops every internal snapshot taken on the old one.
+            self.clear_snapshots(id);
+        }
+        let instance_dir = self.host_instance_dir(id);
+        let assembly_dir = self.host_assembly_dir(id);
+        let launch_ctx = LaunchContext {
+            config: &self.config,
+            id,
+            instance_dir: &instance_dir,
+            assembly_dir: &assembly_dir,
+            boot_image: &metadata.boot_image,
+            init_boot_image: &metadata.init_boot_image,
+            adb_port: metadata.adb_port,
+            options: &options,
+            resume,
+        };
+        backend.prepare(&launch_ctx).map_err(|err| {
+            error_detail(
+                &format!("start_instance_prepare_{}", backend.name()),
+                format!("{err:#}"),
+            )
+        })?;
+        if options.network == NetworkMode::Isolated && backend.uses_cuttlefish_host() {
+            self.create_isolated_taps(id)
+                .map_err(|err| error_detail("start_instance_network", format!("{err:#}")))?;
+        }
 
+        self.rotate_console_log(id, backend.as_ref());
         let run_log = self
             .prepare_run_log(&paths)
             .map_err(|err| error_detail("start_instance_prepare_log", err.to_string()))?;
-        let child = match self.spawn_guest_process(id, &metadata, run_log, !options.disable_webrtc, options.track.as_deref())
+        let mut probe = self
+            .readiness_probe(id, &metadata, &readiness_spec)
+            .map_err(|err| error_detail("start_instance_invalid_options", format!("{err:#}")))?;
+        let (child, launch) = match self.spawn_guest_process(&launch_ctx, backend.as_ref(), run_log)
         {
-            Ok(child) => child,
+            Ok(spawned) => spawned,
             Err(err) => {
                 warn!(
                     target: "cfctl",
@@ -928,7 +2143,7 @@ impl InstanceManager {
                 metadata.updated_at = epoch_secs().map_err(|err| {
                     error_detail("start_instance_failed_timestamp", err.to_string())
                 })?;
-                if let Err(write_err) = self.write_metadata(&paths, &metadata) {
+                if let Err(write_err) = self.save_metadata(&metadata).await {
                     warn!(
                         target: "cfctl",
                         "start_instance: failed to write failed metadata for {}: {:#}",
@@ -936,14 +2151,23 @@ impl InstanceManager {
                         write_err
                     );
                 }
-                self.metadata_cache.insert(id, metadata.clone());
                 return Err(error_detail(
                     "start_instance_spawn_failed",
-                    format!("launching cuttlefish guest: {err:#}"),
+                    format!("launching {} guest: {err:#}", backend.name()),
                 ));
             }
         };
+        let launched = Instant::now();
         let handle = Arc::new(GuestHandle::new(child));
+        metadata.launch = Some(launch);
+        if let Err(err) = self.save_metadata(&metadata).await {
+            warn!(
+                target: "cfctl",
+                "start_instance: failed to record launch command for {}: {:#}",
+                id,
+                err
+            );
+        }
 
         if let Some(existing) = self.guest_registry.insert(id, Arc::clone(&handle)) {
             warn!(
@@ -960,56 +2184,84 @@ impl InstanceManager {
                 "start_instance: skipping adb wait for instance {} (skip_adb_wait enabled)",
                 id
             );
-            
-            let mut metadata = self
-                .metadata(id)
-                .map_err(|err| error_detail("start_instance_metadata_after_skip", err.to_string()))?;
+
+            let mut metadata = self.metadata(id).map_err(|err| {
+                error_detail("start_instance_metadata_after_skip", err.to_string())
+            })?;
             metadata.state = InstanceState::Running;
-            metadata.updated_at = epoch_secs()
-                .map_err(|err| error_detail("start_instance_timestamp_after_skip", err.to_string()))?;
-            let paths = self.paths(id);
-            self.write_metadata(&paths, &metadata)
-                .map_err(|err| error_detail("start_instance_write_metadata_after_skip", err.to_string()))?;
-            self.metadata_cache.insert(id, metadata.clone());
-            
+            metadata.updated_at = epoch_secs().map_err(|err| {
+                error_detail("start_instance_timestamp_after_skip", err.to_string())
+            })?;
+            self.save_metadata(&metadata).await.map_err(|err| {
+                error_detail("start_instance_write_metadata_after_skip", err.to_string())
+            })?;
+
             info!(
                 target: "cfctl",
                 "start_instance: instance {} started without adb wait; registering exit watcher",
                 id
             );
+            self.start_logcat_capture(id, backend.as_ref(), &handle);
             self.spawn_exit_watcher(id, handle);
-            
-            Ok(InstanceActionResponse {
-                summary: metadata.summary(&self.config.adb_host),
-                journal_tail: None,
-                verification: None,
-                cleanup: None,
-                run_log_tail: None,
-                console_snapshot_path: None,
-            })
+
+            Ok(InstanceActionResponse::new(
+                metadata.summary(&self.config.adb_host),
+            ))
         } else {
             let effective_timeout = options
                 .timeout_secs
-                .or_else(|| Some(self.config.start_timeout.as_secs()));
+                .or(Some(self.config.start_timeout.as_secs()));
             let deadline = deadline_from_timeout(effective_timeout);
 
-            let mut response = match self.wait_for_adb(id, secs_remaining(deadline)) {
-                Ok(resp) => resp,
+            let timeout = secs_remaining(deadline)
+                .map(Duration::from_secs)
+                .unwrap_or(self.config.adb_wait_timeout);
+            let code_prefix = match readiness_spec {
+                ReadinessProbeSpec::Adb => "wait_for_adb",
+                _ => "readiness",
+            };
+            let (mut response, ready_after) = match self
+                .wait_for_readiness(id, probe.as_mut(), timeout, code_prefix)
+                .await
+            {
+                Ok(ready) => ready,
                 Err(detail) => {
                     warn!(
                         target: "cfctl",
-                        "start_instance: wait_for_adb failed for instance {}: {:?}",
+                        "start_instance: readiness probe failed for instance {}: {:?}",
                         id,
                         detail
                     );
-                    let _ = self.terminate_guest(id, Duration::from_secs(5));
+                    let _ = self.terminate_guest(id, Duration::from_secs(5)).await;
                     return Err(detail);
                 }
             };
+            match readiness_spec {
+                ReadinessProbeSpec::Adb => timings.adb = Some(launched.elapsed()),
+                ReadinessProbeSpec::ConsoleRegex { .. } if !options.verify_boot => {
+                    timings.marker = Some(launched.elapsed())
+                }
+                _ => {}
+            }
+            if options.readiness.is_some() {
+                response.verification = Some(BootVerificationResult {
+                    adb_ready: readiness_spec == ReadinessProbeSpec::Adb,
+                    boot_marker_observed: false,
+                    failure_reason: None,
+                    readiness_probe: Some(probe.describe()),
+                    ready_after_ms: Some(ready_after.as_millis() as u64),
+                });
+            }
 
             if options.verify_boot {
-                match self.verify_boot_completed(id, secs_remaining(deadline)) {
-                    Ok(verification) => {
+                match self
+                    .verify_boot_completed(id, secs_remaining(deadline))
+                    .await
+                {
+                    Ok(mut verification) => {
+                        timings.marker = Some(launched.elapsed());
+                        verification.readiness_probe = Some(probe.describe());
+                        verification.ready_after_ms = Some(ready_after.as_millis() as u64);
                         response.verification = Some(verification);
                     }
                     Err(detail) => {
@@ -1019,35 +2271,77 @@ impl InstanceManager {
                             id,
                             detail
                         );
+                        if self.cancel.is_cancelled() {
+                            self.roll_back_start(id).await;
+                        }
                         return Err(detail);
                     }
                 }
             }
 
+            if let Err(detail) = self.apply_port_forwards(id, &options.port_forwards).await {
+                warn!(
+                    target: "cfctl",
+                    "start_instance: port forwarding failed for {}: {:?}",
+                    id,
+                    detail
+                );
+                let _ = self.terminate_guest(id, Duration::from_secs(5)).await;
+                return Err(detail);
+            }
+
             info!(
                 target: "cfctl",
-                "start_instance: instance {} reported adb ready; registering exit watcher",
-                id
+                "start_instance: instance {} reported {} ready; registering exit watcher",
+                id,
+                probe.describe()
             );
+            self.start_logcat_capture(id, backend.as_ref(), &handle);
             self.spawn_exit_watcher(id, handle);
 
             Ok(response)
         }
     }
 
-    fn stop_instance(&mut self, id: InstanceId) -> Result<InstanceActionResponse> {
+    /// Undoes a start that was cancelled after the guest launched: the
+    /// guest is terminated and the instance is left `failed`.
+    async fn roll_back_start(&self, id: InstanceId) {
+        info!(target: "cfctl", "roll_back_start: rolling back cancelled start of {}", id);
+        if let Err(err) = self.terminate_guest(id, Duration::from_secs(5)).await {
+            warn!(
+                target: "cfctl",
+                "roll_back_start: terminating guest {} failed: {:#}",
+                id,
+                err
+            );
+        }
+        if let Err(err) = self
+            .blocking(move |manager| manager.mark_metadata_state(id, InstanceState::Failed))
+            .await
+            .and_then(|result| result)
+        {
+            warn!(
+                target: "cfctl",
+                "roll_back_start: marking {} failed: {:#}",
+                id,
+                err
+            );
+        }
+    }
+
+    async fn stop_instance(&self, id: InstanceId) -> Result<InstanceActionResponse> {
         let mut metadata = self.metadata(id)?;
-        let exit = self.terminate_guest(id, Duration::from_secs(10))?;
+        let exit = self.terminate_guest(id, Duration::from_secs(10)).await?;
         metadata.state = match exit {
             Some(info) if info.success() => InstanceState::Stopped,
             Some(_) => InstanceState::Failed,
             None => InstanceState::Stopped,
         };
         metadata.updated_at = epoch_secs()?;
-        let paths = self.paths(id);
-        self.write_metadata(&paths, &metadata)?;
-        self.metadata_cache.insert(id, metadata.clone());
-        let cleanup = self.cleanup_host_state(id);
+        self.save_metadata(&metadata).await?;
+        let cleanup = self
+            .blocking(move |manager| manager.cleanup_host_state(id))
+            .await?;
         if !cleanup.guest_processes_killed {
             warn!(
                 target: "cfctl",
@@ -1058,46 +2352,131 @@ impl InstanceManager {
             let now = epoch_secs()?;
             metadata.state = InstanceState::Failed;
             metadata.updated_at = now;
-            self.write_metadata(&paths, &metadata)?;
-            self.metadata_cache.insert(id, metadata.clone());
+            self.save_metadata(&metadata).await?;
         }
         let cleanup_summary = cleanup.summary();
         Ok(InstanceActionResponse {
-            summary: metadata.summary(&self.config.adb_host),
-            journal_tail: None,
-            verification: None,
             cleanup: Some(cleanup_summary),
-            run_log_tail: None,
-            console_snapshot_path: None,
+            ..InstanceActionResponse::new(metadata.summary(&self.config.adb_host))
         })
     }
 
-    fn hold_instance(&mut self, id: InstanceId) -> Result<InstanceActionResponse> {
+    /// Stops the guest and starts it again with the options stored by its
+    /// last start.
+    async fn restart_instance(
+        &self,
+        id: InstanceId,
+    ) -> Result<InstanceActionResponse, ErrorDetail> {
+        let metadata = self
+            .metadata(id)
+            .map_err(|err| error_detail("instance_not_found", err.to_string()))?;
+        let options = metadata.start_options.ok_or_else(|| {
+            error_detail(
+                "restart_no_start_options",
+                format!("instance {} has never been started", id),
+            )
+        })?;
+        let stopped = self
+            .stop_instance(id)
+            .await
+            .map_err(|err| error_detail("restart_stop_failed", format!("{err:#}")))?;
+        if let Some(cleanup) = stopped.cleanup.as_ref() {
+            if !cleanup.guest_processes_killed {
+                return Err(error_detail(
+                    "restart_stop_failed",
+                    format!(
+                        "instance {} still has running processes: {:?}",
+                        id, cleanup.remaining_pids
+                    ),
+                ));
+            }
+        }
+        let mut response = self.start_instance(id, options).await?;
+        response.cleanup = stopped.cleanup;
+        Ok(response)
+    }
+
+    fn hold_instance(&self, id: InstanceId) -> Result<InstanceActionResponse> {
         let mut metadata = self.metadata(id)?;
         metadata.held = true;
         metadata.updated_at = epoch_secs()?;
-        let paths = self.paths(id);
-        self.write_metadata(&paths, &metadata)?;
-        self.metadata_cache.insert(id, metadata.clone());
+        self.write_metadata(&metadata)?;
         info!(target: "cfctl", "hold_instance: instance {} marked as held", id);
-        Ok(InstanceActionResponse {
-            summary: metadata.summary(&self.config.adb_host),
-            journal_tail: None,
-            verification: None,
-            cleanup: None,
-            run_log_tail: None,
-            console_snapshot_path: None,
+        Ok(InstanceActionResponse::new(
+            metadata.summary(&self.config.adb_host),
+        ))
+    }
+
+    fn release_instance(&self, id: InstanceId) -> Result<InstanceActionResponse> {
+        let mut metadata = self.metadata(id)?;
+        metadata.held = false;
+        metadata.updated_at = epoch_secs()?;
+        self.write_metadata(&metadata)?;
+        info!(target: "cfctl", "release_instance: instance {} no longer held", id);
+        Ok(InstanceActionResponse::new(
+            metadata.summary(&self.config.adb_host),
+        ))
+    }
+
+    async fn history(&self, query: HistoryQuery) -> Result<HistoryResponse, ErrorDetail> {
+        for prefix in [&query.init_sha256, &query.baseline].into_iter().flatten() {
+            if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(error_detail(
+                    "history_invalid_query",
+                    format!("{:?} is not a sha256 prefix", prefix),
+                ));
+            }
+        }
+        let baseline = query.baseline.clone();
+        let mut response = self
+            .blocking(move |manager| manager.history.query(&query))
+            .await
+            .and_then(|result| result)
+            .map_err(|err| error_detail("history_failed", format!("{err:#}")))?;
+        if let Some(baseline) = baseline {
+            if !history::put_baseline_first(&mut response.variants, &baseline) {
+                return Err(error_detail(
+                    "history_baseline_not_found",
+                    format!("no matching runs of an init image starting {}", baseline),
+                ));
+            }
+        }
+        Ok(response)
+    }
+
+    /// Runs the host checks, leaving devices and files of instances that
+    /// are running or starting alone.
+    async fn doctor(&self, fix: bool) -> Result<Vec<DoctorCheck>> {
+        info!(target: "cfctl", "doctor: checking host (fix={})", fix);
+        self.blocking(move |manager| {
+            let in_use = || manager.instances_in_use();
+            Doctor::new(&manager.config, &in_use, fix).run()
         })
+        .await
+    }
+
+    /// Instances whose host devices and cuttlefish config belong to a live
+    /// launch: running guests and starts still in progress.
+    fn instances_in_use(&self) -> Result<BTreeSet<InstanceId>> {
+        let mut in_use: BTreeSet<InstanceId> = self.guest_registry.ids().into_iter().collect();
+        in_use.extend(
+            self.list_instances()?
+                .into_iter()
+                .filter(|summary| summary.state == InstanceState::Starting)
+                .map(|summary| summary.id),
+        );
+        Ok(in_use)
     }
 
-    fn destroy_instance(
-        &mut self,
+    async fn destroy_instance(
+        &self,
         id: InstanceId,
         options: DestroyOptions,
     ) -> Result<InstanceActionResponse, ErrorDetail> {
         debug!(target: "cfctl", "destroy_instance: entering prepare_destroy for {}", id);
         let summary = self
             .prepare_destroy(id)
+            .await
             .map_err(|err| error_detail("destroy_prepare_failed", err.to_string()))?;
         debug!(
             target: "cfctl",
@@ -1105,15 +2484,9 @@ impl InstanceManager {
             id
         );
 
-        let config = self.config.clone();
-        let registry = self.guest_registry.clone();
-        let (tx, rx) = mpsc::channel();
-        thread::spawn(move || {
-            let mut manager = InstanceManager::new(config.clone(), registry.clone());
-            let result = manager.finish_destroy(id);
-            let _ = tx.send(result);
-        });
-
+        // Host cleanup keeps running on the blocking pool even if the
+        // request gives up waiting for it.
+        let cleanup = self.blocking(move |manager| manager.finish_destroy(id));
         let deadline = deadline_from_timeout(options.timeout_secs);
         info!(
             target: "cfctl",
@@ -1121,38 +2494,28 @@ impl InstanceManager {
             id
         );
 
-        let outcome = match deadline {
-            Some(deadline) => {
-                let now = Instant::now();
-                let remaining = deadline.saturating_duration_since(now);
-                match rx.recv_timeout(remaining) {
-                    Ok(result) => result,
-                    Err(mpsc::RecvTimeoutError::Timeout) => {
-                        return Err(error_detail(
-                            "destroy_timeout",
-                            format!("destroy {} exceeded timeout", id),
-                        ))
-                    }
-                    Err(err) => {
-                        return Err(error_detail(
-                            "destroy_recv_failed",
-                            format!("destroy {} recv failed: {}", id, err),
-                        ))
-                    }
-                }
+        let timeout = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let outcome = tokio::select! {
+            outcome = time::timeout(timeout.unwrap_or(Duration::MAX), cleanup) => {
+                outcome.map_err(|_| {
+                    error_detail(
+                        "destroy_timeout",
+                        format!("destroy {} exceeded timeout", id),
+                    )
+                })?
+            }
+            _ = self.cancel.cancelled() => {
+                return Err(error_detail(
+                    "destroy_cancelled",
+                    format!(
+                        "stopped waiting for destroy of {}; host cleanup continues in the background",
+                        id
+                    ),
+                ));
             }
-            None => match rx.recv() {
-                Ok(result) => result,
-                Err(err) => {
-                    return Err(error_detail(
-                        "destroy_recv_failed",
-                        format!("destroy {} recv failed: {}", id, err),
-                    ))
-                }
-            },
         };
 
-        let outcome = outcome.map_err(|err| {
+        let outcome = outcome.and_then(|result| result).map_err(|err| {
             error_detail(
                 "destroy_cleanup_failed",
                 format!("cleanup for {} failed: {:#}", id, err),
@@ -1179,16 +2542,12 @@ impl InstanceManager {
         );
 
         Ok(InstanceActionResponse {
-            summary,
-            journal_tail: None,
-            verification: None,
             cleanup: Some(cleanup_summary),
-            run_log_tail: None,
-            console_snapshot_path: None,
+            ..InstanceActionResponse::new(summary)
         })
     }
 
-    pub(super) fn prepare_destroy(&mut self, id: InstanceId) -> Result<InstanceSummary> {
+    pub(super) async fn prepare_destroy(&self, id: InstanceId) -> Result<InstanceSummary> {
         debug!(target: "cfctl", "prepare_destroy: begin for {}", id);
         let paths = self.paths(id);
 
@@ -1197,13 +2556,20 @@ impl InstanceManager {
             Err(_) => InstanceMetadata {
                 id,
                 purpose: None,
-                adb_port: self.config.base_adb_port + id as u16 - 1,
+                adb_port: SlotAllocator::new(&self.config)
+                    .adb_port(id)
+                    .unwrap_or_default(),
                 state: InstanceState::Destroyed,
                 boot_image: self.config.default_boot_image.clone(),
                 init_boot_image: self.config.default_init_boot_image.clone(),
                 created_at: 0,
                 updated_at: 0,
                 held: false,
+                pooled: false,
+                host_prepared: false,
+                host_assembled: false,
+                start_options: None,
+                launch: None,
             },
         };
 
@@ -1225,22 +2591,26 @@ impl InstanceManager {
                 id,
                 paths.metadata.display()
             );
-            self.write_metadata(&paths, &metadata)?;
+            self.save_metadata(&metadata).await?;
         }
 
         self.metadata_cache.remove(&id);
+        self.logcat.remove(id);
         debug!(
             target: "cfctl",
             "prepare_destroy: terminating guest process for {}",
             id
         );
-        self.terminate_guest(id, Duration::from_secs(5))?;
+        self.terminate_guest(id, Duration::from_secs(5)).await?;
         debug!(
             target: "cfctl",
             "prepare_destroy: guest termination complete for {}",
             id
         );
-        if !self.kill_guest_processes(id) {
+        if !self
+            .blocking(move |manager| manager.kill_guest_processes(id))
+            .await?
+        {
             warn!(
                 target: "cfctl",
                 "prepare_destroy: force kill pass left processes running for {}",
@@ -1252,10 +2622,15 @@ impl InstanceManager {
             id,
             adb: None,
             state: InstanceState::Destroyed,
+            purpose: None,
+            created_at: None,
+            held: false,
+            resources: None,
+            error: None,
         })
     }
 
-    pub(super) fn finish_destroy(&mut self, id: InstanceId) -> Result<CleanupOutcome> {
+    pub(super) fn finish_destroy(&self, id: InstanceId) -> Result<CleanupOutcome> {
         let outcome = self.cleanup_host_state(id);
         if outcome.guest_processes_killed {
             if let Err(err) = self.remove_instance_artifacts(id) {
@@ -1274,7 +2649,7 @@ impl InstanceManager {
         Ok(outcome)
     }
 
-    fn deploy(&mut self, req: crate::protocol::DeployRequest) -> Result<()> {
+    fn deploy(&self, req: crate::protocol::DeployRequest) -> Result<()> {
         let mut metadata = self.metadata(req.id)?;
         let paths = self.paths(req.id);
         if let Some(boot) = req.boot_image {
@@ -1290,10 +2665,11 @@ impl InstanceManager {
             })?;
             metadata.init_boot_image = dest;
         }
+        // Disks assembled by the pool used the images just replaced.
+        metadata.host_assembled = false;
         metadata.updated_at = epoch_secs()?;
-        self.write_metadata(&paths, &metadata)?;
+        self.write_metadata(&metadata)?;
         self.write_env_file(&paths, &metadata)?;
-        self.metadata_cache.insert(req.id, metadata);
         Ok(())
     }
 
@@ -1311,160 +2687,549 @@ impl InstanceManager {
                 .with_context(|| format!("removing instance dir {}", paths.root.display()))?;
         }
 
-        Ok(())
+        Ok(())
+    }
+
+    fn snapshots_path(&self, id: InstanceId) -> PathBuf {
+        self.paths(id).root.join(SNAPSHOTS_FILE)
+    }
+
+    fn read_snapshots(&self, id: InstanceId) -> Result<Vec<SnapshotInfo>> {
+        let path = self.snapshots_path(id);
+        match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("parsing snapshots {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err).with_context(|| format!("reading snapshots {}", path.display())),
+        }
+    }
+
+    fn write_snapshots(&self, id: InstanceId, snapshots: &[SnapshotInfo]) -> Result<()> {
+        let path = self.snapshots_path(id);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(snapshots)?)
+            .with_context(|| format!("writing snapshots tmp {}", tmp.display()))?;
+        fs::rename(&tmp, &path)
+            .with_context(|| format!("renaming snapshots {}", path.display()))?;
+        Ok(())
+    }
+
+    fn clear_snapshots(&self, id: InstanceId) {
+        let path = self.snapshots_path(id);
+        if path.exists() {
+            info!(
+                target: "cfctl",
+                "clear_snapshots: cold start of instance {} discards its snapshots",
+                id
+            );
+            if let Err(err) = fs::remove_file(&path) {
+                warn!(
+                    target: "cfctl",
+                    "clear_snapshots: failed removing {}: {}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+    }
+
+    /// Connects to the QMP socket of the instance's QEMU, which the backend
+    /// places under the instance's runtime dir.
+    fn qemu_monitor(&self, id: InstanceId, code_prefix: &str) -> Result<QmpClient, ErrorDetail> {
+        let unavailable =
+            |message: String| error_detail(&format!("{code_prefix}_monitor_unavailable"), message);
+        let backend = self.guest_backend(id);
+        let path = backend
+            .monitor_socket_path(&self.host_instance_dir(id), id)
+            .ok_or_else(|| {
+                unavailable(format!(
+                    "instance {} runs the {} backend, which has no QEMU monitor",
+                    id,
+                    backend.name()
+                ))
+            })?;
+        if !path.exists() {
+            return Err(unavailable(format!(
+                "no QEMU monitor socket at {} for instance {}",
+                path.display(),
+                id
+            )));
+        }
+        QmpClient::connect(&path, self.config.snapshot_timeout)
+            .map_err(|err| unavailable(format!("{err:#}")))
+    }
+
+    /// Connects to the instance's QEMU monitor and runs a human monitor
+    /// command on the blocking pool, since `savevm`/`loadvm` of a large guest
+    /// take a while. The outer error means the monitor was unreachable.
+    async fn monitor_command(
+        &self,
+        id: InstanceId,
+        code_prefix: &str,
+        command: String,
+    ) -> Result<Result<String>, ErrorDetail> {
+        let prefix = code_prefix.to_string();
+        self.blocking(move |manager| {
+            let mut monitor = manager.qemu_monitor(id, &prefix)?;
+            Ok(monitor.human_command(&command))
+        })
+        .await
+        .unwrap_or_else(|err| {
+            Err(error_detail(
+                &format!("{code_prefix}_monitor_unavailable"),
+                format!("{err:#}"),
+            ))
+        })
+    }
+
+    /// `{code_prefix}_failed` for a monitor command QEMU rejected. One that
+    /// got no answer within `snapshot_timeout` may still complete inside
+    /// QEMU, so it is reported as `{code_prefix}_state_unknown` instead.
+    fn monitor_failure(
+        &self,
+        id: InstanceId,
+        code_prefix: &str,
+        err: anyhow::Error,
+    ) -> ErrorDetail {
+        if qmp::is_timeout(&err) {
+            return error_detail(
+                &format!("{code_prefix}_state_unknown"),
+                format!(
+                    "QEMU monitor of instance {} did not answer within {}s; the command may \
+                     still complete, so the snapshot state is unknown: {:#}",
+                    id,
+                    self.config.snapshot_timeout.as_secs(),
+                    err
+                ),
+            );
+        }
+        error_detail(&format!("{code_prefix}_failed"), format!("{err:#}"))
+    }
+
+    /// Saves the running guest's VM state under `name` (QEMU `savevm`),
+    /// replacing any earlier snapshot with the same name.
+    async fn snapshot_instance(
+        &self,
+        id: InstanceId,
+        name: Option<String>,
+    ) -> Result<SnapshotInfo, ErrorDetail> {
+        let metadata = self
+            .metadata(id)
+            .map_err(|err| error_detail("instance_not_found", err.to_string()))?;
+        if !self.guest_registry.contains(id) {
+            return Err(error_detail(
+                "snapshot_instance_not_running",
+                format!("instance {} has no running guest to snapshot", id),
+            ));
+        }
+        let now =
+            epoch_secs().map_err(|err| error_detail("snapshot_timestamp", err.to_string()))?;
+        let name = name.unwrap_or_else(|| format!("snap-{}", now));
+        validate_snapshot_name(&name)?;
+
+        let started = Instant::now();
+        self.monitor_command(id, "snapshot", format!("savevm {}", name))
+            .await?
+            .map_err(|err| self.monitor_failure(id, "snapshot", err))?;
+        info!(
+            target: "cfctl",
+            "snapshot_instance: saved snapshot {} of instance {} in {:?}",
+            name,
+            id,
+            started.elapsed()
+        );
+
+        let snapshot = SnapshotInfo {
+            name: name.clone(),
+            created_at: now,
+            boot_image: metadata.boot_image.display().to_string(),
+            init_boot_image: metadata.init_boot_image.display().to_string(),
+        };
+        let mut snapshots = self
+            .read_snapshots(id)
+            .map_err(|err| error_detail("snapshot_records", format!("{err:#}")))?;
+        snapshots.retain(|existing| existing.name != name);
+        snapshots.push(snapshot.clone());
+        self.write_snapshots(id, &snapshots)
+            .map_err(|err| error_detail("snapshot_records", format!("{err:#}")))?;
+        Ok(snapshot)
+    }
+
+    /// Restores snapshot `name` (QEMU `loadvm`). A stopped instance is first
+    /// started with `resume` so its overlay, which holds the snapshots, is kept.
+    /// Readiness is then awaited per `options` as for a regular start.
+    async fn restore_instance(
+        &self,
+        id: InstanceId,
+        name: &str,
+        options: StartOptions,
+    ) -> Result<InstanceActionResponse, ErrorDetail> {
+        let snapshots = self
+            .read_snapshots(id)
+            .map_err(|err| error_detail("restore_records", format!("{err:#}")))?;
+        if !snapshots.iter().any(|snapshot| snapshot.name == name) {
+            return Err(error_detail(
+                "snapshot_not_found",
+                format!("instance {} has no snapshot named {}", id, name),
+            ));
+        }
+        let timeout = options
+            .timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(self.config.start_timeout);
+        let deadline = Instant::now() + timeout;
+
+        if !self.guest_registry.contains(id) {
+            info!(
+                target: "cfctl",
+                "restore_instance: starting instance {} with resume before loading {}",
+                id,
+                name
+            );
+            let previous = self
+                .metadata(id)
+                .ok()
+                .and_then(|metadata| metadata.start_options);
+            let start = StartOptions {
+                resume: true,
+                skip_adb_wait: true,
+                verify_boot: false,
+                readiness: None,
+                ..options.clone()
+            };
+            self.start_instance(id, start).await?;
+            // The resume/skip-adb overrides only apply to this boot: a later
+            // restart or clone replays the instance's own start options.
+            let mut metadata = self
+                .metadata(id)
+                .map_err(|err| error_detail("restore_metadata", err.to_string()))?;
+            metadata.start_options = previous.or_else(|| Some(options.clone()));
+            self.save_metadata(&metadata)
+                .await
+                .map_err(|err| error_detail("restore_write_metadata", err.to_string()))?;
+        }
+
+        let loaded = loop {
+            match self
+                .monitor_command(id, "restore", format!("loadvm {}", name))
+                .await
+            {
+                Ok(loaded) => break loaded,
+                Err(detail) if Instant::now() >= deadline => return Err(detail),
+                Err(_) if !self.guest_registry.contains(id) => {
+                    return Err(error_detail(
+                        "restore_guest_exit",
+                        format!("instance {} exited before its monitor came up", id),
+                    ));
+                }
+                Err(_) => self.pause(Duration::from_millis(500), "restore").await?,
+            }
+        };
+        loaded.map_err(|err| self.monitor_failure(id, "restore", err))?;
+        info!(
+            target: "cfctl",
+            "restore_instance: loaded snapshot {} into instance {}",
+            name,
+            id
+        );
+
+        let metadata = self
+            .metadata(id)
+            .map_err(|err| error_detail("restore_metadata", err.to_string()))?;
+        if options.skip_adb_wait {
+            return Ok(InstanceActionResponse::new(
+                metadata.summary(&self.config.adb_host),
+            ));
+        }
+        let spec = options.readiness.unwrap_or(ReadinessProbeSpec::Adb);
+        let mut probe = self
+            .readiness_probe(id, &metadata, &spec)
+            .map_err(|err| error_detail("restore_invalid_options", format!("{err:#}")))?;
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        self.wait_for_readiness(id, probe.as_mut(), remaining, "restore")
+            .await
+            .map(|(response, _)| response)
+    }
+
+    /// Drops snapshot `name`, deleting it from the overlay too when the guest
+    /// is running (a stopped guest's overlay is discarded on the next cold start).
+    async fn delete_snapshot(&self, id: InstanceId, name: &str) -> Result<(), ErrorDetail> {
+        let mut snapshots = self
+            .read_snapshots(id)
+            .map_err(|err| error_detail("snapshot_records", format!("{err:#}")))?;
+        let before = snapshots.len();
+        snapshots.retain(|snapshot| snapshot.name != name);
+        if snapshots.len() == before {
+            return Err(error_detail(
+                "snapshot_not_found",
+                format!("instance {} has no snapshot named {}", id, name),
+            ));
+        }
+        if self.guest_registry.contains(id) {
+            self.monitor_command(id, "snapshot_delete", format!("delvm {}", name))
+                .await?
+                .map_err(|err| self.monitor_failure(id, "snapshot_delete", err))?;
+        }
+        self.write_snapshots(id, &snapshots)
+            .map_err(|err| error_detail("snapshot_records", format!("{err:#}")))
     }
 
-    fn wait_for_adb(
-        &mut self,
+    async fn wait_for_adb(
+        &self,
         id: InstanceId,
         timeout_secs: Option<u64>,
     ) -> Result<InstanceActionResponse, ErrorDetail> {
-        let mut metadata = self
+        let metadata = self
             .metadata(id)
             .map_err(|err| error_detail("wait_for_adb_metadata", err.to_string()))?;
         let timeout = timeout_secs
             .map(Duration::from_secs)
             .unwrap_or(self.config.adb_wait_timeout);
-        let deadline = Instant::now() + timeout;
-        let serial = format!("{}:{}", self.config.adb_host, metadata.adb_port);
-        let connect_serial = format!("0.0.0.0:{}", metadata.adb_port);
-        let addr = format!("{}:{}", self.config.adb_host, metadata.adb_port);
+        let mut probe = self
+            .readiness_probe(id, &metadata, &ReadinessProbeSpec::Adb)
+            .map_err(|err| error_detail("wait_for_adb_probe", format!("{err:#}")))?;
+        self.wait_for_readiness(id, probe.as_mut(), timeout, "wait_for_adb")
+            .await
+            .map(|(response, _)| response)
+    }
+
+    fn readiness_probe(
+        &self,
+        id: InstanceId,
+        metadata: &InstanceMetadata,
+        spec: &ReadinessProbeSpec,
+    ) -> Result<Box<dyn ReadinessProbe>> {
+        let probe: Box<dyn ReadinessProbe> = match spec {
+            ReadinessProbeSpec::Adb => {
+                let (serial, connect_serial) = self.adb_serials(metadata);
+                Box::new(AdbProbe {
+                    serial,
+                    connect_serial,
+                })
+            }
+            ReadinessProbeSpec::ConsoleRegex { pattern } => Box::new(LogRegexProbe::new(
+                "console log",
+                self.console_log_path(id),
+                Regex::new(pattern)
+                    .with_context(|| format!("invalid console readiness regex {:?}", pattern))?,
+            )),
+            ReadinessProbeSpec::TcpPort { port, host } => {
+                let host = host.as_deref().unwrap_or(&self.config.adb_host);
+                let addr = (host, *port)
+                    .to_socket_addrs()
+                    .with_context(|| format!("resolving {}:{}", host, port))?
+                    .next()
+                    .ok_or_else(|| anyhow!("{}:{} resolved to no addresses", host, port))?;
+                Box::new(TcpPortProbe { addr })
+            }
+            ReadinessProbeSpec::ProcessAlive { secs } => Box::new(ProcessAliveProbe {
+                started: Instant::now(),
+                duration: Duration::from_secs(*secs),
+            }),
+        };
+        Ok(probe)
+    }
+
+    /// Polls `probe` until it reports ready, the guest exits, or `timeout`
+    /// elapses. Failures are recorded on the instance and reported with
+    /// `<code_prefix>_guest_exit` / `<code_prefix>_timeout` codes.
+    async fn wait_for_readiness(
+        &self,
+        id: InstanceId,
+        probe: &mut dyn ReadinessProbe,
+        timeout: Duration,
+        code_prefix: &str,
+    ) -> Result<(InstanceActionResponse, Duration), ErrorDetail> {
+        let mut metadata = self
+            .metadata(id)
+            .map_err(|err| error_detail(&format!("{code_prefix}_metadata"), err.to_string()))?;
+        let started = Instant::now();
+        let deadline = started + timeout;
+        let description = probe.describe();
+        let mut last_error: Option<String> = None;
 
         loop {
             let handle = match self.guest_registry.get(id) {
                 Some(handle) => handle,
                 None => {
-                    let message = match self.record_launch_failure(
-                        id,
-                        &mut metadata,
-                        anyhow!("instance {} lost guest handle before adb became ready", id),
-                    ) {
+                    let message = match self
+                        .record_launch_failure(
+                            id,
+                            metadata,
+                            anyhow!(
+                                "instance {} lost guest handle before {} became ready",
+                                id,
+                                description
+                            ),
+                        )
+                        .await
+                    {
                         Ok(err) => format!("{err:#}"),
                         Err(err) => format!(
                             "instance {} lost guest handle and record failed: {:#}",
                             id, err
                         ),
                     };
-                    return Err(error_detail("wait_for_adb_handle_lost", message));
+                    return Err(error_detail(&format!("{code_prefix}_handle_lost"), message));
                 }
             };
 
-            if let Some(exit) = handle
-                .try_wait()
-                .map_err(|err| error_detail("wait_for_adb_wait", err.to_string()))?
-            {
+            if let Some(exit) = handle.try_wait() {
                 self.guest_registry.remove_if_handle(id, &handle);
-                let message = match self.record_launch_failure(
-                    id,
-                    &mut metadata,
-                    anyhow!(
-                        "instance {} exited before adb became ready ({})",
+                let message = match self
+                    .record_launch_failure(
                         id,
-                        exit.describe()
-                    ),
-                ) {
+                        metadata,
+                        anyhow!(
+                            "instance {} exited before {} became ready ({})",
+                            id,
+                            description,
+                            exit.describe()
+                        ),
+                    )
+                    .await
+                {
                     Ok(err) => format!("{err:#}"),
                     Err(err) => format!(
-                        "instance {} exited before adb and record failed: {:#}",
-                        id, err
+                        "instance {} exited before {} and record failed: {:#}",
+                        id, description, err
                     ),
                 };
-                return Err(error_detail("wait_for_adb_guest_exit", message));
+                return Err(error_detail(&format!("{code_prefix}_guest_exit"), message));
             }
 
-            if let Err(err) = self.adb_connect(&connect_serial) {
-                if Instant::now() >= deadline {
-                    let _ = self.terminate_guest(id, Duration::from_secs(2));
-                    let message = match self.record_launch_failure(
+            match probe.poll(self).await {
+                Ok(true) => {
+                    let elapsed = started.elapsed();
+                    info!(
+                        target: "cfctl",
+                        "wait_for_readiness: instance {} ready via {} after {:?}",
                         id,
-                        &mut metadata,
-                        anyhow!("timeout waiting for adb on {}: {}", addr, err),
-                    ) {
-                        Ok(err) => format!("{err:#}"),
-                        Err(record_err) => format!(
-                            "timeout waiting for adb on {} and record failed: {:#}",
-                            addr, record_err
-                        ),
-                    };
-                    return Err(error_detail("wait_for_adb_timeout", message));
-                }
-                drop(handle);
-                thread::sleep(Duration::from_secs(1));
-                continue;
-            }
-
-            match self.resolve_active_adb_serial(&serial, &connect_serial) {
-                Ok(Some(_active_serial)) => {
+                        description,
+                        elapsed
+                    );
                     metadata.state = InstanceState::Running;
-                    metadata.updated_at = epoch_secs()
-                        .map_err(|err| error_detail("wait_for_adb_time", err.to_string()))?;
-                    let paths = self.paths(id);
-                    self.write_metadata(&paths, &metadata).map_err(|err| {
-                        error_detail("wait_for_adb_write_metadata", err.to_string())
+                    metadata.updated_at = epoch_secs().map_err(|err| {
+                        error_detail(&format!("{code_prefix}_time"), err.to_string())
+                    })?;
+                    self.save_metadata(&metadata).await.map_err(|err| {
+                        error_detail(&format!("{code_prefix}_write_metadata"), err.to_string())
                     })?;
-                    self.metadata_cache.insert(id, metadata.clone());
                     let summary = metadata.summary(&self.config.adb_host);
-                    return Ok(InstanceActionResponse {
-                        summary,
-                        journal_tail: None,
-                        verification: None,
-                        cleanup: None,
-                        run_log_tail: None,
-                        console_snapshot_path: None,
-                    });
-                }
-                Ok(None) => {
-                    if Instant::now() >= deadline {
-                        let _ = self.terminate_guest(id, Duration::from_secs(2));
-                        let message = match self.record_launch_failure(
-                            id,
-                            &mut metadata,
-                            anyhow!("timeout waiting for adb device {}", serial),
-                        ) {
-                            Ok(err) => format!("{err:#}"),
-                            Err(record_err) => format!(
-                                "timeout waiting for adb device {} and record failed: {:#}",
-                                serial, record_err
-                            ),
-                        };
-                        return Err(error_detail("wait_for_adb_timeout", message));
-                    }
-                    drop(handle);
-                    thread::sleep(Duration::from_secs(1));
+                    let response = InstanceActionResponse::new(summary);
+                    return Ok((response, elapsed));
                 }
+                Ok(false) => {}
                 Err(err) => {
-                    if Instant::now() >= deadline {
-                        let _ = self.terminate_guest(id, Duration::from_secs(2));
-                        let message = match self.record_launch_failure(
-                            id,
-                            &mut metadata,
-                            anyhow!("adb devices never succeeded for {}: {:#}", serial, err),
-                        ) {
-                            Ok(err) => format!("{err:#}"),
-                            Err(record_err) => format!(
-                                "adb devices failed for {} and record failed: {:#}",
-                                serial, record_err
-                            ),
-                        };
-                        return Err(error_detail("wait_for_adb_timeout", message));
-                    }
-                    drop(handle);
-                    thread::sleep(Duration::from_secs(1));
+                    debug!(
+                        target: "cfctl",
+                        "wait_for_readiness: {} probe transient failure for {}: {:#}",
+                        description,
+                        id,
+                        err
+                    );
+                    last_error = Some(format!("{err:#}"));
+                }
+            }
+
+            drop(handle);
+            let failure = if Instant::now() >= deadline {
+                Some(("timeout", "timeout"))
+            } else if self.pause(probe.interval(), code_prefix).await.is_err() {
+                Some(("cancelled", "cancelled"))
+            } else {
+                None
+            };
+            if let Some((code, what)) = failure {
+                let _ = self.terminate_guest(id, Duration::from_secs(2)).await;
+                let reason = match &last_error {
+                    Some(err) => anyhow!("{} waiting for {}: {}", what, description, err),
+                    None => anyhow!("{} waiting for {}", what, description),
+                };
+                let message = match self.record_launch_failure(id, metadata, reason).await {
+                    Ok(err) => format!("{err:#}"),
+                    Err(record_err) => format!(
+                        "{} waiting for {} and record failed: {:#}",
+                        what, description, record_err
+                    ),
+                };
+                return Err(error_detail(&format!("{code_prefix}_{code}"), message));
+            }
+        }
+    }
+
+    /// Waits until a run log or console log line matches `pattern`, failing
+    /// early if the guest exits. Unlike readiness probes this leaves the
+    /// instance state untouched.
+    pub(super) async fn wait_for_log_marker(
+        &self,
+        id: InstanceId,
+        pattern: &str,
+        timeout: Duration,
+    ) -> Result<String, ErrorDetail> {
+        let regex = Regex::new(pattern).map_err(|err| {
+            error_detail(
+                "marker_invalid",
+                format!("invalid marker regex {:?}: {}", pattern, err),
+            )
+        })?;
+        let paths = self.paths(id);
+        let mut probes = [
+            LogRegexProbe::new("run log", paths.run_log_path().clone(), regex.clone()),
+            LogRegexProbe::new("console log", self.console_log_path(id), regex),
+        ];
+        let deadline = Instant::now() + timeout;
+        loop {
+            for probe in probes.iter_mut() {
+                match probe.scan() {
+                    Ok(true) => return Ok(probe.describe()),
+                    Ok(false) => {}
+                    Err(err) => debug!(
+                        target: "cfctl",
+                        "wait_for_log_marker: {} read failed for {}: {:#}",
+                        probe.label,
+                        id,
+                        err
+                    ),
                 }
             }
+            let exited = match self.guest_registry.get(id) {
+                Some(handle) => handle.try_wait().is_some(),
+                None => true,
+            };
+            if exited {
+                return Err(error_detail(
+                    "marker_guest_exit",
+                    format!(
+                        "instance {} is not running; marker /{}/ never observed",
+                        id, pattern
+                    ),
+                ));
+            }
+            if Instant::now() >= deadline {
+                return Err(error_detail(
+                    "marker_timeout",
+                    format!(
+                        "marker /{}/ not observed for instance {} within {}s",
+                        pattern,
+                        id,
+                        timeout.as_secs()
+                    ),
+                ));
+            }
+            self.pause(Duration::from_millis(500), "marker").await?;
         }
     }
 
-    fn verify_boot_completed(
-        &mut self,
+    async fn verify_boot_completed(
+        &self,
         id: InstanceId,
         timeout_secs: Option<u64>,
     ) -> Result<BootVerificationResult, ErrorDetail> {
         let metadata = self
             .metadata(id)
             .map_err(|err| error_detail("verify_boot_metadata", err.to_string()))?;
-        let serial = format!("{}:{}", self.config.adb_host, metadata.adb_port);
-        let connect_serial = format!("0.0.0.0:{}", metadata.adb_port);
+        let (serial, connect_serial) = self.adb_serials(&metadata);
         let timeout = timeout_secs
             .filter(|secs| *secs > 0)
             .map(Duration::from_secs)
@@ -1472,7 +3237,7 @@ impl InstanceManager {
         let deadline = Instant::now() + timeout;
 
         loop {
-            if let Err(err) = self.adb_connect(&connect_serial) {
+            if let Err(err) = self.adb_connect(&connect_serial).await {
                 let msg = format!("{:#}", err);
                 debug!(
                     target: "cfctl",
@@ -1487,11 +3252,15 @@ impl InstanceManager {
                         format!("adb connect never succeeded for instance {}: {}", id, msg),
                     ));
                 }
-                thread::sleep(Duration::from_millis(500));
+                self.pause(Duration::from_millis(500), "verify_boot")
+                    .await?;
                 continue;
             }
 
-            let active_serial = match self.resolve_active_adb_serial(&serial, &connect_serial) {
+            let active_serial = match self
+                .resolve_active_adb_serial(&serial, &connect_serial)
+                .await
+            {
                 Ok(Some(serial)) => serial,
                 Ok(None) => {
                     if Instant::now() >= deadline {
@@ -1503,7 +3272,8 @@ impl InstanceManager {
                             ),
                         ));
                     }
-                    thread::sleep(Duration::from_millis(500));
+                    self.pause(Duration::from_millis(500), "verify_boot")
+                        .await?;
                     continue;
                 }
                 Err(err) => {
@@ -1520,63 +3290,55 @@ impl InstanceManager {
                             format!("adb devices never succeeded for instance {}: {}", id, msg),
                         ));
                     }
-                    thread::sleep(Duration::from_millis(500));
+                    self.pause(Duration::from_millis(500), "verify_boot")
+                        .await?;
                     continue;
                 }
             };
 
-            let value =
-                match self.adb_shell_getprop(&active_serial, "VIRTUAL_DEVICE_BOOT_COMPLETED") {
-                    Ok(value) => value.trim().to_string(),
-                    Err(err) => {
-                        let msg = format!("{:#}", err);
-                        debug!(
+            let value = match self
+                .adb_shell_getprop(&active_serial, "VIRTUAL_DEVICE_BOOT_COMPLETED")
+                .await
+            {
+                Ok(value) => value.trim().to_string(),
+                Err(err) => {
+                    let msg = format!("{:#}", err);
+                    debug!(
                         target: "cfctl",
-                            "verify_boot: adb getprop transient failure for {}: {}",
-                            id,
-                            msg
+                        "verify_boot: adb getprop transient failure for {}: {}",
+                        id,
+                        msg
+                    );
+                    if self.console_log_has_boot_marker(id) {
+                        info!(
+                            target: "cfctl",
+                            "verify_boot: console log contained boot marker for {}",
+                            id
                         );
-                        if self.console_log_has_boot_marker(id) {
-                            info!(
-                                target: "cfctl",
-                                "verify_boot: console log contained boot marker for {}",
-                                id
-                            );
-                            return Ok(BootVerificationResult {
-                                adb_ready: true,
-                                boot_marker_observed: true,
-                                failure_reason: None,
-                            });
-                        }
-                        if self.run_log_has_boot_marker(id) {
-                            info!(
-                                target: "cfctl",
-                                "verify_boot: run log contained boot marker for {}",
-                                id
-                            );
-                            return Ok(BootVerificationResult {
-                                adb_ready: true,
-                                boot_marker_observed: true,
-                                failure_reason: None,
-                            });
-                        }
-                        if Instant::now() >= deadline {
-                            return Err(error_detail(
-                                "verify_boot_adb_failed",
-                                format!("adb getprop never succeeded for instance {}: {}", id, msg),
-                            ));
-                        }
-                        thread::sleep(Duration::from_millis(500));
-                        continue;
+                        return Ok(boot_marker_verified());
                     }
-                };
+                    if self.run_log_has_boot_marker(id) {
+                        info!(
+                            target: "cfctl",
+                            "verify_boot: run log contained boot marker for {}",
+                            id
+                        );
+                        return Ok(boot_marker_verified());
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(error_detail(
+                            "verify_boot_adb_failed",
+                            format!("adb getprop never succeeded for instance {}: {}", id, msg),
+                        ));
+                    }
+                    self.pause(Duration::from_millis(500), "verify_boot")
+                        .await?;
+                    continue;
+                }
+            };
 
             if matches!(value.as_str(), "1" | "true" | "TRUE") {
-                return Ok(BootVerificationResult {
-                    adb_ready: true,
-                    boot_marker_observed: true,
-                    failure_reason: None,
-                });
+                return Ok(boot_marker_verified());
             }
 
             if self.console_log_has_boot_marker(id) {
@@ -1585,11 +3347,7 @@ impl InstanceManager {
                     "verify_boot: console log contained boot marker for {}",
                     id
                 );
-                return Ok(BootVerificationResult {
-                    adb_ready: true,
-                    boot_marker_observed: true,
-                    failure_reason: None,
-                });
+                return Ok(boot_marker_verified());
             }
 
             if self.run_log_has_boot_marker(id) {
@@ -1598,11 +3356,7 @@ impl InstanceManager {
                     "verify_boot: run log contained boot marker for {}",
                     id
                 );
-                return Ok(BootVerificationResult {
-                    adb_ready: true,
-                    boot_marker_observed: true,
-                    failure_reason: None,
-                });
+                return Ok(boot_marker_verified());
             }
 
             if Instant::now() >= deadline {
@@ -1615,7 +3369,7 @@ impl InstanceManager {
                 ));
             }
 
-            thread::sleep(Duration::from_secs(1));
+            self.pause(Duration::from_secs(1), "verify_boot").await?;
         }
     }
 
@@ -1658,17 +3412,16 @@ impl InstanceManager {
         }
     }
 
-    fn adb_shell_getprop(&self, serial: &str, property: &str) -> Result<String> {
-        let mut cmd = Command::new(&self.config.cuttlefish_fhs);
-        cmd.arg("--")
-            .arg("adb")
-            .arg("-s")
+    async fn adb_shell_getprop(&self, serial: &str, property: &str) -> Result<String> {
+        let mut cmd = self.adb_command();
+        cmd.arg("-s")
             .arg(serial)
             .arg("shell")
             .arg("getprop")
             .arg(property);
         let output = cmd
             .output()
+            .await
             .with_context(|| format!("invoking adb getprop {} for {}", property, serial))?;
         if !output.status.success() {
             return Err(anyhow!(
@@ -1680,11 +3433,12 @@ impl InstanceManager {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
 
-    fn adb_connect(&self, serial: &str) -> Result<()> {
-        let mut cmd = Command::new(&self.config.cuttlefish_fhs);
-        cmd.arg("--").arg("adb").arg("connect").arg(serial);
+    async fn adb_connect(&self, serial: &str) -> Result<()> {
+        let mut cmd = self.adb_command();
+        cmd.arg("connect").arg(serial);
         let output = cmd
             .output()
+            .await
             .with_context(|| format!("invoking adb connect {}", serial))?;
         if !output.status.success() {
             return Err(anyhow!(
@@ -1701,11 +3455,16 @@ impl InstanceManager {
         Ok(())
     }
 
-    fn resolve_active_adb_serial(&self, serial_a: &str, serial_b: &str) -> Result<Option<String>> {
-        let mut cmd = Command::new(&self.config.cuttlefish_fhs);
-        cmd.arg("--").arg("adb").arg("devices");
+    async fn resolve_active_adb_serial(
+        &self,
+        serial_a: &str,
+        serial_b: &str,
+    ) -> Result<Option<String>> {
+        let mut cmd = self.adb_command();
+        cmd.arg("devices");
         let output = cmd
             .output()
+            .await
             .with_context(|| "invoking adb devices".to_string())?;
         if !output.status.success() {
             return Err(anyhow!(
@@ -1732,15 +3491,250 @@ impl InstanceManager {
         Ok(None)
     }
 
+    /// `adb` inside the cuttlefish FHS wrapper, killed if the waiting
+    /// request goes away.
+    fn adb_command(&self) -> TokioCommand {
+        let mut cmd = TokioCommand::new(&self.config.cuttlefish_fhs);
+        cmd.arg("--").arg("adb").kill_on_drop(true);
+        cmd
+    }
+
+    /// Returns the `host:port` serial reported in summaries and the
+    /// `0.0.0.0:port` serial launch_cvd registers with the adb server.
+    fn adb_serials(&self, metadata: &InstanceMetadata) -> (String, String) {
+        (
+            format!("{}:{}", self.config.adb_host, metadata.adb_port),
+            format!("0.0.0.0:{}", metadata.adb_port),
+        )
+    }
+
+    async fn connected_adb_serial(&self, id: InstanceId) -> Result<String, ErrorDetail> {
+        let metadata = self.metadata(id).map_err(|err| {
+            error_detail(
+                "instance_not_found",
+                format!(
+                    "Instance {} does not exist or metadata cannot be read: {}",
+                    id, err
+                ),
+            )
+        })?;
+        let (serial, connect_serial) = self.adb_serials(&metadata);
+        self.adb_connect(&connect_serial).await.map_err(|err| {
+            error_detail(
+                "adb_connect_failed",
+                format!(
+                    "adb connect {} failed for instance {}: {:#}",
+                    connect_serial, id, err
+                ),
+            )
+        })?;
+        match self
+            .resolve_active_adb_serial(&serial, &connect_serial)
+            .await
+        {
+            Ok(Some(active_serial)) => Ok(active_serial),
+            Ok(None) => Err(error_detail(
+                "adb_device_unavailable",
+                format!(
+                    "adb device for instance {} not listed as ready ({} / {})",
+                    id, serial, connect_serial
+                ),
+            )),
+            Err(err) => Err(error_detail(
+                "adb_devices_failed",
+                format!("adb devices failed for instance {}: {:#}", id, err),
+            )),
+        }
+    }
+
+    async fn run_adb_command(
+        &self,
+        id: InstanceId,
+        serial: String,
+        args: &[&str],
+        timeout_secs: Option<u64>,
+    ) -> Result<AdbCommandResponse, ErrorDetail> {
+        let timeout = timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(ADB_COMMAND_TIMEOUT);
+        let mut cmd = self.adb_command();
+        cmd.arg("-s").arg(&serial).args(args);
+        debug!(
+            target: "cfctl",
+            "run_adb_command: instance {} running adb -s {} {:?}",
+            id,
+            serial,
+            args
+        );
+        let output = tokio::select! {
+            output = run_command_timeout(&mut cmd, timeout) => output,
+            // Dropping the command future kills adb.
+            _ = self.cancel.cancelled() => {
+                return Err(error_detail(
+                    "adb_command_cancelled",
+                    format!("adb -s {} {:?} cancelled", serial, args),
+                ));
+            }
+        };
+        let output = output
+            .map_err(|err| {
+                error_detail(
+                    "adb_command_failed",
+                    format!("invoking adb for instance {}: {:#}", id, err),
+                )
+            })?
+            .ok_or_else(|| {
+                error_detail(
+                    "adb_command_timeout",
+                    format!(
+                        "adb {:?} for instance {} exceeded {}s",
+                        args,
+                        id,
+                        timeout.as_secs()
+                    ),
+                )
+            })?;
+        Ok(AdbCommandResponse {
+            serial,
+            exit_code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+
+    async fn adb_shell(
+        &self,
+        id: InstanceId,
+        command: Vec<String>,
+        timeout_secs: Option<u64>,
+    ) -> Result<AdbCommandResponse, ErrorDetail> {
+        if command.is_empty() {
+            return Err(error_detail(
+                "adb_command_invalid",
+                "shell requires a command to run",
+            ));
+        }
+        let serial = self.connected_adb_serial(id).await?;
+        // adb joins its arguments into one line for the guest shell, so
+        // quote each one to keep `command` an argv.
+        let command: Vec<String> = command.iter().map(|arg| shell_quote(arg)).collect();
+        let mut args = vec!["shell"];
+        args.extend(command.iter().map(String::as_str));
+        self.run_adb_command(id, serial, &args, timeout_secs).await
+    }
+
+    async fn adb_push(
+        &self,
+        id: InstanceId,
+        local_path: &str,
+        remote_path: &str,
+        timeout_secs: Option<u64>,
+    ) -> Result<AdbCommandResponse, ErrorDetail> {
+        let local_path = self.transfer_path(local_path, true)?;
+        let local_path = local_path.to_string_lossy();
+        let serial = self.connected_adb_serial(id).await?;
+        self.run_adb_command(
+            id,
+            serial,
+            &["push", &local_path, remote_path],
+            timeout_secs,
+        )
+        .await
+    }
+
+    async fn adb_pull(
+        &self,
+        id: InstanceId,
+        remote_path: &str,
+        local_path: &str,
+        timeout_secs: Option<u64>,
+    ) -> Result<AdbCommandResponse, ErrorDetail> {
+        let local_path = self.transfer_path(local_path, false)?;
+        let local_path = local_path.to_string_lossy();
+        let serial = self.connected_adb_serial(id).await?;
+        self.run_adb_command(
+            id,
+            serial,
+            &["pull", remote_path, &local_path],
+            timeout_secs,
+        )
+        .await
+    }
+
+    /// Resolves a push/pull host path. adb runs with the daemon's
+    /// privileges, so the path has to stay inside the transfer dir once
+    /// symlinks are resolved; a pull may name a file that does not exist yet.
+    fn transfer_path(&self, local_path: &str, must_exist: bool) -> Result<PathBuf, ErrorDetail> {
+        let transfer_dir = &self.config.transfer_dir;
+        let invalid = |reason: String| {
+            error_detail(
+                "adb_command_invalid",
+                format!("local path {} {}", local_path, reason),
+            )
+        };
+        let path = Path::new(local_path);
+        if !path.is_absolute() || path.components().any(|c| c == Component::ParentDir) {
+            return Err(invalid(format!(
+                "must be absolute, without `..`, inside {}",
+                transfer_dir.display()
+            )));
+        }
+        let root = transfer_dir.canonicalize().map_err(|err| {
+            invalid(format!(
+                "cannot be checked: transfer dir {}: {}",
+                transfer_dir.display(),
+                err
+            ))
+        })?;
+        let resolved = match path.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && !must_exist => {
+                // A dangling symlink would be followed by the write.
+                if fs::symlink_metadata(path).is_ok() {
+                    return Err(invalid("is a dangling symlink".to_string()));
+                }
+                let parent = path.parent().and_then(|parent| parent.canonicalize().ok());
+                match (parent, path.file_name()) {
+                    (Some(parent), Some(name)) => parent.join(name),
+                    _ => return Err(invalid("has no existing parent directory".to_string())),
+                }
+            }
+            Err(err) => {
+                return Err(invalid(format!(
+                    "does not exist on the daemon host: {}",
+                    err
+                )))
+            }
+        };
+        if !resolved.starts_with(&root) {
+            return Err(invalid(format!(
+                "is outside the transfer dir {}",
+                transfer_dir.display()
+            )));
+        }
+        Ok(resolved)
+    }
+
     fn console_log_path(&self, id: InstanceId) -> PathBuf {
-        self.host_instance_dir(id)
-            .join("instances")
-            .join(format!("cvd-{}", id))
-            .join("console_log")
+        self.guest_backend(id)
+            .console_log_path(&self.host_instance_dir(id), id)
+    }
+
+    /// Backend of the instance's most recent start (cuttlefish if never started).
+    fn guest_backend(&self, id: InstanceId) -> Box<dyn GuestBackend> {
+        let metadata = match self.metadata_cache.get(&id) {
+            Some(cached) => Some(cached.clone()),
+            None => self.read_metadata(id).ok(),
+        };
+        let spec = metadata
+            .and_then(|metadata| metadata.start_options)
+            .map(|options| options.backend)
+            .unwrap_or_default();
+        backend_for(&self.config, &spec)
     }
 
-    fn logs(
-        &mut self,
+    async fn logs(
+        &self,
         id: InstanceId,
         lines: Option<usize>,
         options: LogsOptions,
@@ -1752,81 +3746,187 @@ impl InstanceManager {
             ));
         }
 
-        if self.metadata(id).is_err() {
+        let source = options.source;
+        let since = options.since.is_some();
+        let timeout_secs = options.timeout_secs;
+        // Ranging and grepping a multi-GB log file takes a while.
+        let (query, selected) = self
+            .blocking(move |manager| manager.select_log_file(id, lines, &options))
+            .await
+            .map_err(|err| error_detail("logs_fetch_failed", format!("{err:#}")))??;
+        let (selection, path) = if let Some((selection, path)) = selected {
+            (selection, Some(path))
+        } else {
+            let serial = self.connected_adb_serial(id).await?;
+            let output = self
+                .run_adb_command(id, serial, &["logcat", "-d", "-v", "epoch"], timeout_secs)
+                .await?;
+            if output.exit_code != Some(0) {
+                return Err(error_detail(
+                    "logs_fetch_failed",
+                    format!(
+                        "adb logcat failed for instance {}: {}",
+                        id,
+                        output.stderr.trim()
+                    ),
+                ));
+            }
+            let selection = query
+                .select(io::Cursor::new(output.stdout.as_bytes()))
+                .map_err(|err| error_detail("logs_fetch_failed", err.to_string()))?;
+            (selection, None)
+        };
+
+        if since && !selection.timestamped && selection.size_bytes > 0 {
             return Err(error_detail(
-                "instance_not_found",
-                format!("Instance {} does not exist or metadata cannot be read", id),
+                "logs_since_unsupported",
+                format!(
+                    "The {} log of instance {} has no timestamps cfctl understands",
+                    source.as_str(),
+                    id
+                ),
             ));
         }
+        Ok(LogsResponse {
+            source,
+            content: selection.content,
+            path,
+            size_bytes: selection.size_bytes,
+            next_byte: selection.next_byte,
+            truncated: selection.truncated,
+        })
+    }
 
-        let journal = self
-            .guest_log_tail(id, lines.unwrap_or(self.config.journal_lines))
-            .map_err(|err| {
-                let paths = self.paths(id);
-                if !paths.run_log_path().exists() {
-                    error_detail(
-                        "logs_not_available",
-                        format!(
-                            "Run log does not exist yet for instance {}. The instance may not have been started.",
-                            id
-                        ),
-                    )
-                } else {
-                    error_detail(
-                        "logs_fetch_failed",
-                        format!("Failed to read logs for instance {}: {}", id, err),
-                    )
-                }
-            })?;
+    /// Builds the query and runs it over the source's log file. Returns no
+    /// selection when the logcat capture is missing, so the caller can ask
+    /// adb instead.
+    fn select_log_file(
+        &self,
+        id: InstanceId,
+        lines: Option<usize>,
+        options: &LogsOptions,
+    ) -> Result<(LogQuery, Option<(LogSelection, String)>), ErrorDetail> {
+        let metadata = self.metadata(id).map_err(|_| {
+            error_detail(
+                "instance_not_found",
+                format!("Instance {} does not exist or metadata cannot be read", id),
+            )
+        })?;
+        let boot_epoch = metadata.launch.as_ref().map(|launch| launch.launched_at);
+        let query = LogQuery::new(options, lines, self.config.journal_lines, boot_epoch)
+            .map_err(|err| error_detail("logs_invalid", err))?;
+
+        let source = options.source;
+        let path = self.log_source_path(id, source)?;
+        if source == LogSource::Logcat && !path.exists() {
+            return Ok((query, None));
+        }
+        let selection = match query.select_file(&path) {
+            Ok(selection) => selection,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(error_detail(
+                    "logs_not_available",
+                    format!(
+                        "The {} log does not exist yet for instance {} ({}). The instance may not have been started.",
+                        source.as_str(),
+                        id,
+                        path.display()
+                    ),
+                ));
+            }
+            Err(err) => {
+                return Err(error_detail(
+                    "logs_fetch_failed",
+                    format!(
+                        "Failed to read logs for instance {} from {}: {}",
+                        id,
+                        path.display(),
+                        err
+                    ),
+                ))
+            }
+        };
+        Ok((query, Some((selection, path.display().to_string()))))
+    }
 
-        let console_path = format!(
-            "/var/lib/cuttlefish/instances/{}/instances/cvd-{}/console_log",
-            id, id
-        );
-        let response = LogsResponse {
-            journal,
-            console_log_path: Some(console_path),
+    /// Host file backing a file-based log source.
+    fn log_source_path(&self, id: InstanceId, source: LogSource) -> Result<PathBuf, ErrorDetail> {
+        let backend = self.guest_backend(id);
+        let instance_dir = self.host_instance_dir(id);
+        let path = match source {
+            LogSource::Run => Some(self.paths(id).run_log),
+            LogSource::Console => Some(backend.console_log_path(&instance_dir, id)),
+            LogSource::Kernel => backend.kernel_log_path(&instance_dir, id),
+            LogSource::Launcher => backend.launcher_log_path(&instance_dir, id),
+            LogSource::Logcat => Some(self.paths(id).root.join(LOGCAT_FILE)),
         };
-        Ok(response)
+        path.ok_or_else(|| {
+            error_detail(
+                "log_source_unavailable",
+                format!(
+                    "The {} backend keeps no {} log file",
+                    backend.name(),
+                    source.as_str()
+                ),
+            )
+        })
     }
 
-    fn record_launch_failure(
-        &mut self,
+    async fn record_launch_failure(
+        &self,
         id: InstanceId,
-        metadata: &mut InstanceMetadata,
+        mut metadata: InstanceMetadata,
         mut err: anyhow::Error,
     ) -> Result<anyhow::Error> {
-        metadata.state = InstanceState::Failed;
-        metadata.updated_at = epoch_secs()?;
-        let paths = self.paths(id);
-        self.write_metadata(&paths, &metadata)?;
-        self.write_env_file(&paths, &metadata)?;
-        self.metadata_cache.insert(id, metadata.clone());
-        if let Some(log_tail) = self.guest_log_tail(id, self.config.journal_lines)? {
-            err = err.context(format!("cfctl-run.log tail:\n{}", log_tail));
+        self.blocking(move |manager| {
+            metadata.state = InstanceState::Failed;
+            metadata.updated_at = epoch_secs()?;
+            let paths = manager.paths(id);
+            manager.write_metadata(&metadata)?;
+            manager.write_env_file(&paths, &metadata)?;
+            if let Some(log_tail) = manager.guest_log_tail(id, manager.config.journal_lines)? {
+                err = err.context(format!("cfctl-run.log tail:\n{}", log_tail));
+            }
+            Ok(err)
+        })
+        .await
+        .and_then(|result| result)
+    }
+
+    /// Fills in `resources` for the instances whose guest is running.
+    fn attach_resources(&self, summaries: &mut [InstanceSummary]) {
+        let roots: Vec<(InstanceId, i32)> = summaries
+            .iter()
+            .filter_map(|summary| Some((summary.id, self.guest_registry.get(summary.id)?.pid())))
+            .collect();
+        let mut usage = procstat::tree_usage(&roots);
+        for summary in summaries {
+            summary.resources = usage.remove(&summary.id);
         }
-        Ok(err)
     }
 
-    fn status(&mut self, id: InstanceId) -> Result<InstanceActionResponse> {
+    fn status(&self, id: InstanceId) -> Result<InstanceActionResponse> {
         let metadata = self.metadata(id)?;
         let summary = metadata.summary(&self.config.adb_host);
-        Ok(InstanceActionResponse {
-            summary,
-            journal_tail: None,
-            verification: None,
-            cleanup: None,
-            run_log_tail: None,
-            console_snapshot_path: None,
-        })
+        Ok(InstanceActionResponse::new(summary))
     }
 
-    fn describe(&mut self, id: InstanceId, run_log_lines: Option<usize>) -> Result<InstanceActionResponse> {
+    async fn describe(
+        &self,
+        id: InstanceId,
+        run_log_lines: Option<usize>,
+    ) -> Result<InstanceActionResponse> {
         let metadata = self.metadata(id)?;
-        let summary = metadata.summary(&self.config.adb_host);
+        let network_mode = metadata
+            .start_options
+            .as_ref()
+            .map(|options| options.network)
+            .unwrap_or_default();
+        let mut summary = metadata.summary(&self.config.adb_host);
+        self.attach_resources(std::slice::from_mut(&mut summary));
         let lines = run_log_lines.unwrap_or(50);
         let run_log_tail = self.guest_log_tail(id, lines)?;
-        
+
         let paths = self.paths(id);
         let console_snapshot = paths.root.join("console_snapshot.log");
         let console_snapshot_path = if console_snapshot.exists() {
@@ -1834,19 +3934,21 @@ impl InstanceManager {
         } else {
             None
         };
-        
+
         Ok(InstanceActionResponse {
-            summary,
-            journal_tail: None,
-            verification: None,
-            cleanup: None,
             run_log_tail,
             console_snapshot_path,
+            network: Some(self.network_status(id, network_mode).await),
+            start_options: metadata.start_options,
+            launch: metadata.launch,
+            logcat: self.logcat.status(id),
+            log_usage: Some(self.log_usage(id)),
+            ..InstanceActionResponse::new(summary)
         })
     }
 
     fn mark_metadata_state(
-        &mut self,
+        &self,
         id: InstanceId,
         state: InstanceState,
     ) -> Result<InstanceMetadata> {
@@ -1863,20 +3965,27 @@ impl InstanceManager {
             Err(_) => InstanceMetadata {
                 id,
                 purpose: None,
-                adb_port: self.config.base_adb_port + id as u16 - 1,
+                adb_port: SlotAllocator::new(&self.config)
+                    .adb_port(id)
+                    .unwrap_or_default(),
                 state: state.clone(),
                 boot_image: self.config.default_boot_image.clone(),
                 init_boot_image: self.config.default_init_boot_image.clone(),
                 created_at: now,
                 updated_at: now,
                 held: false,
+                pooled: false,
+                host_prepared: false,
+                host_assembled: false,
+                start_options: None,
+                launch: None,
             },
         };
 
         let paths = self.paths(id);
         fs::create_dir_all(&paths.root)?;
         fs::create_dir_all(&paths.artifacts).ok();
-        self.write_metadata(&paths, &metadata)?;
+        self.write_metadata(&metadata)?;
         if let Err(err) = self.write_env_file(&paths, &metadata) {
             debug!(
                 target: "cfctl",
@@ -1885,33 +3994,25 @@ impl InstanceManager {
                 err
             );
         }
-        self.metadata_cache.insert(id, metadata.clone());
         Ok(metadata)
     }
 
-    fn metadata(&mut self, id: InstanceId) -> Result<InstanceMetadata> {
+    fn metadata(&self, id: InstanceId) -> Result<InstanceMetadata> {
         if let Some(cached) = self.metadata_cache.get(&id) {
             return Ok(cached.clone());
         }
-        let paths = self.paths(id);
-        let mut file = File::open(&paths.metadata)
-            .with_context(|| format!("opening metadata file {}", paths.metadata.display()))?;
-        let mut buf = String::new();
-        file.read_to_string(&mut buf)?;
-        let metadata: InstanceMetadata =
-            serde_json::from_str(&buf).context("parsing metadata JSON")?;
-        Ok(metadata)
+        Ok(self.read_metadata(id)?)
     }
 
-    fn write_metadata(&self, paths: &InstancePaths, metadata: &InstanceMetadata) -> Result<()> {
-        if let Some(parent) = paths.metadata.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        let tmp = paths.metadata.with_extension("json.tmp");
-        fs::write(&tmp, serde_json::to_vec_pretty(metadata)?)
-            .with_context(|| format!("writing metadata tmp {}", tmp.display()))?;
-        fs::rename(&tmp, &paths.metadata)
-            .with_context(|| format!("renaming metadata {}", paths.metadata.display()))?;
+    fn read_metadata(&self, id: InstanceId) -> Result<InstanceMetadata, LoadError> {
+        MetadataStore::new(&self.config).load(id)
+    }
+
+    /// Persists `metadata` and refreshes the shared cache, which every
+    /// request reads through, so the two never disagree.
+    fn write_metadata(&self, metadata: &InstanceMetadata) -> Result<()> {
+        MetadataStore::new(&self.config).save(metadata)?;
+        self.metadata_cache.insert(metadata.id, metadata.clone());
         Ok(())
     }
 
@@ -1996,167 +4097,141 @@ impl InstanceManager {
         Ok(())
     }
 
+    /// Opens a fresh run log, keeping the previous run's as a rotated copy.
+    /// The file is opened for appending so the log worker can truncate it
+    /// in place while the guest still writes to it.
     fn prepare_run_log(&self, paths: &InstancePaths) -> Result<File> {
-        if let Some(parent) = paths.run_log_path().parent() {
+        let run_log = paths.run_log_path();
+        if let Some(parent) = run_log.parent() {
             fs::create_dir_all(parent)?;
         }
+        if fs::metadata(run_log).is_ok_and(|meta| meta.len() > 0) {
+            logrotate::rotate_by_rename(run_log, self.config.log_rotate_keep)?;
+        }
         let file = OpenOptions::new()
             .create(true)
-            .write(true)
-            .truncate(true)
-            .open(paths.run_log_path())
-            .with_context(|| format!("opening run log {}", paths.run_log_path().display()))?;
+            .append(true)
+            .open(run_log)
+            .with_context(|| format!("opening run log {}", run_log.display()))?;
         Ok(file)
     }
 
-    fn spawn_guest_process(
-        &self,
-        id: InstanceId,
-        metadata: &InstanceMetadata,
-        log_file: File,
-        webrtc_enabled: bool,
-        track: Option<&str>,
-    ) -> Result<Child> {
-        let log_clone = log_file
-            .try_clone()
-            .context("cloning run log file for stderr")?;
+    /// Moves the previous boot's console log aside when the host directory
+    /// survived (resume), so the new boot begins with an empty one.
+    fn rotate_console_log(&self, id: InstanceId, backend: &dyn GuestBackend) {
+        let console_log = backend.console_log_path(&self.host_instance_dir(id), id);
+        if !fs::metadata(&console_log).is_ok_and(|meta| meta.len() > 0) {
+            return;
+        }
+        if let Err(err) = logrotate::rotate_by_rename(&console_log, self.config.log_rotate_keep) {
+            warn!(
+                target: "cfctl",
+                "rotate_console_log: keeping {} in place: {:#}",
+                console_log.display(),
+                err
+            );
+        }
+    }
 
-        let inst_name = id.to_string();
-        let instance_dir = self.host_instance_dir(id);
-        let assembly_dir = self.host_assembly_dir(id);
-        
-        // Use sudo to switch user with configured primary group before entering FHS
-        // This preserves the group through bubblewrap's namespace isolation
-        let target_user = &self.config.guest_user;
-        let primary_group = &self.config.guest_primary_group;
-        
-        // Resolve UIDs/GIDs for logging
-        let uid = resolve_uid(target_user)?;
-        let gid = resolve_gid(primary_group)?;
-        
-        info!(
-            target: "cfctl",
-            "spawn_guest_process: resolved credentials uid={}:{} gid={}:{} caps={:?}",
-            target_user, uid, primary_group, gid, self.config.guest_capabilities
-        );
-        
-        // Use sudo to switch to target user with primary group
-        // Preserve CUTTLEFISH_* environment variables that we set below
-        let preserve_vars = vec![
-            "CUTTLEFISH_INSTANCE",
-            "CUTTLEFISH_INSTANCE_NUM",
-            "CUTTLEFISH_ADB_TCP_PORT",
-            "CUTTLEFISH_DISABLE_HOST_GPU",
-            "GFXSTREAM_DISABLE_GRAPHICS_DETECTOR",
-            "GFXSTREAM_HEADLESS",
-        ];
-        let mut cmd = Command::new("sudo");
-        cmd.arg("-u").arg(target_user)
-           .arg("-g").arg(primary_group);
-        for var in &preserve_vars {
-            cmd.arg(format!("--preserve-env={}", var));
-        }
-        cmd.arg("--");
-        
-        // Add setpriv to set ambient capabilities if any are configured
-        let caps: Vec<String> = self
-            .config
-            .guest_capabilities
-            .iter()
-            .map(|c| c.trim())
-            .filter(|c| !c.is_empty())
-            .map(|c| {
-                if c.starts_with('+') || c.starts_with('-') {
-                    c.to_string()
-                } else {
-                    format!("+{}", c)
+    /// The logs cfctl rotates and counts against the per-instance quota.
+    fn rotated_logs(&self, id: InstanceId) -> [(LogSource, PathBuf); 3] {
+        let paths = self.paths(id);
+        [
+            (LogSource::Run, paths.run_log.clone()),
+            (LogSource::Console, self.console_log_path(id)),
+            (LogSource::Logcat, paths.root.join(LOGCAT_FILE)),
+        ]
+    }
+
+    fn log_usage(&self, id: InstanceId) -> LogUsage {
+        let files: Vec<LogFileUsage> = self
+            .rotated_logs(id)
+            .into_iter()
+            .filter(|(_, path)| path.exists() || !logrotate::segments(path).is_empty())
+            .map(|(source, path)| {
+                let (bytes, segments, segment_bytes) = logrotate::usage(&path);
+                LogFileUsage {
+                    source,
+                    path: path.display().to_string(),
+                    bytes,
+                    segments,
+                    segment_bytes,
                 }
             })
             .collect();
-        if !caps.is_empty() {
-            let caps_arg = caps.join(",");
-            cmd.arg("setpriv")
-               .arg("--ambient-caps")
-               .arg(&caps_arg)
-               .arg("--");
-        }
-        
-        // Use cfenv if track specified, otherwise direct FHS wrapper
-        if let Some(t) = track {
-            info!(target: "cfctl", "spawn_guest_process: using track '{}'", t);
-            cmd.arg("cfenv").arg("-t").arg(t).arg("--");
-        } else {
-            info!(target: "cfctl", "spawn_guest_process: using default FHS wrapper");
-            cmd.arg(&self.config.cuttlefish_fhs).arg("--");
-        };
-        cmd
-            .arg("launch_cvd")
-            .arg(format!(
-                "--system_image_dir={}",
-                self.config.cuttlefish_system_image_dir.display()
-            ))
-            .arg(format!("--instance_dir={}", instance_dir.display()))
-            .arg(format!("--assembly_dir={}", assembly_dir.display()))
-            .arg("--vm_manager=qemu_cli")
-            .arg("--enable_wifi=false")
-            .arg("--enable_host_bluetooth=false")
-            .arg("--enable_modem_simulator=false")
-            .arg(format!(
-                "--start_webrtc={}",
-                if webrtc_enabled { "true" } else { "false" }
-            ))
-            .arg(format!(
-                "--start_webrtc_sig_server={}",
-                if webrtc_enabled { "true" } else { "false" }
-            ))
-            .arg("--report_anonymous_usage_stats=n")
-            .arg("--daemon=false")
-            .arg("--console=true")
-            // Keep ttyS0 attached so the persisted console_log captures Android init chatter.
-            .arg("--extra_kernel_cmdline=console=ttyS0,115200")
-            .arg("--verbosity=DEBUG")
-            .arg("--resume=false");
-
-        if metadata.boot_image.exists() {
-            cmd.arg(format!("--boot_image={}", metadata.boot_image.display()));
-        } else {
-            debug!(
-                target: "cfctl",
-                "spawn_guest_process: boot image {} not found; skipping flag",
-                metadata.boot_image.display()
-            );
-        }
-        if metadata.init_boot_image.exists() {
-            cmd.arg(format!(
-                "--init_boot_image={}",
-                metadata.init_boot_image.display()
-            ));
-        } else {
-            debug!(
-                target: "cfctl",
-                "spawn_guest_process: init boot image {} not found; skipping flag",
-                metadata.init_boot_image.display()
-            );
+        LogUsage {
+            quota_bytes: (self.config.log_quota_bytes > 0).then_some(self.config.log_quota_bytes),
+            used_bytes: files
+                .iter()
+                .map(|file| file.bytes + file.segment_bytes)
+                .sum(),
+            files,
         }
+    }
 
-        if self.config.disable_host_gpu {
-            cmd.env("CUTTLEFISH_DISABLE_HOST_GPU", "1");
+    /// Rotates oversized run and console logs of running guests, compresses
+    /// rotated copies and trims each instance back under its log quota.
+    pub(super) fn maintain_logs(&self) {
+        for id in self.guest_registry.ids() {
+            let logs = self.rotated_logs(id);
+            for (source, path) in &logs {
+                // The logcat capture rotates itself; its copies only need compressing.
+                let oversized = *source != LogSource::Logcat
+                    && fs::metadata(path)
+                        .is_ok_and(|meta| meta.len() > self.config.log_rotate_bytes);
+                if oversized {
+                    info!(
+                        target: "cfctl",
+                        "maintain_logs: rotating {} log of instance {}",
+                        source.as_str(),
+                        id
+                    );
+                    if let Err(err) = logrotate::rotate_by_copy(path, self.config.log_rotate_keep) {
+                        warn!(
+                            target: "cfctl",
+                            "maintain_logs: rotating {}: {:#}",
+                            path.display(),
+                            err
+                        );
+                    }
+                }
+                if let Err(err) = logrotate::compress_segments(path) {
+                    warn!(
+                        target: "cfctl",
+                        "maintain_logs: compressing segments of {}: {:#}",
+                        path.display(),
+                        err
+                    );
+                }
+            }
+            let paths: Vec<PathBuf> = logs.into_iter().map(|(_, path)| path).collect();
+            if let Err(err) = logrotate::enforce_quota(&paths, self.config.log_quota_bytes) {
+                warn!(
+                    target: "cfctl",
+                    "maintain_logs: enforcing log quota of instance {}: {:#}",
+                    id,
+                    err
+                );
+            }
         }
+    }
 
-        cmd.env("GFXSTREAM_DISABLE_GRAPHICS_DETECTOR", "1");
-        cmd.env("GFXSTREAM_HEADLESS", "1");
+    fn spawn_guest_process(
+        &self,
+        ctx: &LaunchContext,
+        backend: &dyn GuestBackend,
+        log_file: File,
+    ) -> Result<(Child, LaunchCommand)> {
+        let log_clone = log_file
+            .try_clone()
+            .context("cloning run log file for stderr")?;
 
-        cmd.env("CUTTLEFISH_INSTANCE", &inst_name);
-        cmd.env("CUTTLEFISH_INSTANCE_NUM", inst_name.clone());
-        cmd.env("CUTTLEFISH_ADB_TCP_PORT", metadata.adb_port.to_string());
+        let id = ctx.id;
+        let mut cmd = backend.command(ctx)?;
         cmd.stdin(Stdio::null())
             .stdout(Stdio::from(log_file))
             .stderr(Stdio::from(log_clone));
 
-        if let Some(parent) = instance_dir.parent() {
-            cmd.current_dir(parent);
-        }
-
         info!(
             target: "cfctl",
             "spawn_guest_process: launching instance {} with command {:?}",
@@ -2164,46 +4239,98 @@ impl InstanceManager {
             cmd
         );
 
-        let child = cmd.spawn().with_context(|| {
-            format!(
-                "spawning cuttlefish guest {} via {}",
-                id,
-                self.config.cuttlefish_fhs.display()
-            )
-        })?;
+        let launch = LaunchCommand {
+            argv: std::iter::once(cmd.get_program())
+                .chain(cmd.get_args())
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect(),
+            env: cmd
+                .get_envs()
+                .filter_map(|(key, value)| {
+                    Some((
+                        key.to_string_lossy().to_string(),
+                        value?.to_string_lossy().to_string(),
+                    ))
+                })
+                .collect(),
+            cwd: cmd.get_current_dir().map(|dir| dir.display().to_string()),
+            launched_at: epoch_secs()?,
+        };
 
-        Ok(child)
+        let program = cmd.get_program().to_string_lossy().to_string();
+        let child = TokioCommand::from(cmd)
+            .spawn()
+            .with_context(|| format!("spawning {} guest {} via {}", backend.name(), id, program))?;
+
+        Ok((child, launch))
+    }
+
+    /// Records the guest's logcat to the instance directory until it exits.
+    fn start_logcat_capture(
+        &self,
+        id: InstanceId,
+        backend: &dyn GuestBackend,
+        handle: &Arc<GuestHandle>,
+    ) {
+        if !self.config.logcat_capture || !backend.has_adb() {
+            return;
+        }
+        let metadata = match self.metadata(id) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                warn!(
+                    target: "cfctl",
+                    "start_logcat_capture: not capturing {}: {:#}",
+                    id,
+                    err
+                );
+                return;
+            }
+        };
+        let (_, connect_serial) = self.adb_serials(&metadata);
+        let target = LogcatTarget {
+            fhs: self.config.cuttlefish_fhs.clone(),
+            serial: connect_serial,
+            path: self.paths(id).root.join(LOGCAT_FILE),
+            rotate_bytes: self.config.logcat_rotate_bytes,
+            rotate_keep: self.config.logcat_rotate_keep,
+        };
+        self.logcat.start(id, target, Arc::clone(handle));
     }
 
     fn spawn_exit_watcher(&self, id: InstanceId, handle: Arc<GuestHandle>) {
-        let config = self.config.clone();
-        let registry = self.guest_registry.clone();
-        thread::spawn(move || match handle.wait() {
-            Ok(exit) => {
-                registry.remove_if_handle(id, &handle);
-                let mut manager = InstanceManager::new(config.clone(), registry.clone());
-                if let Err(err) = manager.handle_guest_exit(id, exit) {
+        let manager = self.clone();
+        task::spawn(async move {
+            let exit = match handle.wait().await {
+                Ok(exit) => exit,
+                Err(err) => {
                     warn!(
                         target: "cfctl",
-                        "spawn_exit_watcher: error handling guest exit {}: {:#}",
+                        "spawn_exit_watcher: failed waiting for guest {} exit: {:#}",
                         id,
                         err
                     );
+                    manager.guest_registry.remove_if_handle(id, &handle);
+                    return;
                 }
-            }
-            Err(err) => {
+            };
+            manager.guest_registry.remove_if_handle(id, &handle);
+            let handled = manager
+                .blocking(move |manager| manager.handle_guest_exit(id, exit))
+                .await
+                .and_then(|result| result);
+            if let Err(err) = handled {
                 warn!(
                     target: "cfctl",
-                    "spawn_exit_watcher: failed waiting for guest {} exit: {:#}",
+                    "spawn_exit_watcher: error handling guest exit {}: {:#}",
                     id,
                     err
                 );
-                registry.remove_if_handle(id, &handle);
             }
         });
     }
 
-    fn handle_guest_exit(&mut self, id: InstanceId, exit: ExitStatusInfo) -> Result<()> {
+    fn handle_guest_exit(&self, id: InstanceId, exit: ExitStatusInfo) -> Result<()> {
         let mut metadata = match self.metadata(id) {
             Ok(metadata) => metadata,
             Err(err) => {
@@ -2234,7 +4361,7 @@ impl InstanceManager {
         metadata.state = new_state.clone();
         metadata.updated_at = epoch_secs()?;
         let paths = self.paths(id);
-        
+
         if new_state == InstanceState::Failed {
             if let Err(err) = self.snapshot_console_on_failure(id, &paths) {
                 warn!(
@@ -2245,57 +4372,17 @@ impl InstanceManager {
                 );
             }
         }
-        
-        self.write_metadata(&paths, &metadata)?;
-        self.metadata_cache.insert(id, metadata.clone());
-        self.cleanup_host_state(id);
-        Ok(())
-    }
-
-    fn ensure_qemu_datadir(&self) -> Result<()> {
-        let datadir = Path::new("/var/lib/cuttlefish/usr/share/qemu/x86_64-linux-gnu");
-        fs::create_dir_all(datadir)
-            .with_context(|| format!("creating qemu datadir {}", datadir.display()))?;
-        let target = datadir.join("kvmvapic.bin");
-        if target.exists() {
-            return Ok(());
-        }
-
-        debug!(
-            target: "cfctl",
-            "ensure_qemu_datadir: populating {} from cuttlefish-fhs",
-            target.display()
-        );
-        let output = Command::new(&self.config.cuttlefish_fhs)
-            .args(["--", "cat", "/usr/share/qemu/kvmvapic.bin"])
-            .output()
-            .with_context(|| {
-                format!(
-                    "ensure_qemu_datadir: invoking {} to read kvmvapic.bin",
-                    self.config.cuttlefish_fhs.display()
-                )
-            })?;
-        if !output.status.success() {
-            warn!(
-                target: "cfctl",
-                "ensure_qemu_datadir: failed to read kvmvapic.bin via cuttlefish-fhs (status: {})",
-                output.status
-            );
-            return Ok(());
-        }
 
-        fs::write(&target, &output.stdout)
-            .with_context(|| format!("ensure_qemu_datadir: writing {}", target.display()))?;
-        fs::set_permissions(&target, fs::Permissions::from_mode(0o644)).with_context(|| {
-            format!(
-                "ensure_qemu_datadir: setting permissions on {}",
-                target.display()
-            )
-        })?;
+        self.write_metadata(&metadata)?;
+        self.cleanup_host_state(id);
         Ok(())
     }
 
-    fn terminate_guest(&self, id: InstanceId, grace: Duration) -> Result<Option<ExitStatusInfo>> {
+    async fn terminate_guest(
+        &self,
+        id: InstanceId,
+        grace: Duration,
+    ) -> Result<Option<ExitStatusInfo>> {
         if let Some(handle) = self.guest_registry.get(id) {
             info!(
                 target: "cfctl",
@@ -2304,7 +4391,7 @@ impl InstanceManager {
                 handle.pid()
             );
             handle.signal(libc::SIGTERM)?;
-            if let Some(exit) = handle.wait_timeout(grace)? {
+            if let Some(exit) = handle.wait_timeout(grace).await? {
                 info!(
                     target: "cfctl",
                     "terminate_guest: instance {} exited cleanly with {}",
@@ -2321,7 +4408,7 @@ impl InstanceManager {
                 grace
             );
             handle.signal(libc::SIGKILL)?;
-            if let Some(exit) = handle.wait_timeout(Duration::from_secs(5))? {
+            if let Some(exit) = handle.wait_timeout(Duration::from_secs(5)).await? {
                 info!(
                     target: "cfctl",
                     "terminate_guest: instance {} exited after SIGKILL with {}",
@@ -2371,9 +4458,12 @@ impl InstanceManager {
                 id
             );
         }
-        self.remove_network_devices(id);
-        self.remove_ephemeral_dirs(id);
-        self.remove_cuttlefish_config_symlink();
+        let cuttlefish_host = self.guest_backend(id).uses_cuttlefish_host();
+        if cuttlefish_host {
+            self.remove_network_devices(id);
+            self.remove_ephemeral_dirs(id);
+            self.remove_cuttlefish_config_symlink();
+        }
         info!(
             target: "cfctl",
             "preflight_cleanup: completed pre-launch cleanup for instance {}",
@@ -2393,21 +4483,32 @@ impl InstanceManager {
         let _ = self.kill_guest_processes(id);
         steps.push("kill_guest_processes".to_string());
 
-        self.remove_network_devices(id);
-        steps.push("remove_network_devices".to_string());
+        let cuttlefish_host = self.guest_backend(id).uses_cuttlefish_host();
+        if cuttlefish_host {
+            self.remove_network_devices(id);
+            steps.push("remove_network_devices".to_string());
+        }
+
+        self.remove_port_forwards(id);
+        steps.push("remove_port_forwards".to_string());
 
-        self.remove_ephemeral_dirs(id);
-        steps.push("remove_ephemeral_dirs".to_string());
+        if cuttlefish_host {
+            self.remove_ephemeral_dirs(id);
+            steps.push("remove_ephemeral_dirs".to_string());
 
-        self.remove_cuttlefish_config_symlink();
-        steps.push("remove_cuttlefish_config_symlink".to_string());
+            self.remove_cuttlefish_config_symlink();
+            steps.push("remove_cuttlefish_config_symlink".to_string());
+        }
 
         self.kill_open_file_holders(&[
-            format!("/var/lib/cuttlefish/instances/{}", id),
-            format!("/var/lib/cuttlefish/assembly/{}", id),
+            self.host_instance_dir(id).display().to_string(),
+            self.host_assembly_dir(id).display().to_string(),
         ]);
         steps.push("kill_open_file_holders".to_string());
-        let remaining = self.collect_guest_pids(id);
+        let remaining = {
+            let _sweep = self.process_sweep.lock().unwrap();
+            self.collect_guest_pids(id)
+        };
         steps.push("collect_guest_pids".to_string());
         if remaining.is_empty() {
             if let Err(err) = self.trash_then_purge_async(&self.host_instance_dir(id)) {
@@ -2436,18 +4537,22 @@ impl InstanceManager {
                 remaining
             );
         }
-        Self::reset_cuttlefish_permissions_async();
-        steps.push("reset_permissions".to_string());
+        if cuttlefish_host {
+            self.reset_cuttlefish_permissions_async();
+            steps.push("reset_permissions".to_string());
+        }
         info!(
             target: "cfctl",
             "cleanup_host_state: cleanup completed for instance {}",
             id
         );
+        self.metrics.record_cleanup(remaining.len());
         CleanupOutcome::from_parts(remaining, steps)
     }
 
     fn kill_guest_processes(&self, id: InstanceId) -> bool {
-        let patterns = Self::guest_process_patterns(id);
+        let _sweep = self.process_sweep.lock().unwrap();
+        let patterns = self.guest_process_patterns(id);
         for pattern in &patterns {
             debug!(
                 target: "cfctl",
@@ -2500,19 +4605,16 @@ impl InstanceManager {
         remaining.is_empty()
     }
 
-    fn guest_process_patterns(id: InstanceId) -> Vec<String> {
-        let inst_str = id.to_string();
-        vec![
-            format!("--instance_dir=/var/lib/cuttlefish/instances/{}", inst_str),
-            format!("--assembly_dir=/var/lib/cuttlefish/assembly/{}", inst_str),
-            format!("/var/lib/cuttlefish/instances/{}/", inst_str),
-            format!("/var/lib/cuttlefish/assembly/{}/", inst_str),
-            format!("cvd-{}", inst_str),
-        ]
+    fn guest_process_patterns(&self, id: InstanceId) -> Vec<String> {
+        self.guest_backend(id).process_patterns(
+            &self.host_instance_dir(id),
+            &self.host_assembly_dir(id),
+            id,
+        )
     }
 
     fn collect_guest_pids(&self, id: InstanceId) -> Vec<i32> {
-        let patterns = Self::guest_process_patterns(id);
+        let patterns = self.guest_process_patterns(id);
         let mut seen: HashSet<i32> = HashSet::new();
         for pattern in &patterns {
             match Command::new("pgrep").args(["-f", pattern]).output() {
@@ -2587,12 +4689,154 @@ impl InstanceManager {
         }
     }
 
+    /// Creates the instance's tap devices owned by the guest user and brings
+    /// them up without attaching them to any bridge.
+    fn create_isolated_taps(&self, id: InstanceId) -> Result<()> {
+        for tap in tap_names(id) {
+            debug!(target: "cfctl", "create_isolated_taps: creating tap device {}", tap);
+            let _ = run_command_allow_failure("ip", &["tuntap", "del", "dev", &tap]);
+            run_command_capture(
+                "ip",
+                &[
+                    "tuntap",
+                    "add",
+                    "dev",
+                    &tap,
+                    "mode",
+                    "tap",
+                    "user",
+                    &self.config.guest_user,
+                    "group",
+                    &self.config.guest_primary_group,
+                ],
+            )?;
+            run_command_capture("ip", &["link", "set", "dev", &tap, "up"])?;
+        }
+        Ok(())
+    }
+
+    async fn network_status(&self, id: InstanceId, mode: NetworkMode) -> NetworkStatus {
+        let devices = tap_names(id)
+            .into_iter()
+            .map(|name| NetworkDeviceStatus {
+                present: tap_present(&name),
+                name,
+            })
+            .collect();
+        let port_forwards = match self.active_port_forwards(id).await {
+            Ok(forwards) => Some(forwards),
+            Err(err) => {
+                debug!(
+                    target: "cfctl",
+                    "network_status: could not list adb forwards for {}: {:#}",
+                    id,
+                    err
+                );
+                None
+            }
+        };
+        NetworkStatus {
+            mode,
+            devices,
+            port_forwards,
+        }
+    }
+
+    async fn apply_port_forwards(
+        &self,
+        id: InstanceId,
+        forwards: &[PortForward],
+    ) -> Result<(), ErrorDetail> {
+        if forwards.is_empty() {
+            return Ok(());
+        }
+        let serial = self.connected_adb_serial(id).await?;
+        for forward in forwards {
+            let local = format!("tcp:{}", forward.host_port);
+            let remote = format!("tcp:{}", forward.guest_port);
+            let output = self
+                .run_adb_command(id, serial.clone(), &["forward", &local, &remote], None)
+                .await?;
+            if output.exit_code != Some(0) {
+                return Err(error_detail(
+                    "port_forward_failed",
+                    format!(
+                        "adb forward {} {} failed for instance {}: {}",
+                        local,
+                        remote,
+                        id,
+                        output.stderr.trim()
+                    ),
+                ));
+            }
+            info!(
+                target: "cfctl",
+                "apply_port_forwards: instance {} forwarding {} -> {}",
+                id,
+                local,
+                remote
+            );
+        }
+        Ok(())
+    }
+
+    /// Lists the TCP forwards adb reports for either of the instance's serials.
+    async fn active_port_forwards(&self, id: InstanceId) -> Result<Vec<PortForward>> {
+        let metadata = self.metadata(id)?;
+        let (serial, connect_serial) = self.adb_serials(&metadata);
+        let mut cmd = self.adb_command();
+        cmd.arg("forward").arg("--list");
+        let output = run_command_timeout(&mut cmd, Duration::from_secs(10))
+            .await?
+            .ok_or_else(|| anyhow!("adb forward --list timed out"))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "adb forward --list returned {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let tcp_port = |spec: &str| spec.strip_prefix("tcp:")?.parse::<u16>().ok();
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let device = fields.next()?;
+                if device != serial && device != connect_serial {
+                    return None;
+                }
+                Some(PortForward {
+                    host_port: tcp_port(fields.next()?)?,
+                    guest_port: tcp_port(fields.next()?)?,
+                })
+            })
+            .collect())
+    }
+
+    fn remove_port_forwards(&self, id: InstanceId) {
+        let Some(metadata) = self.metadata_cache.get(&id) else {
+            return;
+        };
+        let (serial, connect_serial) = self.adb_serials(&metadata);
+        drop(metadata);
+        for serial in [serial, connect_serial] {
+            debug!(target: "cfctl", "cleanup_host_state: removing adb forwards for {}", serial);
+            let mut cmd = Command::new(&self.config.cuttlefish_fhs);
+            cmd.args(["--", "adb", "-s", &serial, "forward", "--remove-all"]);
+            if let Err(err) = run_command_timeout_blocking(&mut cmd, Duration::from_secs(10)) {
+                debug!(
+                    target: "cfctl",
+                    "cleanup_host_state: ignoring adb forward --remove-all for {}: {:#}",
+                    serial,
+                    err
+                );
+            }
+        }
+    }
+
     fn remove_network_devices(&self, id: InstanceId) {
         let inst_padded = format!("{:02}", id);
-        for tap in [
-            format!("cvd-mtap-{}", inst_padded),
-            format!("cvd-tap-{}", inst_padded),
-        ] {
+        for tap in tap_names(id) {
             debug!(target: "cfctl", "cleanup_host_state: removing tap device {}", tap);
             if let Err(err) = run_command_allow_failure("ip", &["tuntap", "del", "dev", &tap]) {
                 debug!(
@@ -2625,7 +4869,7 @@ impl InstanceManager {
 
     fn remove_cuttlefish_config_symlink(&self) {
         debug!(target: "cfctl", "cleanup_host_state: removing cuttlefish config symlink");
-        if let Err(err) = fs::remove_file("/var/lib/cuttlefish/.cuttlefish_config.json") {
+        if let Err(err) = fs::remove_file(CUTTLEFISH_CONFIG_LINK) {
             if err.kind() != std::io::ErrorKind::NotFound {
                 debug!(
                     target: "cfctl",
@@ -2712,19 +4956,22 @@ impl InstanceManager {
         }
     }
 
-    fn reset_cuttlefish_permissions_async() {
-        thread::spawn(|| {
+    fn reset_cuttlefish_permissions_async(&self) {
+        let owner = format!(
+            "{}:{}",
+            self.config.guest_user, self.config.guest_primary_group
+        );
+        thread::spawn(move || {
             debug!(
                 target: "cfctl",
                 "background: resetting ownership/permissions on /var/lib/cuttlefish"
             );
-            Self::reset_cuttlefish_permissions();
+            Self::reset_cuttlefish_permissions(&owner);
         });
     }
 
-    fn reset_cuttlefish_permissions() {
-        if let Err(err) =
-            run_command_allow_failure("chown", &["-R", "justin:cvdnetwork", "/var/lib/cuttlefish"])
+    fn reset_cuttlefish_permissions(owner: &str) {
+        if let Err(err) = run_command_allow_failure("chown", &["-R", owner, "/var/lib/cuttlefish"])
         {
             debug!(
                 target: "cfctl",
@@ -2744,7 +4991,7 @@ impl InstanceManager {
         }
     }
 
-    fn guest_log_tail(&self, id: InstanceId, lines: usize) -> Result<Option<String>> {
+    pub(super) fn guest_log_tail(&self, id: InstanceId, lines: usize) -> Result<Option<String>> {
         let paths = self.paths(id);
         if !paths.run_log_path().exists() {
             return Ok(None);
@@ -2765,9 +5012,14 @@ impl InstanceManager {
         }
 
         let snapshot_path = paths.root.join("console_snapshot.log");
-        fs::copy(&console_log, &snapshot_path)
-            .with_context(|| format!("copying console log {} to {}", console_log.display(), snapshot_path.display()))?;
-        
+        fs::copy(&console_log, &snapshot_path).with_context(|| {
+            format!(
+                "copying console log {} to {}",
+                console_log.display(),
+                snapshot_path.display()
+            )
+        })?;
+
         info!(
             target: "cfctl",
             "snapshot_console_on_failure: saved console snapshot for instance {} to {}",
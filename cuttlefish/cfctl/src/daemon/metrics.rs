@@ -0,0 +1,330 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+use crate::protocol::InstanceState;
+
+/// Request latency buckets, in seconds. Starts and destroys take minutes.
+const REQUEST_BUCKETS: &[f64] = &[
+    0.005, 0.025, 0.1, 0.5, 1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0,
+];
+/// Boot milestone buckets, in seconds since the guest was launched.
+const BOOT_BUCKETS: &[f64] = &[
+    1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 45.0, 60.0, 90.0, 120.0, 180.0, 300.0,
+];
+
+/// States reported by `cfctl_instances`. Destroyed instances are left out:
+/// listings skip them, so the gauge could only ever read 0.
+const STATES: [InstanceState; 6] = [
+    InstanceState::Unknown,
+    InstanceState::Created,
+    InstanceState::Starting,
+    InstanceState::Running,
+    InstanceState::Stopped,
+    InstanceState::Failed,
+];
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    /// Cumulative count per bound, as Prometheus expects.
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter_mut()) {
+            if value <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            let _ = writeln!(out, "{name}_bucket{{{labels}{sep}le=\"{bound}\"}} {bucket}");
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{sep}le=\"+Inf\"}} {}",
+            self.count
+        );
+        let braced = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        let _ = writeln!(out, "{name}_sum{braced} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{braced} {}", self.count);
+    }
+}
+
+struct Registry {
+    requests: BTreeMap<(String, &'static str), u64>,
+    request_seconds: BTreeMap<String, Histogram>,
+    boot_to_adb: Histogram,
+    boot_to_marker: Histogram,
+    cleanups: BTreeMap<&'static str, u64>,
+    cleanup_surviving_pids: u64,
+}
+
+/// Values read from the host when metrics are scraped rather than counted
+/// as they happen.
+#[derive(Debug, Default)]
+pub struct Gauges {
+    pub instances: Vec<InstanceState>,
+    pub trash_backlog: usize,
+    pub in_flight_requests: usize,
+}
+
+/// Counters and histograms for the daemon's lifetime, rendered in the
+/// Prometheus text exposition format.
+pub struct Metrics {
+    registry: Mutex<Registry>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            registry: Mutex::new(Registry {
+                requests: BTreeMap::new(),
+                request_seconds: BTreeMap::new(),
+                boot_to_adb: Histogram::new(BOOT_BUCKETS),
+                boot_to_marker: Histogram::new(BOOT_BUCKETS),
+                cleanups: BTreeMap::new(),
+                cleanup_surviving_pids: 0,
+            }),
+        }
+    }
+}
+
+impl Metrics {
+    /// Records a client request; `result` is `ok`, `error` or `cancelled`.
+    pub fn record_request(&self, action: &str, result: &'static str, elapsed: Duration) {
+        let mut registry = self.registry.lock().unwrap();
+        *registry
+            .requests
+            .entry((action.to_string(), result))
+            .or_default() += 1;
+        registry
+            .request_seconds
+            .entry(action.to_string())
+            .or_insert_with(|| Histogram::new(REQUEST_BUCKETS))
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_boot_to_adb(&self, elapsed: Duration) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.boot_to_adb.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_boot_to_marker(&self, elapsed: Duration) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.boot_to_marker.observe(elapsed.as_secs_f64());
+    }
+
+    /// Records a host cleanup that left `surviving_pids` guest processes
+    /// behind.
+    pub fn record_cleanup(&self, surviving_pids: usize) {
+        let mut registry = self.registry.lock().unwrap();
+        let result = if surviving_pids == 0 {
+            "clean"
+        } else {
+            "incomplete"
+        };
+        *registry.cleanups.entry(result).or_default() += 1;
+        registry.cleanup_surviving_pids += surviving_pids as u64;
+    }
+
+    pub fn render(&self, gauges: &Gauges) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "cfctl_requests_total",
+            "counter",
+            "Client requests handled, by action and result.",
+        );
+        for ((action, result), count) in &registry.requests {
+            let _ = writeln!(
+                out,
+                "cfctl_requests_total{{action=\"{}\",result=\"{}\"}} {}",
+                escape(action),
+                result,
+                count
+            );
+        }
+
+        header(
+            &mut out,
+            "cfctl_request_duration_seconds",
+            "histogram",
+            "Time from decoding a client request to having its response.",
+        );
+        for (action, histogram) in &registry.request_seconds {
+            histogram.render(
+                &mut out,
+                "cfctl_request_duration_seconds",
+                &format!("action=\"{}\"", escape(action)),
+            );
+        }
+
+        header(
+            &mut out,
+            "cfctl_instances",
+            "gauge",
+            "Instances known to the daemon, by state.",
+        );
+        for state in STATES {
+            let count = gauges
+                .instances
+                .iter()
+                .filter(|instance| **instance == state)
+                .count();
+            let _ = writeln!(
+                out,
+                "cfctl_instances{{state=\"{}\"}} {}",
+                state.as_str(),
+                count
+            );
+        }
+
+        header(
+            &mut out,
+            "cfctl_in_flight_requests",
+            "gauge",
+            "Requests the daemon is currently working on.",
+        );
+        let _ = writeln!(
+            out,
+            "cfctl_in_flight_requests {}",
+            gauges.in_flight_requests
+        );
+
+        header(
+            &mut out,
+            "cfctl_boot_to_adb_seconds",
+            "histogram",
+            "Time from launching a guest until adb reported it as a device.",
+        );
+        registry
+            .boot_to_adb
+            .render(&mut out, "cfctl_boot_to_adb_seconds", "");
+
+        header(
+            &mut out,
+            "cfctl_boot_to_marker_seconds",
+            "histogram",
+            "Time from launching a guest until its boot marker was observed.",
+        );
+        registry
+            .boot_to_marker
+            .render(&mut out, "cfctl_boot_to_marker_seconds", "");
+
+        header(
+            &mut out,
+            "cfctl_cleanups_total",
+            "counter",
+            "Host cleanups after stop or destroy, by whether guest processes survived.",
+        );
+        for result in ["clean", "incomplete"] {
+            let count = registry.cleanups.get(result).copied().unwrap_or(0);
+            let _ = writeln!(out, "cfctl_cleanups_total{{result=\"{result}\"}} {count}");
+        }
+
+        header(
+            &mut out,
+            "cfctl_cleanup_surviving_pids_total",
+            "counter",
+            "Guest processes still running after a host cleanup.",
+        );
+        let _ = writeln!(
+            out,
+            "cfctl_cleanup_surviving_pids_total {}",
+            registry.cleanup_surviving_pids
+        );
+
+        header(
+            &mut out,
+            "cfctl_trash_backlog",
+            "gauge",
+            "Trashed host directories not yet removed.",
+        );
+        let _ = writeln!(out, "cfctl_trash_backlog {}", gauges.trash_backlog);
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_uses_prometheus_text_format() {
+        let metrics = Metrics::default();
+        metrics.record_request("StartInstance", "ok", Duration::from_millis(700));
+        metrics.record_request("StartInstance", "error", Duration::from_secs(20));
+        metrics.record_boot_to_adb(Duration::from_secs(12));
+        metrics.record_cleanup(0);
+        metrics.record_cleanup(2);
+        let text = metrics.render(&Gauges {
+            instances: vec![InstanceState::Running, InstanceState::Running],
+            trash_backlog: 3,
+            in_flight_requests: 1,
+        });
+
+        for line in [
+            "# TYPE cfctl_requests_total counter",
+            "cfctl_requests_total{action=\"StartInstance\",result=\"ok\"} 1",
+            "cfctl_requests_total{action=\"StartInstance\",result=\"error\"} 1",
+            "cfctl_request_duration_seconds_bucket{action=\"StartInstance\",le=\"0.5\"} 0",
+            "cfctl_request_duration_seconds_bucket{action=\"StartInstance\",le=\"1\"} 1",
+            "cfctl_request_duration_seconds_bucket{action=\"StartInstance\",le=\"+Inf\"} 2",
+            "cfctl_request_duration_seconds_count{action=\"StartInstance\"} 2",
+            "cfctl_instances{state=\"running\"} 2",
+            "cfctl_instances{state=\"failed\"} 0",
+            "cfctl_boot_to_adb_seconds_bucket{le=\"10\"} 0",
+            "cfctl_boot_to_adb_seconds_bucket{le=\"20\"} 1",
+            "cfctl_boot_to_adb_seconds_sum 12",
+            "cfctl_boot_to_marker_seconds_count 0",
+            "cfctl_cleanups_total{result=\"clean\"} 1",
+            "cfctl_cleanups_total{result=\"incomplete\"} 1",
+            "cfctl_cleanup_surviving_pids_total 2",
+            "cfctl_trash_backlog 3",
+            "cfctl_in_flight_requests 1",
+        ] {
+            assert!(
+                text.lines().any(|candidate| candidate == line),
+                "missing {line:?} in:\n{text}"
+            );
+        }
+        assert!(!text.contains("state=\"destroyed\""));
+        assert_eq!(escape("a\"b\\"), "a\\\"b\\\\");
+    }
+}
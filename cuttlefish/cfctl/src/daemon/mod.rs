@@ -1,47 +1,96 @@
+mod backend;
+mod batch;
+mod cancel;
 mod config;
+mod doctor;
 mod guest;
+mod history;
+mod logcat;
+mod logrotate;
+mod logs;
 mod manager;
+mod metrics;
+mod pool;
+mod procstat;
+mod qmp;
+mod scenario;
+mod slots;
+mod store;
 mod util;
 
 pub use config::CfctlDaemonConfig;
 
-use std::{fs, os::unix::fs::PermissionsExt, sync::Arc, thread};
+use std::{
+    fs,
+    os::unix::{
+        fs::PermissionsExt,
+        io::{AsRawFd, RawFd},
+    },
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
 use dashmap::DashMap;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    net::UnixListener,
-    sync::{Mutex as AsyncMutex, OwnedMutexGuard},
-    task,
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream, UnixListener},
+    signal::unix::{signal, Signal, SignalKind},
+    sync::{Mutex as AsyncMutex, Notify, OwnedMutexGuard},
+    task, time,
 };
 use tracing::{debug, error, info, warn};
 
-use crate::protocol::{InstanceId, Request, Response};
+use crate::protocol::{ErrorDetail, InstanceId, Request, Response};
 
+use cancel::{CancelToken, RequestTracker};
+use config::SharedConfig;
 use guest::GuestRegistry;
 use manager::InstanceManager;
+use metrics::Gauges;
+
+/// How often a connection with a request in flight checks whether its
+/// client has gone away.
+const DISCONNECT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a `Cancel` request waits for the cancelled request to roll back
+/// and release its locks before answering.
+const CANCEL_ROLLBACK_WAIT: Duration = Duration::from_secs(30);
 
 #[derive(Clone)]
 pub struct CfctlDaemon {
-    config: Arc<CfctlDaemonConfig>,
+    /// Settings from flags, which the config file is layered over on every
+    /// (re)load.
+    base_config: Arc<CfctlDaemonConfig>,
+    config: SharedConfig,
     instance_locks: Arc<DashMap<InstanceId, Arc<AsyncMutex<()>>>>,
     id_lock: Arc<AsyncMutex<()>>,
-    guest_registry: Arc<GuestRegistry>,
+    manager: InstanceManager,
+    requests: RequestTracker,
+    pool_wakeup: Arc<Notify>,
 }
 
 impl CfctlDaemon {
     pub fn new(config: CfctlDaemonConfig) -> Self {
+        let shared = SharedConfig::new(config.clone());
         Self {
-            config: Arc::new(config),
+            manager: InstanceManager::new(shared.clone(), Arc::new(GuestRegistry::new())),
+            base_config: Arc::new(config),
+            config: shared,
             instance_locks: Arc::new(DashMap::new()),
             id_lock: Arc::new(AsyncMutex::new(())),
-            guest_registry: Arc::new(GuestRegistry::new()),
+            requests: RequestTracker::default(),
+            pool_wakeup: Arc::new(Notify::new()),
         }
     }
 
     pub async fn run(&self) -> Result<()> {
-        let config = (*self.config).clone();
+        let config = self.base_config.load()?;
+        self.config.replace(config.clone());
+        // Installed before listening: SIGHUP would otherwise kill the daemon.
+        let hangups = signal(SignalKind::hangup()).context("installing SIGHUP handler")?;
         Self::ensure_dirs(&config)?;
         Self::sweep_trash(&config);
 
@@ -59,6 +108,25 @@ impl CfctlDaemon {
         fs::set_permissions(&config.socket_path, fs::Permissions::from_mode(0o660))?;
 
         info!("cfctl daemon listening on {}", config.socket_path.display());
+        if let Some(fake_guest) = &config.fake_guest {
+            warn!(
+                "cfctl daemon running fake guests from {} (test-only)",
+                fake_guest.display()
+            );
+        }
+
+        if let Some(addr) = config.metrics_addr {
+            let metrics_listener = TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("binding metrics listener {}", addr))?;
+            info!("cfctl daemon serving metrics on http://{}/metrics", addr);
+            task::spawn(self.clone().run_metrics_listener(metrics_listener));
+        }
+
+        task::spawn(self.clone().run_pool_worker());
+        task::spawn(self.clone().run_log_worker());
+        task::spawn(self.clone().run_prune_worker());
+        task::spawn(self.clone().run_reload_worker(hangups));
 
         loop {
             let (stream, _) = listener.accept().await?;
@@ -76,6 +144,8 @@ impl CfctlDaemon {
             .with_context(|| format!("creating state dir {}", config.state_dir.display()))?;
         fs::create_dir_all(&config.etc_instances_dir)
             .with_context(|| format!("creating etc dir {}", config.etc_instances_dir.display()))?;
+        fs::create_dir_all(&config.transfer_dir)
+            .with_context(|| format!("creating transfer dir {}", config.transfer_dir.display()))?;
 
         if let Some(parent) = config.socket_path.parent() {
             fs::create_dir_all(parent)
@@ -85,11 +155,14 @@ impl CfctlDaemon {
         Ok(())
     }
 
-    fn sweep_trash(config: &CfctlDaemonConfig) {
+    /// Host directories renamed aside for background removal that are
+    /// still on disk.
+    fn trash_dirs(config: &CfctlDaemonConfig) -> Vec<PathBuf> {
         let bases = [
             &config.cuttlefish_instances_dir,
             &config.cuttlefish_assembly_dir,
         ];
+        let mut trash = Vec::new();
         for base in bases {
             let Ok(entries) = fs::read_dir(base) else {
                 continue;
@@ -99,34 +172,174 @@ impl CfctlDaemon {
                 let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
                     continue;
                 };
-                if !name.contains(".__trash__.") {
-                    continue;
+                if name.contains(".__trash__.") {
+                    trash.push(path);
+                }
+            }
+        }
+        trash
+    }
+
+    fn sweep_trash(config: &CfctlDaemonConfig) {
+        for path in Self::trash_dirs(config) {
+            debug!(
+                target: "cfctl",
+                "sweep_trash: scheduling removal of {}",
+                path.display()
+            );
+            thread::spawn(move || {
+                if let Err(err) = fs::remove_dir_all(&path) {
+                    warn!(
+                        target: "cfctl",
+                        "sweep_trash: failed removing {}: {}",
+                        path.display(),
+                        err
+                    );
+                } else {
+                    debug!(
+                        target: "cfctl",
+                        "sweep_trash: removed {}",
+                        path.display()
+                    );
                 }
-                debug!(
+            });
+        }
+    }
+
+    /// Re-reads the config file on every SIGHUP.
+    async fn run_reload_worker(self, mut hangups: Signal) {
+        while hangups.recv().await.is_some() {
+            self.reload_config();
+        }
+    }
+
+    /// Swaps in the config file's current settings. Running guests, and
+    /// requests already in flight, keep the config they started with; an
+    /// invalid file leaves the current config in place.
+    fn reload_config(&self) {
+        let next = match self.base_config.load() {
+            Ok(next) => next,
+            Err(err) => {
+                warn!(target: "cfctl", "reload_config: keeping current config: {:#}", err);
+                return;
+            }
+        };
+        let (next, kept) = self.config.current().reloaded(next);
+        if !kept.is_empty() {
+            warn!(
+                target: "cfctl",
+                "reload_config: {} only change on restart; keeping current values",
+                kept.join(", ")
+            );
+        }
+        self.config.replace(next);
+        self.wake_pool();
+        info!(target: "cfctl", "reload_config: config reloaded");
+    }
+
+    /// Prunes unheld instances older than `prune_max_age`, when set, every
+    /// `prune_interval`.
+    async fn run_prune_worker(self) {
+        loop {
+            time::sleep(self.config.current().prune_interval).await;
+            let Some(max_age) = self.config.current().prune_max_age else {
+                continue;
+            };
+            let request = Request::PruneExpired {
+                max_age_secs: max_age.as_secs(),
+            };
+            match self.dispatch_locked(request, &CancelToken::new()).await {
+                Ok(response) => debug!(
                     target: "cfctl",
-                    "sweep_trash: scheduling removal of {}",
-                    path.display()
-                );
-                thread::spawn(move || {
-                    if let Err(err) = fs::remove_dir_all(&path) {
-                        warn!(
-                            target: "cfctl",
-                            "sweep_trash: failed removing {}: {}",
-                            path.display(),
-                            err
-                        );
-                    } else {
-                        debug!(
-                            target: "cfctl",
-                            "sweep_trash: removed {}",
-                            path.display()
-                        );
-                    }
-                });
+                    "prune_worker: {}",
+                    response.message.as_deref().unwrap_or("done")
+                ),
+                Err(err) => warn!(target: "cfctl", "prune_worker: {:#}", err),
             }
         }
     }
 
+    /// Renders the metrics, reading the gauges' instance states and trash
+    /// dirs on the blocking pool.
+    async fn render_metrics(&self) -> Result<String> {
+        let config = self.config.current();
+        let (instances, trash_backlog) = self
+            .manager
+            .blocking(move |manager| -> Result<_> {
+                let instances = manager
+                    .list_instances()?
+                    .into_iter()
+                    .map(|summary| summary.state)
+                    .collect();
+                Ok((instances, Self::trash_dirs(&config).len()))
+            })
+            .await
+            .and_then(|result| result)?;
+        Ok(self.manager.metrics().render(&Gauges {
+            instances,
+            trash_backlog,
+            in_flight_requests: self.requests.list().len(),
+        }))
+    }
+
+    /// Serves `GET /metrics` for Prometheus scrapers. Anything else gets a
+    /// 404; connections carry a single request.
+    async fn run_metrics_listener(self, listener: TcpListener) {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    warn!(target: "cfctl", "run_metrics_listener: accept failed: {}", err);
+                    time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+            let daemon = self.clone();
+            task::spawn(async move {
+                if let Err(err) = daemon.serve_metrics(stream).await {
+                    debug!(
+                        target: "cfctl",
+                        "run_metrics_listener: request from {} failed: {:#}",
+                        peer,
+                        err
+                    );
+                }
+            });
+        }
+    }
+
+    async fn serve_metrics(&self, mut stream: TcpStream) -> Result<()> {
+        let mut head = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+                .await
+                .context("timed out reading metrics request")??;
+            if read == 0 || head.len() > 16 * 1024 {
+                break;
+            }
+            head.extend_from_slice(&buf[..read]);
+        }
+        let head = String::from_utf8_lossy(&head);
+        let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+        let (status, body) = match (request_line.next(), request_line.next()) {
+            (Some("GET"), Some("/metrics")) => match self.render_metrics().await {
+                Ok(body) => ("200 OK", body),
+                Err(err) => ("500 Internal Server Error", format!("{:#}\n", err)),
+            },
+            _ => ("404 Not Found", "not found\n".to_string()),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+
     async fn handle_stream(&self, stream: tokio::net::UnixStream) -> Result<()> {
         info!(target: "cfctl", "handle_stream: new connection received");
         let mut reader = BufReader::new(stream);
@@ -143,10 +356,66 @@ impl CfctlDaemon {
 
         info!(target: "cfctl", "handle_stream: parsed request: {:?}", request);
 
-        let response = self.dispatch(request).await.unwrap_or_else(|err| {
+        // Clients half-close after sending, so a full hang-up while the
+        // request is in flight means nobody is waiting for the answer.
+        let mut label = describe_request(&request);
+        let action = request.action_name();
+        let tracked = match request {
+            Request::ListRequests | Request::Cancel { .. } | Request::Metrics => None,
+            _ => Some(
+                self.requests
+                    .register(label.clone(), request_instance_id(&request)),
+            ),
+        };
+        let cancel = match &tracked {
+            Some(tracked) => {
+                label = format!("{} [{}]", label, tracked.id());
+                tracked.token().clone()
+            }
+            None => CancelToken::new(),
+        };
+        let fd = reader.get_ref().as_raw_fd();
+        let started = Instant::now();
+        let dispatch = self.dispatch(request, &cancel);
+        tokio::pin!(dispatch);
+        let mut disconnected = false;
+        let result = loop {
+            tokio::select! {
+                result = &mut dispatch => break result,
+                _ = time::sleep(DISCONNECT_POLL_INTERVAL), if !disconnected => {
+                    if peer_hung_up(fd) {
+                        disconnected = true;
+                        warn!(
+                            target: "cfctl",
+                            "handle_stream: client disconnected, cancelling {}",
+                            label
+                        );
+                        cancel.cancel();
+                    }
+                }
+            }
+        };
+        let response = result.unwrap_or_else(|err| {
             error!(target: "cfctl", "handle_stream: request error: {:#}", err);
             Response::error(err.to_string())
         });
+        let result = match &response.error {
+            _ if response.ok => "ok",
+            Some(detail) if detail.code.ends_with("_cancelled") => "cancelled",
+            _ => "error",
+        };
+        self.manager
+            .metrics()
+            .record_request(action, result, started.elapsed());
+        if disconnected {
+            info!(
+                target: "cfctl",
+                "handle_stream: {} finished after disconnect: {:?}",
+                label,
+                response.error
+            );
+            return Ok(());
+        }
 
         info!(target: "cfctl", "handle_stream: dispatch completed, preparing response");
         debug!(target: "cfctl", "handle_stream: sending response: {:?}", response);
@@ -165,83 +434,130 @@ impl CfctlDaemon {
         Ok(())
     }
 
-    async fn dispatch(&self, request: Request) -> Result<Response> {
+    async fn dispatch(&self, request: Request, cancel: &CancelToken) -> Result<Response> {
+        match request {
+            Request::RunScenario { scenario } => Ok(self.run_scenario(scenario, cancel).await),
+            Request::RunBatch { batch } => Ok(self.run_batch(batch, cancel).await),
+            request @ Request::CreateStartInstance { pool: true, .. } => {
+                let response = self.dispatch_locked(request, cancel).await;
+                self.wake_pool();
+                response
+            }
+            Request::ListRequests => Ok(Response {
+                requests: Some(self.requests.list()),
+                ..Response::ok()
+            }),
+            Request::Cancel { request_id } => Ok(self.cancel_request(&request_id).await),
+            Request::Metrics => Ok(Response {
+                metrics: Some(self.render_metrics().await?),
+                ..Response::ok()
+            }),
+            other => self.dispatch_locked(other, cancel).await,
+        }
+    }
+
+    /// Cancels another in-flight request and waits for it to roll back, so
+    /// the instance lock it held is free once this returns ok.
+    async fn cancel_request(&self, request_id: &str) -> Response {
+        info!(target: "cfctl", "cancel_request: cancelling {}", request_id);
+        match self.requests.cancel(request_id, CANCEL_ROLLBACK_WAIT).await {
+            Some((description, true)) => {
+                Response::ok().with_message(format!("cancelled {} ({})", request_id, description))
+            }
+            Some((description, false)) => Response::ok().with_message(format!(
+                "cancel requested for {} ({}); still rolling back after {}s",
+                request_id,
+                description,
+                CANCEL_ROLLBACK_WAIT.as_secs()
+            )),
+            None => Response::error_with_detail(ErrorDetail {
+                code: "request_not_found".to_string(),
+                message: Some(format!("no in-flight request {}", request_id)),
+            }),
+        }
+    }
+
+    /// Dispatches `request` on behalf of a daemon-side orchestration, turning
+    /// both handler errors and `ok: false` responses into an `ErrorDetail`.
+    async fn dispatch_checked(
+        &self,
+        request: Request,
+        cancel: &CancelToken,
+    ) -> Result<Response, ErrorDetail> {
+        match self.dispatch_locked(request, cancel).await {
+            Ok(response) if response.ok => Ok(response),
+            Ok(response) => Err(match (response.error, response.message) {
+                (Some(detail), _) => detail,
+                (None, message) => ErrorDetail {
+                    code: "request_failed".to_string(),
+                    message,
+                },
+            }),
+            Err(err) => Err(ErrorDetail {
+                code: "request_error".to_string(),
+                message: Some(format!("{err:#}")),
+            }),
+        }
+    }
+
+    /// Runs a single request through the `InstanceManager` while holding the
+    /// per-instance lock (or the id lock for requests that allocate ids, and
+    /// for `doctor --fix` so no create launches while it removes host state).
+    /// Cancelling `cancel` abandons a lock wait and interrupts long waits.
+    async fn dispatch_locked(&self, request: Request, cancel: &CancelToken) -> Result<Response> {
         let request_label = describe_request(&request);
         info!(target: "cfctl", "dispatch: processing request: {}", request_label);
         use Request::*;
 
-        let maybe_instance_id = match &request {
-            StartInstance { id, .. }
-            | StopInstance { id }
-            | HoldInstance { id }
-            | DestroyInstance { id, .. }
-            | WaitForAdb { id, .. }
-            | Logs { id, .. }
-            | Status { id }
-            | Describe { id, .. } => Some(*id),
-            Deploy(req) => Some(req.id),
-            _ => None,
-        };
+        let maybe_instance_id = request_instance_id(&request);
 
-        let mut instance_guard: Option<(InstanceId, OwnedMutexGuard<()>)> = None;
+        let mut instance_guard: Option<OwnedMutexGuard<()>> = None;
         if let Some(id) = maybe_instance_id {
-            let guard = self.lock_instance(id).await;
-            instance_guard = Some((id, guard));
+            tokio::select! {
+                guard = self.lock_instance(id) => instance_guard = Some(guard),
+                _ = cancel.cancelled() => {
+                    self.cleanup_instance_lock(id);
+                    return Ok(cancelled_response(&request_label));
+                }
+            }
         }
 
         let mut id_guard: Option<OwnedMutexGuard<()>> = None;
-        if matches!(request, CreateInstance { .. } | CreateStartInstance { .. }) {
-            id_guard = Some(self.id_lock.clone().lock_owned().await);
+        if matches!(
+            request,
+            CreateInstance { .. }
+                | CreateStartInstance { .. }
+                | CloneInstance { .. }
+                | Doctor { fix: true }
+        ) {
+            tokio::select! {
+                guard = self.id_lock.clone().lock_owned() => id_guard = Some(guard),
+                _ = cancel.cancelled() => {
+                    drop(instance_guard);
+                    if let Some(id) = maybe_instance_id {
+                        self.cleanup_instance_lock(id);
+                    }
+                    return Ok(cancelled_response(&request_label));
+                }
+            }
         }
-
-        let instance_id_for_cleanup = instance_guard.as_ref().map(|(id, _)| *id);
-        let instance_guard_owned = instance_guard.map(|(_, guard)| guard);
-        let id_guard_owned = id_guard;
-        let config = self.config.clone();
-        let guest_registry = self.guest_registry.clone();
         debug!(
             target: "cfctl",
             "dispatch: acquired locks for {}",
             request_label
         );
 
-        let response = match request {
-            other => {
-                let result = task::spawn_blocking(move || {
-                    let _id_guard = id_guard_owned;
-                    let _instance_guard = instance_guard_owned;
-                    let mut manager = InstanceManager::new((*config).clone(), guest_registry);
-                    manager.handle(other)
-                })
-                .await;
-
-                let result = match result {
-                    Ok(inner) => {
-                        debug!(
-                            target: "cfctl",
-                            "dispatch: completed blocking handler for {}",
-                            request_label
-                        );
-                        inner
-                    }
-                    Err(err) => {
-                        error!(
-                            target: "cfctl",
-                            "dispatch: join error for {}: {}",
-                            request_label,
-                            err
-                        );
-                        return Err(err.into());
-                    }
-                }?;
-
-                result
-            }
-        };
-
-        if let Some(id) = instance_id_for_cleanup {
+        let result = self
+            .manager
+            .with_cancel(cancel.clone())
+            .handle(request)
+            .await;
+        drop(id_guard);
+        drop(instance_guard);
+        if let Some(id) = maybe_instance_id {
             self.cleanup_instance_lock(id);
         }
+        let response = result?;
 
         info!(
             target: "cfctl",
@@ -271,23 +587,92 @@ impl CfctlDaemon {
     }
 }
 
+/// The instance a request operates on, whose lock it takes.
+fn request_instance_id(request: &Request) -> Option<InstanceId> {
+    use Request::*;
+    match request {
+        StartInstance { id, .. }
+        | StopInstance { id }
+        | RestartInstance { id }
+        | HoldInstance { id }
+        | ReleaseInstance { id }
+        | SnapshotInstance { id, .. }
+        | RestoreInstance { id, .. }
+        | ListSnapshots { id }
+        | DeleteSnapshot { id, .. }
+        | DestroyInstance { id, .. }
+        | WaitForAdb { id, .. }
+        | Logs { id, .. }
+        | Shell { id, .. }
+        | Push { id, .. }
+        | Pull { id, .. }
+        | Status { id }
+        | Describe { id, .. } => Some(*id),
+        CloneInstance { from, .. } => Some(*from),
+        Deploy(req) => Some(req.id),
+        _ => None,
+    }
+}
+
+fn cancelled_response(request_label: &str) -> Response {
+    Response::error_with_detail(ErrorDetail {
+        code: "request_cancelled".to_string(),
+        message: Some(format!(
+            "{} cancelled while waiting for its lock",
+            request_label
+        )),
+    })
+}
+
+/// Reports whether the peer has closed its end of `fd` entirely. A client
+/// that only shut down its write half does not count.
+fn peer_hung_up(fd: RawFd) -> bool {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: 0,
+        revents: 0,
+    };
+    let ready = unsafe { libc::poll(&mut pollfd, 1, 0) };
+    ready > 0 && pollfd.revents & (libc::POLLHUP | libc::POLLERR) != 0
+}
+
 fn describe_request(request: &Request) -> String {
     match request {
         Request::CreateInstance { .. } => "CreateInstance".to_string(),
+        Request::CreateStartInstance { pool: true, .. } => "CreateStartInstance(pool)".to_string(),
         Request::CreateStartInstance { .. } => "CreateStartInstance".to_string(),
         Request::StartInstance { id, .. } => format!("StartInstance({})", id),
+        Request::CloneInstance { from, .. } => format!("CloneInstance(from={})", from),
         Request::StopInstance { id } => format!("StopInstance({})", id),
+        Request::RestartInstance { id } => format!("RestartInstance({})", id),
         Request::HoldInstance { id } => format!("HoldInstance({})", id),
+        Request::ReleaseInstance { id } => format!("ReleaseInstance({})", id),
+        Request::SnapshotInstance { id, .. } => format!("SnapshotInstance({})", id),
+        Request::RestoreInstance { id, name, .. } => format!("RestoreInstance({}, {})", id, name),
+        Request::ListSnapshots { id } => format!("ListSnapshots({})", id),
+        Request::DeleteSnapshot { id, name } => format!("DeleteSnapshot({}, {})", id, name),
         Request::DestroyInstance { id, .. } => format!("DestroyInstance({})", id),
         Request::Deploy(req) => format!("Deploy({})", req.id),
         Request::WaitForAdb { id, .. } => format!("WaitForAdb({})", id),
         Request::Logs { id, .. } => format!("Logs({})", id),
+        Request::Shell { id, .. } => format!("Shell({})", id),
+        Request::Push { id, .. } => format!("Push({})", id),
+        Request::Pull { id, .. } => format!("Pull({})", id),
         Request::Status { id } => format!("Status({})", id),
         Request::Describe { id, .. } => format!("Describe({})", id),
-        Request::ListInstances => "ListInstances".to_string(),
+        Request::ListInstances { .. } => "ListInstances".to_string(),
+        Request::RunScenario { scenario } => format!("RunScenario({})", scenario.name),
+        Request::RunBatch { batch } => format!("RunBatch(entries={})", batch.entries.len()),
         Request::PruneExpired { max_age_secs } => {
             format!("PruneExpired(max_age_secs={})", max_age_secs)
         }
         Request::PruneAll => "PruneAll".to_string(),
+        Request::ListRequests => "ListRequests".to_string(),
+        Request::Cancel { request_id } => format!("Cancel({})", request_id),
+        Request::Metrics => "Metrics".to_string(),
+        Request::History(query) if query.compare => "History(compare)".to_string(),
+        Request::History(_) => "History".to_string(),
+        Request::Doctor { fix: true } => "Doctor(fix)".to_string(),
+        Request::Doctor { .. } => "Doctor".to_string(),
     }
 }
@@ -0,0 +1,88 @@
+use tokio::task;
+use tracing::{info, warn};
+
+use crate::protocol::InstanceId;
+
+use super::CfctlDaemon;
+
+impl CfctlDaemon {
+    /// Keeps the warm pool topped up to `pool_size`, refilling whenever
+    /// `wake_pool` is signalled (after a claim, a config reload, and once at
+    /// startup).
+    pub(super) async fn run_pool_worker(self) {
+        let config = self.config.current();
+        info!(
+            target: "cfctl",
+            "pool: keeping {} warm instance(s) (precreate_dirs={}, preassemble={})",
+            config.pool_size,
+            config.pool_precreate_dirs,
+            config.pool_preassemble
+        );
+        loop {
+            self.refill_pool().await;
+            self.pool_wakeup.notified().await;
+        }
+    }
+
+    /// Signals the pool worker to refill; a no-op when the pool is disabled.
+    pub(super) fn wake_pool(&self) {
+        if self.config.current().pool_size > 0 {
+            self.pool_wakeup.notify_one();
+        }
+    }
+
+    /// Creates warm instances one at a time, taking the id lock for each so
+    /// client creates are never blocked behind a full refill. Assembly runs
+    /// after the id lock is released, under the new instance's own lock.
+    async fn refill_pool(&self) {
+        loop {
+            let id_guard = self.id_lock.clone().lock_owned().await;
+            let manager = self.manager.refreshed();
+            let pool_size = self.config.current().pool_size;
+            let result = task::spawn_blocking(move || {
+                let _id_guard = id_guard;
+                if manager.pool_instances()?.len() >= pool_size {
+                    return Ok(None);
+                }
+                manager.create_pool_instance().map(Some)
+            })
+            .await;
+
+            match result {
+                Ok(Ok(Some(id))) => {
+                    info!(target: "cfctl", "pool: created warm instance {}", id);
+                    if self.config.current().pool_preassemble {
+                        self.assemble_pool_instance(id).await;
+                    }
+                }
+                Ok(Ok(None)) => return,
+                Ok(Err(err)) => {
+                    warn!(target: "cfctl", "pool: failed to create warm instance: {:#}", err);
+                    return;
+                }
+                Err(err) => {
+                    warn!(target: "cfctl", "pool: refill task failed: {}", err);
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn assemble_pool_instance(&self, id: InstanceId) {
+        let instance_guard = self.lock_instance(id).await;
+        let manager = self.manager.refreshed();
+        let result = task::spawn_blocking(move || manager.assemble_pool_instance(id)).await;
+        drop(instance_guard);
+        self.cleanup_instance_lock(id);
+        match result {
+            Ok(Ok(())) => info!(target: "cfctl", "pool: assembled warm instance {}", id),
+            Ok(Err(err)) => warn!(
+                target: "cfctl",
+                "pool: warm instance {} will boot cold: {:#}",
+                id,
+                err
+            ),
+            Err(err) => warn!(target: "cfctl", "pool: assembly task failed: {}", err),
+        }
+    }
+}
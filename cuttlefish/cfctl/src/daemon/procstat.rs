@@ -0,0 +1,104 @@
+use std::{collections::HashMap, fs};
+
+use crate::protocol::{InstanceId, ResourceUsage};
+
+/// The fields of `/proc/<pid>/stat` cfctl needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ProcStat {
+    ppid: i32,
+    /// utime + stime, in clock ticks.
+    cpu_ticks: u64,
+    rss_pages: u64,
+}
+
+/// Parses a `/proc/<pid>/stat` line. The command name may contain spaces
+/// and parentheses, so fields are counted from the last `)`.
+fn parse_stat(line: &str) -> Option<ProcStat> {
+    let rest = &line[line.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // fields[0] is field 3 (state) of proc(5).
+    let field = |number: usize| fields.get(number - 3).and_then(|value| value.parse().ok());
+    Some(ProcStat {
+        ppid: field(4)? as i32,
+        cpu_ticks: field(14)? + field(15)?,
+        rss_pages: field(24)?,
+    })
+}
+
+fn all_processes() -> HashMap<i32, ProcStat> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return HashMap::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let pid: i32 = entry.ok()?.file_name().to_str()?.parse().ok()?;
+            let line = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            Some((pid, parse_stat(&line)?))
+        })
+        .collect()
+}
+
+/// CPU and memory of each root process and all its descendants, from one
+/// pass over `/proc`. Roots that no longer exist are left out.
+pub fn tree_usage(roots: &[(InstanceId, i32)]) -> HashMap<InstanceId, ResourceUsage> {
+    if roots.is_empty() {
+        return HashMap::new();
+    }
+    let processes = all_processes();
+    let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (&pid, stat) in &processes {
+        children.entry(stat.ppid).or_default().push(pid);
+    }
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64;
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+
+    let mut usage = HashMap::new();
+    for &(id, root) in roots {
+        if !processes.contains_key(&root) {
+            continue;
+        }
+        let mut total = ResourceUsage {
+            processes: 0,
+            rss_bytes: 0,
+            cpu_time_ms: 0,
+        };
+        let mut pending = vec![root];
+        while let Some(pid) = pending.pop() {
+            let Some(stat) = processes.get(&pid) else {
+                continue;
+            };
+            total.processes += 1;
+            total.rss_bytes += stat.rss_pages * page_size;
+            total.cpu_time_ms += stat.cpu_ticks * 1000 / ticks_per_sec;
+            pending.extend(children.get(&pid).into_iter().flatten());
+        }
+        usage.insert(id, total);
+    }
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stat_lines_are_parsed_after_the_command_name() {
+        let line = "4242 (crosvm (vcpu) 1) S 4200 4242 4200 0 -1 4194560 1000 0 0 0 \
+                    150 50 0 0 20 0 9 0 12345 1073741824 2048 18446744073709551615";
+        assert_eq!(
+            parse_stat(line),
+            Some(ProcStat {
+                ppid: 4200,
+                cpu_ticks: 200,
+                rss_pages: 2048,
+            })
+        );
+        assert_eq!(parse_stat("4242 (truncated"), None);
+
+        let own = std::process::id() as i32;
+        let usage = tree_usage(&[(1, own), (2, i32::MAX)]);
+        assert!(usage[&1].processes >= 1);
+        assert!(usage[&1].rss_bytes > 0);
+        assert!(!usage.contains_key(&2));
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+
+/// Minimal QMP client for the QEMU monitor socket of a running guest.
+pub struct QmpClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl QmpClient {
+    /// Connects to `path`, reads the greeting and negotiates capabilities.
+    /// `timeout` bounds every individual read/write on the socket.
+    pub fn connect(path: &Path, timeout: Duration) -> Result<Self> {
+        let stream = UnixStream::connect(path)
+            .with_context(|| format!("connecting to QEMU monitor {}", path.display()))?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        let writer = stream.try_clone()?;
+        let mut client = Self {
+            reader: BufReader::new(stream),
+            writer,
+        };
+        let greeting = client.read_message()?;
+        if greeting.get("QMP").is_none() {
+            bail!("unexpected QMP greeting: {}", greeting);
+        }
+        client.execute("qmp_capabilities", None)?;
+        Ok(client)
+    }
+
+    /// Executes a QMP command and returns its `return` value, skipping any
+    /// asynchronous events delivered in between.
+    pub fn execute(&mut self, command: &str, arguments: Option<Value>) -> Result<Value> {
+        let mut request = json!({ "execute": command });
+        if let Some(arguments) = arguments {
+            request["arguments"] = arguments;
+        }
+        let mut line = serde_json::to_vec(&request)?;
+        line.push(b'\n');
+        self.writer
+            .write_all(&line)
+            .with_context(|| format!("sending QMP command {}", command))?;
+
+        loop {
+            let message = self.read_message()?;
+            if message.get("event").is_some() {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                let desc = error
+                    .get("desc")
+                    .and_then(Value::as_str)
+                    .unwrap_or("unknown error");
+                bail!("QMP command {} failed: {}", command, desc);
+            }
+            if let Some(value) = message.get("return") {
+                return Ok(value.clone());
+            }
+            bail!("unexpected QMP reply to {}: {}", command, message);
+        }
+    }
+
+    /// Runs a human monitor command such as `savevm <tag>`. HMP reports
+    /// failures as text, so any `Error` line in the output fails the call.
+    pub fn human_command(&mut self, command_line: &str) -> Result<String> {
+        let output = self.execute(
+            "human-monitor-command",
+            Some(json!({ "command-line": command_line })),
+        )?;
+        let output = output.as_str().unwrap_or_default().trim().to_string();
+        if output
+            .lines()
+            .any(|line| line.starts_with("Error") || line.contains("Error:"))
+        {
+            bail!("{}: {}", command_line, output);
+        }
+        Ok(output)
+    }
+
+    fn read_message(&mut self) -> Result<Value> {
+        let mut line = String::new();
+        let bytes = self
+            .reader
+            .read_line(&mut line)
+            .context("reading from QEMU monitor")?;
+        if bytes == 0 {
+            return Err(anyhow!("QEMU monitor closed the connection"));
+        }
+        serde_json::from_str(&line).with_context(|| format!("decoding QMP message {}", line.trim()))
+    }
+}
+
+/// Whether `err` is a monitor read or write that ran out of time. The
+/// command may still be running in QEMU, so its outcome is unknown.
+pub fn is_timeout(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause.downcast_ref::<io::Error>().is_some_and(|err| {
+            matches!(
+                err.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            )
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    fn serve(
+        listener: UnixListener,
+        replies: Vec<&'static str>,
+    ) -> thread::JoinHandle<Vec<String>> {
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            writeln!(
+                writer,
+                r#"{{"QMP": {{"version": {{}}, "capabilities": []}}}}"#
+            )
+            .unwrap();
+            let mut received = Vec::new();
+            for reply in replies {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                received.push(line.trim().to_string());
+                writeln!(writer, "{}", reply).unwrap();
+            }
+            received
+        })
+    }
+
+    #[test]
+    fn human_command_skips_events_and_reports_errors() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let path = temp.path().join("qmp.sock");
+        let listener = UnixListener::bind(&path)?;
+        let server = serve(
+            listener,
+            vec![
+                r#"{"return": {}}"#,
+                "{\"event\": \"STOP\", \"timestamp\": {}}\n{\"return\": \"\"}",
+                r#"{"return": "Error: Device 'vda' is writable but does not support snapshots\r\n"}"#,
+            ],
+        );
+
+        let mut client = QmpClient::connect(&path, Duration::from_secs(5))?;
+        assert_eq!(client.human_command("savevm base")?, "");
+        let err = client.human_command("savevm again").unwrap_err();
+        assert!(err.to_string().contains("does not support snapshots"));
+        drop(client);
+
+        let received = server.join().unwrap();
+        assert!(received[0].contains("qmp_capabilities"));
+        assert!(received[1].contains("savevm base"));
+        Ok(())
+    }
+
+    #[test]
+    fn silent_monitor_times_out() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let path = temp.path().join("qmp.sock");
+        let listener = UnixListener::bind(&path)?;
+        // Answers the handshake, then never replies to savevm.
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            writeln!(writer, r#"{{"QMP": {{}}}}"#).unwrap();
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            writeln!(writer, r#"{{"return": {{}}}}"#).unwrap();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 {}
+        });
+
+        let mut client = QmpClient::connect(&path, Duration::from_millis(200))?;
+        let err = client.human_command("savevm slow").unwrap_err();
+        assert!(is_timeout(&err), "{:#}", err);
+        assert!(!is_timeout(&anyhow!("QEMU monitor closed the connection")));
+        drop(client);
+        server.join().unwrap();
+        Ok(())
+    }
+}
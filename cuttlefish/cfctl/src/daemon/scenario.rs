@@ -0,0 +1,462 @@
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use tracing::{info, warn};
+
+use crate::protocol::{
+    DeployRequest, DestroyOptions, ErrorDetail, InstanceId, LogSource, LogsOptions, Request,
+    Response, Scenario, ScenarioAdbCommand, ScenarioCleanup, ScenarioResult, ScenarioStepResult,
+    ScenarioStepStatus,
+};
+
+use super::cancel::CancelToken;
+use super::CfctlDaemon;
+
+const DEFAULT_MARKER_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_LOG_LINES: usize = 200;
+
+/// Accumulates step results; once a step fails the remaining ones are skipped.
+#[derive(Default)]
+struct ScenarioSteps {
+    steps: Vec<ScenarioStepResult>,
+    failed: bool,
+}
+
+impl ScenarioSteps {
+    fn record(&mut self, name: String, started: Instant, outcome: Result<Option<String>, String>) {
+        let duration_ms = started.elapsed().as_millis() as u64;
+        let step = match outcome {
+            Ok(output) => ScenarioStepResult {
+                name,
+                status: ScenarioStepStatus::Passed,
+                duration_ms,
+                message: None,
+                output,
+            },
+            Err(message) => {
+                self.failed = true;
+                ScenarioStepResult {
+                    name,
+                    status: ScenarioStepStatus::Failed,
+                    duration_ms,
+                    message: Some(message),
+                    output: None,
+                }
+            }
+        };
+        self.steps.push(step);
+    }
+
+    fn skip(&mut self, name: String) {
+        self.steps.push(ScenarioStepResult {
+            name,
+            status: ScenarioStepStatus::Skipped,
+            duration_ms: 0,
+            message: None,
+            output: None,
+        });
+    }
+}
+
+impl CfctlDaemon {
+    /// Runs a scenario end to end by issuing the same requests a client
+    /// would, so every step takes the usual per-instance locks.
+    pub(super) async fn run_scenario(&self, scenario: Scenario, cancel: &CancelToken) -> Response {
+        info!(target: "cfctl", "run_scenario: starting scenario {}", scenario.name);
+        let started = Instant::now();
+        let mut steps = ScenarioSteps::default();
+
+        let step_started = Instant::now();
+        let purpose = scenario
+            .purpose
+            .clone()
+            .or_else(|| Some(format!("scenario:{}", scenario.name)));
+        let id = match self.step(Request::CreateInstance { purpose }, cancel).await {
+            Ok(response) => match response.create {
+                Some(create) => {
+                    let id = create.summary.id;
+                    steps.record("create".to_string(), step_started, Ok(None));
+                    Some(id)
+                }
+                None => {
+                    let outcome = Err("create returned no instance".to_string());
+                    steps.record("create".to_string(), step_started, outcome);
+                    None
+                }
+            },
+            Err(message) => {
+                steps.record("create".to_string(), step_started, Err(message));
+                None
+            }
+        };
+
+        if let Some(id) = id {
+            self.run_scenario_steps(id, &scenario, &mut steps, cancel)
+                .await;
+        }
+
+        let logs = match id {
+            Some(id) => self.collect_scenario_logs(id, &scenario).await,
+            None => None,
+        };
+
+        if let Some(id) = id {
+            let destroy = match scenario.cleanup {
+                ScenarioCleanup::Always => true,
+                ScenarioCleanup::OnSuccess => !steps.failed,
+                ScenarioCleanup::Never => false,
+            };
+            if destroy {
+                let step_started = Instant::now();
+                let options = DestroyOptions {
+                    timeout_secs: scenario.destroy_timeout_secs,
+                };
+                // Cleanup runs to completion even if the client went away.
+                let outcome = self
+                    .step(
+                        Request::DestroyInstance { id, options },
+                        &CancelToken::new(),
+                    )
+                    .await
+                    .map(|_| None);
+                if let Err(err) = &outcome {
+                    warn!(target: "cfctl", "run_scenario: destroy of {} failed: {}", id, err);
+                }
+                steps.record("destroy".to_string(), step_started, outcome);
+            } else {
+                steps.skip("destroy".to_string());
+            }
+        }
+
+        let passed = !steps.failed;
+        let mut result = ScenarioResult {
+            name: scenario.name.clone(),
+            instance_id: id,
+            passed,
+            duration_ms: started.elapsed().as_millis() as u64,
+            steps: steps.steps,
+            logs,
+            junit_xml: String::new(),
+        };
+        result.junit_xml = junit_xml(&result);
+        info!(
+            target: "cfctl",
+            "run_scenario: scenario {} finished (passed={}) in {}ms",
+            result.name,
+            passed,
+            result.duration_ms
+        );
+
+        if passed {
+            Response {
+                scenario: Some(result),
+                ..Response::ok()
+            }
+        } else {
+            let failure = result
+                .steps
+                .iter()
+                .find(|step| step.status == ScenarioStepStatus::Failed)
+                .map(|step| format!("step {} failed", step.name))
+                .unwrap_or_else(|| "scenario failed".to_string());
+            Response {
+                scenario: Some(result),
+                ..Response::error_with_detail(ErrorDetail {
+                    code: "scenario_failed".to_string(),
+                    message: Some(failure),
+                })
+            }
+        }
+    }
+
+    async fn run_scenario_steps(
+        &self,
+        id: InstanceId,
+        scenario: &Scenario,
+        steps: &mut ScenarioSteps,
+        cancel: &CancelToken,
+    ) {
+        if scenario.boot_image.is_some() || scenario.init_boot_image.is_some() {
+            let step_started = Instant::now();
+            let request = Request::Deploy(DeployRequest {
+                id,
+                boot_image: scenario.boot_image.clone(),
+                init_boot_image: scenario.init_boot_image.clone(),
+            });
+            let outcome = self
+                .step(request, cancel)
+                .await
+                .map(|response| response.message);
+            steps.record("deploy".to_string(), step_started, outcome);
+        }
+
+        if steps.failed {
+            steps.skip("start".to_string());
+        } else {
+            let step_started = Instant::now();
+            let request = Request::StartInstance {
+                id,
+                options: scenario.start.clone(),
+            };
+            let outcome = self.step(request, cancel).await.map(|response| {
+                response
+                    .action
+                    .and_then(|action| action.verification)
+                    .and_then(|verification| serde_json::to_string(&verification).ok())
+            });
+            steps.record("start".to_string(), step_started, outcome);
+        }
+
+        let marker_timeout = scenario
+            .marker_timeout_secs
+            .unwrap_or(DEFAULT_MARKER_TIMEOUT_SECS);
+        for (index, marker) in scenario.markers.iter().enumerate() {
+            let name = format!("marker[{}] {}", index, marker.pattern);
+            if steps.failed {
+                steps.skip(name);
+                continue;
+            }
+            let step_started = Instant::now();
+            let timeout = Duration::from_secs(marker.timeout_secs.unwrap_or(marker_timeout));
+            let outcome = self
+                .wait_for_marker(id, marker.pattern.clone(), timeout, cancel)
+                .await;
+            steps.record(name, step_started, outcome.map(Some));
+        }
+
+        for (index, command) in scenario.adb_commands.iter().enumerate() {
+            let name = format!("adb[{}] {}", index, command.command.join(" "));
+            if steps.failed {
+                steps.skip(name);
+                continue;
+            }
+            let step_started = Instant::now();
+            let outcome = self.run_scenario_adb_command(id, command, cancel).await;
+            steps.record(name, step_started, outcome.map(Some));
+        }
+    }
+
+    async fn run_scenario_adb_command(
+        &self,
+        id: InstanceId,
+        command: &ScenarioAdbCommand,
+        cancel: &CancelToken,
+    ) -> Result<String, String> {
+        let request = Request::Shell {
+            id,
+            command: command.command.clone(),
+            timeout_secs: command.timeout_secs,
+        };
+        let output = self
+            .step(request, cancel)
+            .await?
+            .adb_command
+            .ok_or_else(|| "shell returned no output".to_string())?;
+        let expected_exit = command.expect_exit_code.unwrap_or(0);
+        if output.exit_code != Some(expected_exit) {
+            return Err(format!(
+                "expected exit code {} but got {:?}\nstdout:\n{}\nstderr:\n{}",
+                expected_exit, output.exit_code, output.stdout, output.stderr
+            ));
+        }
+        if let Some(pattern) = &command.expect_stdout {
+            let regex = Regex::new(pattern)
+                .map_err(|err| format!("invalid expect_stdout regex {:?}: {}", pattern, err))?;
+            if !regex.is_match(&output.stdout) {
+                return Err(format!(
+                    "stdout did not match /{}/:\n{}",
+                    pattern, output.stdout
+                ));
+            }
+        }
+        Ok(output.stdout)
+    }
+
+    async fn wait_for_marker(
+        &self,
+        id: InstanceId,
+        pattern: String,
+        timeout: Duration,
+        cancel: &CancelToken,
+    ) -> Result<String, String> {
+        // Marker polling only reads logs, so it runs without the instance lock.
+        self.manager
+            .with_cancel(cancel.clone())
+            .wait_for_log_marker(id, &pattern, timeout)
+            .await
+            .map(|source| format!("observed in {}", source))
+            .map_err(|detail| describe_error(&detail))
+    }
+
+    async fn collect_scenario_logs(&self, id: InstanceId, scenario: &Scenario) -> Option<String> {
+        let lines = scenario.log_lines.unwrap_or(DEFAULT_LOG_LINES);
+        let mut collected = String::new();
+        for (source, label) in [
+            (LogSource::Run, "cfctl-run.log"),
+            (LogSource::Console, "console_log"),
+        ] {
+            let request = Request::Logs {
+                id,
+                lines: Some(lines),
+                options: LogsOptions {
+                    source,
+                    ..LogsOptions::default()
+                },
+            };
+            if let Ok(Response {
+                logs: Some(logs), ..
+            }) = self.step(request, &CancelToken::new()).await
+            {
+                collected.push_str(&format!("==> {} <==\n", label));
+                collected.push_str(&logs.content);
+                collected.push('\n');
+            }
+        }
+
+        (!collected.is_empty()).then_some(collected)
+    }
+
+    /// Dispatches one scenario step, flattening failures into a message.
+    async fn step(&self, request: Request, cancel: &CancelToken) -> Result<Response, String> {
+        self.dispatch_checked(request, cancel)
+            .await
+            .map_err(|detail| describe_error(&detail))
+    }
+}
+
+fn describe_error(detail: &ErrorDetail) -> String {
+    match &detail.message {
+        Some(message) => format!("{}: {}", detail.code, message),
+        None => detail.code.clone(),
+    }
+}
+
+fn junit_xml(result: &ScenarioResult) -> String {
+    let failures = result
+        .steps
+        .iter()
+        .filter(|step| step.status == ScenarioStepStatus::Failed)
+        .count();
+    let skipped = result
+        .steps
+        .iter()
+        .filter(|step| step.status == ScenarioStepStatus::Skipped)
+        .count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+        xml_escape(&result.name),
+        result.steps.len(),
+        failures,
+        skipped,
+        result.duration_ms as f64 / 1000.0
+    ));
+    for step in &result.steps {
+        xml.push_str(&format!(
+            "  <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+            xml_escape(&result.name),
+            xml_escape(&step.name),
+            step.duration_ms as f64 / 1000.0
+        ));
+        match step.status {
+            ScenarioStepStatus::Passed if step.output.is_none() => xml.push_str("/>\n"),
+            ScenarioStepStatus::Passed => {
+                xml.push_str(">\n");
+                xml.push_str(&format!(
+                    "    <system-out>{}</system-out>\n",
+                    xml_escape(step.output.as_deref().unwrap_or_default())
+                ));
+                xml.push_str("  </testcase>\n");
+            }
+            ScenarioStepStatus::Failed => {
+                let message = step.message.as_deref().unwrap_or("failed");
+                let summary = message.lines().next().unwrap_or_default();
+                xml.push_str(">\n");
+                xml.push_str(&format!(
+                    "    <failure message=\"{}\">{}</failure>\n",
+                    xml_escape(summary),
+                    xml_escape(message)
+                ));
+                xml.push_str("  </testcase>\n");
+            }
+            ScenarioStepStatus::Skipped => xml.push_str(">\n    <skipped/>\n  </testcase>\n"),
+        }
+    }
+    if let Some(logs) = &result.logs {
+        xml.push_str(&format!(
+            "  <system-out>{}</system-out>\n",
+            xml_escape(logs)
+        ));
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\t' | '\r' => escaped.push(ch),
+            ch if (ch as u32) < 0x20 => {}
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_scenario_renders_junit_with_failures() {
+        let scenario: Scenario = toml::from_str(
+            r#"
+            name = "heartbeat"
+            init_boot_image = "/tmp/init_boot.img"
+            cleanup = "on_success"
+
+            [start]
+            disable_webrtc = true
+            readiness = { kind = "console_regex", pattern = "heartbeat" }
+
+            [[markers]]
+            pattern = "VIRTUAL_DEVICE_BOOT_COMPLETED"
+            timeout_secs = 30
+            "#,
+        )
+        .expect("scenario should parse");
+        assert_eq!(scenario.cleanup, ScenarioCleanup::OnSuccess);
+        assert_eq!(scenario.markers[0].timeout_secs, Some(30));
+        assert!(scenario.start.disable_webrtc);
+
+        let mut steps = ScenarioSteps::default();
+        steps.record("create".to_string(), Instant::now(), Ok(None));
+        steps.record(
+            "marker[0] <boot>".to_string(),
+            Instant::now(),
+            Err("marker_timeout: not seen".to_string()),
+        );
+        steps.skip("adb[0] true".to_string());
+        let result = ScenarioResult {
+            name: scenario.name,
+            instance_id: Some(3),
+            passed: !steps.failed,
+            duration_ms: 1500,
+            steps: steps.steps,
+            logs: Some("a & b".to_string()),
+            junit_xml: String::new(),
+        };
+        let xml = junit_xml(&result);
+        assert!(xml.contains(r#"tests="3" failures="1" skipped="1" time="1.500""#));
+        assert!(xml.contains(r#"name="marker[0] &lt;boot&gt;""#));
+        assert!(xml.contains(r#"<failure message="marker_timeout: not seen">"#));
+        assert!(xml.contains("<system-out>a &amp; b</system-out>"));
+        assert!(!result.passed);
+    }
+}
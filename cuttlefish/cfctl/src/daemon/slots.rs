@@ -0,0 +1,116 @@
+use std::{net::TcpListener, path::Path};
+
+use anyhow::{anyhow, Result};
+use tracing::debug;
+
+use crate::protocol::InstanceId;
+
+use super::config::CfctlDaemonConfig;
+
+/// Cuttlefish instance numbers are two digits (tap names use `{:02}`).
+pub const MAX_INSTANCE_NUM: InstanceId = 99;
+
+/// Host resources owned by one instance number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub id: InstanceId,
+    pub adb_port: u16,
+}
+
+/// Picks the lowest instance number whose state dir, ADB port and tap
+/// devices are all free. Callers serialize allocation (id lock plus the
+/// allocation file lock) and reserve the slot before releasing it.
+pub struct SlotAllocator<'a> {
+    config: &'a CfctlDaemonConfig,
+}
+
+impl<'a> SlotAllocator<'a> {
+    pub fn new(config: &'a CfctlDaemonConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn adb_port(&self, id: InstanceId) -> Result<u16> {
+        u16::try_from(id)
+            .ok()
+            .and_then(|id| self.config.base_adb_port.checked_add(id.checked_sub(1)?))
+            .ok_or_else(|| anyhow!("instance {} has no valid adb port", id))
+    }
+
+    /// Returns the first slot not `reserved` (known to cfctl) and not in use
+    /// on the host.
+    pub fn allocate(&self, reserved: impl Fn(InstanceId) -> bool) -> Result<Slot> {
+        let mut reserved_count = 0;
+        let mut conflicts = Vec::new();
+        for id in 1..=MAX_INSTANCE_NUM {
+            if reserved(id) {
+                reserved_count += 1;
+                continue;
+            }
+            let adb_port = self.adb_port(id)?;
+            if !self.port_free(adb_port) {
+                debug!(target: "cfctl", "allocate: skipping slot {}: port {} in use", id, adb_port);
+                conflicts.push(format!("{}: port {}", id, adb_port));
+                continue;
+            }
+            if let Some(tap) = tap_names(id).into_iter().find(|tap| tap_present(tap)) {
+                debug!(target: "cfctl", "allocate: skipping slot {}: tap {} exists", id, tap);
+                conflicts.push(format!("{}: tap {}", id, tap));
+                continue;
+            }
+            return Ok(Slot { id, adb_port });
+        }
+        Err(anyhow!(
+            "no free instance slot (1-{}): {} reserved, host conflicts: [{}]",
+            MAX_INSTANCE_NUM,
+            reserved_count,
+            conflicts.join(", ")
+        ))
+    }
+
+    fn port_free(&self, port: u16) -> bool {
+        [self.config.adb_host.as_str(), "0.0.0.0"]
+            .iter()
+            .all(|host| TcpListener::bind((*host, port)).is_ok())
+    }
+}
+
+/// Tap devices cuttlefish uses for instance `id`.
+pub fn tap_names(id: InstanceId) -> [String; 2] {
+    let inst_padded = format!("{:02}", id);
+    [
+        format!("cvd-mtap-{}", inst_padded),
+        format!("cvd-tap-{}", inst_padded),
+    ]
+}
+
+pub fn tap_present(name: &str) -> bool {
+    Path::new("/sys/class/net").join(name).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocates_lowest_free_slot_and_skips_busy_ports() -> Result<()> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        let busy_port = listener.local_addr()?.port();
+        let config = CfctlDaemonConfig {
+            adb_host: "127.0.0.1".to_string(),
+            base_adb_port: busy_port,
+            ..CfctlDaemonConfig::default()
+        };
+        let allocator = SlotAllocator::new(&config);
+
+        // Slot 1 maps to the bound port, so the next number is chosen.
+        let slot = allocator.allocate(|_| false)?;
+        assert_eq!(slot.id, 2);
+        assert_eq!(slot.adb_port, busy_port + 1);
+
+        drop(listener);
+        assert_eq!(allocator.allocate(|_| false)?.id, 1);
+        assert_eq!(allocator.allocate(|id| id <= 3)?.id, 4);
+        assert!(allocator.allocate(|_| true).is_err());
+        Ok(())
+    }
+}
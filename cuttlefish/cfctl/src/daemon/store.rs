@@ -0,0 +1,409 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::protocol::{
+    AdbInfo, InstanceId, InstanceState, InstanceSummary, LaunchCommand, StartOptions,
+};
+
+use super::config::CfctlDaemonConfig;
+use super::slots::SlotAllocator;
+use super::util::epoch_secs;
+
+pub const METADATA_FILE: &str = "metadata.json";
+
+/// Version written into every metadata file. Bump it together with a new
+/// entry in [`MIGRATIONS`].
+pub const SCHEMA_VERSION: u64 = 2;
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` document to version `n + 2`.
+const MIGRATIONS: &[fn(&mut Value, &MigrationContext) -> Result<()>] = &[migrate_v1_to_v2];
+
+/// Distinguishes the temp files of concurrent saves of the same instance.
+static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceMetadata {
+    pub id: InstanceId,
+    #[serde(default)]
+    pub purpose: Option<String>,
+    pub adb_port: u16,
+    #[serde(default)]
+    pub state: InstanceState,
+    pub boot_image: PathBuf,
+    pub init_boot_image: PathBuf,
+    pub created_at: u64,
+    pub updated_at: u64,
+    #[serde(default)]
+    pub held: bool,
+    /// Warm instance owned by the daemon pool, not yet claimed by a client.
+    #[serde(default)]
+    pub pooled: bool,
+    /// Host instance/assembly directories were prepared ahead of the next start.
+    #[serde(default)]
+    pub host_prepared: bool,
+    /// The pool assembled the instance's disks with the default images, so
+    /// the next start can resume them.
+    #[serde(default)]
+    pub host_assembled: bool,
+    /// Options of the most recent start, reused by `CloneInstance`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_options: Option<StartOptions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launch: Option<LaunchCommand>,
+}
+
+impl InstanceMetadata {
+    pub fn summary(&self, host: &str) -> InstanceSummary {
+        InstanceSummary {
+            id: self.id,
+            adb: Some(AdbInfo {
+                host: host.to_string(),
+                port: self.adb_port,
+                serial: format!("{host}:{}", self.adb_port),
+            }),
+            state: self.state.clone(),
+            purpose: self.purpose.clone(),
+            created_at: Some(self.created_at),
+            held: self.held,
+            resources: None,
+            error: None,
+        }
+    }
+}
+
+/// Why an instance's metadata could not be loaded.
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("metadata file {} does not exist", .0.display())]
+    Missing(PathBuf),
+    /// The file could not be parsed or migrated and was moved aside.
+    #[error("metadata file {} is corrupt ({reason}){}", path.display(), quarantine_note(.quarantined))]
+    Corrupt {
+        path: PathBuf,
+        reason: String,
+        quarantined: Option<PathBuf>,
+    },
+    /// Written by a newer cfctl; left in place untouched.
+    #[error("metadata file {} has schema version {version}, newer than the supported {SCHEMA_VERSION}", path.display())]
+    UnsupportedVersion { path: PathBuf, version: u64 },
+    #[error("reading metadata file {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+}
+
+fn quarantine_note(quarantined: &Option<PathBuf>) -> String {
+    match quarantined {
+        Some(path) => format!("; moved to {}", path.display()),
+        None => String::new(),
+    }
+}
+
+/// Values migrations need that older files did not record.
+struct MigrationContext {
+    id: InstanceId,
+    default_adb_port: Option<u16>,
+}
+
+/// Reads and writes `<state_dir>/instances/<id>/metadata.json`. Writes are
+/// atomic and durable (write to a temp file, fsync, rename, fsync the
+/// directory); reads migrate older schema versions forward and quarantine
+/// files that cannot be understood.
+pub struct MetadataStore<'a> {
+    config: &'a CfctlDaemonConfig,
+}
+
+impl<'a> MetadataStore<'a> {
+    pub fn new(config: &'a CfctlDaemonConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn instances_dir(&self) -> PathBuf {
+        self.config.state_dir.join("instances")
+    }
+
+    pub fn path(&self, id: InstanceId) -> PathBuf {
+        self.instances_dir()
+            .join(id.to_string())
+            .join(METADATA_FILE)
+    }
+
+    pub fn load(&self, id: InstanceId) -> Result<InstanceMetadata, LoadError> {
+        let path = self.path(id);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(LoadError::Missing(path))
+            }
+            Err(source) => return Err(LoadError::Io { path, source }),
+        };
+        let mut document: Value = match serde_json::from_slice(&bytes) {
+            Ok(document) => document,
+            Err(err) => return Err(self.quarantine(path, format!("invalid JSON: {err}"))),
+        };
+        let version = match document.get("schema_version") {
+            None => 1,
+            Some(version) => match version.as_u64() {
+                Some(version) if version >= 1 => version,
+                _ => return Err(self.quarantine(path, format!("invalid schema_version {version}"))),
+            },
+        };
+        if version > SCHEMA_VERSION {
+            return Err(LoadError::UnsupportedVersion { path, version });
+        }
+
+        let context = MigrationContext {
+            id,
+            default_adb_port: SlotAllocator::new(self.config).adb_port(id).ok(),
+        };
+        for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+            if let Err(err) = migration(&mut document, &context) {
+                let reason = format!("migrating from schema version {}: {err:#}", from + 1);
+                return Err(self.quarantine(path, reason));
+            }
+        }
+        let metadata: InstanceMetadata = match serde_json::from_value(document) {
+            Ok(metadata) => metadata,
+            Err(err) => return Err(self.quarantine(path, err.to_string())),
+        };
+        if metadata.id != id {
+            let reason = format!("records instance {} instead of {}", metadata.id, id);
+            return Err(self.quarantine(path, reason));
+        }
+
+        if version < SCHEMA_VERSION {
+            info!(
+                target: "cfctl",
+                "metadata_store: migrated instance {} from schema version {} to {}",
+                id,
+                version,
+                SCHEMA_VERSION
+            );
+            if let Err(err) = self.save(&metadata) {
+                warn!(
+                    target: "cfctl",
+                    "metadata_store: rewriting migrated metadata for {} failed: {:#}",
+                    id,
+                    err
+                );
+            }
+        }
+        Ok(metadata)
+    }
+
+    pub fn save(&self, metadata: &InstanceMetadata) -> Result<()> {
+        let path = self.path(metadata.id);
+        let dir = path
+            .parent()
+            .ok_or_else(|| anyhow!("metadata path {} has no parent", path.display()))?;
+        fs::create_dir_all(dir)?;
+
+        let mut document = serde_json::to_value(metadata)?;
+        document
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("metadata did not serialize to an object"))?
+            .insert("schema_version".to_string(), SCHEMA_VERSION.into());
+        let contents = serde_json::to_vec_pretty(&document)?;
+        // Each save gets its own temp file: two writers sharing one would
+        // rename it out from under each other.
+        let tmp = path.with_extension(format!(
+            "json.tmp.{}",
+            NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Err(err) = write_synced(&tmp, &contents).and_then(|()| {
+            fs::rename(&tmp, &path).with_context(|| format!("renaming metadata {}", path.display()))
+        }) {
+            // The name is unique to this save, so nothing else would reuse it.
+            let _ = fs::remove_file(&tmp);
+            return Err(err);
+        }
+        sync_dir(dir)
+    }
+
+    /// Moves an unreadable metadata file aside so it is kept for inspection
+    /// but no longer read.
+    fn quarantine(&self, path: PathBuf, reason: String) -> LoadError {
+        let stamp = epoch_secs().unwrap_or_default();
+        let target = path.with_extension(format!("json.corrupt-{stamp}"));
+        let quarantined = match fs::rename(&path, &target) {
+            Ok(()) => {
+                warn!(
+                    target: "cfctl",
+                    "metadata_store: quarantined {} as {}: {}",
+                    path.display(),
+                    target.display(),
+                    reason
+                );
+                Some(target)
+            }
+            Err(err) => {
+                warn!(
+                    target: "cfctl",
+                    "metadata_store: could not quarantine {} ({}): {}",
+                    path.display(),
+                    reason,
+                    err
+                );
+                None
+            }
+        };
+        LoadError::Corrupt {
+            path,
+            reason,
+            quarantined,
+        }
+    }
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut file =
+        File::create(path).with_context(|| format!("creating metadata tmp {}", path.display()))?;
+    file.write_all(contents)
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("writing metadata tmp {}", path.display()))
+}
+
+fn sync_dir(dir: &Path) -> Result<()> {
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .with_context(|| format!("syncing directory {}", dir.display()))
+}
+
+/// Version 1 files predate versioning and leaned on `#[serde(default)]`, so
+/// a missing ADB port read back as 0 and missing timestamps as the epoch.
+fn migrate_v1_to_v2(document: &mut Value, context: &MigrationContext) -> Result<()> {
+    let object = document
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("metadata is not a JSON object"))?;
+    if object.get("adb_port").and_then(Value::as_u64).unwrap_or(0) == 0 {
+        let port = context
+            .default_adb_port
+            .ok_or_else(|| anyhow!("instance {} has no adb port to fill in", context.id))?;
+        object.insert("adb_port".to_string(), port.into());
+    }
+    let updated_at = object
+        .get("updated_at")
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    if object
+        .get("created_at")
+        .and_then(Value::as_u64)
+        .unwrap_or(0)
+        == 0
+    {
+        object.insert("created_at".to_string(), updated_at.into());
+    }
+    object.entry("updated_at").or_insert(updated_at.into());
+    for image in ["boot_image", "init_boot_image"] {
+        object.entry(image).or_insert("".into());
+    }
+    object.insert("schema_version".to_string(), 2.into());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_config(root: &Path) -> CfctlDaemonConfig {
+        CfctlDaemonConfig {
+            state_dir: root.to_path_buf(),
+            base_adb_port: 6520,
+            ..CfctlDaemonConfig::default()
+        }
+    }
+
+    #[test]
+    fn migrates_legacy_files_and_quarantines_corrupt_ones() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let config = store_config(temp.path());
+        let store = MetadataStore::new(&config);
+
+        // A pre-versioning file without an ADB port is upgraded in place.
+        fs::create_dir_all(store.path(3).parent().unwrap())?;
+        fs::write(
+            store.path(3),
+            r#"{"id": 3, "state": "running", "updated_at": 1700000000}"#,
+        )?;
+        let metadata = store.load(3).expect("legacy metadata migrates");
+        assert_eq!(metadata.adb_port, 6522);
+        assert_eq!(metadata.created_at, 1_700_000_000);
+        assert_eq!(metadata.state, InstanceState::Running);
+        let rewritten: Value = serde_json::from_slice(&fs::read(store.path(3))?)?;
+        assert_eq!(rewritten["schema_version"], SCHEMA_VERSION);
+
+        fs::create_dir_all(store.path(4).parent().unwrap())?;
+        fs::write(store.path(4), b"{\"id\": 4, \"sta")?;
+        let err = store.load(4).unwrap_err();
+        let LoadError::Corrupt {
+            quarantined: Some(quarantined),
+            ..
+        } = &err
+        else {
+            panic!("expected quarantine, got {err:?}");
+        };
+        assert!(quarantined.exists());
+        assert!(!store.path(4).exists());
+
+        fs::create_dir_all(store.path(5).parent().unwrap())?;
+        fs::write(store.path(5), br#"{"id": 5, "schema_version": 99}"#)?;
+        assert!(matches!(
+            store.load(5),
+            Err(LoadError::UnsupportedVersion { version: 99, .. })
+        ));
+        assert!(store.path(5).exists(), "newer files are left alone");
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_writes_versioned_file_without_leftovers() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let config = store_config(temp.path());
+        let store = MetadataStore::new(&config);
+        let metadata = InstanceMetadata {
+            id: 1,
+            purpose: Some("test".to_string()),
+            adb_port: 6520,
+            state: InstanceState::Created,
+            boot_image: PathBuf::from("boot.img"),
+            init_boot_image: PathBuf::from("init_boot.img"),
+            created_at: 1,
+            updated_at: 2,
+            held: false,
+            pooled: false,
+            host_prepared: false,
+            host_assembled: false,
+            start_options: None,
+            launch: None,
+        };
+        store.save(&metadata)?;
+        let names: Vec<String> = fs::read_dir(store.path(1).parent().unwrap())?
+            .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string()))
+            .collect::<io::Result<_>>()?;
+        assert_eq!(names, vec![METADATA_FILE.to_string()]);
+        let loaded = store.load(1).expect("saved metadata loads");
+        assert_eq!(loaded.purpose.as_deref(), Some("test"));
+        assert_eq!(loaded.updated_at, 2);
+
+        // A failed rename (onto a directory here) removes the temp file.
+        let blocked = store.path(2);
+        fs::create_dir_all(blocked.join("occupied"))?;
+        store
+            .save(&InstanceMetadata { id: 2, ..metadata })
+            .expect_err("renaming onto a directory fails");
+        let names: Vec<String> = fs::read_dir(blocked.parent().unwrap())?
+            .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string()))
+            .collect::<io::Result<_>>()?;
+        assert_eq!(names, vec![METADATA_FILE.to_string()]);
+        Ok(())
+    }
+}
@@ -1,42 +1,25 @@
 use std::{
-    fs::File,
-    io::{Read, Seek, SeekFrom},
+    io::Read,
+    os::unix::process::CommandExt,
     path::Path,
-    process::Command,
-    time::Instant,
+    process::{Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Context, Result};
 use tracing::{debug, error, trace};
 
-pub fn tail_file(path: &Path, lines: usize) -> Result<String> {
-    const MAX_TAIL_BYTES: u64 = 1 * 1024 * 1024; // 1 MiB cap per request
-
-    let mut file =
-        File::open(path).with_context(|| format!("opening log file {}", path.display()))?;
-    let metadata = file
-        .metadata()
-        .with_context(|| format!("stat log file {}", path.display()))?;
-    let len = metadata.len();
-    if len > MAX_TAIL_BYTES {
-        file.seek(SeekFrom::Start(len - MAX_TAIL_BYTES))
-            .with_context(|| format!("seek tail for {}", path.display()))?;
-    } else {
-        file.seek(SeekFrom::Start(0))?;
-    }
-
-    let mut buf = String::new();
-    file.read_to_string(&mut buf)?;
+use super::logrotate;
 
-    // If we started mid-line, drop the first incomplete chunk.
-    if len > MAX_TAIL_BYTES {
-        if let Some(pos) = buf.find('\n') {
-            buf = buf[pos + 1..].to_string();
-        } else {
-            buf.clear();
-        }
-    }
+/// The last `lines` lines of a log, reading into its rotated copies when
+/// the current file is shorter (at most 1 MiB in all).
+pub fn tail_file(path: &Path, lines: usize) -> Result<String> {
+    const MAX_TAIL_BYTES: usize = 1024 * 1024; // 1 MiB cap per request
 
+    let (buf, _) = logrotate::tail_bytes(path, MAX_TAIL_BYTES)
+        .with_context(|| format!("reading log file {}", path.display()))?;
+    let buf = String::from_utf8_lossy(&buf);
     let mut collected: Vec<&str> = buf.lines().collect();
     if collected.len() > lines {
         collected = collected[collected.len() - lines..].to_vec();
@@ -85,6 +68,123 @@ pub fn run_command_allow_failure(cmd: &str, args: &[&str]) -> Result<()> {
     }
 }
 
+/// Run `cmd` with captured stdout/stderr, killing its process group if it
+/// outlives `timeout`. Returns `Ok(None)` when the command was killed. The
+/// command is also killed if the returned future is dropped.
+pub async fn run_command_timeout(
+    cmd: &mut tokio::process::Command,
+    timeout: Duration,
+) -> Result<Option<Output>> {
+    let child = cmd
+        .process_group(0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("launching {:?}", cmd))?;
+    let pgid = child.id();
+    match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(output) => Ok(Some(output?)),
+        Err(_) => {
+            debug!(target: "cfctl", "run_command_timeout: killing {:?} after {:?}", cmd, timeout);
+            if let Some(pgid) = pgid {
+                unsafe {
+                    libc::kill(-(pgid as libc::pid_t), libc::SIGKILL);
+                }
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Blocking [`run_command_timeout`] for cleanup paths that already run on
+/// the blocking pool.
+pub fn run_command_timeout_blocking(
+    cmd: &mut Command,
+    timeout: Duration,
+) -> Result<Option<Output>> {
+    let mut child = cmd
+        .process_group(0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("launching {:?}", cmd))?;
+
+    let mut stdout_pipe = child.stdout.take();
+    let mut stderr_pipe = child.stderr.take();
+    let stdout_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(pipe) = stdout_pipe.as_mut() {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    });
+    let stderr_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(pipe) = stderr_pipe.as_mut() {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if Instant::now() >= deadline {
+            debug!(target: "cfctl", "run_command_timeout_blocking: killing {:?} after {:?}", cmd, timeout);
+            unsafe {
+                libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+            }
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+
+    let stdout = stdout_reader.join().unwrap_or_default();
+    let stderr = stderr_reader.join().unwrap_or_default();
+    Ok(status.map(|status| Output {
+        status,
+        stdout,
+        stderr,
+    }))
+}
+
+/// Number of guests of the given size that fit on this host, based on the
+/// available CPUs and `MemAvailable`. Always at least one.
+pub fn host_guest_capacity(cpus_per_guest: usize, memory_mib_per_guest: u64) -> usize {
+    let cpus = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let by_cpu = cpus / cpus_per_guest.max(1);
+    let by_memory = std::fs::read_to_string("/proc/meminfo")
+        .ok()
+        .and_then(|meminfo| {
+            meminfo
+                .lines()
+                .find(|line| line.starts_with("MemAvailable:"))
+                .and_then(|line| line.split_whitespace().nth(1))
+                .and_then(|kib| kib.parse::<u64>().ok())
+        })
+        .map(|kib| (kib / 1024 / memory_mib_per_guest.max(1)) as usize)
+        .unwrap_or(by_cpu);
+    by_cpu.min(by_memory).max(1)
+}
+
+/// Quotes `arg` for a POSIX shell, leaving plain words as they are.
+pub fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
 pub fn epoch_secs() -> Result<u64> {
     Ok(std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)?
@@ -1,10 +1,18 @@
+pub mod client;
 mod daemon;
 mod protocol;
 
 pub use daemon::{CfctlDaemon, CfctlDaemonConfig};
 pub use protocol::{
-    AdbInfo, BootVerificationResult, CleanupSummary, CreateInstanceResponse, DeployRequest,
-    DestroyOptions, ErrorDetail, InstanceActionResponse, InstanceId, InstanceState,
-    InstanceSummary, LogsOptions, LogsResponse, Request, Response, StartOptions,
+    AdbCommandResponse, AdbInfo, BackendSpec, BatchEntry, BatchEntryResult, BatchRequest,
+    BatchResult, BatchTimelineEvent, BootVerificationResult, CheckStatus, CleanupSummary,
+    CreateInstanceResponse, DeployRequest, DestroyOptions, DoctorCheck, ErrorDetail, HistoryQuery,
+    HistoryResponse, ImageDigest, InFlightRequest, InstanceActionResponse, InstanceId,
+    InstanceState, InstanceSummary, LaunchCommand, LogFileUsage, LogSource, LogUsage,
+    LogcatCaptureStatus, LogsOptions, LogsResponse, NetworkDeviceStatus, NetworkMode,
+    NetworkStatus, PortForward, QemuOptions, ReadinessProbeSpec, Request, ResourceUsage, Response,
+    RunOutcome, RunRecord, Scenario, ScenarioAdbCommand, ScenarioCleanup, ScenarioMarker,
+    ScenarioResult, ScenarioStepResult, ScenarioStepStatus, SnapshotInfo, StartOptions,
+    TimingStats, VariantStats,
 };
 // Force rebuild for track support
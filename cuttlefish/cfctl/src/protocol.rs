@@ -1,3 +1,5 @@
+use std::{collections::BTreeMap, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 
 pub type InstanceId = u64;
@@ -7,6 +9,28 @@ pub struct InstanceSummary {
     pub id: InstanceId,
     pub adb: Option<AdbInfo>,
     pub state: InstanceState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<String>,
+    /// Unix time the instance was created; absent when unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    #[serde(default)]
+    pub held: bool,
+    /// Usage of the running guest; absent when no guest process is tracked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourceUsage>,
+    /// Set when the instance's metadata could not be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// CPU and memory of an instance's launcher and its descendant processes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub processes: usize,
+    pub rss_bytes: u64,
+    /// User plus system CPU time used so far; compare two samples for a rate.
+    pub cpu_time_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +53,20 @@ pub enum InstanceState {
     Destroyed,
 }
 
+impl InstanceState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Unknown => "unknown",
+            Self::Created => "created",
+            Self::Starting => "starting",
+            Self::Running => "running",
+            Self::Stopped => "stopped",
+            Self::Failed => "failed",
+            Self::Destroyed => "destroyed",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateInstanceResponse {
     pub summary: InstanceSummary,
@@ -47,14 +85,108 @@ pub struct InstanceActionResponse {
     pub run_log_tail: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub console_snapshot_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_options: Option<StartOptions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launch: Option<LaunchCommand>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<NetworkStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logcat: Option<LogcatCaptureStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_usage: Option<LogUsage>,
+    /// Milliseconds from launch until adb answered, set by starts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adb_ms: Option<u64>,
+    /// Milliseconds from launch until the boot marker was seen, set by starts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marker_ms: Option<u64>,
+}
+
+/// The daemon's continuous `adb logcat` capture for an instance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogcatCaptureStatus {
+    pub path: String,
+    /// The capture keeps running (and reconnecting) until the guest exits.
+    pub active: bool,
+    /// `adb logcat` is currently streaming.
+    pub connected: bool,
+    pub started_at: u64,
+    pub bytes_written: u64,
+    pub reconnects: u32,
+    pub rotations: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Disk used by an instance's run, console and logcat logs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogUsage {
+    /// Per-instance cap including rotated copies; `None` when unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota_bytes: Option<u64>,
+    pub used_bytes: u64,
+    pub files: Vec<LogFileUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogFileUsage {
+    pub source: LogSource,
+    pub path: String,
+    pub bytes: u64,
+    /// Rotated copies (`<path>.N.gz`) still on disk.
+    pub segments: usize,
+    pub segment_bytes: u64,
+}
+
+/// The exact launcher invocation used for the most recent start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LaunchCommand {
+    pub argv: Vec<String>,
+    /// Variables set explicitly on top of the daemon's environment.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    pub launched_at: u64,
+}
+
+impl InstanceActionResponse {
+    pub fn new(summary: InstanceSummary) -> Self {
+        Self {
+            summary,
+            journal_tail: None,
+            verification: None,
+            cleanup: None,
+            run_log_tail: None,
+            console_snapshot_path: None,
+            start_options: None,
+            launch: None,
+            network: None,
+            logcat: None,
+            log_usage: None,
+            adb_ms: None,
+            marker_ms: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogsResponse {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub journal: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub console_log_path: Option<String>,
+    pub source: LogSource,
+    /// Selected lines, joined with newlines.
+    pub content: String,
+    /// Host file the content was read from; absent for a live logcat dump.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Size of the source when it was read.
+    pub size_bytes: u64,
+    /// Byte offset just past the last line read; pass it as `from_byte` to
+    /// continue from there.
+    pub next_byte: u64,
+    /// The selection hit the per-request size cap and was cut short.
+    #[serde(default)]
+    pub truncated: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,6 +198,60 @@ pub struct DeployRequest {
     pub init_boot_image: Option<String>,
 }
 
+/// Signal used to decide that a freshly launched guest is ready.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReadinessProbeSpec {
+    /// `adb connect` succeeds and the device is listed as `device`.
+    Adb,
+    /// A line of the guest console log matches `pattern`.
+    ConsoleRegex { pattern: String },
+    /// A TCP connection to `host:port` succeeds (host defaults to the adb host).
+    TcpPort {
+        port: u16,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        host: Option<String>,
+    },
+    /// The launcher process is still alive after `secs` seconds.
+    ProcessAlive { secs: u64 },
+}
+
+impl FromStr for ReadinessProbeSpec {
+    type Err = String;
+
+    /// Parses `adb`, `console:<regex>`, `tcp:<port>`, `tcp:<host>:<port>` or `alive:<secs>`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = match value.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (value, None),
+        };
+        match (kind, arg) {
+            ("adb", None) => Ok(Self::Adb),
+            ("console", Some(pattern)) if !pattern.is_empty() => Ok(Self::ConsoleRegex {
+                pattern: pattern.to_string(),
+            }),
+            ("tcp", Some(arg)) => {
+                let (host, port) = match arg.rsplit_once(':') {
+                    Some((host, port)) => (Some(host.to_string()), port),
+                    None => (None, arg),
+                };
+                let port = port
+                    .parse()
+                    .map_err(|err| format!("invalid tcp port {:?}: {}", port, err))?;
+                Ok(Self::TcpPort { port, host })
+            }
+            ("alive", Some(secs)) => secs
+                .parse()
+                .map(|secs| Self::ProcessAlive { secs })
+                .map_err(|err| format!("invalid alive seconds {:?}: {}", secs, err)),
+            _ => Err(format!(
+                "unknown readiness probe {:?} (expected adb, console:<regex>, tcp:<port> or alive:<secs>)",
+                value
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct StartOptions {
     #[serde(default)]
@@ -78,6 +264,141 @@ pub struct StartOptions {
     pub skip_adb_wait: bool,
     #[serde(default)]
     pub track: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readiness: Option<ReadinessProbeSpec>,
+    /// Keep the existing disk overlay (and its snapshots) instead of a fresh one.
+    #[serde(default)]
+    pub resume: bool,
+    #[serde(default)]
+    pub network: NetworkMode,
+    /// Host-to-guest TCP forwards set up (via adb) once the guest is ready.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub port_forwards: Vec<PortForward>,
+    #[serde(default)]
+    pub backend: BackendSpec,
+}
+
+/// VM launcher used to run the guest.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BackendSpec {
+    /// `launch_cvd` with the instance's boot and init_boot images.
+    #[default]
+    Cuttlefish,
+    /// Plain QEMU booting a kernel and initramfs directly (as `qemu-init/run.sh` does).
+    Qemu(QemuOptions),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QemuOptions {
+    /// Kernel image path on the daemon host.
+    pub kernel: String,
+    /// Initramfs path on the daemon host.
+    pub initramfs: String,
+    /// Extra kernel command line, appended after `console=ttyS0 init=/init panic=1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub append: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_mib: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<u32>,
+    /// QEMU binary, defaults to `qemu-system-x86_64` from the daemon's PATH.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary: Option<String>,
+    /// Extra arguments passed to QEMU verbatim.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkMode {
+    /// Use whatever tap devices the host's cuttlefish networking provides.
+    #[default]
+    Launcher,
+    /// cfctl creates the instance taps itself, unbridged, so the guest has no
+    /// network path to the host or outside world beyond adb.
+    Isolated,
+}
+
+impl FromStr for NetworkMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "launcher" => Ok(Self::Launcher),
+            "isolated" => Ok(Self::Isolated),
+            other => Err(format!(
+                "unknown network mode {:?} (expected launcher or isolated)",
+                other
+            )),
+        }
+    }
+}
+
+/// Forwards `host_port` on the host to `guest_port` inside the guest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortForward {
+    pub host_port: u16,
+    pub guest_port: u16,
+}
+
+impl FromStr for PortForward {
+    type Err = String;
+
+    /// Parses `HOST:GUEST`, or a single port used on both sides.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let parse = |port: &str| {
+            port.parse::<u16>()
+                .map_err(|err| format!("invalid port {:?}: {}", port, err))
+        };
+        let (host_port, guest_port) = match value.split_once(':') {
+            Some((host, guest)) => (parse(host)?, parse(guest)?),
+            None => (parse(value)?, parse(value)?),
+        };
+        Ok(Self {
+            host_port,
+            guest_port,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkDeviceStatus {
+    pub name: String,
+    pub present: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkStatus {
+    pub mode: NetworkMode,
+    pub devices: Vec<NetworkDeviceStatus>,
+    /// Forwards adb currently reports for the instance; absent when adb
+    /// could not be queried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port_forwards: Option<Vec<PortForward>>,
+}
+
+/// A saved QEMU VM state for an instance, restorable with `RestoreInstance`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub name: String,
+    pub created_at: u64,
+    pub boot_image: String,
+    pub init_boot_image: String,
+}
+
+/// A request the daemon is still working on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InFlightRequest {
+    pub request_id: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<InstanceId>,
+    pub started_at: u64,
+    /// Set once a cancel was requested; the request is rolling back.
+    #[serde(default)]
+    pub cancelled: bool,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -92,6 +413,74 @@ pub struct LogsOptions {
     pub timeout_secs: Option<u64>,
     #[serde(default)]
     pub stream_stdout: bool,
+    #[serde(default)]
+    pub source: LogSource,
+    /// Start of a byte range of the source (inclusive).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_byte: Option<u64>,
+    /// End of a byte range of the source (exclusive).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_byte: Option<u64>,
+    /// First line to return, counting source lines from 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_line: Option<usize>,
+    /// Last line to return (inclusive).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_line: Option<usize>,
+    /// Only return lines matching this regex.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grep: Option<String>,
+    /// Only return lines logged at or after this Unix time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<u64>,
+}
+
+/// Which of an instance's logs `Request::Logs` reads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogSource {
+    /// stdout/stderr of the launcher process (`cfctl-run.log`).
+    #[default]
+    Run,
+    /// The guest serial console.
+    Console,
+    /// The guest kernel log.
+    Kernel,
+    /// The launcher's own log file (cuttlefish `launcher.log`).
+    Launcher,
+    /// The daemon's continuous logcat capture, or a live `adb logcat`
+    /// dump when nothing was captured.
+    Logcat,
+}
+
+impl LogSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Run => "run",
+            Self::Console => "console",
+            Self::Kernel => "kernel",
+            Self::Launcher => "launcher",
+            Self::Logcat => "logcat",
+        }
+    }
+}
+
+impl FromStr for LogSource {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "run" => Ok(Self::Run),
+            "console" => Ok(Self::Console),
+            "kernel" => Ok(Self::Kernel),
+            "launcher" => Ok(Self::Launcher),
+            "logcat" => Ok(Self::Logcat),
+            other => Err(format!(
+                "unknown log source {:?} (expected run, console, kernel, launcher or logcat)",
+                other
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -100,6 +489,343 @@ pub struct BootVerificationResult {
     pub boot_marker_observed: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub failure_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readiness_probe: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ready_after_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdbCommandResponse {
+    pub serial: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+}
+
+/// What to do with the scenario's instance once the run finishes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScenarioCleanup {
+    #[default]
+    Always,
+    OnSuccess,
+    Never,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioMarker {
+    /// Regex matched against run log and console log lines.
+    pub pattern: String,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioAdbCommand {
+    pub command: Vec<String>,
+    /// Expected exit code; defaults to 0.
+    #[serde(default)]
+    pub expect_exit_code: Option<i32>,
+    /// Regex the command's stdout must match.
+    #[serde(default)]
+    pub expect_stdout: Option<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// End-to-end test description executed by `RunScenario`:
+/// create, deploy, start, wait for markers, run adb commands, collect logs, clean up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub purpose: Option<String>,
+    #[serde(default)]
+    pub boot_image: Option<String>,
+    #[serde(default)]
+    pub init_boot_image: Option<String>,
+    #[serde(default)]
+    pub start: StartOptions,
+    #[serde(default)]
+    pub markers: Vec<ScenarioMarker>,
+    #[serde(default)]
+    pub marker_timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub adb_commands: Vec<ScenarioAdbCommand>,
+    #[serde(default)]
+    pub log_lines: Option<usize>,
+    #[serde(default)]
+    pub destroy_timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub cleanup: ScenarioCleanup,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScenarioStepStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioStepResult {
+    pub name: String,
+    pub status: ScenarioStepStatus,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioResult {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<InstanceId>,
+    pub passed: bool,
+    pub duration_ms: u64,
+    pub steps: Vec<ScenarioStepResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logs: Option<String>,
+    pub junit_xml: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEntry {
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub boot_image: Option<String>,
+    #[serde(default)]
+    pub init_boot_image: Option<String>,
+}
+
+/// Boots one instance per entry, at most `max_parallel` at a time, and
+/// destroys them afterwards unless `keep_instances` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub entries: Vec<BatchEntry>,
+    #[serde(default)]
+    pub purpose: Option<String>,
+    #[serde(default)]
+    pub start: StartOptions,
+    /// Upper bound on concurrent guests; clamped to the host capacity.
+    #[serde(default)]
+    pub max_parallel: Option<usize>,
+    #[serde(default)]
+    pub keep_instances: bool,
+    #[serde(default)]
+    pub destroy_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchTimelineEvent {
+    pub event: String,
+    /// Milliseconds since the entry started.
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEntryResult {
+    pub label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<InstanceId>,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<BootVerificationResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorDetail>,
+    #[serde(default)]
+    pub timeline: Vec<BatchTimelineEvent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cleanup: Option<CleanupSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub max_parallel: usize,
+    pub duration_ms: u64,
+    pub entries: Vec<BatchEntryResult>,
+}
+
+/// How a recorded start attempt ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunOutcome {
+    Ok,
+    Failed,
+    Cancelled,
+}
+
+impl RunOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl FromStr for RunOutcome {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "ok" => Ok(Self::Ok),
+            "failed" => Ok(Self::Failed),
+            "cancelled" => Ok(Self::Cancelled),
+            other => Err(format!(
+                "unknown outcome {:?} (use ok, failed or cancelled)",
+                other
+            )),
+        }
+    }
+}
+
+/// A guest image identified by its contents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageDigest {
+    pub path: String,
+    pub sha256: String,
+}
+
+/// One start attempt from the daemon's run history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub run_id: u64,
+    pub instance_id: InstanceId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<String>,
+    pub started_at: u64,
+    pub backend: String,
+    /// The boot image, or the kernel for QEMU guests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_image: Option<ImageDigest>,
+    /// The init_boot image, or the initramfs for QEMU guests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_image: Option<ImageDigest>,
+    pub options: StartOptions,
+    /// Milliseconds from launching the guest until adb reported it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adb_ms: Option<u64>,
+    /// Milliseconds from launching the guest until its boot marker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marker_ms: Option<u64>,
+    /// Milliseconds the whole start request took.
+    pub duration_ms: u64,
+    pub outcome: RunOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<ErrorDetail>,
+}
+
+/// Selects runs from the history; unset filters match every run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryQuery {
+    #[serde(default)]
+    pub instance_id: Option<InstanceId>,
+    /// Prefix of the init image's sha256.
+    #[serde(default)]
+    pub init_sha256: Option<String>,
+    #[serde(default)]
+    pub purpose: Option<String>,
+    #[serde(default)]
+    pub outcome: Option<RunOutcome>,
+    /// Only runs started at or after this Unix time.
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// Most recent runs to list (default 50). Comparisons use every
+    /// matching run.
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Group the matching runs by init image instead of listing them.
+    #[serde(default)]
+    pub compare: bool,
+    /// Prefix of the init sha256 to list first in a comparison, so
+    /// differences are relative to it. Defaults to the oldest variant.
+    #[serde(default)]
+    pub baseline: Option<String>,
+}
+
+/// Nearest-rank percentiles of a boot milestone, in milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimingStats {
+    pub samples: usize,
+    pub min: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub max: u64,
+}
+
+/// The runs of one init image in a history comparison.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantStats {
+    /// None groups the runs with no init image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_sha256: Option<String>,
+    /// Path the image had on its most recent run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_image: Option<String>,
+    pub first_run_at: u64,
+    pub last_run_at: u64,
+    pub runs: usize,
+    pub ok: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adb_ms: Option<TimingStats>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marker_ms: Option<TimingStats>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryResponse {
+    /// Matching runs, most recent first (empty for comparisons).
+    #[serde(default)]
+    pub runs: Vec<RunRecord>,
+    /// Matching runs grouped by init image, baseline first.
+    #[serde(default)]
+    pub variants: Vec<VariantStats>,
+}
+
+/// Outcome of one host check run by `Doctor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    /// Fine, but held by a running or starting instance.
+    InUse,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::InUse => "in_use",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+    /// How to remedy a warning or failure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    /// The daemon repaired the issue (`Doctor { fix: true }`); `status`
+    /// describes the host before the repair.
+    #[serde(default)]
+    pub fixed: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -135,13 +861,55 @@ pub enum Request {
         purpose: Option<String>,
         #[serde(default)]
         options: StartOptions,
+        /// Claim a warm instance from the daemon pool when one is available.
+        #[serde(default)]
+        pool: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        boot_image: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        init_boot_image: Option<String>,
+    },
+    /// Create a new instance with `from`'s deployed images and start options.
+    CloneInstance {
+        from: InstanceId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        purpose: Option<String>,
+        /// Start the clone with the copied start options.
+        #[serde(default)]
+        start: bool,
     },
     StopInstance {
         id: InstanceId,
     },
+    /// Stop the instance and relaunch it with its stored start options.
+    RestartInstance {
+        id: InstanceId,
+    },
+    SnapshotInstance {
+        id: InstanceId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+    RestoreInstance {
+        id: InstanceId,
+        name: String,
+        #[serde(default)]
+        options: StartOptions,
+    },
+    ListSnapshots {
+        id: InstanceId,
+    },
+    DeleteSnapshot {
+        id: InstanceId,
+        name: String,
+    },
     HoldInstance {
         id: InstanceId,
     },
+    /// Undo `HoldInstance`, making the instance prunable again.
+    ReleaseInstance {
+        id: InstanceId,
+    },
     DestroyInstance {
         id: InstanceId,
         #[serde(default)]
@@ -160,6 +928,28 @@ pub enum Request {
         #[serde(default)]
         options: LogsOptions,
     },
+    Shell {
+        id: InstanceId,
+        /// Argument vector; each element reaches the guest as one argument.
+        command: Vec<String>,
+        /// Defaults to 60 seconds.
+        #[serde(default)]
+        timeout_secs: Option<u64>,
+    },
+    Push {
+        id: InstanceId,
+        local_path: String,
+        remote_path: String,
+        #[serde(default)]
+        timeout_secs: Option<u64>,
+    },
+    Pull {
+        id: InstanceId,
+        remote_path: String,
+        local_path: String,
+        #[serde(default)]
+        timeout_secs: Option<u64>,
+    },
     Status {
         id: InstanceId,
     },
@@ -168,11 +958,78 @@ pub enum Request {
         #[serde(default)]
         run_log_lines: Option<usize>,
     },
-    ListInstances,
+    ListInstances {
+        /// Also report CPU, memory and process counts of running guests,
+        /// which walks /proc.
+        #[serde(default)]
+        resources: bool,
+    },
+    RunScenario {
+        scenario: Scenario,
+    },
+    RunBatch {
+        batch: BatchRequest,
+    },
     PruneExpired {
         max_age_secs: u64,
     },
     PruneAll,
+    /// List the requests the daemon is currently working on.
+    ListRequests,
+    /// Cancel an in-flight request by the id `ListRequests` reports for it.
+    Cancel {
+        request_id: String,
+    },
+    /// Daemon metrics in the Prometheus text exposition format.
+    Metrics,
+    /// Query the recorded start attempts.
+    History(HistoryQuery),
+    /// Check the host for problems that make launches fail.
+    Doctor {
+        /// Also repair issues that are safe to fix (stale devices and files).
+        #[serde(default)]
+        fix: bool,
+    },
+}
+
+impl Request {
+    /// The request's name without its arguments, as used in the daemon log
+    /// and the metric labels.
+    pub fn action_name(&self) -> &'static str {
+        match self {
+            Self::CreateInstance { .. } => "CreateInstance",
+            Self::CreateStartInstance { .. } => "CreateStartInstance",
+            Self::StartInstance { .. } => "StartInstance",
+            Self::CloneInstance { .. } => "CloneInstance",
+            Self::StopInstance { .. } => "StopInstance",
+            Self::RestartInstance { .. } => "RestartInstance",
+            Self::HoldInstance { .. } => "HoldInstance",
+            Self::ReleaseInstance { .. } => "ReleaseInstance",
+            Self::SnapshotInstance { .. } => "SnapshotInstance",
+            Self::RestoreInstance { .. } => "RestoreInstance",
+            Self::ListSnapshots { .. } => "ListSnapshots",
+            Self::DeleteSnapshot { .. } => "DeleteSnapshot",
+            Self::DestroyInstance { .. } => "DestroyInstance",
+            Self::Deploy(_) => "Deploy",
+            Self::WaitForAdb { .. } => "WaitForAdb",
+            Self::Logs { .. } => "Logs",
+            Self::Shell { .. } => "Shell",
+            Self::Push { .. } => "Push",
+            Self::Pull { .. } => "Pull",
+            Self::Status { .. } => "Status",
+            Self::Describe { .. } => "Describe",
+            Self::ListInstances { .. } => "ListInstances",
+            Self::RunScenario { .. } => "RunScenario",
+            Self::RunBatch { .. } => "RunBatch",
+            Self::PruneExpired { .. } => "PruneExpired",
+            Self::PruneAll => "PruneAll",
+            Self::ListRequests => "ListRequests",
+            Self::Cancel { .. } => "Cancel",
+            Self::Metrics => "Metrics",
+            Self::History(_) => "History",
+            Self::Doctor { .. } => "Doctor",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -189,6 +1046,22 @@ pub struct Response {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub instances: Option<Vec<InstanceSummary>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub adb_command: Option<AdbCommandResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scenario: Option<ScenarioResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch: Option<BatchResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshots: Option<Vec<SnapshotInfo>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests: Option<Vec<InFlightRequest>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history: Option<HistoryResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doctor: Option<Vec<DoctorCheck>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<ErrorDetail>,
 }
 
@@ -201,6 +1074,14 @@ impl Response {
             action: None,
             logs: None,
             instances: None,
+            adb_command: None,
+            scenario: None,
+            batch: None,
+            snapshots: None,
+            requests: None,
+            metrics: None,
+            history: None,
+            doctor: None,
             error: None,
         }
     }
@@ -214,23 +1095,15 @@ impl Response {
         Self {
             ok: false,
             message: Some(msg.into()),
-            create: None,
-            action: None,
-            logs: None,
-            instances: None,
-            error: None,
+            ..Self::ok()
         }
     }
 
     pub fn error_with_detail(detail: ErrorDetail) -> Self {
         Self {
             ok: false,
-            message: None,
-            create: None,
-            action: None,
-            logs: None,
-            instances: None,
             error: Some(detail),
+            ..Self::ok()
         }
     }
 }
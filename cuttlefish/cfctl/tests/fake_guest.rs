@@ -0,0 +1,1124 @@
+//! Drives a real `cfctl-daemon` over its Unix socket, with guests played by
+//! `cfctl-fake-guest` instead of launch_cvd and adb.
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpStream},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::atomic::{AtomicU16, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use cfctl::{
+    client::{AsyncClient, ClientError, CreateStart},
+    CheckStatus, DeployRequest, DestroyOptions, HistoryQuery, InstanceId, InstanceState, LogSource,
+    LogsOptions, ReadinessProbeSpec, Request, Response, RunOutcome, StartOptions,
+};
+use tempfile::TempDir;
+
+const FAKE_GUEST: &str = env!("CARGO_BIN_EXE_cfctl-fake-guest");
+const DAEMON: &str = env!("CARGO_BIN_EXE_cfctl-daemon");
+const CLI: &str = env!("CARGO_BIN_EXE_cfctl");
+
+/// Each daemon gets its own adb port range so tests can run in parallel.
+static NEXT_BASE_PORT: AtomicU16 = AtomicU16::new(0);
+
+const BOOTING_GUEST: &str = "\
+console fake kernel booting
+logcat init: starting services
+logcat ActivityManager: system ready
+adb
+console VIRTUAL_DEVICE_BOOT_COMPLETED
+prop sys.boot_completed 1
+";
+
+struct TestDaemon {
+    temp: TempDir,
+    socket: PathBuf,
+    child: Child,
+}
+
+impl TestDaemon {
+    fn start() -> Result<Self> {
+        Self::start_with(&[])
+    }
+
+    /// Starts a daemon with `extra_args` appended to the usual test flags.
+    fn start_with(extra_args: &[&str]) -> Result<Self> {
+        let temp = tempfile::tempdir()?;
+        let root = temp.path();
+        let images = root.join("images");
+        fs::create_dir_all(&images)?;
+        fs::write(images.join("boot.img"), b"unused")?;
+        fs::write(images.join("init_boot.img"), BOOTING_GUEST)?;
+        for dir in ["state", "etc", "instances", "assembly", "adb"] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        let base_port = 20000
+            + (std::process::id() % 200) as u16 * 100
+            + NEXT_BASE_PORT.fetch_add(1, Ordering::SeqCst) * 10;
+        let socket = root.join("cfctl.sock");
+        let log = fs::File::create(root.join("daemon.log"))?;
+        let child = Command::new(DAEMON)
+            .arg("--socket")
+            .arg(&socket)
+            .arg("--state-dir")
+            .arg(root.join("state"))
+            .arg("--etc-instances-dir")
+            .arg(root.join("etc"))
+            .arg("--default-boot-image")
+            .arg(images.join("boot.img"))
+            .arg("--default-init-boot-image")
+            .arg(images.join("init_boot.img"))
+            .arg("--cuttlefish-instances-dir")
+            .arg(root.join("instances"))
+            .arg("--cuttlefish-assembly-dir")
+            .arg(root.join("assembly"))
+            .arg("--cuttlefish-system-image-dir")
+            .arg(&images)
+            .arg("--transfer-dir")
+            .arg(root.join("transfer"))
+            .arg("--cuttlefish-fhs")
+            .arg(FAKE_GUEST)
+            .arg("--fake-guest")
+            .arg(FAKE_GUEST)
+            .arg("--base-adb-port")
+            .arg(base_port.to_string())
+            .arg("--start-timeout-secs")
+            .arg("20")
+            .arg("--log-check-secs")
+            .arg("1")
+            .args(extra_args)
+            .env("CFCTL_FAKE_ADB_DIR", root.join("adb"))
+            .env("CFCTL_GUEST_USER", "root")
+            .env("RUST_LOG", "cfctl=debug")
+            .stdout(Stdio::from(log.try_clone()?))
+            .stderr(Stdio::from(log))
+            .spawn()
+            .context("spawning cfctl-daemon")?;
+        let daemon = Self {
+            temp,
+            socket,
+            child,
+        };
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while UnixStream::connect(&daemon.socket).is_err() {
+            if Instant::now() >= deadline {
+                bail!("daemon socket never appeared:\n{}", daemon.log());
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        Ok(daemon)
+    }
+
+    fn root(&self) -> &Path {
+        self.temp.path()
+    }
+
+    fn log(&self) -> String {
+        fs::read_to_string(self.root().join("daemon.log")).unwrap_or_default()
+    }
+
+    fn request(&self, request: Request) -> Result<Response> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        serde_json::to_writer(&mut stream, &request)?;
+        stream.write_all(b"\n")?;
+        stream.shutdown(Shutdown::Write)?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    /// Sends `request` and fails with the daemon's error (and log) unless ok.
+    fn ok(&self, request: Request) -> Result<Response> {
+        let response = self.request(request)?;
+        if !response.ok {
+            bail!("request failed: {:?}\n{}", response.error, self.log());
+        }
+        Ok(response)
+    }
+
+    fn guest_script(&self, name: &str, script: &str) -> Result<String> {
+        let path = self.root().join("images").join(name);
+        fs::write(&path, script)?;
+        Ok(path.display().to_string())
+    }
+
+    fn create_start(
+        &self,
+        init_boot_image: Option<String>,
+        options: StartOptions,
+    ) -> Result<Response> {
+        self.request(Request::CreateStartInstance {
+            purpose: Some("test".to_string()),
+            options,
+            pool: false,
+            boot_image: None,
+            init_boot_image,
+        })
+    }
+
+    fn state(&self, id: InstanceId) -> Result<InstanceState> {
+        let response = self.ok(Request::Status { id })?;
+        Ok(response.action.unwrap().summary.state)
+    }
+}
+
+impl Drop for TestDaemon {
+    fn drop(&mut self) {
+        let _ = self.request(Request::PruneAll);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn created_id(response: &Response) -> InstanceId {
+    match (&response.create, &response.action) {
+        (Some(create), _) => create.summary.id,
+        (None, Some(action)) => action.summary.id,
+        (None, None) => panic!("no instance in response: {:?}", response),
+    }
+}
+
+fn adb_port(response: &Response) -> u16 {
+    let action = response.action.as_ref().expect("action response");
+    action.summary.adb.as_ref().expect("adb info").port
+}
+
+#[test]
+fn verify_boot_shell_logs_and_destroy_cleanup() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let response = daemon.create_start(
+        None,
+        StartOptions {
+            verify_boot: true,
+            ..StartOptions::default()
+        },
+    )?;
+    assert!(
+        response.ok,
+        "start failed: {:?}\n{}",
+        response.error,
+        daemon.log()
+    );
+    let id = created_id(&response);
+    let verification = response
+        .action
+        .as_ref()
+        .and_then(|action| action.verification.clone())
+        .expect("verification");
+    assert!(verification.adb_ready);
+    assert!(verification.boot_marker_observed);
+    assert_eq!(daemon.state(id)?, InstanceState::Running);
+
+    let shell = daemon.ok(Request::Shell {
+        id,
+        command: vec!["echo".to_string(), "hello".to_string()],
+        timeout_secs: Some(10),
+    })?;
+    let shell = shell.adb_command.unwrap();
+    assert_eq!(shell.exit_code, Some(0));
+    assert_eq!(shell.stdout.trim(), "hello");
+
+    let logs = daemon.ok(Request::Logs {
+        id,
+        lines: Some(20),
+        options: LogsOptions::default(),
+    })?;
+    let logs = logs.logs.unwrap();
+    assert!(logs.content.contains("fake guest: running"));
+    let logs_from = |source, options: LogsOptions| {
+        daemon
+            .ok(Request::Logs {
+                id,
+                lines: None,
+                options: LogsOptions { source, ..options },
+            })
+            .map(|response| response.logs.unwrap())
+    };
+    let console = logs_from(LogSource::Console, LogsOptions::default())?;
+    assert!(console.content.contains("fake kernel booting"));
+    let missing = daemon.request(Request::Logs {
+        id,
+        lines: None,
+        options: LogsOptions {
+            source: LogSource::Kernel,
+            ..LogsOptions::default()
+        },
+    })?;
+    assert_eq!(missing.error.unwrap().code, "logs_not_available");
+
+    let port = adb_port(&response);
+    let instance_dir = daemon.root().join("instances").join(id.to_string());
+    assert!(instance_dir.exists());
+    daemon.ok(Request::DestroyInstance {
+        id,
+        options: DestroyOptions {
+            timeout_secs: Some(30),
+        },
+    })?;
+    assert!(
+        TcpStream::connect(("127.0.0.1", port)).is_err(),
+        "guest still serving adb"
+    );
+    let listed = daemon
+        .ok(Request::ListInstances { resources: false })?
+        .instances
+        .unwrap();
+    assert!(listed.is_empty(), "instance still listed: {:?}", listed);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while instance_dir.exists() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(!instance_dir.exists(), "host instance dir not purged");
+    Ok(())
+}
+
+#[test]
+fn logcat_capture_survives_adb_drops() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let script = daemon.guest_script(
+        "logcat.img",
+        "logcat init: starting services\nadb\nlogcat ActivityManager: system ready\n\
+         sleep 1\ndrop-adb\nsleep 3\nlogcat ActivityManager: after reconnect\nsleep 60\n",
+    )?;
+    let response = daemon.create_start(Some(script), StartOptions::default())?;
+    assert!(
+        response.ok,
+        "start failed: {:?}\n{}",
+        response.error,
+        daemon.log()
+    );
+    let id = created_id(&response);
+
+    // The capture reconnects after `drop-adb` and picks up the later line
+    // without repeating the one it resumed from.
+    let deadline = Instant::now() + Duration::from_secs(15);
+    let (logcat, status) = loop {
+        let logcat = daemon
+            .ok(Request::Logs {
+                id,
+                lines: None,
+                options: LogsOptions {
+                    source: LogSource::Logcat,
+                    grep: Some("ActivityManager".to_string()),
+                    since: Some(1),
+                    ..LogsOptions::default()
+                },
+            })?
+            .logs
+            .unwrap();
+        let status = daemon
+            .ok(Request::Describe {
+                id,
+                run_log_lines: None,
+            })?
+            .action
+            .and_then(|action| action.logcat)
+            .expect("logcat capture status");
+        if logcat.content.contains("after reconnect") && status.reconnects >= 1 {
+            break (logcat, status);
+        }
+        if Instant::now() >= deadline {
+            bail!(
+                "logcat never caught up: {:?} {:?}\n{}",
+                logcat.content,
+                status,
+                daemon.log()
+            );
+        }
+        thread::sleep(Duration::from_millis(200));
+    };
+    assert_eq!(logcat.content.lines().count(), 2, "{}", logcat.content);
+    assert!(logcat.content.contains("ActivityManager: system ready"));
+    let path = logcat.path.expect("capture file");
+    assert!(path.ends_with("logcat.log"), "{}", path);
+    assert!(status.active);
+    assert!(status.bytes_written > 0);
+    assert_eq!(status.path, path);
+    Ok(())
+}
+
+#[test]
+fn restart_rotates_and_compresses_previous_logs() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let response = daemon.create_start(None, StartOptions::default())?;
+    assert!(
+        response.ok,
+        "start failed: {:?}\n{}",
+        response.error,
+        daemon.log()
+    );
+    let id = created_id(&response);
+    daemon.ok(Request::RestartInstance { id })?;
+
+    // The first run's log became a segment the worker compresses.
+    let deadline = Instant::now() + Duration::from_secs(15);
+    let usage = loop {
+        let usage = daemon
+            .ok(Request::Describe {
+                id,
+                run_log_lines: None,
+            })?
+            .action
+            .and_then(|action| action.log_usage)
+            .expect("log usage");
+        let run = usage
+            .files
+            .iter()
+            .find(|file| file.source == LogSource::Run)
+            .expect("run log usage");
+        let compressed = PathBuf::from(format!("{}.1.gz", run.path));
+        if run.segments == 1 && compressed.exists() {
+            break usage;
+        }
+        if Instant::now() >= deadline {
+            bail!("run log never rotated: {:?}\n{}", usage, daemon.log());
+        }
+        thread::sleep(Duration::from_millis(200));
+    };
+    assert!(usage.quota_bytes.is_some());
+    assert!(usage.used_bytes > 0);
+
+    // A plain tail reads on into the compressed copy.
+    let logs = daemon
+        .ok(Request::Logs {
+            id,
+            lines: Some(100),
+            options: LogsOptions::default(),
+        })?
+        .logs
+        .unwrap();
+    assert_eq!(
+        logs.content.matches("fake guest: running").count(),
+        2,
+        "{}",
+        logs.content
+    );
+    Ok(())
+}
+
+#[test]
+fn crashing_guest_fails_start_and_is_marked_failed() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let script = daemon.guest_script("crash.img", "console about to crash\ncrash\n")?;
+    let response = daemon.create_start(Some(script), StartOptions::default())?;
+    assert!(!response.ok);
+    let error = response.error.expect("error detail");
+    assert_eq!(error.code, "wait_for_adb_guest_exit");
+    assert!(
+        error
+            .message
+            .unwrap_or_default()
+            .contains("fake guest: crashing"),
+        "run log tail missing from error"
+    );
+
+    let listed = daemon
+        .ok(Request::ListInstances { resources: false })?
+        .instances
+        .unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].state, InstanceState::Failed);
+    Ok(())
+}
+
+#[test]
+fn hung_guest_times_out_and_destroy_still_cleans_up() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let script = daemon.guest_script("hang.img", "console stuck in early init\nhang\n")?;
+    let response = daemon.create_start(
+        Some(script.clone()),
+        StartOptions {
+            timeout_secs: Some(2),
+            ..StartOptions::default()
+        },
+    )?;
+    assert!(!response.ok);
+    assert_eq!(response.error.unwrap().code, "wait_for_adb_timeout");
+
+    // A hung guest that ignores SIGTERM but reached readiness still goes away on destroy.
+    let create = daemon.ok(Request::CreateInstance { purpose: None })?;
+    let id = created_id(&create);
+    daemon.ok(Request::Deploy(DeployRequest {
+        id,
+        boot_image: None,
+        init_boot_image: Some(daemon.guest_script("ready-then-hang.img", "console up\nhang\n")?),
+    }))?;
+    daemon.ok(Request::StartInstance {
+        id,
+        options: StartOptions {
+            readiness: Some(ReadinessProbeSpec::ConsoleRegex {
+                pattern: "^up$".to_string(),
+            }),
+            ..StartOptions::default()
+        },
+    })?;
+    assert_eq!(daemon.state(id)?, InstanceState::Running);
+    let pattern = daemon.root().join("instances").join(id.to_string());
+    daemon.ok(Request::DestroyInstance {
+        id,
+        options: DestroyOptions {
+            timeout_secs: Some(30),
+        },
+    })?;
+    let pgrep = Command::new("pgrep")
+        .arg("-f")
+        .arg(format!("{}/", pattern.display()))
+        .output()?;
+    assert!(pgrep.stdout.is_empty(), "guest survived destroy");
+    Ok(())
+}
+
+#[test]
+fn prune_all_stops_and_removes_every_instance() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let mut ports = Vec::new();
+    for _ in 0..2 {
+        let response = daemon.create_start(None, StartOptions::default())?;
+        assert!(
+            response.ok,
+            "start failed: {:?}\n{}",
+            response.error,
+            daemon.log()
+        );
+        ports.push(adb_port(&response));
+    }
+    assert_ne!(ports[0], ports[1]);
+
+    let pruned = daemon.ok(Request::PruneAll)?;
+    assert!(pruned.message.is_some());
+    assert!(daemon
+        .ok(Request::ListInstances { resources: false })?
+        .instances
+        .unwrap()
+        .is_empty());
+    for port in ports {
+        assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+    }
+    Ok(())
+}
+
+#[test]
+fn client_disconnect_cancels_in_flight_start() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let script = daemon.guest_script("hang.img", "console stuck in early init\nhang\n")?;
+    let mut stream = UnixStream::connect(&daemon.socket)?;
+    let request = Request::CreateStartInstance {
+        purpose: Some("abandoned".to_string()),
+        options: StartOptions::default(),
+        pool: false,
+        boot_image: None,
+        init_boot_image: Some(script),
+    };
+    serde_json::to_writer(&mut stream, &request)?;
+    stream.write_all(b"\n")?;
+    stream.shutdown(Shutdown::Write)?;
+    thread::sleep(Duration::from_secs(1));
+    drop(stream);
+
+    // The daemon's start timeout is 20s; cancellation must fail it well before.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let listed = daemon
+            .ok(Request::ListInstances { resources: false })?
+            .instances
+            .unwrap();
+        if listed
+            .first()
+            .is_some_and(|instance| instance.state == InstanceState::Failed)
+        {
+            break;
+        }
+        if Instant::now() >= deadline {
+            bail!("start was not cancelled: {:?}\n{}", listed, daemon.log());
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+    assert!(daemon
+        .log()
+        .contains("client disconnected, cancelling CreateStartInstance"));
+    Ok(())
+}
+
+#[test]
+fn cancel_rolls_back_start_and_releases_the_instance() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let script = daemon.guest_script("hang.img", "console stuck in early init\nhang\n")?;
+    let response = thread::scope(|scope| -> Result<Response> {
+        let start = scope.spawn(|| daemon.create_start(Some(script), StartOptions::default()));
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let request = loop {
+            let listed = daemon.ok(Request::ListRequests)?.requests.unwrap();
+            if let Some(request) = listed.into_iter().find(|request| {
+                request.description == "CreateStartInstance" && request.instance_id.is_none()
+            }) {
+                break request;
+            }
+            if Instant::now() >= deadline {
+                bail!("start never showed up in ListRequests\n{}", daemon.log());
+            }
+            thread::sleep(Duration::from_millis(100));
+        };
+        // Let the guest launch so the cancel has something to roll back.
+        thread::sleep(Duration::from_secs(1));
+
+        let cancelled = daemon.ok(Request::Cancel {
+            request_id: request.request_id.clone(),
+        })?;
+        assert!(
+            cancelled
+                .message
+                .unwrap_or_default()
+                .starts_with("cancelled"),
+            "cancel did not wait for the rollback"
+        );
+        start.join().unwrap()
+    })?;
+    assert!(!response.ok);
+    assert_eq!(response.error.unwrap().code, "wait_for_adb_cancelled");
+
+    let listed = daemon
+        .ok(Request::ListInstances { resources: false })?
+        .instances
+        .unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].state, InstanceState::Failed);
+    assert!(daemon
+        .ok(Request::ListRequests)?
+        .requests
+        .unwrap()
+        .is_empty());
+    // The instance lock is free again.
+    let id = listed[0].id;
+    assert_eq!(daemon.state(id)?, InstanceState::Failed);
+
+    let unknown = daemon.request(Request::Cancel {
+        request_id: "r999".to_string(),
+    })?;
+    assert_eq!(unknown.error.unwrap().code, "request_not_found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn async_client_starts_reads_logs_and_destroys() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let client = AsyncClient::new(&daemon.socket);
+
+    let started = client
+        .create_start(CreateStart {
+            purpose: Some("client".to_string()),
+            ..CreateStart::default()
+        })
+        .await?;
+    let id = started.summary.id;
+    assert_eq!(started.summary.state, InstanceState::Running);
+
+    let logs = client.logs(id, Some(50), LogsOptions::default()).await?;
+    assert!(
+        logs.content.contains("fake guest: running"),
+        "{}",
+        logs.content
+    );
+
+    client.destroy(id, DestroyOptions::default()).await?;
+    let err = client.status(id).await.unwrap_err();
+    assert!(matches!(err, ClientError::Daemon { .. }), "{:?}", err);
+    assert!(err.to_string().contains("does not exist"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn cli_formats_output_and_maps_exit_codes() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let response = daemon.create_start(None, StartOptions::default())?;
+    let id = created_id(&response);
+    let cfctl = |args: &[&str]| -> Result<(i32, String)> {
+        let output = Command::new(CLI)
+            .arg("--socket")
+            .arg(&daemon.socket)
+            .args(args)
+            .output()?;
+        Ok((
+            output.status.code().unwrap_or(-1),
+            String::from_utf8(output.stdout)?,
+        ))
+    };
+
+    let (code, stdout) = cfctl(&["-o", "ids", "instance", "list"])?;
+    assert_eq!((code, stdout), (0, format!("{}\n", id)));
+
+    let (code, stdout) = cfctl(&["--output", "table", "instance", "list"])?;
+    assert_eq!(code, 0);
+    let mut lines = stdout.lines();
+    let header: Vec<_> = lines.next().unwrap().split_whitespace().collect();
+    assert_eq!(
+        header,
+        ["ID", "STATE", "PURPOSE", "AGE", "ADB", "SERIAL", "HELD"]
+    );
+    let row: Vec<_> = lines.next().unwrap().split_whitespace().collect();
+    assert_eq!(row[..3], [id.to_string().as_str(), "running", "test"]);
+    assert_eq!(row[5], "no");
+
+    let (code, stdout) = cfctl(&["-o", "yaml", "instance", "status", &id.to_string()])?;
+    assert_eq!(code, 0);
+    assert!(stdout.contains("state: running"), "{}", stdout);
+
+    // Piped output stays JSON unless a format is asked for.
+    let (code, stdout) = cfctl(&["logs", "999"])?;
+    assert_eq!(code, 3, "instance_not_found exits 3");
+    let response: Response = serde_json::from_str(&stdout)?;
+    assert_eq!(response.error.unwrap().code, "instance_not_found");
+    Ok(())
+}
+
+#[test]
+fn list_reports_guest_resources_on_request_and_release_undoes_hold() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let response = daemon.create_start(None, StartOptions::default())?;
+    let id = created_id(&response);
+
+    let listed = daemon
+        .ok(Request::ListInstances { resources: false })?
+        .instances
+        .unwrap();
+    assert!(listed[0].resources.is_none(), "resources only on request");
+    let listed = daemon
+        .ok(Request::ListInstances { resources: true })?
+        .instances
+        .unwrap();
+    let resources = listed[0].resources.expect("running guest has resources");
+    assert!(resources.processes >= 1);
+    assert!(resources.rss_bytes > 0);
+
+    let held = daemon.ok(Request::HoldInstance { id })?.action.unwrap();
+    assert!(held.summary.held);
+    let released = daemon.ok(Request::ReleaseInstance { id })?.action.unwrap();
+    assert!(!released.summary.held);
+    let listed = daemon
+        .ok(Request::ListInstances { resources: false })?
+        .instances
+        .unwrap();
+    assert!(!listed[0].held);
+    Ok(())
+}
+
+#[test]
+fn metrics_count_requests_boots_and_cleanups() -> Result<()> {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port();
+    let addr = format!("127.0.0.1:{}", port);
+    let daemon = TestDaemon::start_with(&["--metrics-addr", &addr])?;
+    let response = daemon.create_start(
+        None,
+        StartOptions {
+            verify_boot: true,
+            ..StartOptions::default()
+        },
+    )?;
+    assert!(
+        response.ok,
+        "start failed: {:?}\n{}",
+        response.error,
+        daemon.log()
+    );
+    let id = created_id(&response);
+    daemon.ok(Request::DestroyInstance {
+        id,
+        options: DestroyOptions::default(),
+    })?;
+    let _ = daemon.request(Request::Status { id: 999 })?;
+
+    let text = daemon.ok(Request::Metrics)?.metrics.expect("metrics text");
+    for line in [
+        "cfctl_requests_total{action=\"CreateStartInstance\",result=\"ok\"} 1",
+        "cfctl_requests_total{action=\"DestroyInstance\",result=\"ok\"} 1",
+        "cfctl_requests_total{action=\"Status\",result=\"error\"} 1",
+        "cfctl_request_duration_seconds_count{action=\"CreateStartInstance\"} 1",
+        "cfctl_instances{state=\"running\"} 0",
+        "cfctl_boot_to_adb_seconds_count 1",
+        "cfctl_boot_to_marker_seconds_count 1",
+        "cfctl_cleanups_total{result=\"incomplete\"} 0",
+        "cfctl_cleanup_surviving_pids_total 0",
+    ] {
+        assert!(
+            text.lines().any(|candidate| candidate == line),
+            "missing {:?} in:\n{}",
+            line,
+            text
+        );
+    }
+    assert!(
+        !text.contains("cfctl_cleanups_total{result=\"clean\"} 0"),
+        "{}",
+        text
+    );
+
+    let mut stream = TcpStream::connect(&addr)?;
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+    let mut http = String::new();
+    stream.read_to_string(&mut http)?;
+    assert!(http.starts_with("HTTP/1.1 200 OK\r\n"), "{}", http);
+    assert!(
+        http.contains("cfctl_requests_total{action=\"Metrics\",result=\"ok\"} 1\n"),
+        "{}",
+        http
+    );
+
+    let mut stream = TcpStream::connect(&addr)?;
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n")?;
+    let mut http = String::new();
+    stream.read_to_string(&mut http)?;
+    assert!(http.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", http);
+    Ok(())
+}
+
+#[test]
+fn history_outlives_instances_and_compares_init_images() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let variant = daemon.guest_script(
+        "variant.img",
+        &format!("console variant init\n{}", BOOTING_GUEST),
+    )?;
+    for init in [None, Some(variant)] {
+        let response = daemon.create_start(
+            init,
+            StartOptions {
+                verify_boot: true,
+                ..StartOptions::default()
+            },
+        )?;
+        assert!(
+            response.ok,
+            "start failed: {:?}\n{}",
+            response.error,
+            daemon.log()
+        );
+        daemon.ok(Request::DestroyInstance {
+            id: created_id(&response),
+            options: DestroyOptions::default(),
+        })?;
+    }
+
+    let history = daemon
+        .ok(Request::History(HistoryQuery::default()))?
+        .history
+        .unwrap();
+    assert_eq!(history.runs.len(), 2);
+    let latest = &history.runs[0];
+    assert_eq!(latest.outcome, RunOutcome::Ok);
+    assert_eq!(latest.purpose.as_deref(), Some("test"));
+    assert!(latest.options.verify_boot);
+    assert!(latest.adb_ms.is_some() && latest.marker_ms.is_some());
+    let variant_sha = latest.init_image.as_ref().unwrap().sha256.clone();
+    assert_ne!(
+        Some(&variant_sha),
+        history.runs[1]
+            .init_image
+            .as_ref()
+            .map(|image| &image.sha256)
+    );
+
+    let output = Command::new(CLI)
+        .arg("--socket")
+        .arg(&daemon.socket)
+        .args(["-o", "json", "history", "compare", "--baseline"])
+        .arg(&variant_sha[..10])
+        .output()?;
+    assert!(output.status.success(), "{:?}", output);
+    let response: Response = serde_json::from_slice(&output.stdout)?;
+    let variants = response.history.unwrap().variants;
+    assert_eq!(variants.len(), 2);
+    assert_eq!(variants[0].init_sha256.as_ref(), Some(&variant_sha));
+    for variant in &variants {
+        assert_eq!((variant.runs, variant.ok), (1, 1));
+        assert_eq!(variant.marker_ms.as_ref().unwrap().samples, 1);
+    }
+
+    let response = daemon.request(Request::History(HistoryQuery {
+        compare: true,
+        baseline: Some("not-hex".to_string()),
+        ..HistoryQuery::default()
+    }))?;
+    assert_eq!(response.error.unwrap().code, "history_invalid_query");
+    Ok(())
+}
+
+#[test]
+fn doctor_reports_host_checks_and_fails_on_missing_guest_user() -> Result<()> {
+    let daemon =
+        TestDaemon::start_with(&["--guest-user", "root", "--guest-primary-group", "root"])?;
+    let checks = daemon.ok(Request::Doctor { fix: false })?.doctor.unwrap();
+    let names: Vec<&str> = checks.iter().map(|check| check.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "kvm",
+            "guest_user",
+            "guest_group",
+            "cuttlefish_fhs",
+            "network_devices",
+            "cuttlefish_config",
+            "disk_space",
+        ]
+    );
+    for name in ["guest_user", "guest_group", "cuttlefish_fhs"] {
+        let check = checks.iter().find(|check| check.name == name).unwrap();
+        assert_eq!(check.status, CheckStatus::Pass, "{:?}", check);
+        assert!(!check.fixed);
+    }
+    let disk = checks
+        .iter()
+        .find(|check| check.name == "disk_space")
+        .unwrap();
+    assert!(disk.message.contains("GiB free"), "{:?}", disk);
+
+    let daemon = TestDaemon::start_with(&["--guest-user", "no-such-cfctl-user"])?;
+    let output = Command::new(CLI)
+        .arg("--socket")
+        .arg(&daemon.socket)
+        .args(["--output", "table", "doctor"])
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("guest_user"), "{}", stdout);
+    assert!(
+        stdout.contains("user 'no-such-cfctl-user' not found"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("guest_user: create the user"), "{}", stdout);
+    Ok(())
+}
+
+#[test]
+fn config_file_sets_limits_and_reloads_on_sighup() -> Result<()> {
+    let config_dir = tempfile::tempdir()?;
+    let config = config_dir.path().join("cfctl.toml");
+    fs::write(
+        &config,
+        "[capacity]\nmax_running = 1\n\n[prune]\ninterval_secs = 1\n",
+    )?;
+    let daemon = TestDaemon::start_with(&["--config", config.to_str().unwrap()])?;
+    let reload = |contents: &str, logged: &str| -> Result<()> {
+        let before = daemon.log().matches(logged).count();
+        fs::write(&config, contents)?;
+        let status = Command::new("kill")
+            .arg("-HUP")
+            .arg(daemon.child.id().to_string())
+            .status()?;
+        assert!(status.success());
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while daemon.log().matches(logged).count() == before {
+            if Instant::now() >= deadline {
+                bail!("daemon never logged {:?}:\n{}", logged, daemon.log());
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        Ok(())
+    };
+
+    let first = created_id(&daemon.create_start(None, StartOptions::default())?);
+    let response = daemon.create_start(None, StartOptions::default())?;
+    let error = response
+        .error
+        .as_ref()
+        .expect("second start is over max_running");
+    assert_eq!(error.code, "start_instance_capacity_exceeded");
+    let second = daemon
+        .ok(Request::ListInstances { resources: false })?
+        .instances
+        .unwrap()
+        .iter()
+        .map(|summary| summary.id)
+        .find(|id| *id != first)
+        .expect("instance created by the refused start");
+
+    reload(
+        "[capacity]\nmax_running = 2\n\n[prune]\ninterval_secs = 1\n\n[adb]\nbase_port = 30000\n",
+        "config reloaded",
+    )?;
+    assert!(daemon
+        .log()
+        .contains("base_adb_port only change on restart"));
+    daemon.ok(Request::StartInstance {
+        id: second,
+        options: StartOptions::default(),
+    })?;
+    let status = daemon.ok(Request::Status { id: first })?;
+    assert_eq!(status.action.unwrap().summary.state, InstanceState::Running);
+
+    reload("[capacity]\nmax_runing = 2\n", "keeping current config")?;
+    assert!(daemon.log().contains("unknown field `max_runing`"));
+
+    daemon.ok(Request::HoldInstance { id: first })?;
+    reload(
+        "[capacity]\nmax_running = 2\n\n[prune]\ninterval_secs = 1\nmax_age_secs = 1\n",
+        "config reloaded",
+    )?;
+    let deadline = Instant::now() + Duration::from_secs(15);
+    loop {
+        let instances = daemon
+            .ok(Request::ListInstances { resources: false })?
+            .instances
+            .unwrap();
+        let ids: Vec<InstanceId> = instances.iter().map(|summary| summary.id).collect();
+        if ids == [first] {
+            break;
+        }
+        if Instant::now() >= deadline {
+            bail!("background prune left {:?}:\n{}", ids, daemon.log());
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+
+    fs::write(&config, "[timeouts]\nstart_secs = 0\n")?;
+    let output = Command::new(DAEMON)
+        .arg("--socket")
+        .arg(config_dir.path().join("unused.sock"))
+        .arg("--config")
+        .arg(&config)
+        .env_remove("CFCTL_GUEST_USER")
+        .output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("guest_user is not set"), "{}", stderr);
+    assert!(
+        stderr.contains("start_timeout must be greater than zero"),
+        "{}",
+        stderr
+    );
+    Ok(())
+}
+
+#[test]
+fn batch_reports_each_entry_and_destroys_unless_kept() -> Result<()> {
+    let daemon = TestDaemon::start()?;
+    let good_a = daemon.guest_script("good-a.img", BOOTING_GUEST)?;
+    let good_b = daemon.guest_script("good-b.img", BOOTING_GUEST)?;
+    let crash = daemon.guest_script("crash.img", "console about to crash\ncrash\n")?;
+    let batch = |args: &[&str]| -> Result<(i32, Response)> {
+        let output = Command::new(CLI)
+            .arg("--socket")
+            .arg(&daemon.socket)
+            .args(["batch", "--json", "--verify-boot"])
+            .args(args)
+            .output()?;
+        Ok((
+            output.status.code().unwrap_or(-1),
+            serde_json::from_slice(&output.stdout)?,
+        ))
+    };
+
+    let (code, response) = batch(&["--max-parallel", "2", &good_a, &crash, &good_b])?;
+    assert_eq!(code, 1, "a failed entry fails the batch");
+    assert_eq!(response.error.unwrap().code, "batch_failed");
+    let result = response.batch.expect("batch result");
+    assert!((1..=2).contains(&result.max_parallel));
+    let labels: Vec<_> = result.entries.iter().map(|entry| &entry.label).collect();
+    assert_eq!(labels, [&good_a, &crash, &good_b]);
+    for entry in [&result.entries[0], &result.entries[2]] {
+        assert!(entry.ok, "{:?}\n{}", entry.error, daemon.log());
+        assert!(entry.verification.as_ref().unwrap().boot_marker_observed);
+        let events: Vec<_> = entry.timeline.iter().map(|e| e.event.as_str()).collect();
+        assert_eq!(
+            events,
+            [
+                "created",
+                "deployed",
+                "adb_ready",
+                "boot_marker",
+                "ready",
+                "destroyed"
+            ]
+        );
+        assert!(entry
+            .timeline
+            .windows(2)
+            .all(|pair| pair[0].elapsed_ms <= pair[1].elapsed_ms));
+        assert!(entry.cleanup.is_some());
+    }
+    let crashed = &result.entries[1];
+    assert!(!crashed.ok);
+    assert_eq!(
+        crashed.error.as_ref().unwrap().code,
+        "wait_for_adb_guest_exit"
+    );
+    let events: Vec<_> = crashed.timeline.iter().map(|e| e.event.as_str()).collect();
+    assert_eq!(events, ["created", "deployed", "failed", "destroyed"]);
+    assert!(daemon
+        .ok(Request::ListInstances { resources: false })?
+        .instances
+        .unwrap()
+        .is_empty());
+
+    let (code, response) = batch(&["--keep", &good_a])?;
+    assert_eq!(code, 0, "{:?}\n{}", response.error, daemon.log());
+    let entry = &response.batch.unwrap().entries[0];
+    assert!(entry.ok);
+    assert_eq!(entry.timeline.last().unwrap().event, "ready");
+    let id = entry.instance_id.unwrap();
+    assert_eq!(daemon.state(id)?, InstanceState::Running);
+    Ok(())
+}
+
+#[test]
+fn preassembled_pool_instance_boots_with_resume() -> Result<()> {
+    let daemon = TestDaemon::start_with(&["--pool-size", "1", "--pool-preassemble"])?;
+    let wait_assembled = |count: usize| -> Result<()> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while daemon
+            .log()
+            .matches("pool: assembled warm instance")
+            .count()
+            < count
+        {
+            if Instant::now() >= deadline {
+                bail!(
+                    "pool never assembled instance #{}:\n{}",
+                    count,
+                    daemon.log()
+                );
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        Ok(())
+    };
+    let claim = |init_boot_image: Option<String>| -> Result<String> {
+        let response = daemon.ok(Request::CreateStartInstance {
+            purpose: Some("test".to_string()),
+            options: StartOptions::default(),
+            pool: true,
+            boot_image: None,
+            init_boot_image,
+        })?;
+        let logs = daemon.ok(Request::Logs {
+            id: created_id(&response),
+            lines: None,
+            options: LogsOptions::default(),
+        })?;
+        Ok(logs.logs.unwrap().content)
+    };
+
+    wait_assembled(1)?;
+    let run_log = claim(None)?;
+    assert!(
+        run_log.contains("fake guest: resuming (assembled ahead of time)"),
+        "{}",
+        run_log
+    );
+
+    // New images invalidate the pool's assembly, so the claim boots cold.
+    wait_assembled(2)?;
+    let script = daemon.guest_script("other.img", BOOTING_GUEST)?;
+    let run_log = claim(Some(script))?;
+    assert!(!run_log.contains("fake guest: resuming"), "{}", run_log);
+    Ok(())
+}